@@ -0,0 +1,219 @@
+//! An in-process cache of assembled programs. Entries are keyed by everything
+//! that goes into building a program (the target, the source and the linker
+//! script's contents) so identical snippets are only run through the toolchain
+//! once. The least recently used entries are evicted once the cache grows past
+//! its capacity.
+
+use std::{
+    collections::HashMap,
+    io,
+    sync::{Mutex, OnceLock},
+};
+
+use crate::Symbols;
+
+/// The default value for [`set_cache_capacity`].
+pub const DEFAULT_CACHE_CAPACITY: usize = 16 * 1024 * 1024;
+
+/// Sets the maximum number of bytes the assembled program cache may hold.
+/// Entries are evicted least recently used first until the cache fits. A
+/// capacity of 0 disables caching.
+pub fn set_cache_capacity(bytes: usize) {
+    global_cache().set_capacity(bytes);
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub(crate) struct CacheKey {
+    target: &'static str,
+    source: String,
+    linker_script: String,
+    with_symbols: bool,
+}
+
+impl CacheKey {
+    pub(crate) fn new(
+        target: &'static str,
+        source: &str,
+        linker_script: &str,
+        with_symbols: bool,
+    ) -> Self {
+        CacheKey {
+            target,
+            source: source.to_owned(),
+            linker_script: linker_script.to_owned(),
+            with_symbols,
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.target.len() + self.source.len() + self.linker_script.len()
+    }
+}
+
+pub(crate) type Assembled = (Vec<u8>, Option<Symbols>);
+
+/// Returns the cached program for `key` or assembles it with `assemble` and
+/// caches the result. The cache is not locked while assembling so threads
+/// building different programs don't wait on each other.
+pub(crate) fn get_or_assemble<F>(key: CacheKey, assemble: F) -> io::Result<Assembled>
+where
+    F: FnOnce() -> io::Result<Assembled>,
+{
+    let cache = global_cache();
+    if let Some(assembled) = cache.get(&key) {
+        return Ok(assembled);
+    }
+    let assembled = assemble()?;
+    cache.insert(key, assembled.clone());
+    Ok(assembled)
+}
+
+fn global_cache() -> &'static AssemblyCache {
+    static CACHE: OnceLock<AssemblyCache> = OnceLock::new();
+    CACHE.get_or_init(|| AssemblyCache::new(DEFAULT_CACHE_CAPACITY))
+}
+
+pub(crate) struct AssemblyCache {
+    inner: Mutex<AssemblyCacheInner>,
+}
+
+struct AssemblyCacheInner {
+    capacity: usize,
+    size: usize,
+    /// Incremented on every access and used to find the least recently used entry.
+    clock: u64,
+    entries: HashMap<CacheKey, CacheEntry>,
+}
+
+struct CacheEntry {
+    assembled: Assembled,
+    size: usize,
+    last_used: u64,
+}
+
+impl AssemblyCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        AssemblyCache {
+            inner: Mutex::new(AssemblyCacheInner {
+                capacity,
+                size: 0,
+                clock: 0,
+                entries: HashMap::new(),
+            }),
+        }
+    }
+
+    pub(crate) fn get(&self, key: &CacheKey) -> Option<Assembled> {
+        let mut inner = self.lock();
+        inner.clock += 1;
+        let clock = inner.clock;
+        let entry = inner.entries.get_mut(key)?;
+        entry.last_used = clock;
+        Some(entry.assembled.clone())
+    }
+
+    pub(crate) fn insert(&self, key: CacheKey, assembled: Assembled) {
+        let size = key.size() + assembled_size(&assembled);
+        let mut inner = self.lock();
+        if size > inner.capacity {
+            return;
+        }
+
+        inner.clock += 1;
+        let entry = CacheEntry {
+            assembled,
+            size,
+            last_used: inner.clock,
+        };
+        if let Some(replaced) = inner.entries.insert(key, entry) {
+            inner.size -= replaced.size;
+        }
+        inner.size += size;
+        inner.evict();
+    }
+
+    pub(crate) fn set_capacity(&self, capacity: usize) {
+        let mut inner = self.lock();
+        inner.capacity = capacity;
+        inner.evict();
+    }
+
+    #[cfg(test)]
+    pub(crate) fn contains(&self, key: &CacheKey) -> bool {
+        self.lock().entries.contains_key(key)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, AssemblyCacheInner> {
+        // The cache is always left in a consistent state so it's fine to keep
+        // using it after a panic on another thread.
+        self.inner.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl AssemblyCacheInner {
+    fn evict(&mut self) {
+        while self.size > self.capacity {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+
+            if let Some(evicted) = self.entries.remove(&oldest) {
+                self.size -= evicted.size;
+            }
+        }
+    }
+}
+
+fn assembled_size((binary, symbols): &Assembled) -> usize {
+    let symbols_size = symbols.as_ref().map_or(0, |symbols| {
+        symbols
+            .keys()
+            .map(|name| name.len() + std::mem::size_of::<u32>())
+            .sum()
+    });
+    binary.len() + symbols_size
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AssemblyCache, CacheKey};
+
+    fn key(index: usize) -> CacheKey {
+        CacheKey::new("arm", &format!("source {index:02}"), "", false)
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        // Each entry is 3 + 9 + 7 = 19 bytes so the cache holds four of them.
+        let cache = AssemblyCache::new(4 * 19);
+        for index in 0..4 {
+            cache.insert(key(index), (vec![0; 7], None));
+        }
+        assert!((0..4).all(|index| cache.contains(&key(index))));
+
+        // Touching the oldest entry makes entry 1 the least recently used.
+        assert!(cache.get(&key(0)).is_some());
+
+        for index in 4..6 {
+            cache.insert(key(index), (vec![0; 7], None));
+        }
+        assert!(!cache.contains(&key(1)));
+        assert!(!cache.contains(&key(2)));
+        assert!(cache.contains(&key(0)));
+        assert!((3..6).all(|index| cache.contains(&key(index))));
+
+        cache.set_capacity(19);
+        assert!(cache.contains(&key(5)));
+        assert!((0..5).all(|index| !cache.contains(&key(index))));
+
+        // Entries larger than the whole cache are never stored.
+        cache.insert(key(6), (vec![0; 100], None));
+        assert!(!cache.contains(&key(6)));
+        assert!(cache.contains(&key(5)));
+    }
+}
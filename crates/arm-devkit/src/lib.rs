@@ -5,106 +5,127 @@ use std::{
     path::{Path, PathBuf},
     process::{self, Command},
     sync::{Arc, Mutex, OnceLock, Weak},
+    time::{Duration, Instant},
 };
 
 use tempfile::{NamedTempFile, TempPath};
 
-fn find_arm_binary_uncached(name: &str) -> Option<PathBuf> {
-    let arm_none_eabi_name = PathBuf::from(&(format!("arm-none-eabi-{name}")));
+mod cache;
 
-    #[cfg(target_os = "windows")]
-    let arm_none_eabi_name_exe = PathBuf::from(&(format!("arm-none-eabi-{name}.exe")));
+pub use cache::{set_cache_capacity, DEFAULT_CACHE_CAPACITY};
 
+fn find_arm_binary_uncached(name: &str, searched: &mut Vec<String>) -> Option<PathBuf> {
+    let arm_none_eabi_name = format!("arm-none-eabi-{name}");
+
+    searched.push("PATH".to_owned());
     if let Ok(path) = which::which(&arm_none_eabi_name) {
         return Some(path);
     }
 
-    if let Ok(arm_binaries_path) = std::env::var("ARM_BINARIES_DIR") {
-        let path = PathBuf::from(&arm_binaries_path).join(&arm_none_eabi_name);
-        if path.exists() {
-            return Some(path);
-        }
-
-        #[cfg(target_os = "windows")]
+    if let Some(arm_binaries_path) = std::env::var_os("ARM_BINARIES_DIR") {
+        let arm_binaries_path = PathBuf::from(arm_binaries_path);
+        let source = "ARM_BINARIES_DIR";
+        if let Some(path) =
+            find_executable_in(source, &arm_binaries_path, &arm_none_eabi_name, searched)
         {
-            let path = PathBuf::from(&arm_binaries_path).join(&arm_none_eabi_name_exe);
-            if path.exists() {
-                return Some(path);
-            }
+            return Some(path);
         }
+    } else {
+        searched.push("ARM_BINARIES_DIR (not set)".to_owned());
     }
 
-    let devkitarm_path = if let Ok(devkitarm_path) = std::env::var("DEVKITARM") {
-        PathBuf::from(devkitarm_path)
-    } else if let Ok(devkitpro_path) = std::env::var("DEVKITPRO") {
-        PathBuf::from(devkitpro_path).join("devkitARM")
+    let (source, devkitarm_path) = if let Some(devkitarm_path) = std::env::var_os("DEVKITARM") {
+        ("DEVKITARM", PathBuf::from(devkitarm_path))
+    } else if let Some(devkitpro_path) = std::env::var_os("DEVKITPRO") {
+        searched.push("DEVKITARM (not set)".to_owned());
+        ("DEVKITPRO", PathBuf::from(devkitpro_path).join("devkitARM"))
     } else {
+        searched.push("DEVKITARM (not set)".to_owned());
+        searched.push("DEVKITPRO (not set)".to_owned());
         return None;
     };
 
-    let path = devkitarm_path.join(&arm_none_eabi_name);
-    if path.exists() {
-        return Some(path);
-    }
-
-    #[cfg(target_os = "windows")]
-    {
-        let path = devkitarm_path.join(&arm_none_eabi_name_exe);
-        if path.exists() {
+    for directory in [devkitarm_path.clone(), devkitarm_path.join("bin")] {
+        if let Some(path) = find_executable_in(source, &directory, &arm_none_eabi_name, searched) {
             return Some(path);
         }
 
         // The path for devkitPro is usually /opt/devkitPro
-        if let Ok(path_without_opt) = path.strip_prefix("/opt") {
-            let path_from_c = Path::new("C:\\").join(path_without_opt);
-            if path_from_c.exists() {
-                return Some(path_from_c);
+        #[cfg(target_os = "windows")]
+        if let Ok(path_without_opt) = directory.strip_prefix("/opt") {
+            let directory = Path::new("C:\\").join(path_without_opt);
+            if let Some(path) =
+                find_executable_in(source, &directory, &arm_none_eabi_name, searched)
+            {
+                return Some(path);
             }
         }
     }
 
-    let devkitarm_bin_path = devkitarm_path.join("bin");
+    None
+}
 
-    let path = devkitarm_bin_path.join(&arm_none_eabi_name);
-    if path.exists() {
-        return Some(path);
+/// Looks for `name` (or `name.exe` on Windows) in `directory`, recording every
+/// path that was checked in `searched` along with the variable it came from.
+fn find_executable_in(
+    source: &str,
+    directory: &Path,
+    name: &str,
+    searched: &mut Vec<String>,
+) -> Option<PathBuf> {
+    let mut candidates = vec![directory.join(name)];
+    if cfg!(target_os = "windows") {
+        candidates.push(directory.join(format!("{name}.exe")));
     }
 
-    #[cfg(target_os = "windows")]
-    {
-        let path = devkitarm_bin_path.join(&arm_none_eabi_name_exe);
-        if path.exists() {
-            return Some(path);
+    for candidate in candidates {
+        if is_executable(&candidate) {
+            return Some(candidate);
         }
+        searched.push(format!("{source}: {}", candidate.display()));
+    }
+    None
+}
 
-        // The path for devkitARM is usually /opt/devkitPro/devkitARM
-        if let Ok(path_without_opt) = path.strip_prefix("/opt") {
-            let path_from_c = Path::new("C:\\").join(path_without_opt);
-            if path_from_c.exists() {
-                return Some(path_from_c);
-            }
-        }
+fn is_executable(path: &Path) -> bool {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return false;
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt as _;
+        metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
     }
 
-    None
+    #[cfg(not(unix))]
+    {
+        metadata.is_file()
+    }
 }
 
-fn find_arm_binary(name: &str) -> Option<PathBuf> {
+fn find_arm_binary(name: &str) -> io::Result<PathBuf> {
     static ARM_BINARY_CACHE: OnceLock<Mutex<HashMap<String, PathBuf>>> = OnceLock::new();
 
     let cache = ARM_BINARY_CACHE.get_or_init(Default::default);
     let mut cache = cache.lock().unwrap();
 
     if let Some(path) = cache.get(name) {
-        return Some(path.clone());
+        return Ok(path.clone());
     }
 
-    if let Some(uncached_path) = find_arm_binary_uncached(name) {
+    let mut searched = Vec::new();
+    if let Some(uncached_path) = find_arm_binary_uncached(name, &mut searched) {
         cache.insert(name.to_owned(), uncached_path.clone());
-        Some(uncached_path)
-    } else {
-        None
+        return Ok(uncached_path);
     }
+
+    let mut message = format!("no executable found for `arm-none-eabi-{name}`, searched:");
+    for location in searched {
+        message.push_str("\n  ");
+        message.push_str(&location);
+    }
+    Err(io::Error::new(io::ErrorKind::NotFound, message))
 }
 
 fn run_arm_executable(
@@ -112,30 +133,18 @@ fn run_arm_executable(
     args: &[&OsStr],
     stdin: Option<&str>,
 ) -> io::Result<process::ExitStatus> {
-    println!("executing: {name:?} {args:?}");
-
-    let binary_path = find_arm_binary(name)
-        .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "binary for program not found"))?;
+    run_arm_executable_with_output(name, args, stdin).map(|output| output.status)
+}
 
-    let mut cmd = Command::new(binary_path);
-    cmd.args(args);
-    cmd.stdout(process::Stdio::piped());
-    cmd.stderr(process::Stdio::piped());
-    if stdin.is_some() {
-        cmd.stdin(process::Stdio::piped());
-    } else {
-        cmd.stdin(process::Stdio::null());
-    }
-    let child = cmd.spawn()?;
+fn run_arm_executable_with_output(
+    name: &str,
+    args: &[&OsStr],
+    stdin: Option<&str>,
+) -> io::Result<process::Output> {
+    println!("executing: {name:?} {args:?}");
 
-    if let Some(stdin) = stdin {
-        child
-            .stdin
-            .as_ref()
-            .expect("no stdin")
-            .write_all(stdin.as_bytes())?;
-    }
-    let output = child.wait_with_output()?;
+    let binary_path = find_arm_binary(name)?;
+    let output = run_with_timeout(name, &binary_path, args, stdin, toolchain_timeout())?;
 
     let mut had_output = false;
 
@@ -175,7 +184,114 @@ fn run_arm_executable(
         println!("  err: {}", line.trim_end());
     }
 
-    Ok(output.status)
+    Ok(output)
+}
+
+/// The default value for [`set_toolchain_timeout`].
+pub const DEFAULT_TOOLCHAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+static TOOLCHAIN_TIMEOUT: Mutex<Duration> = Mutex::new(DEFAULT_TOOLCHAIN_TIMEOUT);
+
+/// Sets how long a toolchain program (`as`, `ld`, ...) may run before it is killed
+/// and the invocation fails with [`io::ErrorKind::TimedOut`].
+pub fn set_toolchain_timeout(timeout: Duration) {
+    *TOOLCHAIN_TIMEOUT
+        .lock()
+        .unwrap_or_else(|err| err.into_inner()) = timeout;
+}
+
+fn toolchain_timeout() -> Duration {
+    *TOOLCHAIN_TIMEOUT
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+}
+
+/// Runs the program at `binary_path` to completion, killing it if it is still running
+/// after `timeout`. Arguments are passed straight to the program without going
+/// through a shell, so paths containing spaces don't need any quoting. stdout and
+/// stderr are read on separate threads so that a chatty child can't block on a full
+/// pipe while we wait for it.
+fn run_with_timeout(
+    name: &str,
+    binary_path: &Path,
+    args: &[&OsStr],
+    stdin: Option<&str>,
+    timeout: Duration,
+) -> io::Result<process::Output> {
+    let mut cmd = Command::new(binary_path);
+    cmd.args(args);
+    cmd.stdout(process::Stdio::piped());
+    cmd.stderr(process::Stdio::piped());
+    if stdin.is_some() {
+        cmd.stdin(process::Stdio::piped());
+    } else {
+        cmd.stdin(process::Stdio::null());
+    }
+    let mut child = cmd.spawn()?;
+
+    let child_stdin = child.stdin.take();
+    let mut child_stdout = child.stdout.take().expect("no stdout");
+    let mut child_stderr = child.stderr.take().expect("no stderr");
+
+    std::thread::scope(|scope| {
+        // Dropping stdin once everything is written closes it so the child sees EOF.
+        let stdin_writer = scope.spawn(move || match (child_stdin, stdin) {
+            (Some(mut child_stdin), Some(stdin)) => child_stdin.write_all(stdin.as_bytes()),
+            _ => Ok(()),
+        });
+        let stdout_reader = scope.spawn(move || {
+            let mut buffer = Vec::new();
+            io::Read::read_to_end(&mut child_stdout, &mut buffer).map(|_| buffer)
+        });
+        let stderr_reader = scope.spawn(move || {
+            let mut buffer = Vec::new();
+            io::Read::read_to_end(&mut child_stderr, &mut buffer).map(|_| buffer)
+        });
+
+        let status = match wait_until(&mut child, Instant::now() + timeout) {
+            Ok(Some(status)) => status,
+            result => {
+                // The reader threads only finish once the child's end of the pipes
+                // is closed, so the child must be gone before leaving the scope.
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(result.err().unwrap_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        format!("`{name}` timed out after {timeout:?}"),
+                    )
+                }));
+            }
+        };
+
+        // A child that exits without reading all of its input is not an error here,
+        // its exit status says whether it failed.
+        let _ = stdin_writer.join().expect("stdin writer panicked");
+        let stdout = stdout_reader.join().expect("stdout reader panicked")?;
+        let stderr = stderr_reader.join().expect("stderr reader panicked")?;
+
+        Ok(process::Output {
+            status,
+            stdout,
+            stderr,
+        })
+    })
+}
+
+/// Waits for `child` to exit. Returns `None` if it is still running at `deadline`.
+fn wait_until(
+    child: &mut process::Child,
+    deadline: Instant,
+) -> io::Result<Option<process::ExitStatus>> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            return Ok(None);
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
 }
 
 static INTERNAL_TEMPFILE_DIRECTORY: OnceLock<PathBuf> = OnceLock::new();
@@ -200,13 +316,66 @@ fn temppath_internal() -> io::Result<TempPath> {
     tempfile_internal().map(|file| file.into_temp_path())
 }
 
+/// The symbols defined by an assembled program mapped to their addresses.
+pub type Symbols = HashMap<String, u32>;
+
+fn read_symbols(elf_file_path: &Path) -> io::Result<Symbols> {
+    let output = run_arm_executable_with_output("nm", &[elf_file_path.as_ref()], None)?;
+    if !output.status.success() {
+        return Err(io::Error::other("failed to read symbols"));
+    }
+
+    // Defined symbols are listed as `<address> <type> <name>`. Undefined symbols
+    // have no address and are skipped.
+    let symbols = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let address = u32::from_str_radix(parts.next()?, 16).ok()?;
+            let name = parts.nth(1)?;
+            Some((name.to_owned(), address))
+        })
+        .collect();
+    Ok(symbols)
+}
+
 pub mod arm {
-    use crate::temppath_internal;
+    use crate::{cache::CacheKey, read_symbols, temppath_internal, Symbols};
 
     use super::{run_arm_executable, LinkerScript};
     use std::{borrow::Cow, io, path::Path};
 
     pub fn assemble(source: &str, linker_script: LinkerScript) -> io::Result<Vec<u8>> {
+        assemble_internal(source, linker_script, false).map(|(binary, _)| binary)
+    }
+
+    /// Like [`assemble`] but also returns the address of every symbol in the
+    /// linked program, so several snippets can be assembled at once and found
+    /// again by their labels.
+    pub fn assemble_with_symbols(
+        source: &str,
+        linker_script: LinkerScript,
+    ) -> io::Result<(Vec<u8>, Symbols)> {
+        let (binary, symbols) = assemble_internal(source, linker_script, true)?;
+        Ok((binary, symbols.unwrap_or_default()))
+    }
+
+    fn assemble_internal(
+        source: &str,
+        linker_script: LinkerScript,
+        with_symbols: bool,
+    ) -> io::Result<(Vec<u8>, Option<Symbols>)> {
+        let key = CacheKey::new("arm", source, &linker_script.0.source, with_symbols);
+        crate::cache::get_or_assemble(key, || {
+            assemble_uncached(source, linker_script, with_symbols)
+        })
+    }
+
+    fn assemble_uncached(
+        source: &str,
+        linker_script: LinkerScript,
+        with_symbols: bool,
+    ) -> io::Result<(Vec<u8>, Option<Symbols>)> {
         let mut source = Cow::Borrowed(source);
         if !source.ends_with('\n') {
             let mut new_source = String::with_capacity(source.len() + 1);
@@ -214,7 +383,7 @@ pub mod arm {
             new_source.push('\n');
             source = Cow::Owned(new_source);
         }
-        let linker_script_path: &Path = &linker_script.0;
+        let linker_script_path: &Path = &linker_script.0.path;
 
         let object_file_path = temppath_internal()?;
         let as_args = &[
@@ -269,17 +438,52 @@ pub mod arm {
             return Err(io::Error::new(io::ErrorKind::Other, message));
         }
 
-        std::fs::read(bin_file_path)
+        let symbols = if with_symbols {
+            Some(read_symbols(elf_file_path.as_ref())?)
+        } else {
+            None
+        };
+        Ok((std::fs::read(bin_file_path)?, symbols))
     }
 }
 
 pub mod thumb {
-    use crate::temppath_internal;
+    use crate::{cache::CacheKey, read_symbols, temppath_internal, Symbols};
 
     use super::{run_arm_executable, LinkerScript};
     use std::{borrow::Cow, io, path::Path};
 
     pub fn assemble(source: &str, linker_script: LinkerScript) -> io::Result<Vec<u8>> {
+        assemble_internal(source, linker_script, false).map(|(binary, _)| binary)
+    }
+
+    /// Like [`assemble`] but also returns the address of every symbol in the
+    /// linked program, so several snippets can be assembled at once and found
+    /// again by their labels.
+    pub fn assemble_with_symbols(
+        source: &str,
+        linker_script: LinkerScript,
+    ) -> io::Result<(Vec<u8>, Symbols)> {
+        let (binary, symbols) = assemble_internal(source, linker_script, true)?;
+        Ok((binary, symbols.unwrap_or_default()))
+    }
+
+    fn assemble_internal(
+        source: &str,
+        linker_script: LinkerScript,
+        with_symbols: bool,
+    ) -> io::Result<(Vec<u8>, Option<Symbols>)> {
+        let key = CacheKey::new("thumb", source, &linker_script.0.source, with_symbols);
+        crate::cache::get_or_assemble(key, || {
+            assemble_uncached(source, linker_script, with_symbols)
+        })
+    }
+
+    fn assemble_uncached(
+        source: &str,
+        linker_script: LinkerScript,
+        with_symbols: bool,
+    ) -> io::Result<(Vec<u8>, Option<Symbols>)> {
         let mut source = Cow::Borrowed(source);
         if !source.ends_with('\n') {
             let mut new_source = String::with_capacity(source.len() + 1);
@@ -287,7 +491,7 @@ pub mod thumb {
             new_source.push('\n');
             source = Cow::Owned(new_source);
         }
-        let linker_script_path: &Path = &linker_script.0;
+        let linker_script_path: &Path = &linker_script.0.path;
 
         let object_file_path = temppath_internal()?;
         let as_args = &[
@@ -345,20 +549,45 @@ pub mod thumb {
             return Err(io::Error::new(io::ErrorKind::Other, message));
         }
 
-        std::fs::read(bin_file_path)
+        let symbols = if with_symbols {
+            Some(read_symbols(elf_file_path.as_ref())?)
+        } else {
+            None
+        };
+        Ok((std::fs::read(bin_file_path)?, symbols))
     }
 }
 
 #[derive(Clone)]
-pub struct LinkerScript(Arc<TempPath>);
+pub struct LinkerScript(Arc<LinkerScriptFile>);
 #[derive(Clone)]
-pub struct LinkerScriptWeakRef(Weak<TempPath>);
+pub struct LinkerScriptWeakRef(Weak<LinkerScriptFile>);
+
+struct LinkerScriptFile {
+    path: TempPath,
+    /// Kept around so that programs can be cached by the linker script's contents.
+    source: String,
+}
 
 impl LinkerScript {
     pub fn new(source: &str) -> io::Result<LinkerScript> {
         let mut file = tempfile_internal()?;
         file.write_all(source.as_bytes())?;
-        Ok(LinkerScript(Arc::new(file.into_temp_path())))
+        Ok(LinkerScript(Arc::new(LinkerScriptFile {
+            path: file.into_temp_path(),
+            source: source.to_owned(),
+        })))
+    }
+
+    /// A linker script that places everything at address 0. See [`SIMPLE_LINKER_SCRIPT`].
+    pub fn simple() -> io::Result<LinkerScript> {
+        Self::new(SIMPLE_LINKER_SCRIPT)
+    }
+
+    /// A linker script that places code in GBA ROM and the BSS in IWRAM.
+    /// See [`GBA_LINKER_SCRIPT`].
+    pub fn gba() -> io::Result<LinkerScript> {
+        Self::new(GBA_LINKER_SCRIPT)
     }
 
     pub fn weak(&self) -> LinkerScriptWeakRef {
@@ -416,3 +645,134 @@ SECTIONS
         *(.ARM.attributes);
     }
 }"#;
+
+/// Places `.text`, `.data` and `.rodata` in GBA ROM starting at 0x08000000 and `.bss`
+/// in IWRAM starting at 0x03000000. The assembled binary only contains the ROM.
+///
+/// `.data` stays in ROM instead of being copied to RAM because test programs don't have
+/// any startup code, so it can be read but not written on the GBA.
+pub const GBA_LINKER_SCRIPT: &str = r#"
+ENTRY(_start);
+MEMORY
+{
+    iwram (rwx) : ORIGIN = 0x03000000, LENGTH = 32K
+    rom (rx)    : ORIGIN = 0x08000000, LENGTH = 32M
+}
+SECTIONS
+{
+    /* Place special section .text.prologue before everything else */
+    .text : {
+        . = ALIGN(4);
+        *(.text.prologue);
+        *(.text*);
+        . = ALIGN(4);
+    } > rom
+
+    /* Output the data sections */
+    .data : {
+        . = ALIGN(4);
+        *(.data*);
+    } > rom
+
+    .rodata : {
+        . = ALIGN(4);
+        *(.rodata*);
+    } > rom
+
+    /* The BSS section for uninitialized data */
+    .bss (NOLOAD) : {
+        . = ALIGN(4);
+        __bss_start = .;
+        *(COMMON);
+        *(.bss);
+        . = ALIGN(4);
+        __bss_end = .;
+    } > iwram
+
+    /* Size of the BSS section in case it is needed */
+    __bss_size = ((__bss_end)-(__bss_start));
+
+    /* Remove the note that may be placed before the code by LD */
+    /DISCARD/ : {
+        *(.note.gnu.build-id);
+        *(.ARM.attributes);
+    }
+}"#;
+
+#[cfg(test)]
+mod tests {
+    use crate::LinkerScript;
+
+    #[test]
+    fn gba_linker_script_regions() {
+        let source = "
+            .global _start
+            _start:
+                ldr r0, =value
+                ldr r1, =counter
+                b _start
+            .data
+            value:
+                .word 0xDEADBEEF
+            .bss
+            counter:
+                .space 4
+        ";
+        let script = LinkerScript::gba().unwrap();
+        let (binary, symbols) = crate::arm::assemble_with_symbols(source, script).unwrap();
+
+        assert_eq!(symbols["_start"], 0x08000000);
+        let value = symbols["value"];
+        assert!(
+            (0x08000000..0x0A000000).contains(&value),
+            "value at {value:#X}"
+        );
+        let offset = (value - 0x08000000) as usize;
+        assert_eq!(binary[offset..offset + 4], 0xDEADBEEFu32.to_le_bytes());
+        assert_eq!(symbols["counter"], 0x03000000);
+        assert_eq!(symbols["__bss_size"], 4);
+        // The BSS isn't part of the ROM.
+        assert_eq!(binary.len(), offset + 4);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn toolchain_invocation_times_out() {
+        let sleep = which::which("sleep").expect("sleep not found");
+        let err = super::run_with_timeout(
+            "sleep",
+            &sleep,
+            &["5".as_ref()],
+            None,
+            std::time::Duration::from_millis(100),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+        assert!(err.to_string().contains("sleep"), "{err}");
+
+        let output = super::run_with_timeout(
+            "sleep",
+            &sleep,
+            &["0".as_ref()],
+            None,
+            std::time::Duration::from_secs(5),
+        )
+        .unwrap();
+        assert!(output.status.success());
+    }
+
+    #[test]
+    fn missing_binary_lists_searched_locations() {
+        let err = super::find_arm_binary("pyrite-missing-tool").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+
+        let message = err.to_string();
+        assert!(
+            message.contains("arm-none-eabi-pyrite-missing-tool"),
+            "{message}"
+        );
+        assert!(message.contains("PATH"), "{message}");
+        assert!(message.contains("ARM_BINARIES_DIR"), "{message}");
+        assert!(message.contains("DEVKITARM"), "{message}");
+    }
+}
@@ -1,11 +1,11 @@
-use std::fmt::Write;
+use std::{fmt::Write, sync::OnceLock};
 
 use util::bits::BitOps as _;
 
 use crate::{
     common::{
         Condition, DataProc, DataTransferDirection, DataTransferIndexing, DataTransferOp, Register,
-        RegisterList, RegisterOrImmediate, SDTDataType,
+        RegisterList, RegisterOrImmediate, SDTDataType, Shift,
     },
     MemoryView,
 };
@@ -23,7 +23,7 @@ const DISASM_TABLE: &[(u32, u32, ArmDisasmFn)] = &[
     (0x0E000000, 0x04000000, disasm_single_data_transfer), // single data transfer immediate offset
     (0x0E000010, 0x06000000, disasm_single_data_transfer), // single data transfer offset shift by imm
     (0x0FB00FF0, 0x01000090, disasm_single_data_swap),
-    (0x0E400F90, 0x00000090, disasm_signed_and_halfword_data_transfer),
+    (0x0E000090, 0x00000090, disasm_signed_and_halfword_data_transfer),
     (0x0F000000, 0x0F000000, disasm_software_interrupt),
     (0x0E000000, 0x08000000, disasm_block_data_transfer),
     (0x0E000000, 0x0A000000, disasm_b_and_bl),
@@ -33,7 +33,14 @@ const DISASM_TABLE: &[(u32, u32, ArmDisasmFn)] = &[
 ];
 
 pub fn disasm(instr: u32, address: u32) -> ArmInstr {
-    for &(mask, check, disasm_fn) in DISASM_TABLE {
+    match dispatch_table()[dispatch_index(instr)] {
+        Dispatch::Entry(index) => disasm_scan(&DISASM_TABLE[index..=index], instr, address),
+        Dispatch::Scan(start) => disasm_scan(&DISASM_TABLE[start..], instr, address),
+    }
+}
+
+fn disasm_scan(table: &[(u32, u32, ArmDisasmFn)], instr: u32, address: u32) -> ArmInstr {
+    for &(mask, check, disasm_fn) in table {
         if instr & mask == check {
             #[cfg(test)]
             {
@@ -48,6 +55,50 @@ pub fn disasm(instr: u32, address: u32) -> ArmInstr {
     ArmInstr::Undefined { cond, instr }
 }
 
+/// Bits 20-27 and 4-7, which are enough to pick the instruction class for most
+/// instructions.
+const DISPATCH_BITS: u32 = 0x0FF000F0;
+
+fn dispatch_index(instr: u32) -> usize {
+    (((instr >> 16) & 0xFF0) | ((instr >> 4) & 0xF)) as usize
+}
+
+#[derive(Clone, Copy)]
+enum Dispatch {
+    /// Every instruction in the bucket matches this entry of [`DISASM_TABLE`].
+    Entry(usize),
+    /// Whether an entry matches depends on bits outside of [`DISPATCH_BITS`], so the
+    /// table is scanned starting at the first entry that can match. This is past the
+    /// end of the table if no entry can match.
+    Scan(usize),
+}
+
+/// Lookup table keyed by [`dispatch_index`] so that [`disasm`] only has to scan
+/// [`DISASM_TABLE`] for the few buckets where the decode bits are ambiguous.
+fn dispatch_table() -> &'static [Dispatch; 4096] {
+    static TABLE: OnceLock<[Dispatch; 4096]> = OnceLock::new();
+
+    TABLE.get_or_init(|| {
+        let mut table = [Dispatch::Scan(0); 4096];
+        for (index, dispatch) in table.iter_mut().enumerate() {
+            let index = index as u32;
+            let bits = ((index & 0xFF0) << 16) | ((index & 0xF) << 4);
+            let first_match = DISASM_TABLE
+                .iter()
+                .position(|&(mask, check, _)| bits & mask & DISPATCH_BITS == check & DISPATCH_BITS);
+
+            *dispatch = match first_match {
+                Some(entry) if DISASM_TABLE[entry].0 & !DISPATCH_BITS == 0 => {
+                    Dispatch::Entry(entry)
+                }
+                Some(entry) => Dispatch::Scan(entry),
+                None => Dispatch::Scan(DISASM_TABLE.len()),
+            };
+        }
+        table
+    })
+}
+
 pub fn disasm_bx(instr: u32, _address: u32) -> ArmInstr {
     let cond = Condition::from(instr.get_bit_range(28..=31));
     ArmInstr::BranchAndExchange {
@@ -209,20 +260,26 @@ pub fn disasm_single_data_transfer(instr: u32, _address: u32) -> ArmInstr {
 
 pub fn disasm_signed_and_halfword_data_transfer(instr: u32, _address: u32) -> ArmInstr {
     let cond = Condition::from(instr.get_bit_range(28..=31));
+    let (op, data_type) = match (instr.get_bit(20), instr.get_bit_range(5..=6)) {
+        // Bits 8-11 must be zero in the register offset form.
+        _ if !instr.get_bit(22) && instr.get_bit_range(8..=11) != 0 => {
+            return ArmInstr::Undefined { cond, instr }
+        }
+        (true, 0b01) => (DataTransferOp::Load, SDTDataType::Halfword),
+        (true, 0b10) => (DataTransferOp::Load, SDTDataType::SignedByte),
+        (true, 0b11) => (DataTransferOp::Load, SDTDataType::SignedHalfword),
+        (false, 0b01) => (DataTransferOp::Store, SDTDataType::Halfword),
+        (false, 0b10) => (DataTransferOp::Load, SDTDataType::Doubleword),
+        (false, 0b11) => (DataTransferOp::Store, SDTDataType::Doubleword),
+        // SH=00 is SWP or a multiply, which are matched before this if they are valid.
+        // Overlapping table entries can still send anything here so this must not panic.
+        _ => return ArmInstr::Undefined { cond, instr },
+    };
+
     ArmInstr::SingleDataTransfer {
         cond,
-        op: if instr.get_bit(20) {
-            DataTransferOp::Load
-        } else {
-            DataTransferOp::Store
-        },
-        data_type: match instr.get_bit_range(5..=6) {
-            0b00 => unreachable!("SWP not LDRH/STRH/LDRSB/LDRSH"),
-            0b01 => SDTDataType::Halfword,
-            0b10 => SDTDataType::SignedByte,
-            0b11 => SDTDataType::SignedHalfword,
-            _ => unreachable!(),
-        },
+        op,
+        data_type,
         indexing: if instr.get_bit(24) {
             DataTransferIndexing::Pre
         } else {
@@ -236,7 +293,12 @@ pub fn disasm_signed_and_halfword_data_transfer(instr: u32, _address: u32) -> Ar
         writeback: instr.get_bit(21),
         rn: Register::from(instr.get_bit_range(16..=19)),
         rd: Register::from(instr.get_bit_range(12..=15)),
-        offset: RegisterOrImmediate::Register(Register::from(instr.get_bit_range(0..=3))),
+        offset: if instr.get_bit(22) {
+            let offset = (instr.get_bit_range(8..=11) << 4) | instr.get_bit_range(0..=3);
+            RegisterOrImmediate::Immediate(offset)
+        } else {
+            RegisterOrImmediate::Register(Register::from(instr.get_bit_range(0..=3)))
+        },
     }
 }
 
@@ -286,6 +348,7 @@ pub fn disasm_software_interrupt(instr: u32, _address: u32) -> ArmInstr {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ArmInstr {
     DataProc {
         cond: Condition,
@@ -432,6 +495,7 @@ impl ArmInstr {
                     SDTDataType::Halfword => "h",
                     SDTDataType::SignedByte => "sb",
                     SDTDataType::SignedHalfword => "sh",
+                    SDTDataType::Doubleword => "d",
                     SDTDataType::Word => "",
                 };
                 let t = if matches!(data_type, SDTDataType::Word | SDTDataType::Byte)
@@ -642,6 +706,12 @@ impl ArmInstr {
                             let data = m.view8(data_addr) as i8;
                             write!(f, "{rd} = 0x{data:02x}")
                         }
+                        SDTDataType::Doubleword => {
+                            let lo = m.view32(data_addr & !0x03);
+                            let hi = m.view32((data_addr & !0x03).wrapping_add(4));
+                            let rd_hi = Register::from(u32::from(rd).wrapping_add(1) & 0xF);
+                            write!(f, "{rd} = 0x{lo:08x}, {rd_hi} = 0x{hi:08x}")
+                        }
                     }
                 } else {
                     write!(f, "{rd} = [0x{data_addr:08x}]")
@@ -668,6 +738,14 @@ impl ArmInstr {
         crate::Comment(self, addr, m)
     }
 
+    pub fn full<'s>(
+        &'s self,
+        addr: u32,
+        m: Option<&'s dyn MemoryView>,
+    ) -> crate::Full<'s, 's, Self> {
+        crate::Full(self, addr, m)
+    }
+
     pub fn condition(&self) -> Condition {
         match self {
             ArmInstr::Undefined { cond, .. } => *cond,
@@ -684,14 +762,321 @@ impl ArmInstr {
             ArmInstr::SoftwareInterrupt { cond, .. } => *cond,
         }
     }
+
+    /// Returns false if the instruction is probably data or code for the other
+    /// instruction set. This is a conservative guess: undefined instructions, the
+    /// never condition, a zero word (`andeq r0, r0, r0`) and encodings whose behavior
+    /// is unpredictable on the ARM7TDMI are rejected. Everything else is assumed to
+    /// be valid.
+    pub fn looks_valid(&self) -> bool {
+        if matches!(self.condition(), Condition::Nv) {
+            return false;
+        }
+
+        let pc = Register::R15;
+        match *self {
+            ArmInstr::Undefined { .. } => false,
+            ArmInstr::DataProc {
+                cond: Condition::Eq,
+                proc: DataProc::And,
+                s: false,
+                rd: Register::R0,
+                rn: Register::R0,
+                op2: RegisterOrImmediate::Register(Register::R0),
+            } => false,
+            ArmInstr::Multiply { rd, rn, rs, rm, .. } => {
+                rd != rm && ![rd, rn, rs, rm].contains(&pc)
+            }
+            ArmInstr::MultiplyLong {
+                rd_hi,
+                rd_lo,
+                rs,
+                rm,
+                ..
+            } => rd_hi != rd_lo && ![rd_hi, rd_lo, rs, rm].contains(&pc),
+            ArmInstr::SingleDataTransfer {
+                indexing,
+                writeback,
+                rn,
+                ..
+            } => !(rn == pc && (writeback || indexing == DataTransferIndexing::Post)),
+            ArmInstr::SingleDataSwap { rn, rd, rm, .. } => ![rn, rd, rm].contains(&pc),
+            ArmInstr::BlockDataTransfer { rn, registers, .. } => rn != pc && !registers.is_empty(),
+            _ => true,
+        }
+    }
 }
 
+impl ArmInstr {
+    /// Encodes the instruction back into its 32-bit ARM representation. `address` is
+    /// the address the instruction will be placed at and is only used to compute the
+    /// offset of branches, mirroring the `address` argument of [`disasm`].
+    ///
+    /// Every variant can be encoded, but some operands cannot be represented:
+    /// - data processing and `msr` immediates that are not an 8-bit value rotated by
+    ///   an even amount
+    /// - `msr` to all PSR fields from an immediate
+    /// - branch targets that are not word aligned or are further than 32MB away
+    /// - single data transfer offsets that are too large or shifted by a register
+    /// - halfword and signed data transfer offsets that are shifted
+    ///
+    /// These return an [`EncodeError`].
+    pub fn encode(&self, address: u32) -> Result<u32, EncodeError> {
+        let cond = u32::from(self.condition()) << 28;
+
+        let bits = match *self {
+            ArmInstr::Undefined { instr, .. } => return Ok(instr),
+
+            ArmInstr::DataProc {
+                proc,
+                s,
+                rd,
+                rn,
+                op2,
+                ..
+            } => {
+                let op2 = match op2 {
+                    RegisterOrImmediate::Immediate(imm) => encode_rotated_imm(imm)? | (1 << 25),
+                    RegisterOrImmediate::Register(rm) => u32::from(rm),
+                    RegisterOrImmediate::ShiftedRegister(rm, shift) => {
+                        u32::from(rm) | shift.encode()
+                    }
+                };
+                (u32::from(proc) << 21)
+                    | ((s as u32) << 20)
+                    | (u32::from(rn) << 16)
+                    | (u32::from(rd) << 12)
+                    | op2
+            }
+
+            ArmInstr::BranchAndExchange { rn, .. } => 0x012FFF10 | u32::from(rn),
+
+            ArmInstr::Branch { target, link, .. } => {
+                let offset = target.wrapping_sub(address.wrapping_add(8));
+                if offset & 0x3 != 0 {
+                    return Err(EncodeError::MisalignedBranchTarget(target));
+                }
+                let offset = (offset as i32) >> 2;
+                if !(-0x800000..0x800000).contains(&offset) {
+                    return Err(EncodeError::BranchOutOfRange(target));
+                }
+                0x0A000000 | ((link as u32) << 24) | (offset as u32 & 0xFFFFFF)
+            }
+
+            ArmInstr::PsrToRegister { rd, src, .. } => {
+                0x010F0000 | (src.is_spsr() as u32) << 22 | (u32::from(rd) << 12)
+            }
+
+            ArmInstr::RegisterToPsr { dst, src, .. } => {
+                let psr = (dst.is_spsr() as u32) << 22;
+                match (dst.flags_only(), src) {
+                    (false, RegisterOrImmediate::Register(rm)) => 0x0129F000 | psr | u32::from(rm),
+                    (true, RegisterOrImmediate::Register(rm)) => 0x0128F000 | psr | u32::from(rm),
+                    (true, RegisterOrImmediate::Immediate(imm)) => {
+                        0x0328F000 | psr | encode_rotated_imm(imm)?
+                    }
+                    _ => return Err(EncodeError::UnsupportedOperand),
+                }
+            }
+
+            ArmInstr::Multiply {
+                a,
+                s,
+                rd,
+                rn,
+                rs,
+                rm,
+                ..
+            } => {
+                0x00000090
+                    | ((a as u32) << 21)
+                    | ((s as u32) << 20)
+                    | (u32::from(rd) << 16)
+                    | (u32::from(rn) << 12)
+                    | (u32::from(rs) << 8)
+                    | u32::from(rm)
+            }
+
+            ArmInstr::MultiplyLong {
+                u,
+                a,
+                s,
+                rd_hi,
+                rd_lo,
+                rs,
+                rm,
+                ..
+            } => {
+                0x00800090
+                    | ((u as u32) << 22)
+                    | ((a as u32) << 21)
+                    | ((s as u32) << 20)
+                    | (u32::from(rd_hi) << 16)
+                    | (u32::from(rd_lo) << 12)
+                    | (u32::from(rs) << 8)
+                    | u32::from(rm)
+            }
+
+            ArmInstr::SingleDataTransfer {
+                op,
+                data_type,
+                direction,
+                indexing,
+                writeback,
+                rn,
+                rd,
+                offset,
+                ..
+            } => {
+                let common = ((indexing == DataTransferIndexing::Pre) as u32) << 24
+                    | ((direction == DataTransferDirection::Up) as u32) << 23
+                    | (writeback as u32) << 21
+                    | ((op == DataTransferOp::Load) as u32) << 20
+                    | (u32::from(rn) << 16)
+                    | (u32::from(rd) << 12);
+
+                match data_type {
+                    SDTDataType::Word | SDTDataType::Byte => {
+                        let offset = match offset {
+                            RegisterOrImmediate::Immediate(imm) if imm <= 0xFFF => imm,
+                            RegisterOrImmediate::Immediate(imm) => {
+                                return Err(EncodeError::ImmediateOutOfRange(imm))
+                            }
+                            RegisterOrImmediate::Register(rm) => (1 << 25) | u32::from(rm),
+                            RegisterOrImmediate::ShiftedRegister(rm, Shift::Imm(shift)) => {
+                                (1 << 25) | u32::from(rm) | shift.encode()
+                            }
+                            RegisterOrImmediate::ShiftedRegister(_, Shift::Reg(_)) => {
+                                return Err(EncodeError::UnsupportedOperand)
+                            }
+                        };
+                        let b = ((data_type == SDTDataType::Byte) as u32) << 22;
+                        0x04000000 | common | b | offset
+                    }
+
+                    SDTDataType::Halfword
+                    | SDTDataType::SignedByte
+                    | SDTDataType::SignedHalfword
+                    | SDTDataType::Doubleword => {
+                        // Doubleword transfers are encoded as "stores" of signed data.
+                        let (common, sh) = match (data_type, op) {
+                            (SDTDataType::Halfword, _) => (common, 0b01),
+                            (SDTDataType::SignedByte, _) => (common, 0b10),
+                            (SDTDataType::SignedHalfword, _) => (common, 0b11),
+                            (_, DataTransferOp::Load) => (common & !(1 << 20), 0b10),
+                            (_, DataTransferOp::Store) => (common, 0b11),
+                        };
+                        let offset = match offset {
+                            RegisterOrImmediate::Immediate(imm) if imm <= 0xFF => {
+                                (1 << 22) | ((imm & 0xF0) << 4) | (imm & 0xF)
+                            }
+                            RegisterOrImmediate::Immediate(imm) => {
+                                return Err(EncodeError::ImmediateOutOfRange(imm))
+                            }
+                            RegisterOrImmediate::Register(rm) => u32::from(rm),
+                            RegisterOrImmediate::ShiftedRegister(..) => {
+                                return Err(EncodeError::UnsupportedOperand)
+                            }
+                        };
+                        0x00000090 | common | (sh << 5) | offset
+                    }
+                }
+            }
+
+            ArmInstr::SingleDataSwap { b, rn, rd, rm, .. } => {
+                0x01000090
+                    | ((b as u32) << 22)
+                    | (u32::from(rn) << 16)
+                    | (u32::from(rd) << 12)
+                    | u32::from(rm)
+            }
+
+            ArmInstr::BlockDataTransfer {
+                op,
+                direction,
+                indexing,
+                w,
+                s,
+                rn,
+                registers,
+                ..
+            } => {
+                0x08000000
+                    | ((indexing == DataTransferIndexing::Pre) as u32) << 24
+                    | ((direction == DataTransferDirection::Up) as u32) << 23
+                    | (s as u32) << 22
+                    | (w as u32) << 21
+                    | ((op == DataTransferOp::Load) as u32) << 20
+                    | (u32::from(rn) << 16)
+                    | registers.bits() as u32
+            }
+
+            ArmInstr::SoftwareInterrupt { comment, .. } => 0x0F000000 | (comment & 0xFFFFFF),
+        };
+
+        Ok(cond | bits)
+    }
+}
+
+/// Encodes an immediate as an 8-bit value rotated right by an even amount,
+/// returning bits 0-11 of the instruction.
+fn encode_rotated_imm(imm: u32) -> Result<u32, EncodeError> {
+    (0..16)
+        .find(|rot| imm.rotate_left(rot * 2) <= 0xFF)
+        .map(|rot| (rot << 8) | imm.rotate_left(rot * 2))
+        .ok_or(EncodeError::ImmediateOutOfRange(imm))
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EncodeError {
+    /// The immediate cannot be represented in the instruction's immediate field.
+    ImmediateOutOfRange(u32),
+    /// The branch target is not word aligned.
+    MisalignedBranchTarget(u32),
+    /// The branch target is too far away from the branch.
+    BranchOutOfRange(u32),
+    /// The combination of operands has no encoding.
+    UnsupportedOperand,
+}
+
+impl std::fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncodeError::ImmediateOutOfRange(imm) => {
+                write!(f, "immediate 0x{imm:08x} cannot be encoded")
+            }
+            EncodeError::MisalignedBranchTarget(target) => {
+                write!(f, "branch target 0x{target:08x} is not word aligned")
+            }
+            EncodeError::BranchOutOfRange(target) => {
+                write!(f, "branch target 0x{target:08x} is out of range")
+            }
+            EncodeError::UnsupportedOperand => write!(f, "operand cannot be encoded"),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Psr {
     Cpsr(/* flags only */ bool),
     Spsr(/* flags only */ bool),
 }
 
+impl Psr {
+    pub fn is_spsr(&self) -> bool {
+        matches!(self, Psr::Spsr(_))
+    }
+
+    pub fn flags_only(&self) -> bool {
+        match *self {
+            Psr::Cpsr(flags_only) | Psr::Spsr(flags_only) => flags_only,
+        }
+    }
+}
+
 impl std::fmt::Display for Psr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -703,11 +1088,15 @@ impl std::fmt::Display for Psr {
 
 #[cfg(test)]
 mod tests {
-    use crate::arm::Condition;
+    use crate::{arm::Condition, common::RegisterOrImmediate};
 
     use super::disasm;
-    use arm_devkit::LinkerScriptWeakRef;
-    use std::sync::RwLock;
+    use arm_devkit::{LinkerScript, LinkerScriptWeakRef};
+    use std::{
+        collections::BTreeMap,
+        fmt::Write as _,
+        sync::{Mutex, RwLock},
+    };
     use util::bits::BitOps as _;
 
     #[test]
@@ -726,21 +1115,175 @@ mod tests {
         }
     }
 
+    #[test]
+    fn encode_round_trip() {
+        // (randomized bits, fixed bits) for each instruction class.
+        #[rustfmt::skip]
+        const CLASSES: &[(u32, u32)] = &[
+            (0xF1FFFFFF, 0x02000000), // dataproc immediate op2
+            (0xF1FFFFEF, 0x00000000), // dataproc op2 shift by imm
+            (0xF1FFFF6F, 0x00000010), // dataproc op2 shift by reg
+            (0xF1FFFFFF, 0x04000000), // single data transfer immediate offset
+            (0xF1FFFFEF, 0x06000000), // single data transfer offset shift by imm
+            (0xF1FFFF0F, 0x000000B0), // halfword data transfer
+            (0xF1FFFF0F, 0x000000D0), // signed byte data transfer and ldrd
+            (0xF1FFFF0F, 0x000000F0), // signed halfword data transfer and strd
+            (0xF1FFFFFF, 0x08000000), // block data transfer
+            (0xF1FFFFFF, 0x0A000000), // branch
+            (0xF0FFFFFF, 0x0F000000), // software interrupt
+            (0xF03FFF0F, 0x00000090), // multiply
+            (0xF07FFF0F, 0x00800090), // multiply long
+            (0xF04FF00F, 0x01000090), // single data swap
+        ];
+
+        let mut rand = util::wyhash::WyHash::new(0x5bd1e9955bd1e995);
+        for &(random_mask, fixed) in CLASSES {
+            for _ in 0..4096 {
+                let instr = (rand.next_rand() as u32 & random_mask) | fixed;
+                let address = (rand.next_rand() as u32) & !0x3;
+                let decoded = disasm(instr, address);
+                let encoded = decoded
+                    .encode(address)
+                    .unwrap_or_else(|err| panic!("failed to encode 0x{instr:08x}: {err}"));
+
+                // Fields that the decoder does not preserve exactly.
+                let dont_care = match decoded {
+                    // Rotated immediates can have more than one encoding.
+                    super::ArmInstr::DataProc {
+                        op2: RegisterOrImmediate::Immediate(_),
+                        ..
+                    }
+                    | super::ArmInstr::RegisterToPsr {
+                        src: RegisterOrImmediate::Immediate(_),
+                        ..
+                    } => 0x00000FFF,
+                    _ => 0,
+                };
+                assert_eq!(
+                    instr & !dont_care,
+                    encoded & !dont_care,
+                    "0x{instr:08x} re-encoded as 0x{encoded:08x}"
+                );
+                assert_eq!(
+                    format!("{:?}", disasm(encoded, address)),
+                    format!("{decoded:?}"),
+                    "0x{instr:08x} re-encoded as 0x{encoded:08x}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn dispatch_table_matches_scan() {
+        let rand = util::wyhash::WyHash::new(0x2d358dccaa6c78a5);
+        for bits in rand.take(1 << 16) {
+            let instr = bits as u32;
+            assert_eq!(
+                format!("{:?}", disasm(instr, 0x0)),
+                format!("{:?}", super::disasm_scan(super::DISASM_TABLE, instr, 0x0)),
+                "0x{instr:08x}"
+            );
+        }
+    }
+
+    #[test]
+    fn disasm_never_panics() {
+        let memory = [0xA5u8; 0x100];
+        let rand = util::wyhash::WyHash::new(0x9e3779b97f4a7c15);
+        for bits in rand.take(1 << 18) {
+            let instr = bits as u32;
+            let addr = (bits >> 32) as u32 & 0xFC;
+            let dis = disasm(instr, addr);
+            let _ = dis.full(addr, None).to_string();
+            let _ = dis.full(addr, Some(&&memory[..])).to_string();
+        }
+    }
+
+    #[test]
+    fn disasm_doubleword_transfers() {
+        // ldrd and strd are ARMv5TE instructions so they can't be assembled for the ARM7TDMI.
+        #[rustfmt::skip]
+        const CASES: &[(u32, &str, &str)] = &[
+            (0xE1C100D8, "ldrd", "r0, [r1, #0x8]"),
+            (0xE1A540D6, "ldrd", "r4, [r5, r6]!"),
+            (0xE04321F0, "strd", "r2, [r3], -#0x10"),
+            (0x11C100F0, "strned", "r0, [r1, #0x0]"),
+        ];
+
+        for &(instr, mnemonic, arguments) in CASES {
+            let dis = disasm(instr, 0x0);
+            assert_eq!(mnemonic, dis.mnemonic().to_string());
+            assert_eq!(arguments, dis.arguments().to_string());
+            assert_eq!(dis.encode(0x0), Ok(instr));
+        }
+
+        // ldrd r2, [pc, #0x8]
+        let dis = disasm(0xE1CF20D8, 0x0);
+        let mut memory = [0u8; 0x18];
+        memory[0x10..].copy_from_slice(&0x0123456789abcdef_u64.to_le_bytes());
+        assert_eq!("r2 = [0x00000010]", dis.comment(0, None).to_string());
+        assert_eq!(
+            "r2 = 0x89abcdef, r3 = 0x01234567",
+            dis.comment(0, Some(&&memory[..])).to_string()
+        );
+
+        // Not a swap, multiply or halfword transfer.
+        assert_eq!("undef", disasm(0xE1600090, 0x0).mnemonic().to_string());
+    }
+
+    #[test]
+    fn encode_errors() {
+        use super::{ArmInstr, EncodeError};
+
+        let instr = ArmInstr::DataProc {
+            cond: Condition::Al,
+            proc: crate::common::DataProc::Mov,
+            s: false,
+            rd: crate::common::Register::R0,
+            rn: crate::common::Register::R0,
+            op2: RegisterOrImmediate::Immediate(0x101),
+        };
+        assert_eq!(
+            instr.encode(0),
+            Err(EncodeError::ImmediateOutOfRange(0x101))
+        );
+
+        let instr = ArmInstr::Branch {
+            cond: Condition::Al,
+            target: 0x08000002,
+            link: false,
+        };
+        assert_eq!(
+            instr.encode(0x08000000),
+            Err(EncodeError::MisalignedBranchTarget(0x08000002))
+        );
+
+        let instr = ArmInstr::Branch {
+            cond: Condition::Al,
+            target: 0x04000000,
+            link: true,
+        };
+        assert_eq!(
+            instr.encode(0x00000000),
+            Err(EncodeError::BranchOutOfRange(0x04000000))
+        );
+    }
+
     macro_rules! make_test {
-        ($name:ident, $source:literal, $mnemonic:literal, $arguments:literal) => {
+        ($name:ident, $assemble:expr, $mnemonic:literal, $arguments:literal) => {
             #[test]
             fn $name() {
-                let asm = assemble_one($source).unwrap();
+                let asm = $assemble.unwrap();
                 let dis = disasm(asm, 0x0);
                 assert_eq!($mnemonic, dis.mnemonic().to_string());
                 assert_eq!($arguments, dis.arguments().to_string());
             }
         };
 
-        ($name:ident, $source:literal, $mnemonic:literal, $arguments:literal, $comment:literal) => {
+        ($name:ident, $assemble:expr, $mnemonic:literal, $arguments:literal, $comment:literal) => {
             #[test]
             fn $name() {
-                let asm = assemble_one($source).unwrap();
+                let asm = $assemble.unwrap();
                 let dis = disasm(asm, 0x0);
                 assert_eq!($mnemonic, dis.mnemonic().to_string());
                 assert_eq!($arguments, dis.arguments().to_string());
@@ -750,14 +1293,25 @@ mod tests {
     }
 
     macro_rules! make_tests {
+        // Assembles every source in the group with a single toolchain invocation. Only
+        // usable for instructions that are encoded the same way at any address.
+        (batched; $([$name:ident, $source:literal, $mnemonic:literal, $arguments:literal $(, $comment:literal)?]),+ $(,)?) => {
+            make_tests!(@batched [$($source),+]; $([$name, $source, $mnemonic, $arguments $(, $comment)?]),+);
+        };
+
+        (@batched $sources:tt; $([$name:ident, $source:literal, $mnemonic:literal, $arguments:literal $(, $comment:literal)?]),+) => {
+            $(make_test!($name, assemble_batched(&$sources, $source), $mnemonic, $arguments $(, $comment)?);)+
+        };
+
         ($([$name:ident, $source:literal, $mnemonic:literal, $arguments:literal $(, $comment:literal)?]),+ $(,)?) => {
-            $(make_test!($name, $source, $mnemonic, $arguments $(, $comment)?);)+
+            $(make_test!($name, assemble_one($source), $mnemonic, $arguments $(, $comment)?);)+
         };
     }
 
     // DATA PROCESSING INSTRUCTIONS
     #[rustfmt::skip]
     make_tests! {
+        batched;
         // AND
         [disasm_and_imm, "and r0, r1, #0x4", "and", "r0, r1, #0x4", "rhs = 4"],
         [disasm_ands_imm, "ands r0, r1, #0x4", "ands", "r0, r1, #0x4"],
@@ -1018,6 +1572,7 @@ mod tests {
     // CONDITION CODES
     #[rustfmt::skip]
     make_tests! {
+        batched;
         // AND / ANDS
         [disasm_and_eq, "andeq r0, r1, #0x4", "andeq", "r0, r1, #0x4"],
         [disasm_ands_eq, "andeqs r0, r1, #0x4", "andeqs", "r0, r1, #0x4"],
@@ -1082,6 +1637,7 @@ mod tests {
     // REGISTERS
     #[rustfmt::skip]
     make_tests! {
+        batched;
         [disasm_mov_r0, "mov r0, r0", "mov", "r0, r0"],
         [disasm_mov_r1, "mov r0, r1", "mov", "r0, r1"],
         [disasm_mov_r2, "mov r0, r2", "mov", "r0, r2"],
@@ -1113,6 +1669,7 @@ mod tests {
     // PSR transfer
     #[rustfmt::skip]
     make_tests! {
+        batched;
         [disasm_mrs, "mrs r8, cpsr_all", "mrs", "r8, cpsr_all"],
         [disasm_msr_cpsr_all, "msr cpsr_all, r9", "msr", "cpsr_all, r9"],
         [disasm_msr_spsr_all, "msr spsr_all, r9", "msr", "spsr_all, r9"],
@@ -1125,6 +1682,7 @@ mod tests {
     // Multiply
     #[rustfmt::skip]
     make_tests! {
+        batched;
         [disasm_mul, "mul r0, r1, r2", "mul", "r0, r1, r2"],
         [disasm_muls, "muls r0, r1, r2", "muls", "r0, r1, r2"],
         [disasm_mla, "mla r0, r1, r2, r3", "mla", "r0, r1, r2, r3"],
@@ -1134,6 +1692,7 @@ mod tests {
     // Multiply Long
     #[rustfmt::skip]
     make_tests! {
+        batched;
         [disasm_umull, "umull r0, r1, r2, r3", "umull", "r0, r1, r2, r3"],
         [disasm_umlal, "umlal r0, r1, r2, r3", "umlal", "r0, r1, r2, r3"],
         [disasm_smull, "smull r0, r1, r2, r3", "smull", "r0, r1, r2, r3"],
@@ -1143,6 +1702,7 @@ mod tests {
     // Single Data Transfer
     #[rustfmt::skip]
     make_tests! {
+        batched;
         // LDR
         [disasm_ldr_imm_pre, "ldr r0, [r1, #0x4]", "ldr", "r0, [r1, #0x4]"],
         [disasm_ldr_imm_pre_writeback, "ldr r0, [r1, #0x4]!", "ldr", "r0, [r1, #0x4]!"],
@@ -1267,6 +1827,9 @@ mod tests {
         [disasm_ldrh_reg_pre, "ldrh r0, [r1, r2]", "ldrh", "r0, [r1, r2]"],
         [disasm_ldrh_reg_pre_writeback, "ldrh r0, [r1, r2]!", "ldrh", "r0, [r1, r2]!"],
         [disasm_ldrh_reg_post, "ldrh r0, [r1], r2", "ldrh", "r0, [r1], r2"],
+        [disasm_ldrh_imm_pre, "ldrh r0, [r1, #0x4]", "ldrh", "r0, [r1, #0x4]"],
+        [disasm_ldrh_imm_pre_writeback, "ldrh r0, [r1, #0x84]!", "ldrh", "r0, [r1, #0x84]!"],
+        [disasm_ldrh_imm_post, "ldrh r0, [r1], #-0x4", "ldrh", "r0, [r1], -#0x4"],
 
         // STRH
         [disasm_strh_reg_pre, "strh r0, [r1, r2]", "strh", "r0, [r1, r2]"],
@@ -1287,6 +1850,7 @@ mod tests {
     // Block Data Transfer
     #[rustfmt::skip]
     make_tests! {
+        batched;
         // LDM
         [disasm_ldmib, "ldmib r0, {r1,r3-r4,r6-r10,lr}", "ldmib", "r0, {r1,r3-r4,r6-r10,lr}"],
         [disasm_ldmia, "ldmia r0, {r1,r3-r4,r6-r10,lr}", "ldmia", "r0, {r1,r3-r4,r6-r10,lr}"],
@@ -1327,6 +1891,7 @@ mod tests {
     // Single Data Swap
     #[rustfmt::skip]
     make_tests! {
+        batched;
         [disasm_swp, "swp r0, r1, [r2]", "swp", "r0, r1, [r2]"],
         [disasm_swpb, "swpb r0, r1, [r2]", "swpb", "r0, r1, [r2]"],
     }
@@ -1334,39 +1899,107 @@ mod tests {
     // Software Interrupt
     #[rustfmt::skip]
     make_tests! {
+        batched;
         [disasm_swi, "swi #0x123456", "swi", "#0x123456"],
     }
 
     // Load PC-relative
     #[rustfmt::skip]
     make_tests! {
+        batched;
         [disasm_ldr_pc_relative, "ldr r0, [pc, #0x4]", "ldr", "r0, [pc, #0x4]", "r0 = [0x0000000c]"],
     }
 
-    fn assemble_one(source: &str) -> std::io::Result<u32> {
+    #[test]
+    fn batched_assembly_matches_per_instruction() {
+        let sources = [
+            "and r0, r1, #0x4",
+            "mov r0, r14",
+            "mul r0, r1, r2",
+            "ldr r0, [pc, #0x4]",
+            "ldmia r0!, {r1,r3-r4,r6-r10,lr}",
+            "swp r0, r1, [r2]",
+            "swi #0x123456",
+        ];
+        let batched = assemble_many(&sources).unwrap();
+        for (source, batched) in sources.into_iter().zip(batched) {
+            assert_eq!(assemble_one(source).unwrap(), batched, "{source}");
+        }
+    }
+
+    fn linker_script() -> std::io::Result<LinkerScript> {
         static LINKER_SCRIPT: RwLock<Option<LinkerScriptWeakRef>> = RwLock::new(None);
 
         let guard = LINKER_SCRIPT.read().unwrap();
         let maybe_linker_script = guard.as_ref().and_then(|ls| ls.upgrade());
         drop(guard);
-        let linker_script = if let Some(linker_script) = maybe_linker_script {
-            linker_script
-        } else {
-            let linker_script = arm_devkit::LinkerScript::new(arm_devkit::SIMPLE_LINKER_SCRIPT)?;
-            LINKER_SCRIPT
-                .write()
-                .unwrap()
-                .replace(linker_script.clone().weak());
-            linker_script
-        };
+        if let Some(linker_script) = maybe_linker_script {
+            return Ok(linker_script);
+        }
 
-        let assembled = arm_devkit::arm::assemble(source, linker_script)?;
+        let linker_script = LinkerScript::new(arm_devkit::SIMPLE_LINKER_SCRIPT)?;
+        LINKER_SCRIPT
+            .write()
+            .unwrap()
+            .replace(linker_script.clone().weak());
+        Ok(linker_script)
+    }
+
+    fn assemble_one(source: &str) -> std::io::Result<u32> {
+        let assembled = arm_devkit::arm::assemble(source, linker_script()?)?;
         assert!(assembled.len() >= 4);
+        Ok(read_instr(&assembled, 0))
+    }
+
+    /// Assembles every source after its own label with a single toolchain invocation
+    /// and returns the instructions in the same order as the sources.
+    fn assemble_many(sources: &[&str]) -> std::io::Result<Vec<u32>> {
+        let mut batch = String::new();
+        for (idx, source) in sources.iter().enumerate() {
+            writeln!(batch, "batch_{idx}:\n{source}").unwrap();
+        }
+
+        let (assembled, symbols) =
+            arm_devkit::arm::assemble_with_symbols(&batch, linker_script()?)?;
+        (0..sources.len())
+            .map(|idx| {
+                let label = format!("batch_{idx}");
+                let address = symbols
+                    .get(&label)
+                    .ok_or_else(|| std::io::Error::other(format!("no symbol for {label}")))?;
+                Ok(read_instr(&assembled, *address as usize))
+            })
+            .collect()
+    }
+
+    /// Returns the instruction for `source`, assembling all of `sources` together the
+    /// first time that any of them is needed. If the batch fails to assemble, each
+    /// source is assembled on its own so that errors are reported by the right test.
+    fn assemble_batched(sources: &[&'static str], source: &'static str) -> std::io::Result<u32> {
+        static BATCHED: Mutex<BTreeMap<&str, Option<u32>>> = Mutex::new(BTreeMap::new());
+
+        let mut batched = BATCHED.lock().unwrap_or_else(|err| err.into_inner());
+        if !batched.contains_key(source) {
+            let instrs = assemble_many(sources).ok();
+            for (idx, &batch_source) in sources.iter().enumerate() {
+                let instr = instrs.as_ref().map(|instrs| instrs[idx]);
+                batched.insert(batch_source, instr);
+            }
+        }
+
+        match batched[source] {
+            Some(instr) => Ok(instr),
+            None => {
+                drop(batched);
+                assemble_one(source)
+            }
+        }
+    }
 
-        let instr = (assembled[0] as u32)
-            | ((assembled[1] as u32) << 8)
-            | ((assembled[2] as u32) << 16)
-            | ((assembled[3] as u32) << 24);
-        Ok(instr)
+    fn read_instr(assembled: &[u8], address: usize) -> u32 {
+        (assembled[address] as u32)
+            | ((assembled[address + 1] as u32) << 8)
+            | ((assembled[address + 2] as u32) << 16)
+            | ((assembled[address + 3] as u32) << 24)
     }
 }
@@ -1,6 +1,9 @@
 use std::fmt::Write as _;
 
+use util::bits::BitOps as _;
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Register {
     R0,
     R1,
@@ -73,8 +76,14 @@ impl From<u16> for Register {
     }
 }
 
+/// Registers are displayed with their ABI names, so r13-r15 are `sp`, `lr` and `pc`.
+/// The alternate form (`{:#}`) always uses numeric names instead.
 impl std::fmt::Display for Register {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            return f.pad(&format!("r{}", u32::from(*self)));
+        }
+
         match self {
             Register::R0 => f.pad("r0"),
             Register::R1 => f.pad("r1"),
@@ -97,6 +106,7 @@ impl std::fmt::Display for Register {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DataProc {
     And,
     Eor,
@@ -140,6 +150,29 @@ impl From<u32> for DataProc {
     }
 }
 
+impl From<DataProc> for u32 {
+    fn from(val: DataProc) -> Self {
+        match val {
+            DataProc::And => 0x0,
+            DataProc::Eor => 0x1,
+            DataProc::Sub => 0x2,
+            DataProc::Rsb => 0x3,
+            DataProc::Add => 0x4,
+            DataProc::Adc => 0x5,
+            DataProc::Sbc => 0x6,
+            DataProc::Rsc => 0x7,
+            DataProc::Tst => 0x8,
+            DataProc::Teq => 0x9,
+            DataProc::Cmp => 0xA,
+            DataProc::Cmn => 0xB,
+            DataProc::Orr => 0xC,
+            DataProc::Mov => 0xD,
+            DataProc::Bic => 0xE,
+            DataProc::Mvn => 0xF,
+        }
+    }
+}
+
 impl std::fmt::Display for DataProc {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -164,6 +197,7 @@ impl std::fmt::Display for DataProc {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RegisterOrImmediate {
     Immediate(u32),
     Register(Register),
@@ -209,6 +243,7 @@ impl std::fmt::LowerHex for RegisterOrImmediate {
 }
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ShiftType {
     Lsl,
     Lsr,
@@ -230,6 +265,7 @@ impl std::fmt::Display for ShiftType {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Shift {
     Imm(ImmShift),
     Reg(RegShift),
@@ -245,6 +281,16 @@ impl From<u32> for Shift {
     }
 }
 
+impl Shift {
+    /// Encodes the shift into bits 4-11 of an ARM instruction.
+    pub(crate) fn encode(&self) -> u32 {
+        match self {
+            Shift::Imm(imm) => imm.encode(),
+            Shift::Reg(reg) => reg.encode(),
+        }
+    }
+}
+
 impl std::fmt::Display for Shift {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -264,6 +310,7 @@ impl std::fmt::LowerHex for Shift {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ImmShift {
     Lsl(u8),
     Lsr(u8),
@@ -292,6 +339,19 @@ impl ImmShift {
             ImmShift::Rrx => 1,
         }
     }
+
+    /// Encodes the shift into bits 4-11 of an ARM instruction. LSR #32 and ASR #32
+    /// are encoded with a shift amount of 0.
+    pub(crate) fn encode(&self) -> u32 {
+        let (shift_type, amount) = match *self {
+            ImmShift::Lsl(imm) => (0x0, imm),
+            ImmShift::Lsr(imm) => (0x1, imm),
+            ImmShift::Asr(imm) => (0x2, imm),
+            ImmShift::Ror(imm) => (0x3, imm),
+            ImmShift::Rrx => (0x3, 0),
+        };
+        (((amount as u32) & 0x1F) << 7) | (shift_type << 5)
+    }
 }
 
 impl From<u32> for ImmShift {
@@ -333,6 +393,7 @@ impl std::fmt::LowerHex for ImmShift {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RegShift {
     Lsl(Register),
     Lsr(Register),
@@ -353,6 +414,19 @@ impl From<u32> for RegShift {
     }
 }
 
+impl RegShift {
+    /// Encodes the shift into bits 4-11 of an ARM instruction.
+    pub(crate) fn encode(&self) -> u32 {
+        let (shift_type, rs) = match *self {
+            RegShift::Lsl(rs) => (0x0, rs),
+            RegShift::Lsr(rs) => (0x1, rs),
+            RegShift::Asr(rs) => (0x2, rs),
+            RegShift::Ror(rs) => (0x3, rs),
+        };
+        (u32::from(rs) << 8) | (shift_type << 5) | 0x10
+    }
+}
+
 impl std::fmt::Display for RegShift {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -365,39 +439,72 @@ impl std::fmt::Display for RegShift {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DataTransferOp {
     Load,
     Store,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SDTDataType {
     Word,
     Byte,
     Halfword,
     SignedHalfword,
     SignedByte,
+    /// A pair of words transferred to or from an even register and the register after it
+    /// (`ldrd`/`strd`, ARMv5TE).
+    Doubleword,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DataTransferIndexing {
     Pre,
     Post,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DataTransferDirection {
     Up,
     Down,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RegisterList(u16);
 
 impl RegisterList {
     pub fn set(&mut self, register: Register) {
         self.0 |= 1 << (u32::from(register));
     }
+
+    pub fn bits(&self) -> u16 {
+        self.0
+    }
+
+    pub fn contains(&self, register: Register) -> bool {
+        self.0.get_bit(u32::from(register))
+    }
+
+    /// The number of registers in the list.
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Iterates over the registers in the list from lowest to highest.
+    pub fn iter(&self) -> impl Iterator<Item = Register> {
+        let bits = self.0;
+        (0u32..16)
+            .filter(move |&register| bits.get_bit(register))
+            .map(Register::from)
+    }
 }
 
 impl From<u16> for RegisterList {
@@ -423,9 +530,16 @@ impl std::fmt::Display for RegisterList {
                 not_first_write = true;
                 ""
             };
+            // The alternate form is passed on so that registers use numeric names.
+            let alternate = f.alternate();
             match (*start, *end) {
+                (Some(start), None) if alternate => write!(f, "{prefix}{start:#}")?,
                 (Some(start), None) => write!(f, "{prefix}{start}")?,
+                (Some(start), Some(end)) if start == end && alternate => {
+                    write!(f, "{prefix}{start:#}")?
+                }
                 (Some(start), Some(end)) if start == end => write!(f, "{prefix}{start}")?,
+                (Some(start), Some(end)) if alternate => write!(f, "{prefix}{start:#}-{end:#}")?,
                 (Some(start), Some(end)) => write!(f, "{prefix}{start}-{end}")?,
                 (None, None) | (None, Some(_)) => return Ok(()),
             }
@@ -451,6 +565,7 @@ impl std::fmt::Display for RegisterList {
 }
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Condition {
     Eq,
     Ne,
@@ -470,6 +585,37 @@ pub enum Condition {
     Nv,
 }
 
+impl Condition {
+    /// Returns true if an instruction with this condition would be executed with
+    /// the given N, Z, C, and V flags. `NV` never passes since it is reserved on ARMv4.
+    pub fn passes(self, n: bool, z: bool, c: bool, v: bool) -> bool {
+        match self {
+            Condition::Eq => z,
+            Condition::Ne => !z,
+            Condition::Cs => c,
+            Condition::Cc => !c,
+            Condition::Mi => n,
+            Condition::Pl => !n,
+            Condition::Vs => v,
+            Condition::Vc => !v,
+            Condition::Hi => c && !z,
+            Condition::Ls => !c || z,
+            Condition::Ge => n == v,
+            Condition::Lt => n != v,
+            Condition::Gt => !z && n == v,
+            Condition::Le => z || n != v,
+            Condition::Al => true,
+            Condition::Nv => false,
+        }
+    }
+
+    /// Same as [`Condition::passes`] but reads the flags from the top 4 bits of a CPSR value.
+    pub fn passes_cpsr(self, cpsr: u32) -> bool {
+        let flag = |bit: u32| (cpsr >> bit) & 1 != 0;
+        self.passes(flag(31), flag(30), flag(29), flag(28))
+    }
+}
+
 impl From<u32> for Condition {
     fn from(val: u32) -> Self {
         match val {
@@ -494,6 +640,29 @@ impl From<u32> for Condition {
     }
 }
 
+impl From<Condition> for u32 {
+    fn from(val: Condition) -> Self {
+        match val {
+            Condition::Eq => 0x0,
+            Condition::Ne => 0x1,
+            Condition::Cs => 0x2,
+            Condition::Cc => 0x3,
+            Condition::Mi => 0x4,
+            Condition::Pl => 0x5,
+            Condition::Vs => 0x6,
+            Condition::Vc => 0x7,
+            Condition::Hi => 0x8,
+            Condition::Ls => 0x9,
+            Condition::Ge => 0xA,
+            Condition::Lt => 0xB,
+            Condition::Gt => 0xC,
+            Condition::Le => 0xD,
+            Condition::Al => 0xE,
+            Condition::Nv => 0xF,
+        }
+    }
+}
+
 impl std::fmt::Display for Condition {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -516,3 +685,106 @@ impl std::fmt::Display for Condition {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Condition, Register, RegisterList};
+
+    #[test]
+    fn register_list_empty() {
+        let list = RegisterList::from(0);
+        assert!(list.is_empty());
+        assert_eq!(list.len(), 0);
+        assert_eq!(list.iter().next(), None);
+        assert!(!list.contains(Register::R0));
+    }
+
+    #[test]
+    fn register_list_full() {
+        let list = RegisterList::from(0xFFFF);
+        assert!(!list.is_empty());
+        assert_eq!(list.len(), 16);
+        assert_eq!(
+            list.iter().collect::<Vec<_>>(),
+            (0u32..16).map(Register::from).collect::<Vec<_>>()
+        );
+        assert!((0u32..16).all(|r| list.contains(Register::from(r))));
+    }
+
+    #[test]
+    fn register_list_sparse() {
+        let mut list = RegisterList::from(0);
+        list.set(Register::R1);
+        list.set(Register::R3);
+        list.set(Register::R4);
+        list.set(Register::R14);
+        assert_eq!(list.len(), 4);
+        assert_eq!(
+            list.iter().collect::<Vec<_>>(),
+            vec![Register::R1, Register::R3, Register::R4, Register::R14]
+        );
+        assert!(list.contains(Register::R3));
+        assert!(!list.contains(Register::R2));
+        assert!(!list.contains(Register::R15));
+        assert_eq!(list.to_string(), "{r1,r3-r4,lr}");
+        assert_eq!(format!("{list:#}"), "{r1,r3-r4,r14}");
+    }
+
+    #[test]
+    fn register_names() {
+        for (register, abi, numeric) in [
+            (Register::R0, "r0", "r0"),
+            (Register::R12, "r12", "r12"),
+            (Register::R13, "sp", "r13"),
+            (Register::R14, "lr", "r14"),
+            (Register::R15, "pc", "r15"),
+        ] {
+            assert_eq!(register.to_string(), abi);
+            assert_eq!(format!("{register:#}"), numeric);
+        }
+        assert_eq!(
+            format!("{:>4}|{:<4}|", Register::R13, Register::R13),
+            "  sp|sp  |"
+        );
+        assert_eq!(format!("{:>#4}", Register::R13), " r13");
+    }
+
+    #[test]
+    fn condition_truth_table() {
+        // Bit `nzcv` of each mask is set if the condition passes with those flags.
+        let table = [
+            (Condition::Eq, 0b1111000011110000u16),
+            (Condition::Ne, 0b0000111100001111),
+            (Condition::Cs, 0b1100110011001100),
+            (Condition::Cc, 0b0011001100110011),
+            (Condition::Mi, 0b1111111100000000),
+            (Condition::Pl, 0b0000000011111111),
+            (Condition::Vs, 0b1010101010101010),
+            (Condition::Vc, 0b0101010101010101),
+            (Condition::Hi, 0b0000110000001100),
+            (Condition::Ls, 0b1111001111110011),
+            (Condition::Ge, 0b1010101001010101),
+            (Condition::Lt, 0b0101010110101010),
+            (Condition::Gt, 0b0000101000000101),
+            (Condition::Le, 0b1111010111111010),
+            (Condition::Al, 0b1111111111111111),
+            (Condition::Nv, 0b0000000000000000),
+        ];
+
+        for (condition, mask) in table {
+            for nzcv in 0u32..16 {
+                let expected = (mask >> nzcv) & 1 != 0;
+                let (n, z, c, v) = (nzcv & 8 != 0, nzcv & 4 != 0, nzcv & 2 != 0, nzcv & 1 != 0);
+                assert_eq!(
+                    condition.passes(n, z, c, v),
+                    expected,
+                    "{condition:?} with nzcv={nzcv:04b}"
+                );
+
+                // the other bits of the CPSR are ignored
+                let cpsr = (nzcv << 28) | 0x0000_00DF;
+                assert_eq!(condition.passes_cpsr(cpsr), expected);
+            }
+        }
+    }
+}
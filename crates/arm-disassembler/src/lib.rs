@@ -2,12 +2,59 @@ pub mod arm;
 pub mod common;
 pub mod thumb;
 
+use std::fmt::Write as _;
+
+/// The instruction set used to decode instructions in [`AnyInstr::decode`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InstructionSet {
+    Arm,
+    Thumb,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AnyInstr {
     Arm(arm::ArmInstr),
     Thumb(thumb::ThumbInstr),
 }
 
 impl AnyInstr {
+    /// Decodes the instruction at the start of `bytes`, which are located at `address`.
+    /// Returns the instruction and the number of bytes that it uses, or `None` if
+    /// there are not enough bytes for a single instruction. In THUMB mode both
+    /// halves of a `bl` are decoded together if they are both present.
+    pub fn decode(isa: InstructionSet, bytes: &[u8], address: u32) -> Option<(AnyInstr, usize)> {
+        match isa {
+            InstructionSet::Arm => {
+                let instr = u32::from_le_bytes(bytes.get(..4)?.try_into().unwrap());
+                Some((arm::disasm(instr, address).into(), 4))
+            }
+
+            InstructionSet::Thumb => {
+                let instr = u16::from_le_bytes(bytes.get(..2)?.try_into().unwrap());
+                if let Some(next) = bytes.get(2..4) {
+                    let next = u16::from_le_bytes(next.try_into().unwrap());
+                    if let Some(bl) = thumb::disasm_bl_pair(instr, next, address) {
+                        return Some((bl.into(), 4));
+                    }
+                }
+                Some((thumb::disasm(instr, address).into(), 2))
+            }
+        }
+    }
+
+    /// Returns false if the instruction is probably data or code for the other
+    /// instruction set. Listings can use this to grey out lines. See
+    /// [`arm::ArmInstr::looks_valid`] and [`thumb::ThumbInstr::looks_valid`] for what
+    /// is checked.
+    pub fn looks_valid(&self) -> bool {
+        match self {
+            AnyInstr::Arm(instr) => instr.looks_valid(),
+            AnyInstr::Thumb(instr) => instr.looks_valid(),
+        }
+    }
+
     pub fn mnemonic(&self) -> crate::Mnemonic<'_, Self> {
         Mnemonic(self)
     }
@@ -27,6 +74,50 @@ impl AnyInstr {
     ) -> crate::Comment<'s, 's, Self> {
         Comment(self, addr, m)
     }
+
+    /// Mnemonic, arguments and comment formatted together as a single line of a
+    /// listing. See [`Full`].
+    pub fn full<'s>(
+        &'s self,
+        addr: u32,
+        m: Option<&'s dyn MemoryView>,
+    ) -> crate::Full<'s, 's, Self> {
+        Full(self, addr, m)
+    }
+}
+
+/// Decodes every instruction in a buffer one after another, yielding each
+/// instruction with its address. THUMB `bl` pairs are yielded as a single 4 byte
+/// instruction. Iteration stops when there are not enough bytes left for another
+/// instruction.
+#[derive(Debug, Clone)]
+pub struct Disassembler<'b> {
+    isa: InstructionSet,
+    bytes: &'b [u8],
+    address: u32,
+}
+
+impl<'b> Disassembler<'b> {
+    /// Creates a disassembler for `bytes`, the first of which is located at `address`.
+    pub fn new(isa: InstructionSet, bytes: &'b [u8], address: u32) -> Self {
+        Disassembler {
+            isa,
+            bytes,
+            address,
+        }
+    }
+}
+
+impl Iterator for Disassembler<'_> {
+    type Item = (u32, AnyInstr);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (instr, size) = AnyInstr::decode(self.isa, self.bytes, self.address)?;
+        let address = self.address;
+        self.bytes = &self.bytes[size..];
+        self.address = self.address.wrapping_add(size as u32);
+        Some((address, instr))
+    }
 }
 
 impl From<arm::ArmInstr> for AnyInstr {
@@ -50,9 +141,19 @@ pub struct Arguments<'i, 'm, I>(&'i I, u32, Option<&'m dyn MemoryView>);
 #[derive(Clone, Copy)]
 pub struct Comment<'i, 'm, I>(&'i I, u32, Option<&'m dyn MemoryView>);
 
+/// Formats an instruction as `mnemonic arguments ; comment` with the mnemonic
+/// padded to [`Full::MNEMONIC_WIDTH`] so that lines in a listing are aligned.
+/// The comment is omitted if it is empty.
+#[derive(Clone, Copy)]
+pub struct Full<'i, 'm, I>(&'i I, u32, Option<&'m dyn MemoryView>);
+
+impl<I> Full<'_, '_, I> {
+    pub const MNEMONIC_WIDTH: usize = 8;
+}
+
 impl std::fmt::Display for Mnemonic<'_, arm::ArmInstr> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut buffer = WriteBuffer::<32>::new();
+        let mut buffer = WriteBuffer::<64>::new();
         self.0.write_mnemonic(&mut buffer)?;
         f.pad(buffer.as_str())
     }
@@ -60,7 +161,7 @@ impl std::fmt::Display for Mnemonic<'_, arm::ArmInstr> {
 
 impl std::fmt::Display for Mnemonic<'_, thumb::ThumbInstr> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut buffer = WriteBuffer::<32>::new();
+        let mut buffer = WriteBuffer::<64>::new();
         self.0.write_mnemonic(&mut buffer)?;
         f.pad(buffer.as_str())
     }
@@ -77,7 +178,7 @@ impl std::fmt::Display for Mnemonic<'_, AnyInstr> {
 
 impl std::fmt::Display for Arguments<'_, '_, arm::ArmInstr> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut buffer = WriteBuffer::<32>::new();
+        let mut buffer = WriteBuffer::<64>::new();
         self.0.write_arguments(&mut buffer)?;
         f.pad(buffer.as_str())
     }
@@ -85,7 +186,7 @@ impl std::fmt::Display for Arguments<'_, '_, arm::ArmInstr> {
 
 impl std::fmt::Display for Arguments<'_, '_, thumb::ThumbInstr> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut buffer = WriteBuffer::<32>::new();
+        let mut buffer = WriteBuffer::<64>::new();
         self.0.write_arguments(&mut buffer, self.1, self.2)?;
         f.pad(buffer.as_str())
     }
@@ -102,7 +203,7 @@ impl std::fmt::Display for Arguments<'_, '_, AnyInstr> {
 
 impl std::fmt::Display for Comment<'_, '_, arm::ArmInstr> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut buffer = WriteBuffer::<32>::new();
+        let mut buffer = WriteBuffer::<64>::new();
         self.0.write_comment(&mut buffer, self.1, self.2)?;
         f.pad(buffer.as_str())
     }
@@ -110,7 +211,7 @@ impl std::fmt::Display for Comment<'_, '_, arm::ArmInstr> {
 
 impl std::fmt::Display for Comment<'_, '_, thumb::ThumbInstr> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut buffer = WriteBuffer::<32>::new();
+        let mut buffer = WriteBuffer::<64>::new();
         self.0.write_comment(&mut buffer, self.1, self.2)?;
         f.pad(buffer.as_str())
     }
@@ -125,6 +226,31 @@ impl std::fmt::Display for Comment<'_, '_, AnyInstr> {
     }
 }
 
+impl<'i, 'm, I> std::fmt::Display for Full<'i, 'm, I>
+where
+    Mnemonic<'i, I>: std::fmt::Display,
+    Arguments<'i, 'm, I>: std::fmt::Display,
+    Comment<'i, 'm, I>: std::fmt::Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mnemonic = Mnemonic(self.0);
+        let arguments = Arguments(self.0, self.1, self.2);
+
+        let mut comment = WriteBuffer::<64>::new();
+        write!(&mut comment, "{}", Comment(self.0, self.1, self.2))?;
+
+        write!(
+            f,
+            "{mnemonic:<width$} {arguments}",
+            width = Self::MNEMONIC_WIDTH
+        )?;
+        if !comment.as_str().is_empty() {
+            write!(f, " ; {}", comment.as_str())?;
+        }
+        Ok(())
+    }
+}
+
 impl<I: std::fmt::Debug> std::fmt::Debug for Comment<'_, '_, I> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_tuple("Comment")
@@ -163,7 +289,7 @@ impl<const N: usize> WriteBuffer<N> {
     }
 }
 
-impl std::fmt::Write for &'_ mut WriteBuffer<32> {
+impl<const N: usize> std::fmt::Write for &'_ mut WriteBuffer<N> {
     fn write_str(&mut self, s: &str) -> std::fmt::Result {
         let bytes = s.as_bytes();
 
@@ -181,9 +307,18 @@ pub trait MemoryView {
     fn view8(&self, address: u32) -> u8;
     fn view16(&self, address: u32) -> u16;
     fn view32(&self, address: u32) -> u32;
+
+    /// Reads `len` bytes starting at `start`, wrapping around to address 0 after
+    /// 0xFFFFFFFF. Views backed by contiguous memory should override this to copy
+    /// the bytes instead of reading them one at a time.
+    fn read_range(&self, start: u32, len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|offset| self.view8(start.wrapping_add(offset as u32)))
+            .collect()
+    }
 }
 
-impl MemoryView for &'_ [u8] {
+impl MemoryView for [u8] {
     fn view8(&self, address: u32) -> u8 {
         self.get(address as usize).copied().unwrap_or(0)
     }
@@ -211,4 +346,271 @@ impl MemoryView for &'_ [u8] {
                 .unwrap_or(0),
         ])
     }
+
+    fn read_range(&self, start: u32, len: usize) -> Vec<u8> {
+        let mut bytes = vec![0; len];
+        let copy_from = |start: usize, buffer: &mut [u8]| {
+            let available = self.get(start..).unwrap_or_default();
+            let count = buffer.len().min(available.len());
+            buffer[..count].copy_from_slice(&available[..count]);
+        };
+        let before_wrap = len.min(((u32::MAX - start) as usize).saturating_add(1));
+        let (head, tail) = bytes.split_at_mut(before_wrap);
+        copy_from(start as usize, head);
+        copy_from(0, tail);
+        bytes
+    }
+}
+
+/// Implements [`MemoryView`] for a type that derefs to `[u8]`.
+macro_rules! impl_memory_view_for_bytes {
+    ($([$($generics:tt)*] $ty:ty),+ $(,)?) => {
+        $(
+            impl<$($generics)*> MemoryView for $ty {
+                fn view8(&self, address: u32) -> u8 {
+                    self[..].view8(address)
+                }
+
+                fn view16(&self, address: u32) -> u16 {
+                    self[..].view16(address)
+                }
+
+                fn view32(&self, address: u32) -> u32 {
+                    self[..].view32(address)
+                }
+
+                fn read_range(&self, start: u32, len: usize) -> Vec<u8> {
+                    self[..].read_range(start, len)
+                }
+            }
+        )+
+    };
+}
+
+impl_memory_view_for_bytes! {
+    [] &'_ [u8],
+    [] &'_ mut [u8],
+    [] Vec<u8>,
+    [const N: usize] [u8; N],
+}
+
+/// Reassembles the 16-bit and 32-bit values of a little-endian [`MemoryView`]
+/// as big-endian. This is useful for inspecting big-endian memory dumps.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BigEndian<T>(pub T);
+
+impl<T: MemoryView> MemoryView for BigEndian<T> {
+    fn view8(&self, address: u32) -> u8 {
+        self.0.view8(address)
+    }
+
+    fn view16(&self, address: u32) -> u16 {
+        self.0.view16(address).swap_bytes()
+    }
+
+    fn view32(&self, address: u32) -> u32 {
+        self.0.view32(address).swap_bytes()
+    }
+
+    fn read_range(&self, start: u32, len: usize) -> Vec<u8> {
+        self.0.read_range(start, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{arm, thumb, AnyInstr, BigEndian, InstructionSet, MemoryView};
+
+    #[test]
+    fn memory_view_for_owned_buffers() {
+        let vec: Vec<u8> = vec![0xEF, 0xBE, 0xAD, 0xDE, 0x01];
+        assert_eq!(vec.view32(0), 0xDEADBEEF);
+        assert_eq!(vec.view16(2), 0xDEAD);
+        assert_eq!(vec.view8(4), 0x01);
+        assert_eq!(vec.view32(2), 0x0001DEAD);
+
+        let array = [0xEFu8, 0xBE, 0xAD, 0xDE];
+        assert_eq!(array.view32(0), 0xDEADBEEF);
+
+        let mut buffer = [0xEFu8, 0xBE, 0xAD, 0xDE];
+        let slice: &mut [u8] = &mut buffer;
+        assert_eq!(slice.view32(0), 0xDEADBEEF);
+
+        let view: &dyn MemoryView = &vec;
+        assert_eq!(view.view16(0), 0xBEEF);
+    }
+
+    #[test]
+    fn memory_view_read_range() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        let slice: &[u8] = &bytes;
+        assert_eq!(slice.read_range(0, 256), bytes);
+        assert_eq!(slice.read_range(16, 32), &bytes[16..48]);
+        assert_eq!(
+            slice.read_range(250, 8),
+            [250, 251, 252, 253, 254, 255, 0, 0]
+        );
+        assert_eq!(slice.read_range(0xFFFFFFFE, 4), [0, 0, 0, 1]);
+        assert!(slice.read_range(300, 0).is_empty());
+
+        // The default implementation reads the same bytes one at a time.
+        struct Bytewise<'a>(&'a [u8]);
+        impl MemoryView for Bytewise<'_> {
+            fn view8(&self, address: u32) -> u8 {
+                self.0.view8(address)
+            }
+            fn view16(&self, address: u32) -> u16 {
+                self.0.view16(address)
+            }
+            fn view32(&self, address: u32) -> u32 {
+                self.0.view32(address)
+            }
+        }
+        for (start, len) in [(0, 256), (16, 32), (250, 8), (0xFFFFFFFE, 4)] {
+            assert_eq!(
+                Bytewise(slice).read_range(start, len),
+                slice.read_range(start, len)
+            );
+        }
+    }
+
+    #[test]
+    fn memory_view_byte_order() {
+        let bytes: &[u8] = &[0x01, 0x02, 0x03, 0x04];
+        assert_eq!(bytes.view32(0), 0x04030201);
+        assert_eq!(bytes.view16(0), 0x0201);
+
+        let big_endian = BigEndian(bytes);
+        assert_eq!(big_endian.view32(0), 0x01020304);
+        assert_eq!(big_endian.view16(0), 0x0102);
+        assert_eq!(big_endian.view16(2), 0x0304);
+        assert_eq!(big_endian.view8(3), 0x04);
+    }
+
+    #[test]
+    fn full_instruction_formatting() {
+        // mov r0, r1
+        let mov = AnyInstr::from(arm::disasm(0xE1A00001, 0));
+        let manual = format!("{:<8} {}", mov.mnemonic(), mov.arguments(0, None));
+        assert_eq!(mov.full(0, None).to_string(), manual);
+        assert_eq!(mov.full(0, None).to_string(), "mov      r0, r1");
+
+        // ldr r0, [pc, #0x4]
+        let ldr = AnyInstr::from(arm::disasm(0xE59F0004, 0));
+        let manual = format!(
+            "{:<8} {} ; {}",
+            ldr.mnemonic(),
+            ldr.arguments(0, None),
+            ldr.comment(0, None)
+        );
+        assert_eq!(ldr.full(0, None).to_string(), manual);
+        assert_eq!(
+            ldr.full(0, None).to_string(),
+            "ldr      r0, [pc, #0x4] ; r0 = [0x0000000c]"
+        );
+
+        // ldr r0, [pc, #0x4]
+        let memory: &[u8] = &[0, 0, 0, 0, 0, 0, 0, 0, 0xEF, 0xBE, 0xAD, 0xDE];
+        let ldr = thumb::disasm(0x4801, 2);
+        assert_eq!(
+            ldr.full(2, Some(&memory)).to_string(),
+            format!(
+                "{:<8} {} ; {}",
+                ldr.mnemonic(),
+                ldr.arguments(2, Some(&memory)),
+                ldr.comment(2, Some(&memory))
+            )
+        );
+    }
+
+    #[test]
+    fn instructions_that_look_valid() {
+        let arm = |instr: u32| AnyInstr::from(arm::disasm(instr, 0x08000000)).looks_valid();
+        assert!(arm(0xE1A00001)); // mov r0, r1
+        assert!(arm(0xE59F0004)); // ldr r0, [pc, #0x4]
+        assert!(arm(0xEAFFFFFE)); // b .
+        assert!(arm(0xE8BD8010)); // pop {r4, pc}
+        assert!(arm(0x00000001)); // andeq r0, r0, r1
+        assert!(!arm(0xE7F000F0)); // undef
+        assert!(!arm(0x00000000)); // andeq r0, r0, r0
+        assert!(!arm(0xFFFFFFFF)); // never condition
+        assert!(!arm(0xE00F0291)); // mul pc, r1, r2
+        assert!(!arm(0xE8BD0000)); // ldm sp!, {}
+        assert!(!arm(0xE5BF0004)); // ldr r0, [pc, #0x4]!
+
+        let thumb = |instr: u16| AnyInstr::from(thumb::disasm(instr, 0x08000000)).looks_valid();
+        assert!(thumb(0x1C08)); // adds r0, r1, #0
+        assert!(thumb(0x4801)); // ldr r0, [pc, #0x4]
+        assert!(thumb(0xB510)); // push {r4, lr}
+        assert!(thumb(0x0000)); // lsls r0, r0, #0
+        assert!(!thumb(0xDE00)); // undefined
+        assert!(thumb(0xF800)); // second half of a bl on its own
+        assert!(!thumb(0xC800)); // ldmia r0!, {}
+
+        let bl = [0x00, 0xF0, 0x00, 0xF8];
+        let (instr, _) = AnyInstr::decode(InstructionSet::Thumb, &bl, 0).unwrap();
+        assert!(instr.looks_valid());
+    }
+
+    #[test]
+    fn decode_arm() {
+        // mov r0, r1
+        let bytes = 0xE1A00001u32.to_le_bytes();
+        let (instr, size) = AnyInstr::decode(InstructionSet::Arm, &bytes, 0).unwrap();
+        assert!(matches!(instr, AnyInstr::Arm(_)));
+        assert_eq!(size, 4);
+        assert_eq!(instr.full(0, None).to_string(), "mov      r0, r1");
+
+        assert!(AnyInstr::decode(InstructionSet::Arm, &bytes[..3], 0).is_none());
+    }
+
+    #[test]
+    fn decode_thumb() {
+        // lsl r0, r1, #12 ; followed by half of a second instruction
+        let bytes = [0x08, 0x03, 0x00];
+        let (instr, size) = AnyInstr::decode(InstructionSet::Thumb, &bytes, 0).unwrap();
+        assert!(matches!(instr, AnyInstr::Thumb(_)));
+        assert_eq!(size, 2);
+        assert_eq!(instr.mnemonic().to_string(), "lsl");
+        assert_eq!(instr.arguments(0, None).to_string(), "r0, r1, #12");
+
+        assert!(AnyInstr::decode(InstructionSet::Thumb, &bytes[..1], 0).is_none());
+    }
+
+    #[test]
+    fn decode_thumb_bl_pair() {
+        // bl 0x1234 at 0x100
+        let bytes = [0x01, 0xF0, 0x98, 0xF8];
+        let (instr, size) = AnyInstr::decode(InstructionSet::Thumb, &bytes, 0x100).unwrap();
+        assert_eq!(size, 4);
+        assert_eq!(instr.mnemonic().to_string(), "bl");
+        assert_eq!(instr.arguments(0x100, None).to_string(), "0x00001234");
+
+        // only the first half is present
+        let (instr, size) = AnyInstr::decode(InstructionSet::Thumb, &bytes[..2], 0x100).unwrap();
+        assert_eq!(size, 2);
+        assert_eq!(instr.mnemonic().to_string(), "bl_setup");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        // ldr r0, [pc, #0x4] ; bl 0x1234
+        let instrs = [
+            AnyInstr::from(arm::disasm(0xE59F0004, 0)),
+            AnyInstr::decode(InstructionSet::Thumb, &[0x01, 0xF0, 0x98, 0xF8], 0x100)
+                .unwrap()
+                .0,
+        ];
+
+        for instr in instrs {
+            let json = serde_json::to_string(&instr).unwrap();
+            let decoded: AnyInstr = serde_json::from_str(&json).unwrap();
+            assert_eq!(format!("{decoded:?}"), format!("{instr:?}"));
+            assert_eq!(
+                decoded.full(0, None).to_string(),
+                instr.full(0, None).to_string()
+            );
+        }
+    }
 }
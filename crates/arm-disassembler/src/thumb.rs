@@ -58,7 +58,8 @@ fn disasm_move_shifted_register(instr: u16) -> ThumbInstr {
             0 => ShiftType::Lsl,
             1 => ShiftType::Lsr,
             2 => ShiftType::Asr,
-            _ => unreachable!("invalid shift type"),
+            // 3 is ADD/SUB which is decoded separately.
+            _ => return ThumbInstr::Undefined(instr),
         },
         lhs: Some(Register::from(instr.get_bit_range(3..=5))),
         dst: Register::from(instr.get_bit_range(0..=2)),
@@ -99,7 +100,7 @@ fn disasm_mov_cmp_add_sub_imm8(instr: u16) -> ThumbInstr {
             1 => DataProc::Cmp,
             2 => DataProc::Add,
             3 => DataProc::Sub,
-            _ => unreachable!("invalid opcode"),
+            _ => return ThumbInstr::Undefined(instr),
         },
         dst: Register::from(instr.get_bit_range(8..=10)),
         lhs: None,
@@ -144,7 +145,7 @@ fn disasm_alu_op(instr: u16) -> ThumbInstr {
         0b1101 => return ThumbInstr::Multiply { dst, rhs },
         0b1110 => DataProc::Bic,
         0b1111 => DataProc::Mvn,
-        _ => unreachable!("invalid opcode"),
+        _ => return ThumbInstr::Undefined(instr),
     };
 
     ThumbInstr::DataProc {
@@ -166,7 +167,7 @@ fn disasm_hi_reg_op(instr: u16) -> ThumbInstr {
         0b01 => DataProc::Cmp,
         0b10 => DataProc::Mov,
         0b11 => return ThumbInstr::BranchAndExchange { rs: rhs },
-        _ => unreachable!("invalid opcode"),
+        _ => return ThumbInstr::Undefined(instr),
     };
 
     // The action of H1= 0, H2 = 0 for Op = 00 (ADD), Op =01 (CMP) and Op = 10 (MOV) is
@@ -218,7 +219,7 @@ fn disasm_ldrh_and_strsb_reg(instr: u16) -> ThumbInstr {
         0b01 => (DataTransferOp::Load, SDTDataType::SignedByte),
         0b10 => (DataTransferOp::Load, SDTDataType::Halfword),
         0b11 => (DataTransferOp::Load, SDTDataType::SignedHalfword),
-        _ => unreachable!("invalid opcode"),
+        _ => return ThumbInstr::Undefined(instr),
     };
 
     ThumbInstr::SingleDataTransfer {
@@ -349,7 +350,7 @@ fn disasm_push_pop_registers(instr: u16) -> ThumbInstr {
         Some(Register::R14) => register_list.set(Register::R14),
         Some(Register::R15) => register_list.set(Register::R15),
         None => (),
-        _ => unreachable!("invalid extra register"),
+        _ => return ThumbInstr::Undefined(instr),
     }
 
     ThumbInstr::BlockDataTransfer {
@@ -408,6 +409,20 @@ fn disasm_bl_complete(instr: u16) -> ThumbInstr {
     ThumbInstr::BranchAndLink(off)
 }
 
+/// Disassembles the two halves of a `bl` at `address` as a single instruction.
+/// Returns `None` if `setup` and `complete` are not the first and second half of a `bl`.
+pub fn disasm_bl_pair(setup: u16, complete: u16, address: u32) -> Option<ThumbInstr> {
+    match (
+        disasm(setup, address),
+        disasm(complete, address.wrapping_add(2)),
+    ) {
+        (ThumbInstr::BrandAndLinkSetup(lr), ThumbInstr::BranchAndLink(offset)) => Some(
+            ThumbInstr::BranchAndLinkPair(lr.wrapping_add(offset) & 0xFFFFFFFE),
+        ),
+        _ => None,
+    }
+}
+
 fn disasm_bkpt(instr: u16) -> ThumbInstr {
     ThumbInstr::Undefined(instr)
 }
@@ -423,6 +438,7 @@ fn disasm_swi(instr: u16) -> ThumbInstr {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ThumbInstr {
     Undefined(u16),
 
@@ -476,9 +492,25 @@ pub enum ThumbInstr {
 
     BrandAndLinkSetup(u32),
     BranchAndLink(u32),
+
+    /// Both halves of a `bl` decoded together with the destination resolved.
+    /// See [`disasm_bl_pair`].
+    BranchAndLinkPair(u32),
 }
 
 impl ThumbInstr {
+    /// Returns false if the instruction is probably data or code for the other
+    /// instruction set. This is a conservative guess: only undefined instructions and
+    /// block transfers with an empty register list are rejected. The halves of a `bl`
+    /// are accepted on their own because listings often decode them separately.
+    pub fn looks_valid(&self) -> bool {
+        match self {
+            ThumbInstr::Undefined(_) => false,
+            ThumbInstr::BlockDataTransfer { registers, .. } => !registers.is_empty(),
+            _ => true,
+        }
+    }
+
     pub(crate) fn write_mnemonic<W: Write>(&self, mut f: W) -> std::fmt::Result {
         match self {
             ThumbInstr::Undefined(_) => write!(f, "undef"),
@@ -508,6 +540,7 @@ impl ThumbInstr {
                     SDTDataType::Halfword => "h",
                     SDTDataType::SignedHalfword => "sh",
                     SDTDataType::SignedByte => "sb",
+                    SDTDataType::Doubleword => "d",
                 };
 
                 write!(f, "{op}{dt}")
@@ -540,7 +573,22 @@ impl ThumbInstr {
                         DataTransferDirection::Up,
                         DataTransferIndexing::Post,
                     ) => "stmia",
-                    _ => unreachable!("invalid block data transfer"),
+                    // Only reachable for instructions that weren't created by `disasm`.
+                    (op, direction, indexing) => {
+                        let op = match op {
+                            DataTransferOp::Load => "ldm",
+                            DataTransferOp::Store => "stm",
+                        };
+                        let direction = match direction {
+                            DataTransferDirection::Up => "i",
+                            DataTransferDirection::Down => "d",
+                        };
+                        let indexing = match indexing {
+                            DataTransferIndexing::Pre => "b",
+                            DataTransferIndexing::Post => "a",
+                        };
+                        return write!(f, "{op}{direction}{indexing}");
+                    }
                 };
                 write!(f, "{proc}")
             }
@@ -548,7 +596,7 @@ impl ThumbInstr {
                 write!(f, "b{condition}")
             }
             ThumbInstr::BrandAndLinkSetup(..) => write!(f, "bl_setup"),
-            ThumbInstr::BranchAndLink(..) => write!(f, "bl"),
+            ThumbInstr::BranchAndLink(..) | ThumbInstr::BranchAndLinkPair(..) => write!(f, "bl"),
         }
     }
 
@@ -626,6 +674,7 @@ impl ThumbInstr {
             ThumbInstr::Branch { dest, .. } => write!(f, "0x{dest:08x}"),
 
             ThumbInstr::BrandAndLinkSetup(..) => Ok(()),
+            ThumbInstr::BranchAndLinkPair(dest) => write!(f, "0x{dest:08x}"),
             &ThumbInstr::BranchAndLink(offset) => {
                 if let Some(memory) = memory {
                     let setup_instr_bytes = memory.view16(addr.wrapping_sub(2));
@@ -658,6 +707,8 @@ impl ThumbInstr {
                 src: Register::R15,
                 off: RegisterOrImmediate::Immediate(off),
             } => {
+                // PC-relative loads use the address of the instruction plus 4 with bit 1
+                // cleared so that the result is always word aligned.
                 let pc = addr.wrapping_add(4) & !0x3;
                 let data_addr = pc.wrapping_add(off);
                 if let Some(m) = m {
@@ -668,13 +719,25 @@ impl ThumbInstr {
                                 .rotate_right(8 * (data_addr % 4));
                             write!(f, "{dst} = 0x{data:08x}")
                         }
-                        _ => unreachable!("invalid data type"),
+                        // THUMB only has word sized PC-relative loads.
+                        _ => write!(f, "{dst} = [0x{data_addr:08x}]"),
                     }
                 } else {
                     write!(f, "{dst} = [0x{data_addr:08x}]")
                 }
             }
 
+            ThumbInstr::DataProc {
+                op: DataProc::Add,
+                dst,
+                lhs: Some(Register::R15),
+                rhs: RegisterOrImmediate::Immediate(off),
+            } => {
+                // Same word aligned base as the PC-relative loads.
+                let pc = addr.wrapping_add(4) & !0x3;
+                write!(f, "{dst} = 0x{:08x}", pc.wrapping_add(off))
+            }
+
             ThumbInstr::BrandAndLinkSetup(setup) => {
                 write!(f, "lr = 0x{:08x}", setup)
             }
@@ -702,13 +765,25 @@ impl ThumbInstr {
     ) -> crate::Comment<'s, 's, Self> {
         crate::Comment(self, addr, m)
     }
+
+    pub fn full<'s>(
+        &'s self,
+        addr: u32,
+        m: Option<&'s dyn MemoryView>,
+    ) -> crate::Full<'s, 's, Self> {
+        crate::Full(self, addr, m)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::disasm;
-    use arm_devkit::LinkerScriptWeakRef;
-    use std::sync::RwLock;
+    use arm_devkit::{LinkerScript, LinkerScriptWeakRef};
+    use std::{
+        collections::BTreeMap,
+        fmt::Write as _,
+        sync::{Mutex, RwLock},
+    };
 
     #[test]
     fn disasm_undef() {
@@ -752,21 +827,54 @@ mod tests {
         assert_eq!("lr = 0x00001004", dis.comment(0, None).to_string());
     }
 
+    #[test]
+    fn disasm_never_panics() {
+        let memory = [0xA5u8; 0x100];
+        for instr in 0..=u16::MAX {
+            for addr in [0x80, 0x82] {
+                let dis = disasm(instr, addr);
+                let _ = dis.full(addr, None).to_string();
+                let _ = dis.full(addr, Some(&&memory[..])).to_string();
+            }
+        }
+    }
+
+    #[test]
+    fn disasm_pc_relative_comments_are_word_aligned() {
+        let mut memory = [0u8; 16];
+        memory[8..12].copy_from_slice(&0xdeadbeef_u32.to_le_bytes());
+        let ldr = 0x4801; // ldr r0, [pc, #0x4]
+        let adr = 0xa001; // add r0, pc, #0x4
+
+        // Instructions at 0x0 and 0x2 both use 0x4 as their PC-relative base.
+        for addr in [0x0, 0x2] {
+            let dis = disasm(ldr, addr);
+            assert_eq!("r0 = [0x00000008]", dis.comment(addr, None).to_string());
+            assert_eq!(
+                "r0 = 0xdeadbeef",
+                dis.comment(addr, Some(&&memory[..])).to_string()
+            );
+
+            let dis = disasm(adr, addr);
+            assert_eq!("r0 = 0x00000008", dis.comment(addr, None).to_string());
+        }
+    }
+
     macro_rules! make_test {
-        ($name:ident, $source:literal, $mnemonic:literal, $arguments:literal) => {
+        ($name:ident, $assemble:expr, $mnemonic:literal, $arguments:literal) => {
             #[test]
             fn $name() {
-                let asm = assemble_one($source).unwrap();
+                let asm = $assemble.unwrap();
                 let dis = disasm(asm, 0x0);
                 assert_eq!($mnemonic, dis.mnemonic().to_string());
                 assert_eq!($arguments, dis.arguments(0, None).to_string());
             }
         };
 
-        ($name:ident, $source:literal, $mnemonic:literal, $arguments:literal, $comment:literal) => {
+        ($name:ident, $assemble:expr, $mnemonic:literal, $arguments:literal, $comment:literal) => {
             #[test]
             fn $name() {
-                let asm = assemble_one($source).unwrap();
+                let asm = $assemble.unwrap();
                 let dis = disasm(asm, 0x0);
                 assert_eq!($mnemonic, dis.mnemonic().to_string());
                 assert_eq!($arguments, dis.arguments(0, None).to_string());
@@ -776,14 +884,25 @@ mod tests {
     }
 
     macro_rules! make_tests {
+        // Assembles every source in the group with a single toolchain invocation. Only
+        // usable for instructions that are encoded the same way at any address.
+        (batched; $([$name:ident, $source:literal, $mnemonic:literal, $arguments:literal $(, $comment:literal)?]),+ $(,)?) => {
+            make_tests!(@batched [$($source),+]; $([$name, $source, $mnemonic, $arguments $(, $comment)?]),+);
+        };
+
+        (@batched $sources:tt; $([$name:ident, $source:literal, $mnemonic:literal, $arguments:literal $(, $comment:literal)?]),+) => {
+            $(make_test!($name, assemble_batched(&$sources, $source), $mnemonic, $arguments $(, $comment)?);)+
+        };
+
         ($([$name:ident, $source:literal, $mnemonic:literal, $arguments:literal $(, $comment:literal)?]),+ $(,)?) => {
-            $(make_test!($name, $source, $mnemonic, $arguments $(, $comment)?);)+
+            $(make_test!($name, assemble_one($source), $mnemonic, $arguments $(, $comment)?);)+
         };
     }
 
     // Move shifted register (imm5)
     #[rustfmt::skip]
     make_tests! {
+        batched;
         [disasm_lsl_imm5, "lsl r0, r1, #12", "lsl", "r0, r1, #12"],
         [disasm_lsr_imm5, "lsr r0, r1, #12", "lsr", "r0, r1, #12"],
         [disasm_asr_imm5, "asr r0, r1, #12", "asr", "r0, r1, #12"],
@@ -792,6 +911,7 @@ mod tests {
     // ADD/SUB (imm3)
     #[rustfmt::skip]
     make_tests! {
+        batched;
         [disasm_add_imm3, "add r0, r1, #0x6", "add", "r0, r1, #0x6"],
         [disasm_sub_imm3, "sub r0, r1, #0x6", "sub", "r0, r1, #0x6"],
         [disasm_add_reg3, "add r0, r1, r2", "add", "r0, r1, r2"],
@@ -801,6 +921,7 @@ mod tests {
     // MOVE/COMPARE/ADD/SUBTRACT (imm8)
     #[rustfmt::skip]
     make_tests! {
+        batched;
         [disasm_mov_imm8, "mov r5, #0xab", "mov", "r5, #0xab"],
         [disasm_cmp_imm8, "cmp r5, #0xab", "cmp", "r5, #0xab"],
         [disasm_add_imm8, "add r5, #0xab", "add", "r5, #0xab"],
@@ -810,6 +931,7 @@ mod tests {
     // Data processing
     #[rustfmt::skip]
     make_tests! {
+        batched;
         [disasm_and_alu, "and r1, r2", "and", "r1, r2"],
         [disasm_eor_alu, "eor r1, r2", "eor", "r1, r2"],
         [disasm_lsl_alu, "lsl r1, r2", "lsl", "r1, r2"],
@@ -831,6 +953,7 @@ mod tests {
     // Hi register operations/branch exchange
     #[rustfmt::skip]
     make_tests! {
+        batched;
         [disasm_add_lo_hi_reg, "add r1, r10", "add", "r1, r10"],
         [disasm_add_hi_lo_reg, "add r10, r1", "add", "r10, r1"],
         [disasm_cmp_lo_hi_reg, "cmp r1, r10", "cmp", "r1, r10"],
@@ -844,12 +967,14 @@ mod tests {
     // PC-relative load
     #[rustfmt::skip]
     make_tests! {
+        batched;
         [disasm_ldr_pc_relative, "ldr r0, [pc, #0x4]", "ldr", "r0, [pc, #0x4]", "r0 = [0x00000008]"],
     }
 
     // SP-relative load/store
     #[rustfmt::skip]
     make_tests! {
+        batched;
         [disasm_ldr_sp_relative, "ldr r0, [sp, #0x4]", "ldr", "r0, [sp, #0x4]"],
         [disasm_str_sp_relative, "str r0, [sp, #0x4]", "str", "r0, [sp, #0x4]"],
     }
@@ -857,6 +982,7 @@ mod tests {
     // LDR/STR (register)
     #[rustfmt::skip]
     make_tests! {
+        batched;
         [disasm_ldr_reg, "ldr r0, [r1, r2]", "ldr", "r0, [r1, r2]"],
         [disasm_str_reg, "str r0, [r1, r2]", "str", "r0, [r1, r2]"],
         [disasm_ldrb_reg, "ldrb r0, [r1, r2]", "ldrb", "r0, [r1, r2]"],
@@ -879,13 +1005,15 @@ mod tests {
     // load address
     #[rustfmt::skip]
     make_tests! {
-        [disasm_load_address_pc, "add r0, pc, #0x4", "add", "r0, pc, #0x4"],
+        batched;
+        [disasm_load_address_pc, "add r0, pc, #0x4", "add", "r0, pc, #0x4", "r0 = 0x00000008"],
         [disasm_load_address_sp, "add r0, sp, #0x4", "add", "r0, sp, #0x4"],
     }
 
     // add offset to stack pointer
     #[rustfmt::skip]
     make_tests! {
+        batched;
         [disasm_add_sp, "add sp, #0x4", "add", "sp, #0x4"],
         [disasm_sub_sp, "sub sp, #0x4", "sub", "sp, #0x4"],
     }
@@ -893,6 +1021,7 @@ mod tests {
     // push/pop
     #[rustfmt::skip]
     make_tests! {
+        batched;
         [disasm_push, "push {r0,r2-r4,r6}", "push", "{r0,r2-r4,r6}"],
         [disasm_push_lr, "push {r0,r4-r5,lr}", "push", "{r0,r4-r5,lr}"],
         [disasm_pop, "pop {r0,r2-r4,r6}", "pop", "{r0,r2-r4,r6}"],
@@ -902,6 +1031,7 @@ mod tests {
     // ldmia/stmia
     #[rustfmt::skip]
     make_tests! {
+        batched;
         [disasm_ldmia, "ldmia r1!, {r0,r2-r4,r6}", "ldmia", "r1!, {r0,r2-r4,r6}"],
         [disasm_stmia, "stmia r1!, {r0,r2-r4,r6}", "stmia", "r1!, {r0,r2-r4,r6}"],
     }
@@ -929,41 +1059,155 @@ mod tests {
 
     #[rustfmt::skip]
     make_tests! {
+        batched;
         [disasm_swi, "swi #0x56", "swi", "#0x56"],
     }
 
-    fn assemble(source: &str) -> std::io::Result<Vec<u8>> {
+    #[test]
+    fn disassemble_function() {
+        let (assembled, symbols) = arm_devkit::thumb::assemble_with_symbols(
+            "
+            function:
+                push {r4, lr}
+                mov r4, #0x3
+                add r0, r4, #0x1
+                bl callee
+                lsl r0, r0, #2
+                pop {r4, pc}
+            callee:
+                bx lr
+            ",
+            linker_script().unwrap(),
+        )
+        .unwrap();
+        let function = symbols["function"];
+        let callee = symbols["callee"];
+        let bytes = &assembled[function as usize..callee as usize + 2];
+
+        let listing: Vec<(u32, String, String)> =
+            crate::Disassembler::new(crate::InstructionSet::Thumb, bytes, function)
+                .map(|(address, instr)| {
+                    (
+                        address,
+                        instr.mnemonic().to_string(),
+                        instr.arguments(address, None).to_string(),
+                    )
+                })
+                .collect();
+        let expected = [
+            (0x0, "push", "{r4,lr}".to_owned()),
+            (0x2, "mov", "r4, #0x3".to_owned()),
+            (0x4, "add", "r0, r4, #0x1".to_owned()),
+            (0x6, "bl", format!("0x{callee:08x}")),
+            (0xA, "lsl", "r0, r0, #2".to_owned()),
+            (0xC, "pop", "{r4,pc}".to_owned()),
+            (0xE, "bx", "lr".to_owned()),
+        ];
+        let expected: Vec<(u32, String, String)> = expected
+            .into_iter()
+            .map(|(offset, mnemonic, arguments)| {
+                (function + offset, mnemonic.to_owned(), arguments)
+            })
+            .collect();
+        assert_eq!(listing, expected);
+    }
+
+    #[test]
+    fn batched_assembly_matches_per_instruction() {
+        let sources = [
+            "lsl r0, r1, #12",
+            "add r10, r1",
+            "ldr r0, [pc, #0x4]",
+            "push {r0,r4-r5,lr}",
+            "ldmia r1!, {r0,r2-r4,r6}",
+            "swi #0x56",
+        ];
+        let batched = assemble_many(&sources).unwrap();
+        for (source, batched) in sources.into_iter().zip(batched) {
+            assert_eq!(assemble_one(source).unwrap(), batched, "{source}");
+        }
+    }
+
+    fn linker_script() -> std::io::Result<LinkerScript> {
         static LINKER_SCRIPT: RwLock<Option<LinkerScriptWeakRef>> = RwLock::new(None);
 
         let guard = LINKER_SCRIPT.read().unwrap();
         let maybe_linker_script = guard.as_ref().and_then(|ls| ls.upgrade());
         drop(guard);
-        let linker_script = if let Some(linker_script) = maybe_linker_script {
-            linker_script
-        } else {
-            let linker_script = arm_devkit::LinkerScript::new(arm_devkit::SIMPLE_LINKER_SCRIPT)?;
-            LINKER_SCRIPT
-                .write()
-                .unwrap()
-                .replace(linker_script.clone().weak());
-            linker_script
-        };
+        if let Some(linker_script) = maybe_linker_script {
+            return Ok(linker_script);
+        }
+
+        let linker_script = LinkerScript::new(arm_devkit::SIMPLE_LINKER_SCRIPT)?;
+        LINKER_SCRIPT
+            .write()
+            .unwrap()
+            .replace(linker_script.clone().weak());
+        Ok(linker_script)
+    }
+
+    fn assemble(source: &str) -> std::io::Result<Vec<u8>> {
+        arm_devkit::thumb::assemble(source, linker_script()?)
+    }
+
+    /// Assembles every source after its own label with a single toolchain invocation
+    /// and returns the instructions in the same order as the sources.
+    fn assemble_many(sources: &[&str]) -> std::io::Result<Vec<u16>> {
+        let mut batch = String::new();
+        for (idx, source) in sources.iter().enumerate() {
+            writeln!(batch, "batch_{idx}:\n{source}").unwrap();
+        }
+
+        let (assembled, symbols) =
+            arm_devkit::thumb::assemble_with_symbols(&batch, linker_script()?)?;
+        (0..sources.len())
+            .map(|idx| {
+                let label = format!("batch_{idx}");
+                let address = symbols
+                    .get(&label)
+                    .ok_or_else(|| std::io::Error::other(format!("no symbol for {label}")))?;
+                Ok(read_instr(&assembled, *address as usize))
+            })
+            .collect()
+    }
+
+    /// Returns the instruction for `source`, assembling all of `sources` together the
+    /// first time that any of them is needed. If the batch fails to assemble, each
+    /// source is assembled on its own so that errors are reported by the right test.
+    fn assemble_batched(sources: &[&'static str], source: &'static str) -> std::io::Result<u16> {
+        static BATCHED: Mutex<BTreeMap<&str, Option<u16>>> = Mutex::new(BTreeMap::new());
+
+        let mut batched = BATCHED.lock().unwrap_or_else(|err| err.into_inner());
+        if !batched.contains_key(source) {
+            let instrs = assemble_many(sources).ok();
+            for (idx, &batch_source) in sources.iter().enumerate() {
+                let instr = instrs.as_ref().map(|instrs| instrs[idx]);
+                batched.insert(batch_source, instr);
+            }
+        }
+
+        match batched[source] {
+            Some(instr) => Ok(instr),
+            None => {
+                drop(batched);
+                assemble_one(source)
+            }
+        }
+    }
 
-        arm_devkit::thumb::assemble(source, linker_script)
+    fn read_instr(assembled: &[u8], address: usize) -> u16 {
+        (assembled[address] as u16) | ((assembled[address + 1] as u16) << 8)
     }
 
     fn assemble_one(source: &str) -> std::io::Result<u16> {
         let assembled = assemble(source)?;
         assert!(assembled.len() >= 2);
-        let instr = (assembled[0] as u16) | ((assembled[1] as u16) << 8);
-        Ok(instr)
+        Ok(read_instr(&assembled, 0))
     }
 
     fn assemble_two(source: &str) -> std::io::Result<(u16, u16)> {
         let assembled = assemble(source)?;
         assert!(assembled.len() >= 4);
-        let instr1 = (assembled[0] as u16) | ((assembled[1] as u16) << 8);
-        let instr2 = (assembled[2] as u16) | ((assembled[3] as u16) << 8);
-        Ok((instr1, instr2))
+        Ok((read_instr(&assembled, 0), read_instr(&assembled, 2)))
     }
 }
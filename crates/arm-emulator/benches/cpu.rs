@@ -0,0 +1,47 @@
+use arm_emulator::InstructionSet;
+use common::Executor;
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+#[path = "../tests/common/mod.rs"]
+pub mod common;
+
+/// The number of instructions executed by each iteration of a benchmark.
+const INSTRUCTIONS: u64 = 10_000;
+
+/// Sums an array of 256 words forever. Only the loop body is executed most of the time
+/// so this is mostly loads, data processing and taken branches.
+const SUM_LOOP: &str = "
+restart:
+    ldr     r1, =array
+    mov     r2, #256
+    mov     r0, #0
+loop:
+    ldr     r3, [r1], #4
+    add     r0, r0, r3
+    subs    r2, r2, #1
+    bne     loop
+    b       restart
+";
+
+fn cpu_throughput(c: &mut Criterion) {
+    // The program is assembled once up front so that the toolchain isn't part of
+    // the measurement.
+    let mut exec = Executor::new(InstructionSet::Arm);
+    exec.data("array: .fill 256, 4, 3");
+    exec.push_no_exec(SUM_LOOP);
+    exec.assemble();
+
+    let mut group = c.benchmark_group("cpu");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+    group.bench_function("arm_sum_loop", |b| {
+        b.iter(|| {
+            for _ in 0..INSTRUCTIONS {
+                black_box(exec.cpu.step(&mut exec.mem));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, cpu_throughput);
+criterion_main!(benches);
@@ -10,7 +10,7 @@ use crate::{
     clock::Cycles,
     cpu::Cpu,
     memory::Memory,
-    transfer::{BlockDataTransfer, IndexingMode, SDTCalculateOffset, SingleDataTransfer},
+    transfer::{self, BlockDataTransfer, IndexingMode, SDTCalculateOffset, SingleDataTransfer},
     AccessType, CpsrFlag, CpuException, CpuMode,
 };
 
@@ -385,20 +385,7 @@ pub fn arm_swp<const BYTE: bool>(instr: u32, cpu: &mut Cpu, memory: &mut dyn Mem
     let rm = instr.get_bit_range(0..=3);
 
     let address = cpu.registers.read(rn);
-    let source = cpu.registers.read(rm);
-
-    cpu.access_type = AccessType::NonSequential;
-    if BYTE {
-        let (temp, wait_load) = memory.load8(address, cpu);
-        cpu.registers.write(rd, temp as u32);
-        let wait_store = memory.store8(address, source as u8, cpu);
-        Cycles::one() + wait_load + wait_store
-    } else {
-        let (temp, wait_load) = memory.load32(address, cpu);
-        cpu.registers.write(rd, temp);
-        let wait_store = memory.store32(address, source, cpu);
-        Cycles::one() + wait_load + wait_store
-    }
+    transfer::single_data_swap::<BYTE>(rd, rm, address, cpu, memory)
 }
 
 /// Software Interrupt (SWI)
@@ -1,6 +1,9 @@
-use std::ops::{Add, AddAssign, Sub, SubAssign};
+use std::{
+    iter::Sum,
+    ops::{Add, AddAssign, Sub, SubAssign},
+};
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug, PartialOrd, Ord)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, PartialOrd, Ord, Default)]
 pub struct Cycles(u32);
 
 impl Cycles {
@@ -28,6 +31,26 @@ impl Cycles {
     pub const fn saturating_sub(self, other: Cycles) -> Cycles {
         Cycles(self.0.saturating_sub(other.0))
     }
+
+    #[inline]
+    pub const fn saturating_add(self, other: Cycles) -> Cycles {
+        Cycles(self.0.saturating_add(other.0))
+    }
+
+    #[inline]
+    pub const fn as_u64(self) -> u64 {
+        self.0 as u64
+    }
+
+    /// Converts a 64-bit cycle count, saturating at [`u32::MAX`] cycles.
+    #[inline]
+    pub const fn from_u64(cycles: u64) -> Self {
+        if cycles > u32::MAX as u64 {
+            Cycles(u32::MAX)
+        } else {
+            Cycles(cycles as u32)
+        }
+    }
 }
 
 impl From<u32> for Cycles {
@@ -76,6 +99,12 @@ impl SubAssign for Cycles {
     }
 }
 
+impl Sum for Cycles {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Cycles::zero(), Add::add)
+    }
+}
+
 impl Add<Waitstates> for Cycles {
     type Output = Self;
 
@@ -90,7 +119,7 @@ impl AddAssign<Waitstates> for Cycles {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, PartialOrd, Ord, Default)]
 pub struct Waitstates(u32);
 
 impl Waitstates {
@@ -108,6 +137,16 @@ impl Waitstates {
     pub const fn is_zero(self) -> bool {
         self.0 == 0
     }
+
+    #[inline]
+    pub const fn saturating_sub(self, other: Waitstates) -> Waitstates {
+        Waitstates(self.0.saturating_sub(other.0))
+    }
+
+    #[inline]
+    pub const fn as_u64(self) -> u64 {
+        self.0 as u64
+    }
 }
 
 impl From<u32> for Waitstates {
@@ -140,6 +179,21 @@ impl AddAssign for Waitstates {
     }
 }
 
+impl Sub for Waitstates {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, rhs: Self) -> Self::Output {
+        Waitstates(self.0 - rhs.0)
+    }
+}
+
+impl Sum for Waitstates {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Waitstates::zero(), Add::add)
+    }
+}
+
 impl Add<Cycles> for Waitstates {
     type Output = Cycles;
 
@@ -147,3 +201,50 @@ impl Add<Cycles> for Waitstates {
         Cycles(self.0 + rhs.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Cycles, Waitstates};
+
+    #[test]
+    fn cycles_accumulate() {
+        let mut cycles = Cycles::zero();
+        cycles += Cycles::from(3);
+        cycles += Waitstates::from(2);
+        assert_eq!(cycles + Cycles::one(), Cycles::new(6));
+        cycles += Cycles::one();
+        assert_eq!(cycles.as_u64(), 6);
+
+        cycles -= Cycles::from(4);
+        assert_eq!(cycles, Cycles::new(2));
+        assert_eq!(cycles.saturating_sub(Cycles::new(5)), Cycles::zero());
+
+        let total: Cycles = (1..=4).map(Cycles::from).sum();
+        assert_eq!(total, Cycles::new(10));
+
+        let wait: Waitstates = [Waitstates::one(), Waitstates::from(2)].into_iter().sum();
+        assert_eq!(wait, Waitstates::from(3));
+        assert_eq!(wait + Cycles::one(), Cycles::new(4));
+    }
+
+    #[test]
+    fn cycles_ordering() {
+        assert!(Cycles::new(1) < Cycles::new(2));
+        assert!(Cycles::zero() <= Cycles::zero());
+        assert_eq!(
+            Cycles::new(7).max(Cycles::new(3)).min(Cycles::new(5)),
+            Cycles::new(5)
+        );
+        assert!(Waitstates::one() > Waitstates::zero());
+    }
+
+    #[test]
+    fn cycles_u64_conversions() {
+        assert_eq!(Cycles::from_u64(1234), Cycles::new(1234));
+        assert_eq!(Cycles::from_u64(u64::MAX), Cycles::new(u32::MAX));
+        assert_eq!(
+            Cycles::new(u32::MAX).saturating_add(Cycles::one()),
+            Cycles::new(u32::MAX)
+        );
+    }
+}
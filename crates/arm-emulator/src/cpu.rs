@@ -23,6 +23,19 @@ pub struct Cpu {
 
     decoded: u32,
     exception_handler: Option<ExceptionHandler>,
+
+    /// Set when an exception handler returns [`ExceptionHandlerResult::Halt`].
+    halted: bool,
+
+    /// The most recent exception. Cleared by [`Cpu::take_exception`].
+    last_exception: Option<CpuException>,
+
+    /// Set by [`Cpu::signal_abort`] during a memory access.
+    abort_signaled: bool,
+    /// The fetch of the decoded opcode was aborted.
+    decoded_aborted: bool,
+    /// The fetch of the fetched opcode was aborted.
+    fetched_aborted: bool,
 }
 
 #[derive(PartialEq, Clone, Copy, Eq)]
@@ -31,6 +44,16 @@ pub enum InstructionSet {
     Thumb,
 }
 
+/// The reason that [`Cpu::run_until`] stopped.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub enum RunOutcome {
+    /// An exception handler halted the CPU after executing `instructions` instructions.
+    Halted { instructions: u64 },
+
+    /// The CPU executed the maximum number of instructions without halting.
+    CapReached { instructions: u64 },
+}
+
 impl Cpu {
     /// **IMPORTANT**: [`Cpu::branch`] must always be called with the starting address of the CPU
     /// before [`Cpu::step`] if this method is used to construct a [`Cpu`]. If not the PC
@@ -52,6 +75,11 @@ impl Cpu {
             access_type: AccessType::NonSequential,
             fetched: noop_opcode,
             decoded: noop_opcode,
+            halted: false,
+            last_exception: None,
+            abort_signaled: false,
+            decoded_aborted: false,
+            fetched_aborted: false,
         }
     }
 
@@ -68,8 +96,14 @@ impl Cpu {
     /// At the start of the step function, the program counter will be one instruction ahead of the address
     /// of the instruction that wil be executed. Before execution occurs it will be set to be two instructions
     /// ahead.
+    ///
+    /// If the CPU is halted this does nothing and returns zero cycles.
     #[inline]
     pub fn step(&mut self, memory: &mut dyn Memory) -> Cycles {
+        if self.halted {
+            return Cycles::zero();
+        }
+
         if self.registers.get_flag(CpsrFlag::T) {
             self.step_thumb(memory)
         } else {
@@ -77,11 +111,33 @@ impl Cpu {
         }
     }
 
+    /// Steps the CPU until it is halted or until it has executed `max_instructions`
+    /// instructions. Unlike a wall-clock timeout this always stops at the same
+    /// point for the same program.
+    pub fn run_until(&mut self, memory: &mut dyn Memory, max_instructions: u64) -> RunOutcome {
+        let mut instructions = 0;
+        loop {
+            if self.halted {
+                return RunOutcome::Halted { instructions };
+            }
+            if instructions >= max_instructions {
+                return RunOutcome::CapReached { instructions };
+            }
+            self.step(memory);
+            instructions += 1;
+        }
+    }
+
     /// Returns the number of cycles required to step the CPU in the ARM state.
     #[inline(never)]
     fn step_arm(&mut self, memory: &mut dyn Memory) -> Cycles {
+        if self.decoded_aborted {
+            return self.exception(CpuException::PrefetchAbort, memory);
+        }
+
         let opcode = self.decoded;
         self.decoded = self.fetched;
+        self.decoded_aborted = self.fetched_aborted;
 
         let mut cycles = Cycles::zero();
         let fetch_pc = (self.registers.read(15) & !0x3).wrapping_add(4);
@@ -91,22 +147,31 @@ impl Cpu {
         self.access_type = AccessType::Sequential;
 
         self.fetched = fetched;
+        self.fetched_aborted = std::mem::take(&mut self.abort_signaled);
         cycles += Cycles::one() + wait;
 
         if check_condition(opcode >> 28, &self.registers) {
             let exec_fn = lookup::decode_arm_opcode(opcode);
-            cycles + exec_fn(opcode, self, memory)
-        } else {
-            cycles
+            cycles += exec_fn(opcode, self, memory);
         }
+
+        if self.abort_signaled {
+            cycles += self.data_abort(memory);
+        }
+        cycles
     }
 
     /// Returns the number of cycles required to step the CPU in the THUMB state.
     #[inline]
     fn step_thumb(&mut self, memory: &mut dyn Memory) -> Cycles {
+        if self.decoded_aborted {
+            return self.exception(CpuException::PrefetchAbort, memory);
+        }
+
         let opcode = self.decoded;
         let exec_fn = lookup::decode_thumb_opcode(opcode);
         self.decoded = self.fetched;
+        self.decoded_aborted = self.fetched_aborted;
 
         let mut cycles = Cycles::zero();
         let fetch_pc = (self.registers.read(15) & !0x1).wrapping_add(2);
@@ -116,9 +181,33 @@ impl Cpu {
         self.access_type = AccessType::Sequential;
 
         self.fetched = fetched as u32;
+        self.fetched_aborted = std::mem::take(&mut self.abort_signaled);
         cycles += Cycles::one() + wait;
 
-        cycles + exec_fn(opcode, self, memory)
+        cycles += exec_fn(opcode, self, memory);
+
+        if self.abort_signaled {
+            cycles += self.data_abort(memory);
+        }
+        cycles
+    }
+
+    /// Takes a data abort for the instruction that was just executed. The return
+    /// address is the address of the aborted instruction + 8 in both ARM and THUMB state.
+    fn data_abort(&mut self, memory: &mut dyn Memory) -> Cycles {
+        self.abort_signaled = false;
+        let return_addr = self.exception_address().wrapping_add(4);
+        self.exception_with_ret(CpuException::DataAbort, return_addr, memory)
+    }
+
+    /// Signals that the memory access currently being performed failed. This is meant
+    /// to be called by [`Memory`] implementations from within a load or store.
+    ///
+    /// If the access was an opcode fetch a prefetch abort is taken when the opcode
+    /// would have been executed. Otherwise a data abort is taken once the current
+    /// instruction is complete.
+    pub fn signal_abort(&mut self) {
+        self.abort_signaled = true;
     }
 
     pub fn branch(&mut self, address: u32, memory: &mut dyn Memory) -> Cycles {
@@ -136,15 +225,21 @@ impl Cpu {
 
         self.registers.write(15, address);
         self.access_type = AccessType::NonSequential;
+        // An abort signaled by the instruction that caused this branch still has
+        // to be taken once the instruction is complete.
+        let data_abort = std::mem::take(&mut self.abort_signaled);
         let (decoded, wait) = memory.load32(address.wrapping_sub(4), self);
+        self.decoded_aborted = std::mem::take(&mut self.abort_signaled);
         cycles += Cycles::one() + wait;
 
         self.access_type = AccessType::Sequential;
         let (fetched, wait) = memory.load32(address, self);
+        self.fetched_aborted = std::mem::take(&mut self.abort_signaled);
         cycles += Cycles::one() + wait;
 
         self.decoded = decoded;
         self.fetched = fetched;
+        self.abort_signaled = data_abort;
 
         cycles
     }
@@ -155,11 +250,15 @@ impl Cpu {
         let mut cycles = Cycles::zero();
 
         self.access_type = AccessType::NonSequential;
+        let data_abort = std::mem::take(&mut self.abort_signaled);
         let (decoded, wait) = memory.load16(address, self);
+        self.decoded_aborted = std::mem::take(&mut self.abort_signaled);
         cycles += Cycles::one() + wait;
 
         self.access_type = AccessType::Sequential;
         let (fetched, wait) = memory.load16(address.wrapping_add(2), self);
+        self.fetched_aborted = std::mem::take(&mut self.abort_signaled);
+        self.abort_signaled = data_abort;
         cycles += Cycles::one() + wait;
 
         self.decoded = decoded as u32;
@@ -179,6 +278,19 @@ impl Cpu {
         }
     }
 
+    /// The opcode that was most recently fetched by the pipeline. In ARM state this
+    /// is the opcode at `$+8` and in THUMB state this is the opcode at `$+4` where
+    /// `$` is the address of the instruction currently being executed.
+    pub fn prefetched_opcode(&self) -> u32 {
+        self.fetched
+    }
+
+    /// The opcode that has been decoded and will be executed after the current
+    /// instruction (`$+4` in ARM state, `$+2` in THUMB state).
+    pub fn decoded_opcode(&self) -> u32 {
+        self.decoded
+    }
+
     /// The address at which an exception occurred. This is only really useful
     /// from within an exception handler that was set via [`Cpu::set_exception_handler`]
     pub fn exception_address(&self) -> u32 {
@@ -189,6 +301,12 @@ impl Cpu {
         }
     }
 
+    /// The address of the exception vector that the CPU branches to when `exception`
+    /// is raised and no exception handler takes care of it.
+    pub fn exception_vector(&self, exception: CpuException) -> u32 {
+        EXCEPTION_BASE + exception.info().offset
+    }
+
     /// Sets the exception handler that will be called whenever the CPU encounters an
     /// exception such as an IRQ, SWI, ect.
     ///
@@ -204,6 +322,23 @@ impl Cpu {
         self.exception_handler.replace(Box::new(handler))
     }
 
+    /// Returns true if an exception handler halted the CPU.
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Returns the most recent exception that was raised since the last call, whether
+    /// or not an exception handler took care of it.
+    pub fn take_exception(&mut self) -> Option<CpuException> {
+        self.last_exception.take()
+    }
+
+    /// Resumes execution after the CPU was halted by an exception handler. Execution
+    /// continues with the instruction after the one that caused the exception.
+    pub fn resume(&mut self) {
+        self.halted = false;
+    }
+
     pub fn exception(&mut self, exception: CpuException, memory: &mut dyn Memory) -> Cycles {
         self.exception_with_ret(exception, self.next_execution_address(), memory)
     }
@@ -240,8 +375,9 @@ impl Cpu {
         return_addr: u32,
         memory: &mut dyn Memory,
     ) -> Cycles {
+        self.last_exception = Some(exception);
         let exception_info = exception.info();
-        let exception_vector = EXCEPTION_BASE + exception_info.offset;
+        let exception_vector = self.exception_vector(exception);
 
         // we temporarily remove the handler while processing and exception
         // we don't want reentrant exception handling and Rust's borrow checker
@@ -249,8 +385,13 @@ impl Cpu {
         if let Some(mut handler) = self.exception_handler.take() {
             let result = handler(self, memory, exception);
             self.exception_handler = Some(handler); // put it back
-            if let ExceptionHandlerResult::Handled(cycles) = result {
-                return cycles;
+            match result {
+                ExceptionHandlerResult::Handled(cycles) => return cycles,
+                ExceptionHandlerResult::Halt => {
+                    self.halted = true;
+                    return Cycles::zero();
+                }
+                ExceptionHandlerResult::Ignored => {}
             }
         }
 
@@ -307,3 +448,26 @@ pub(crate) fn check_condition(cond: u32, regs: &Registers) -> bool {
         _ => unreachable!("bad condition code: 0x{:08X} ({:04b})", cond, cond),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Cpu, CpuException, CpuMode, InstructionSet};
+
+    #[test]
+    fn exception_vectors() {
+        let cpu = Cpu::uninitialized(InstructionSet::Arm, CpuMode::System);
+        let vectors = [
+            (CpuException::Reset, 0x00),
+            (CpuException::Undefined, 0x04),
+            (CpuException::Swi, 0x08),
+            (CpuException::PrefetchAbort, 0x0C),
+            (CpuException::DataAbort, 0x10),
+            (CpuException::AddressExceeds26Bit, 0x14),
+            (CpuException::Irq, 0x18),
+            (CpuException::Fiq, 0x1C),
+        ];
+        for (exception, vector) in vectors {
+            assert_eq!(cpu.exception_vector(exception), vector, "{exception}");
+        }
+    }
+}
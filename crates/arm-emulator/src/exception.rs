@@ -7,6 +7,10 @@ pub type ExceptionHandler =
 pub enum ExceptionHandlerResult {
     Handled(Cycles),
     Ignored,
+
+    /// Halts the CPU without taking the exception. [`Cpu::step`] will not execute
+    /// anything until [`Cpu::resume`] is called.
+    Halt,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -11,7 +11,7 @@ mod transfer;
 
 pub use alu::{ArithmeticShr, RotateRightExtended};
 pub use clock::{Cycles, Waitstates};
-pub use cpu::{Cpu, InstructionSet};
+pub use cpu::{Cpu, InstructionSet, RunOutcome};
 pub use exception::{CpuException, ExceptionHandler, ExceptionHandlerResult};
-pub use memory::{AccessType, Memory};
-pub use registers::{CpsrFlag, CpuMode, Registers};
+pub use memory::{load_slice_bytewise, store_slice_bytewise, AccessType, Memory};
+pub use registers::{CpsrFields, CpsrFlag, CpuMode, Registers, RegistersSnapshot};
@@ -31,10 +31,56 @@ pub trait Memory {
 
     fn store8(&mut self, address: u32, value: u8, cpu: &mut Cpu) -> Waitstates;
 
+    /// Reads `buffer.len()` bytes starting at `address`. By default this is the same
+    /// as calling [`Memory::load8`] for every byte but implementations can override
+    /// this with a faster copy for contiguous memory.
+    fn load_slice(&mut self, address: u32, buffer: &mut [u8], cpu: &mut Cpu) -> Waitstates {
+        load_slice_bytewise(self, address, buffer, cpu)
+    }
+
+    /// Writes all of `data` starting at `address`. By default this is the same as
+    /// calling [`Memory::store8`] for every byte but implementations can override
+    /// this with a faster copy for contiguous memory.
+    fn store_slice(&mut self, address: u32, data: &[u8], cpu: &mut Cpu) -> Waitstates {
+        store_slice_bytewise(self, address, data, cpu)
+    }
+
     fn as_any(&self) -> &dyn Any;
     fn as_mut_any(&mut self) -> &mut dyn Any;
 }
 
+/// The default implementation of [`Memory::load_slice`], for implementations that
+/// only have a faster copy for some addresses.
+pub fn load_slice_bytewise<M: Memory + ?Sized>(
+    memory: &mut M,
+    address: u32,
+    buffer: &mut [u8],
+    cpu: &mut Cpu,
+) -> Waitstates {
+    let mut wait = Waitstates::zero();
+    for (offset, byte) in buffer.iter_mut().enumerate() {
+        let (value, byte_wait) = memory.load8(address.wrapping_add(offset as u32), cpu);
+        *byte = value;
+        wait += byte_wait;
+    }
+    wait
+}
+
+/// The default implementation of [`Memory::store_slice`], for implementations that
+/// only have a faster copy for some addresses.
+pub fn store_slice_bytewise<M: Memory + ?Sized>(
+    memory: &mut M,
+    address: u32,
+    data: &[u8],
+    cpu: &mut Cpu,
+) -> Waitstates {
+    let mut wait = Waitstates::zero();
+    for (offset, &value) in data.iter().enumerate() {
+        wait += memory.store8(address.wrapping_add(offset as u32), value, cpu);
+    }
+    wait
+}
+
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub enum AccessType {
     Sequential,
@@ -102,6 +102,50 @@ pub enum CpsrFlag {
     T = 5,
 }
 
+/// The fields of the CPSR, see [`Registers::cpsr_fields`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct CpsrFields {
+    pub n: bool,
+    pub z: bool,
+    pub c: bool,
+    pub v: bool,
+    /// IRQs are disabled.
+    pub i: bool,
+    /// FIQs are disabled.
+    pub f: bool,
+    /// THUMB state.
+    pub t: bool,
+    pub mode: CpuMode,
+}
+
+/// A copy of all of the CPU's registers, including the banked registers of modes
+/// other than the current one. See [`Registers::snapshot`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct RegistersSnapshot {
+    /// r0-r15 for the mode in the CPSR.
+    pub registers: [u32; 16],
+
+    /// Banked registers that aren't in use by the current mode, in the same order
+    /// as [`Registers`] stores them:
+    /// - 0-4:   r8_fiq - r12_fiq (or r8-r12 while in FIQ mode)
+    /// - 5-6:   r13_fiq & r14_fiq
+    /// - 7-8:   r13_svc & r14_svc
+    /// - 9-10:  r13_abt & r14_abt
+    /// - 11-12: r13_irq & r14_irq
+    /// - 13-14: r13_und & r14_und
+    ///
+    /// The entries for the current mode hold the user mode values that it replaced.
+    pub banked_registers: [u32; 15],
+
+    /// SPSR_fiq, SPSR_svc, SPSR_abt, SPSR_irq and SPSR_und.
+    pub banked_spsr: [u32; 5],
+
+    pub cpsr: u32,
+
+    /// The SPSR of the current mode.
+    pub spsr: u32,
+}
+
 pub struct Registers {
     /// The currently in use general purpose registers (r0-r15).
     gp_registers: [u32; 16],
@@ -287,6 +331,41 @@ impl Registers {
         }
     }
 
+    /// Returns the flags and the mode in the CPSR.
+    #[must_use]
+    pub fn cpsr_fields(&self) -> CpsrFields {
+        CpsrFields {
+            n: self.get_flag(CpsrFlag::N),
+            z: self.get_flag(CpsrFlag::Z),
+            c: self.get_flag(CpsrFlag::C),
+            v: self.get_flag(CpsrFlag::V),
+            i: self.get_flag(CpsrFlag::I),
+            f: self.get_flag(CpsrFlag::F),
+            t: self.get_flag(CpsrFlag::T),
+            mode: self.read_mode(),
+        }
+    }
+
+    /// Sets the flags and the mode in the CPSR the same way as [`Registers::write_cpsr`].
+    /// The reserved bits are left as they are.
+    pub fn set_cpsr_fields(&mut self, fields: CpsrFields) {
+        let flags = [
+            (CpsrFlag::N, fields.n),
+            (CpsrFlag::Z, fields.z),
+            (CpsrFlag::C, fields.c),
+            (CpsrFlag::V, fields.v),
+            (CpsrFlag::I, fields.i),
+            (CpsrFlag::F, fields.f),
+            (CpsrFlag::T, fields.t),
+        ];
+        let cpsr = flags
+            .into_iter()
+            .fold(self.cpsr & !0x1F, |cpsr, (flag, value)| {
+                cpsr.put_bit(flag as u8, value)
+            });
+        self.write_cpsr(cpsr | fields.mode.bits());
+    }
+
     // #TODO(LOW): might want to make this panic or show a warning in debug mode
     //             when it is called and the CPU is in User or System mode.
     /// Reads the value of the Saved Program Status Register (SPSR)
@@ -308,6 +387,28 @@ impl Registers {
         self.spsr = value;
     }
 
+    /// Copies the value of every register.
+    #[must_use]
+    pub fn snapshot(&self) -> RegistersSnapshot {
+        RegistersSnapshot {
+            registers: self.gp_registers,
+            banked_registers: self.bk_registers,
+            banked_spsr: self.bk_spsr,
+            cpsr: self.cpsr,
+            spsr: self.spsr,
+        }
+    }
+
+    /// Sets every register, including the CPSR and the current mode, to the values
+    /// in a snapshot.
+    pub fn restore(&mut self, snapshot: &RegistersSnapshot) {
+        self.gp_registers = snapshot.registers;
+        self.bk_registers = snapshot.banked_registers;
+        self.bk_spsr = snapshot.banked_spsr;
+        self.cpsr = snapshot.cpsr;
+        self.spsr = snapshot.spsr;
+    }
+
     /// Called during a mode switch to switch the general purpose registers
     /// and the spsr to their proper banked versions.
     fn on_mode_switch(&mut self, old_mode: CpuMode, new_mode: CpuMode) {
@@ -486,4 +587,71 @@ mod tests {
         assert_registers(CpuMode::IRQ);
         assert_registers(CpuMode::Undefined);
     }
+
+    #[test]
+    fn snapshot_and_restore() {
+        let mut registers = Registers::new(CpuMode::System);
+        for register in 0..16 {
+            registers.write(register, register);
+        }
+        registers.write_with_mode(CpuMode::IRQ, 13, 0x03007FA0);
+        registers.write_with_mode(CpuMode::Supervisor, 13, 0x03007FE0);
+        registers.write_mode(CpuMode::IRQ);
+        registers.write_spsr(0x1F);
+        registers.write_mode(CpuMode::Supervisor);
+        registers.write_spsr(0x10);
+        registers.write(14, 0x08000100);
+        registers.set_flag(CpsrFlag::C);
+
+        let snapshot = registers.snapshot();
+
+        registers.write(0, 0xDEADBEEF);
+        registers.write(14, 0);
+        registers.write_spsr(0);
+        registers.write_mode(CpuMode::IRQ);
+        registers.write(13, 0);
+        registers.write_spsr(0);
+        registers.write_mode(CpuMode::User);
+        registers.clear_flag(CpsrFlag::C);
+
+        registers.restore(&snapshot);
+        assert_eq!(registers.snapshot(), snapshot);
+        assert_eq!(registers.read_mode(), CpuMode::Supervisor);
+        assert!(registers.get_flag(CpsrFlag::C));
+        assert_eq!(registers.read(0), 0);
+        assert_eq!(registers.read(13), 0x03007FE0);
+        assert_eq!(registers.read(14), 0x08000100);
+        assert_eq!(registers.read_spsr(), 0x10);
+        assert_eq!(registers.read_with_mode(CpuMode::IRQ, 13), 0x03007FA0);
+        assert_eq!(registers.read_with_mode(CpuMode::System, 13), 13);
+        assert_eq!(registers.read_with_mode(CpuMode::System, 14), 14);
+        registers.write_mode(CpuMode::IRQ);
+        assert_eq!(registers.read_spsr(), 0x1F);
+    }
+
+    #[test]
+    fn cpsr_fields_round_trip() {
+        let mut registers = Registers::new(CpuMode::System);
+        registers.write_cpsr(0xA00000B3);
+
+        let fields = registers.cpsr_fields();
+        assert_eq!(
+            fields,
+            CpsrFields {
+                n: true,
+                z: false,
+                c: true,
+                v: false,
+                i: true,
+                f: false,
+                t: true,
+                mode: CpuMode::Supervisor,
+            }
+        );
+
+        registers.write_cpsr(0x0000001F);
+        registers.set_cpsr_fields(fields);
+        assert_eq!(registers.read_cpsr(), 0xA00000B3);
+        assert_eq!(registers.read_mode(), CpuMode::Supervisor);
+    }
 }
@@ -2,7 +2,7 @@ use util::bits::BitOps;
 
 use crate::{
     alu::{AriOp2, ExtractOp2, LliOp2, LriOp2, RriOp2},
-    Cpu, CpuMode, Cycles, Memory, Registers,
+    AccessType, Cpu, CpuMode, Cycles, Memory, Registers,
 };
 
 pub struct Ldr<const USER_MODE: bool = false>;
@@ -125,10 +125,12 @@ impl SingleDataTransfer for Ldrh {
     const IS_LOAD: bool = true;
 
     fn transfer(rd: u32, addr: u32, cpu: &mut Cpu, memory: &mut dyn Memory) -> Cycles {
-        // We don't align the address here. If bit 0 is high then behavior is just
-        // unpredictable (depends on memory hardware).
-        let (value, wait) = memory.load16(addr, cpu);
-        cpu.registers.write(rd, value as u32);
+        // The ARM7TDMI loads a halfword at an odd address from the aligned address
+        // and then rotates it the same way as an unaligned word load, so the
+        // addressed byte ends up in bits 0-7 and the other byte in bits 24-31.
+        let (value, wait) = memory.load16(addr & !0x1, cpu);
+        cpu.registers
+            .write(rd, (value as u32).rotate_right(8 * (addr & 0x1)));
         Cycles::one() + wait
     }
 }
@@ -163,10 +165,16 @@ impl SingleDataTransfer for Ldrsh {
     const IS_LOAD: bool = true;
 
     fn transfer(rd: u32, addr: u32, cpu: &mut Cpu, memory: &mut dyn Memory) -> Cycles {
-        // We don't align the address here. If bit 0 is high then behavior is just
-        // unpredictable (depends on memory hardware).
-        let (value, wait) = memory.load16(addr, cpu);
-        cpu.registers.write(rd, value as i16 as i32 as u32);
+        // On the ARM7TDMI a signed halfword load from an odd address only loads the
+        // addressed byte and sign extends it like LDRSB.
+        let (value, wait) = if addr & 0x1 == 0 {
+            let (value, wait) = memory.load16(addr, cpu);
+            (value as i16 as i32 as u32, wait)
+        } else {
+            let (value, wait) = memory.load8(addr, cpu);
+            (value as i8 as i32 as u32, wait)
+        };
+        cpu.registers.write(rd, value);
         Cycles::one() + wait
     }
 }
@@ -382,6 +390,38 @@ impl BlockDataTransfer for Stm {
     }
 }
 
+/// Swaps register `rm` with the byte or word at `address`, writing the old memory value
+/// to `rd`. The load and store are locked together on the bus so nothing can access
+/// memory between them.
+///
+/// Word swaps behave like an LDR followed by an STR: a word at an unaligned address is
+/// loaded from the aligned address and rotated so that the addressed byte is in bits
+/// 0-7, and `rm` is stored to the aligned address without being rotated.
+pub fn single_data_swap<const BYTE: bool>(
+    rd: u32,
+    rm: u32,
+    address: u32,
+    cpu: &mut Cpu,
+    memory: &mut dyn Memory,
+) -> Cycles {
+    // rm is read before rd is written so that swapping a register with itself works.
+    let source = cpu.registers.read(rm);
+
+    cpu.access_type = AccessType::NonSequential;
+    if BYTE {
+        let (value, wait_load) = memory.load8(address, cpu);
+        cpu.registers.write(rd, value as u32);
+        let wait_store = memory.store8(address, source as u8, cpu);
+        Cycles::one() + wait_load + wait_store
+    } else {
+        let (value, wait_load) = memory.load32(address & !0x3, cpu);
+        cpu.registers
+            .write(rd, value.rotate_right(8 * (address % 4)));
+        let wait_store = memory.store32(address & !0x3, source, cpu);
+        Cycles::one() + wait_load + wait_store
+    }
+}
+
 pub trait SingleDataTransfer {
     const IS_LOAD: bool;
 
@@ -1,6 +1,10 @@
-use arm_emulator::{CpsrFlag, CpuMode};
+use arm_emulator::{CpsrFlag, CpuMode, ExceptionHandlerResult, InstructionSet, RunOutcome};
 
-use crate::common::operands::{bools, imm32, rand_operand};
+use crate::common::{
+    expected_ldr, expected_ldrh, expected_ldrsh,
+    operands::{bools, imm32, rand_operand},
+    rand_scratch_address, Executor,
+};
 
 #[macro_use]
 pub mod common;
@@ -1077,16 +1081,21 @@ test_combinations! {
 
 #[test]
 pub fn test_ldr() {
-    let (cpu, _mem) = arm! {"
-        ldr r1, =deadbeef
-        mov r2, r1
-        ldr r0, [r1]
-    .data
+    let mut exec = Executor::new(InstructionSet::Arm);
+    exec.data(
+        "
     deadbeef:
         .word 0xDEADBEEF
-    "};
-    assert_eq!(cpu.registers.read(1), cpu.registers.read(2));
-    assert_eq!(cpu.registers.read(0), 0xDEADBEEF);
+    ",
+    );
+    exec.push(
+        "
+        ldr r1, =deadbeef
+        ldr r0, [r1]
+    ",
+    );
+    assert_eq!(Some(exec.cpu.registers.read(1)), exec.symbol("deadbeef"));
+    assert_eq!(exec.cpu.registers.read(0), 0xDEADBEEF);
 }
 
 #[test]
@@ -1103,6 +1112,67 @@ pub fn test_ldr_unaligned() {
     assert_eq!(cpu.registers.read(0), 0xBEEFDEAD);
 }
 
+/// Every addressing mode of the word and halfword loads rotates unaligned values
+/// the same way.
+#[test]
+pub fn test_unaligned_loads_rotate() {
+    let mut exec = Executor::new(InstructionSet::Arm);
+    exec.push_no_exec(
+        "
+        ldr     r0, [r1, r2]
+        ldr     r3, [r4], r5
+        ldrh    r6, [r7, r8]
+        ldrsh   r9, [r10], r11
+        ldr     r12, [r1, #1]!
+        ",
+    );
+    exec.assemble();
+
+    for _ in 0..256 {
+        let address = rand_scratch_address(&mut exec);
+        exec.cpu.registers.write(1, address & !0xF);
+        exec.cpu.registers.write(2, address & 0xF);
+        exec.cpu.registers.write(4, address);
+        exec.cpu.registers.write(5, 4);
+        exec.cpu.registers.write(7, address & !0x7);
+        exec.cpu.registers.write(8, address & 0x7);
+        exec.cpu.registers.write(10, address);
+        exec.cpu.registers.write(11, 2);
+        exec.run_from_start(5);
+
+        let ctx = format!("address=0x{address:08X}");
+        assert_eq!(
+            exec.cpu.registers.read(0),
+            expected_ldr(&exec.mem, address),
+            "{ctx}"
+        );
+        assert_eq!(
+            exec.cpu.registers.read(3),
+            expected_ldr(&exec.mem, address),
+            "{ctx}"
+        );
+        assert_eq!(exec.cpu.registers.read(4), address + 4, "{ctx}");
+        assert_eq!(
+            exec.cpu.registers.read(6),
+            expected_ldrh(&exec.mem, address),
+            "{ctx}"
+        );
+        assert_eq!(
+            exec.cpu.registers.read(9),
+            expected_ldrsh(&exec.mem, address),
+            "{ctx}"
+        );
+        assert_eq!(exec.cpu.registers.read(10), address + 2, "{ctx}");
+        let pre_indexed = (address & !0xF) + 1;
+        assert_eq!(
+            exec.cpu.registers.read(12),
+            expected_ldr(&exec.mem, pre_indexed),
+            "{ctx}"
+        );
+        assert_eq!(exec.cpu.registers.read(1), pre_indexed, "{ctx}");
+    }
+}
+
 #[test]
 pub fn test_ldr_post_index() {
     let (cpu, _mem) = arm! {"
@@ -1737,3 +1807,137 @@ pub fn test_swpb() {
     assert_eq!(cpu.registers.read(0), 0xDD);
     assert_eq!(mem.view32(cpu.registers.read(2)), 0xAABBCCEF);
 }
+
+#[test]
+pub fn test_swp_unaligned() {
+    let (cpu, mem) = arm! {"
+        ldr r1, =0xDEADBEEF
+        ldr r2, =data
+        add r2, r2, #1
+        swp r0, r1, [r2]
+    .data
+    data:
+        .word 0xAABBCCDD
+    "};
+
+    // The loaded word is rotated like an LDR and the stored word is not rotated.
+    assert_eq!(cpu.registers.read(0), 0xDDAABBCC);
+    assert_eq!(mem.view32(cpu.registers.read(2) & !0x3), 0xDEADBEEF);
+}
+
+#[test]
+pub fn test_swpb_unaligned() {
+    let (cpu, mem) = arm! {"
+        ldr     r1, =0xDEADBEEF
+        ldr     r2, =data
+        add     r2, r2, #3
+        swpb    r0, r1, [r2]
+    .data
+    data:
+        .word 0xAABBCCDD
+    "};
+
+    assert_eq!(cpu.registers.read(0), 0xAA);
+    assert_eq!(mem.view32(cpu.registers.read(2) & !0x3), 0xEFBBCCDD);
+}
+
+#[test]
+pub fn test_swp_same_register() {
+    let (cpu, mem) = arm! {"
+        ldr r0, =0x12345678
+        ldr r2, =data
+        swp r0, r0, [r2]
+    .data
+    data:
+        .word 0xAABBCCDD
+    "};
+
+    assert_eq!(cpu.registers.read(0), 0xAABBCCDD);
+    assert_eq!(mem.view32(cpu.registers.read(2)), 0x12345678);
+}
+
+/// Exception vectors that end execution for the abort tests.
+const ABORT_VECTORS: &str = "
+        b       start
+        .word   0, 0
+        .word   0xF777F777      @ 0x0C: prefetch abort
+        .word   0xF777F777      @ 0x10: data abort
+    start:
+";
+
+#[test]
+pub fn test_data_abort() {
+    let mut exec = Executor::new(InstructionSet::Arm);
+    exec.mem.abort_range = Some(0x10000000..0x20000000);
+    exec.push_no_exec(ABORT_VECTORS);
+    exec.push(
+        "
+        msr     cpsr_f, #0x20000000
+        ldr     r1, =0x10000000
+        ldr     r0, [r1]        @ 0x1C
+        mov     r2, #1
+    ",
+    );
+
+    let cpu = exec.cpu;
+    assert_eq!(cpu.registers.read_mode(), CpuMode::Abort);
+    assert_eq!(cpu.registers.read(14), 0x1C + 8);
+    assert_eq!(cpu.registers.read_spsr(), 0x20000000 | 0x1F);
+    assert_eq!(cpu.registers.read(2), 0);
+}
+
+#[test]
+pub fn test_prefetch_abort() {
+    let mut exec = Executor::new(InstructionSet::Arm);
+    exec.mem.abort_range = Some(0x10000000..0x20000000);
+    exec.push_no_exec(ABORT_VECTORS);
+    exec.push(
+        "
+        ldr     pc, =0x10000000
+    ",
+    );
+
+    let cpu = exec.cpu;
+    assert_eq!(cpu.registers.read_mode(), CpuMode::Abort);
+    assert_eq!(cpu.registers.read(14), 0x10000000 + 4);
+    assert_eq!(cpu.registers.read_spsr() & 0x1F, 0x1F);
+}
+
+#[test]
+pub fn test_run_until_instruction_cap() {
+    let mut exec = Executor::new(InstructionSet::Arm);
+    exec.push_no_exec(
+        "
+        mov     r0, #0
+    loop:
+        add     r0, r0, #1
+        b       loop
+    ",
+    );
+    exec.assemble();
+
+    let outcome = exec.cpu.run_until(&mut exec.mem, 21);
+    assert_eq!(outcome, RunOutcome::CapReached { instructions: 21 });
+    assert_eq!(exec.cpu.registers.read(0), 10);
+}
+
+#[test]
+pub fn test_run_until_halted() {
+    let mut exec = Executor::new(InstructionSet::Arm);
+    exec.cpu
+        .set_exception_handler(|_, _, _| ExceptionHandlerResult::Halt);
+    exec.push_no_exec(
+        "
+        mov     r0, #1
+        mov     r1, #2
+        swi     #0
+        mov     r2, #3
+    ",
+    );
+    exec.assemble();
+
+    let outcome = exec.cpu.run_until(&mut exec.mem, 100);
+    assert_eq!(outcome, RunOutcome::Halted { instructions: 3 });
+    assert_eq!(exec.cpu.registers.read(1), 2);
+    assert_eq!(exec.cpu.registers.read(2), 0);
+}
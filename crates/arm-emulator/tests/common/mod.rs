@@ -1,8 +1,10 @@
 pub mod operands;
 
-use std::sync::Mutex;
+use std::{ops::Range, sync::Mutex};
 
-use arm_devkit::{LinkerScript, LinkerScriptWeakRef};
+use rand::Rng as _;
+
+use arm_devkit::{LinkerScript, LinkerScriptWeakRef, Symbols};
 use arm_emulator::{CpsrFlag, Cpu, CpuMode, InstructionSet, Memory, Waitstates};
 
 #[macro_use]
@@ -11,6 +13,9 @@ mod test_combinations;
 #[derive(Default)]
 pub struct TestMemory {
     data: Vec<u8>,
+
+    /// Accesses to addresses in this range signal an abort.
+    pub abort_range: Option<Range<u32>>,
 }
 
 impl TestMemory {
@@ -25,15 +30,34 @@ impl TestMemory {
     pub fn view8(&self, address: u32) -> u8 {
         self.data[address as usize % self.data.len()]
     }
+
+    /// Makes the memory at least `len` bytes long. The new bytes are zero.
+    pub fn grow(&mut self, len: usize) {
+        if self.data.len() < len {
+            self.data.resize(len, 0);
+        }
+    }
+}
+
+impl TestMemory {
+    fn check_abort(&self, address: u32, cpu: &mut Cpu) {
+        if let Some(ref range) = self.abort_range {
+            if range.contains(&address) {
+                cpu.signal_abort();
+            }
+        }
+    }
 }
 
 impl Memory for TestMemory {
-    fn load8(&mut self, address: u32, _cpu: &mut Cpu) -> (u8, Waitstates) {
+    fn load8(&mut self, address: u32, cpu: &mut Cpu) -> (u8, Waitstates) {
+        self.check_abort(address, cpu);
         let address = address as usize % self.data.len();
         (self.data[address], Waitstates::zero())
     }
 
-    fn store8(&mut self, address: u32, value: u8, _cpu: &mut Cpu) -> Waitstates {
+    fn store8(&mut self, address: u32, value: u8, cpu: &mut Cpu) -> Waitstates {
+        self.check_abort(address, cpu);
         let address = address as usize % self.data.len();
         self.data[address] = value;
         Waitstates::zero()
@@ -75,6 +99,7 @@ pub struct Executor {
     data: String,
     source: String,
     base_isa: InstructionSet,
+    symbols: Symbols,
 }
 
 impl Executor {
@@ -85,6 +110,21 @@ impl Executor {
             source: String::new(),
             data: String::new(),
             base_isa,
+            symbols: Symbols::default(),
+        }
+    }
+
+    /// Returns the address of a label in the source that was last assembled.
+    pub fn symbol(&self, name: &str) -> Option<u32> {
+        self.symbols.get(name).copied()
+    }
+
+    /// Branches back to the start of the source that was last assembled and
+    /// executes `count` instructions.
+    pub fn run_from_start(&mut self, count: usize) {
+        self.cpu.branch(0, &mut self.mem);
+        for _ in 0..count {
+            self.cpu.step(&mut self.mem);
         }
     }
 
@@ -107,7 +147,9 @@ impl Executor {
         self.execute();
     }
 
-    fn execute(&mut self) {
+    /// Assembles the source that was pushed so far and branches to it without
+    /// executing anything.
+    pub fn assemble(&mut self) {
         let mut source = String::new();
         source.push_str(".text\n");
 
@@ -138,16 +180,20 @@ impl Executor {
         // but not your temporary directory.
         arm_devkit::set_internal_tempfile_directory(env!("CARGO_TARGET_TMPDIR"));
 
-        self.mem.data = if self.base_isa == InstructionSet::Arm {
-            arm_devkit::arm::assemble(&source, simple_linker_script()).unwrap()
+        (self.mem.data, self.symbols) = if self.base_isa == InstructionSet::Arm {
+            arm_devkit::arm::assemble_with_symbols(&source, simple_linker_script()).unwrap()
         } else {
-            arm_devkit::thumb::assemble(&source, simple_linker_script()).unwrap()
+            arm_devkit::thumb::assemble_with_symbols(&source, simple_linker_script()).unwrap()
         };
 
         self.cpu
             .registers
             .put_flag(CpsrFlag::T, self.base_isa == InstructionSet::Thumb);
         self.cpu.branch(0, &mut self.mem);
+    }
+
+    fn execute(&mut self) {
+        self.assemble();
 
         let start_time = std::time::Instant::now();
         let mut steps_since_time_chek = 0;
@@ -155,15 +201,24 @@ impl Executor {
         loop {
             let next_pc = self.cpu.next_execution_address();
 
+            // opcodes fetched from an aborted address are never executed
+            let aborted = self
+                .mem
+                .abort_range
+                .as_ref()
+                .is_some_and(|range| range.contains(&next_pc));
+
             // break in ARM mode
-            if !self.cpu.registers.get_flag(CpsrFlag::T)
+            if !aborted
+                && !self.cpu.registers.get_flag(CpsrFlag::T)
                 && self.mem.view32(next_pc) == ARM_END_OPCODE
             {
                 break;
             }
 
             // break in THUMB mode
-            if self.cpu.registers.get_flag(CpsrFlag::T)
+            if !aborted
+                && self.cpu.registers.get_flag(CpsrFlag::T)
                 && self.mem.view16(next_pc) == THUMB_END_OPCODE
             {
                 break;
@@ -204,6 +259,40 @@ fn simple_linker_script() -> LinkerScript {
     static SOURCE: &str = include_str!("../data/simple.ld");
 }
 
+/// Where the unaligned load tests put the data that they load.
+pub const SCRATCH: u32 = 0x1000;
+pub const SCRATCH_LEN: u32 = 0x100;
+
+/// The value of an ARM7TDMI word load from a possibly unaligned `address`: the word at
+/// the aligned address rotated right so that the addressed byte is in bits 0-7.
+pub fn expected_ldr(mem: &TestMemory, address: u32) -> u32 {
+    mem.view32(address & !0x3).rotate_right(8 * (address & 0x3))
+}
+
+/// The value of an ARM7TDMI unsigned halfword load, which rotates like [`expected_ldr`].
+pub fn expected_ldrh(mem: &TestMemory, address: u32) -> u32 {
+    (mem.view16(address & !0x1) as u32).rotate_right(8 * (address & 0x1))
+}
+
+/// The value of an ARM7TDMI signed halfword load. Odd addresses load a signed byte.
+pub fn expected_ldrsh(mem: &TestMemory, address: u32) -> u32 {
+    if address & 0x1 == 0 {
+        mem.view16(address) as i16 as i32 as u32
+    } else {
+        mem.view8(address) as i8 as i32 as u32
+    }
+}
+
+/// Fills the scratch area with random bytes and returns a random address in it.
+pub fn rand_scratch_address(exec: &mut Executor) -> u32 {
+    let mut rng = rand::thread_rng();
+    exec.mem.grow((SCRATCH + SCRATCH_LEN) as usize);
+    for address in SCRATCH..(SCRATCH + SCRATCH_LEN) {
+        exec.mem.store8(address, rng.gen(), &mut exec.cpu);
+    }
+    SCRATCH + rng.gen_range(0..SCRATCH_LEN - 8)
+}
+
 #[macro_export]
 macro_rules! arm {
     ($source:expr) => {
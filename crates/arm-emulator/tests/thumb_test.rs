@@ -1,9 +1,13 @@
 #[macro_use]
 pub mod common;
 
-use arm_emulator::{CpsrFlag, CpuMode};
+use arm_emulator::{CpsrFlag, CpuMode, InstructionSet};
 
-use crate::common::operands::{bools, imm3, imm32, imm8};
+use crate::common::{
+    expected_ldr, expected_ldrh, expected_ldrsh,
+    operands::{bools, imm3, imm32, imm8},
+    rand_scratch_address, Executor,
+};
 
 #[test]
 pub fn test_lsl_imm() {
@@ -930,6 +934,58 @@ pub fn test_ldr_unaligned() {
     assert_eq!(cpu.registers.read(0), 0xBEEFDEAD);
 }
 
+/// Every addressing mode of the word and halfword loads rotates unaligned values
+/// the same way.
+#[test]
+pub fn test_unaligned_loads_rotate() {
+    let mut exec = Executor::new(InstructionSet::Thumb);
+    exec.push_no_exec(
+        "
+        ldr     r0, [r1, r2]
+        ldr     r3, [r4, #4]
+        ldrh    r5, [r1, r2]
+        ldrh    r6, [r4, #4]
+        ldrsh   r7, [r1, r2]
+        ",
+    );
+    exec.assemble();
+
+    for _ in 0..256 {
+        let address = rand_scratch_address(&mut exec);
+        exec.cpu.registers.write(1, address & !0xF);
+        exec.cpu.registers.write(2, address & 0xF);
+        exec.cpu.registers.write(4, address - 4);
+        exec.run_from_start(5);
+
+        let ctx = format!("address=0x{address:08X}");
+        assert_eq!(
+            exec.cpu.registers.read(0),
+            expected_ldr(&exec.mem, address),
+            "{ctx}"
+        );
+        assert_eq!(
+            exec.cpu.registers.read(3),
+            expected_ldr(&exec.mem, address),
+            "{ctx}"
+        );
+        assert_eq!(
+            exec.cpu.registers.read(5),
+            expected_ldrh(&exec.mem, address),
+            "{ctx}"
+        );
+        assert_eq!(
+            exec.cpu.registers.read(6),
+            expected_ldrh(&exec.mem, address),
+            "{ctx}"
+        );
+        assert_eq!(
+            exec.cpu.registers.read(7),
+            expected_ldrsh(&exec.mem, address),
+            "{ctx}"
+        );
+    }
+}
+
 #[test]
 pub fn test_ldr_pre_increment() {
     let (cpu, _mem) = thumb! {"
@@ -1269,3 +1325,31 @@ pub fn test_function_call() {
     assert_eq!(cpu.registers.read(2), 13);
     assert_eq!(cpu.registers.read(3), 7);
 }
+
+#[test]
+pub fn test_data_abort() {
+    let mut exec = Executor::new(InstructionSet::Thumb);
+    exec.mem.abort_range = Some(0x10000000..0x20000000);
+    exec.push(
+        "
+        b       start
+        .hword  0
+        .word   0, 0
+        .word   0xF777F777      @ 0x0C: prefetch abort
+        .word   0xF777F777      @ 0x10: data abort
+    start:
+        ldr     r1, =0x10000000
+        ldr     r0, [r1]        @ 0x16
+        mov     r2, #1
+        b       _exit
+    .pool
+    ",
+    );
+
+    let cpu = exec.cpu;
+    assert_eq!(cpu.registers.read_mode(), CpuMode::Abort);
+    assert!(!cpu.registers.get_flag(CpsrFlag::T));
+    assert_eq!(cpu.registers.read(14), 0x16 + 8);
+    assert_eq!(cpu.registers.read_spsr() & 0x3F, 0x20 | 0x1F);
+    assert_eq!(cpu.registers.read(2), 0);
+}
@@ -3,3 +3,29 @@ pub use arm_disassembler as disasm;
 
 #[cfg(feature = "arm-emulator")]
 pub use arm_emulator as emu;
+
+/// Disassembly helpers for [`emu::Cpu`], available when both the emulator and the
+/// disassembler are enabled.
+#[cfg(all(feature = "arm-emulator", feature = "arm-disassembler"))]
+pub trait CpuDisasmExt {
+    /// Decodes the instruction at [`emu::Cpu::next_execution_address`] using the
+    /// instruction set selected by the CPSR T flag. In THUMB state both halves of a
+    /// `bl` are decoded together.
+    fn current_instruction(&self, memory: &dyn disasm::MemoryView) -> disasm::AnyInstr;
+}
+
+#[cfg(all(feature = "arm-emulator", feature = "arm-disassembler"))]
+impl CpuDisasmExt for emu::Cpu {
+    fn current_instruction(&self, memory: &dyn disasm::MemoryView) -> disasm::AnyInstr {
+        let address = self.next_execution_address();
+        if self.registers.get_flag(emu::CpsrFlag::T) {
+            let instr = memory.view16(address);
+            let next = memory.view16(address.wrapping_add(2));
+            disasm::thumb::disasm_bl_pair(instr, next, address)
+                .unwrap_or_else(|| disasm::thumb::disasm(instr, address))
+                .into()
+        } else {
+            disasm::arm::disasm(memory.view32(address), address).into()
+        }
+    }
+}
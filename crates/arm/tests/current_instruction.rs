@@ -0,0 +1,70 @@
+#![cfg(all(feature = "arm-emulator", feature = "arm-disassembler"))]
+
+use arm::{
+    disasm::MemoryView,
+    emu::{Cpu, CpuMode, InstructionSet, Memory, Waitstates},
+    CpuDisasmExt as _,
+};
+
+struct TestMemory(Vec<u8>);
+
+impl Memory for TestMemory {
+    fn load8(&mut self, address: u32, _cpu: &mut Cpu) -> (u8, Waitstates) {
+        (self.view8(address), Waitstates::zero())
+    }
+
+    fn store8(&mut self, address: u32, value: u8, _cpu: &mut Cpu) -> Waitstates {
+        if let Some(byte) = self.0.get_mut(address as usize) {
+            *byte = value;
+        }
+        Waitstates::zero()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_mut_any(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+impl MemoryView for TestMemory {
+    fn view8(&self, address: u32) -> u8 {
+        self.0.view8(address)
+    }
+
+    fn view16(&self, address: u32) -> u16 {
+        self.0.view16(address)
+    }
+
+    fn view32(&self, address: u32) -> u32 {
+        self.0.view32(address)
+    }
+}
+
+#[test]
+fn current_instruction_follows_the_instruction_set() {
+    let mut memory = TestMemory(vec![
+        0x01, 0x00, 0x8F, 0xE2, // 0x00: add r0, pc, #1
+        0x10, 0xFF, 0x2F, 0xE1, // 0x04: bx r0
+        0x05, 0x20, // 0x08: mov r0, #0x5
+        0x00, 0xF0, 0x00, 0xF8, // 0x0A: bl 0x0000000e
+        0x00, 0x00, // 0x0E: lsl r0, r0, #0
+    ]);
+    let mut cpu = Cpu::new(InstructionSet::Arm, CpuMode::System, &mut memory);
+
+    let expected = [
+        ("add", "r0, pc, #0x1"),
+        ("bx", "r0"),
+        ("mov", "r0, #0x5"),
+        ("bl", "0x0000000e"),
+    ];
+    for (mnemonic, arguments) in expected {
+        let address = cpu.next_execution_address();
+        let instr = cpu.current_instruction(&memory);
+        assert_eq!(mnemonic, instr.mnemonic().to_string());
+        assert_eq!(arguments, instr.arguments(address, None).to_string());
+        cpu.step(&mut memory);
+    }
+}
@@ -0,0 +1,679 @@
+use arm::emu::{
+    CpsrFlag, Cpu, CpuException, CpuMode, Cycles, ExceptionHandlerResult, Memory, Waitstates,
+};
+
+use crate::GbaMemoryMappedHardware;
+
+/// Selects how BIOS calls are serviced.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BiosMode {
+    /// Execute the code in the BIOS region. This is either the bundled custom
+    /// BIOS or a BIOS image loaded with [`crate::Gba::set_bios`].
+    #[default]
+    Real,
+
+    /// Skip the BIOS boot sequence and service known SWI calls in the emulator
+    /// instead of executing BIOS code. SWIs that are not handled here still fall
+    /// through to the BIOS region.
+    Hle,
+}
+
+pub const SWI_DIV: u32 = 0x06;
+pub const SWI_DIV_ARM: u32 = 0x07;
+pub const SWI_SQRT: u32 = 0x08;
+pub const SWI_ARCTAN: u32 = 0x09;
+pub const SWI_ARCTAN2: u32 = 0x0A;
+pub const SWI_CPU_SET: u32 = 0x0B;
+pub const SWI_CPU_FAST_SET: u32 = 0x0C;
+pub const SWI_BG_AFFINE_SET: u32 = 0x0E;
+pub const SWI_OBJ_AFFINE_SET: u32 = 0x0F;
+pub const SWI_LZ77_UNCOMP_WRAM: u32 = 0x11;
+pub const SWI_LZ77_UNCOMP_VRAM: u32 = 0x12;
+pub const SWI_HUFF_UNCOMP: u32 = 0x13;
+pub const SWI_RL_UNCOMP_WRAM: u32 = 0x14;
+pub const SWI_RL_UNCOMP_VRAM: u32 = 0x15;
+
+/// The tracing target that every SWI taken is logged to at the `TRACE` level,
+/// along with its comment and argument registers. Requires the `arm-disassembler`
+/// feature.
+pub const SWI_LOG_TARGET: &str = "gba::swi";
+
+/// Exception handler that services SWIs when the hardware is in [`BiosMode::Hle`].
+/// It returns [`ExceptionHandlerResult::Ignored`] for everything else so that the
+/// CPU takes the exception normally.
+pub fn exception_handler(
+    cpu: &mut Cpu,
+    memory: &mut dyn Memory,
+    exception: CpuException,
+) -> ExceptionHandlerResult {
+    if exception != CpuException::Swi {
+        return ExceptionHandlerResult::Ignored;
+    }
+
+    #[cfg(feature = "arm-disassembler")]
+    if tracing::enabled!(target: SWI_LOG_TARGET, tracing::Level::TRACE) {
+        log_swi(cpu, memory);
+    }
+
+    let hle_enabled = memory
+        .as_any()
+        .downcast_ref::<GbaMemoryMappedHardware>()
+        .map(|mapped| mapped.bios_mode == BiosMode::Hle)
+        .unwrap_or(false);
+    if !hle_enabled {
+        return ExceptionHandlerResult::Ignored;
+    }
+
+    let (comment, wait) = swi_comment(cpu, memory);
+    match hle_swi(comment, cpu, memory) {
+        Some(cycles) => ExceptionHandlerResult::Handled(cycles + wait),
+        None => {
+            tracing::debug!("unhandled HLE SWI 0x{comment:02X}, falling back to BIOS");
+            ExceptionHandlerResult::Ignored
+        }
+    }
+}
+
+/// Logs the SWI that caused the current exception. The comment is read with
+/// [`MemoryView`] so that logging doesn't affect the prefetch buffer, open bus values
+/// or memory stats.
+#[cfg(feature = "arm-disassembler")]
+fn log_swi(cpu: &Cpu, memory: &dyn Memory) {
+    use arm::disasm::MemoryView as _;
+
+    let Some(mapped) = memory.as_any().downcast_ref::<GbaMemoryMappedHardware>() else {
+        return;
+    };
+    let address = cpu.exception_address();
+    let comment = if cpu.registers.get_flag(CpsrFlag::T) {
+        mapped.view16(address) as u32 & 0xFF
+    } else {
+        (mapped.view32(address) >> 16) & 0xFF
+    };
+    let [r0, r1, r2, r3] = [0, 1, 2, 3].map(|register| cpu.registers.read(register));
+    tracing::trace!(
+        target: SWI_LOG_TARGET,
+        "SWI 0x{comment:02X} at 0x{address:08X}: r0=0x{r0:08X} r1=0x{r1:08X} r2=0x{r2:08X} r3=0x{r3:08X}"
+    );
+}
+
+/// Reads the comment field of the SWI that caused the current exception. Like the
+/// real BIOS this uses bits 16-23 of the comment in ARM state (e.g. `swi #0x60000`)
+/// and bits 0-7 in THUMB state.
+fn swi_comment(cpu: &mut Cpu, memory: &mut dyn Memory) -> (u32, Waitstates) {
+    let address = cpu.exception_address();
+    if cpu.registers.get_flag(CpsrFlag::T) {
+        let (instr, wait) = memory.load16(address, cpu);
+        (instr as u32 & 0xFF, wait)
+    } else {
+        let (instr, wait) = memory.load32(address, cpu);
+        ((instr >> 16) & 0xFF, wait)
+    }
+}
+
+/// Services a single SWI. Returns [`None`] if the SWI is not implemented.
+fn hle_swi(comment: u32, cpu: &mut Cpu, memory: &mut dyn Memory) -> Option<Cycles> {
+    match comment {
+        SWI_DIV => swi_div(cpu),
+        SWI_DIV_ARM => swi_div_arm(cpu),
+        SWI_SQRT => Some(swi_sqrt(cpu)),
+        SWI_ARCTAN => Some(swi_arctan(cpu)),
+        SWI_ARCTAN2 => Some(swi_arctan2(cpu)),
+        SWI_CPU_SET => Some(swi_cpu_set(cpu, memory)),
+        SWI_CPU_FAST_SET => Some(swi_cpu_fast_set(cpu, memory)),
+        SWI_BG_AFFINE_SET => Some(swi_bg_affine_set(cpu, memory)),
+        SWI_OBJ_AFFINE_SET => Some(swi_obj_affine_set(cpu, memory)),
+        SWI_LZ77_UNCOMP_WRAM => Some(swi_lz77_uncomp(cpu, memory, TransferWidth::Byte)),
+        SWI_LZ77_UNCOMP_VRAM => Some(swi_lz77_uncomp(cpu, memory, TransferWidth::Halfword)),
+        SWI_HUFF_UNCOMP => Some(swi_huff_uncomp(cpu, memory)),
+        SWI_RL_UNCOMP_WRAM => Some(swi_rl_uncomp(cpu, memory, TransferWidth::Byte)),
+        SWI_RL_UNCOMP_VRAM => Some(swi_rl_uncomp(cpu, memory, TransferWidth::Halfword)),
+        _ => None,
+    }
+}
+
+/// Sets up the CPU the same way the BIOS would have after its boot sequence
+/// (SWI 0x00 SoftReset) and jumps to the start of the gamepak.
+pub(crate) fn hle_boot(cpu: &mut Cpu, mapped: &mut GbaMemoryMappedHardware) {
+    cpu.registers.write_mode(CpuMode::System);
+    cpu.registers.clear_flag(CpsrFlag::T);
+    for register in 0..=12 {
+        cpu.registers.write(register, 0);
+    }
+    cpu.registers.write_with_mode(CpuMode::IRQ, 13, 0x03007FA0);
+    cpu.registers.write_with_mode(CpuMode::IRQ, 14, 0);
+    cpu.registers
+        .write_with_mode(CpuMode::Supervisor, 13, 0x03007FE0);
+    cpu.registers.write_with_mode(CpuMode::Supervisor, 14, 0);
+    cpu.registers.write(13, 0x03007F00);
+    cpu.registers.write(14, 0);
+
+    // The last opcode fetched from the BIOS after it is done booting.
+    mapped.last_bios_value = 0xE129F000;
+
+    cpu.branch(0x08000000, mapped);
+}
+
+/// SWI 0x06 - Div
+///
+/// Signed division of r0 by r1. Returns the quotient in r0, the remainder
+/// in r1 and the absolute value of the quotient in r3.
+fn swi_div(cpu: &mut Cpu) -> Option<Cycles> {
+    let numerator = cpu.registers.read(0) as i32;
+    let denominator = cpu.registers.read(1) as i32;
+
+    if denominator == 0 {
+        // The real BIOS never returns from a division by zero.
+        return None;
+    }
+
+    let quotient = numerator.wrapping_div(denominator);
+    let remainder = numerator.wrapping_rem(denominator);
+    cpu.registers.write(0, quotient as u32);
+    cpu.registers.write(1, remainder as u32);
+    cpu.registers.write(3, quotient.unsigned_abs());
+    Some(Cycles::one())
+}
+
+/// SWI 0x07 - DivArm
+///
+/// Same as Div but with the numerator in r1 and the denominator in r0.
+fn swi_div_arm(cpu: &mut Cpu) -> Option<Cycles> {
+    let denominator = cpu.registers.read(0);
+    let numerator = cpu.registers.read(1);
+    cpu.registers.write(0, numerator);
+    cpu.registers.write(1, denominator);
+    let cycles = swi_div(cpu);
+    if cycles.is_none() {
+        // Leave the registers as they were for the BIOS.
+        cpu.registers.write(0, denominator);
+        cpu.registers.write(1, numerator);
+    }
+    cycles
+}
+
+/// SWI 0x08 - Sqrt
+///
+/// Returns the integer square root of the unsigned value in r0, rounded down.
+fn swi_sqrt(cpu: &mut Cpu) -> Cycles {
+    let value = cpu.registers.read(0);
+    cpu.registers.write(0, value.isqrt());
+    Cycles::one()
+}
+
+/// SWI 0x09 - ArcTan
+///
+/// r0 is the tangent as a signed 1.14 fixed point number. Returns the angle in r0
+/// in the range -PI/2 to PI/2 (0xC000 to 0x4000, sign extended). This uses the same
+/// polynomial approximation as the BIOS so r1 and r3 are also set to its
+/// intermediate values.
+fn swi_arctan(cpu: &mut Cpu) -> Cycles {
+    let (angle, a, b) = arctan(cpu.registers.read(0) as i32);
+    cpu.registers.write(0, angle as u32);
+    cpu.registers.write(1, a as u32);
+    cpu.registers.write(3, b as u32);
+    Cycles::one()
+}
+
+/// SWI 0x0A - ArcTan2
+///
+/// r0 is X and r1 is Y, both as signed 1.14 fixed point numbers. Returns the angle
+/// of the point in r0 in the range 0 to 2PI (0x0000 to 0xFFFF).
+fn swi_arctan2(cpu: &mut Cpu) -> Cycles {
+    let x = cpu.registers.read(0) as i32;
+    let y = cpu.registers.read(1) as i32;
+
+    let mut arctan_of = |numerator: i32, denominator: i32| {
+        let (angle, a, _) = arctan(numerator.wrapping_shl(14).wrapping_div(denominator));
+        cpu.registers.write(1, a as u32);
+        angle
+    };
+
+    let angle = match (x, y) {
+        (x, 0) if x >= 0 => 0,
+        (_, 0) => 0x8000,
+        (0, y) if y >= 0 => 0x4000,
+        (0, _) => 0xC000,
+        (x, y) if y >= 0 && x >= 0 && x >= y => arctan_of(y, x),
+        // Negated in 64 bits because a game can pass i32::MIN.
+        (x, y) if y >= 0 && x < 0 && -i64::from(x) >= i64::from(y) => arctan_of(y, x) + 0x8000,
+        (x, y) if y >= 0 => 0x4000 - arctan_of(x, y),
+        (x, y) if x <= 0 && -i64::from(x) > -i64::from(y) => arctan_of(y, x) + 0x8000,
+        (x, y) if x > 0 && i64::from(x) >= -i64::from(y) => arctan_of(y, x) + 0x10000,
+        (x, y) => 0xC000 - arctan_of(x, y),
+    };
+    cpu.registers.write(0, angle as u16 as u32);
+    cpu.registers.write(3, 0x170);
+    Cycles::one()
+}
+
+/// The BIOS's polynomial approximation of the arctangent of a 1.14 fixed point
+/// number. Returns the angle along with the two intermediate values that the BIOS
+/// leaves in r1 and r3.
+fn arctan(tan: i32) -> (i32, i32, i32) {
+    let a = -(tan.wrapping_mul(tan) >> 14);
+    let mut b = ((0xA9 * a) >> 14) + 0x390;
+    for coefficient in [0x91C, 0xFB6, 0x16AA, 0x2081, 0x3651, 0xA2F9] {
+        b = (b.wrapping_mul(a) >> 14).wrapping_add(coefficient);
+    }
+    let angle = tan.wrapping_mul(b) >> 16;
+    (angle as i16 as i32, a, b)
+}
+
+/// Set in r2 of CpuSet and CpuFastSet to fill the destination with the first unit
+/// of the source instead of copying.
+const CPU_SET_FIXED_SOURCE: u32 = 1 << 24;
+/// Set in r2 of CpuSet to transfer words instead of halfwords.
+const CPU_SET_32BIT: u32 = 1 << 26;
+/// Mask for the number of units to transfer in r2 of CpuSet and CpuFastSet.
+const CPU_SET_COUNT_MASK: u32 = 0x1FFFFF;
+
+/// SWI 0x0B - CpuSet
+///
+/// Copies or fills memory from r0 to r1. r2 contains the number of halfwords or
+/// words to transfer in bits 0-20, the fixed source (fill) flag in bit 24 and the
+/// data size in bit 26 (0 = 16-bit, 1 = 32-bit). Addresses are aligned to the data
+/// size.
+fn swi_cpu_set(cpu: &mut Cpu, memory: &mut dyn Memory) -> Cycles {
+    let control = cpu.registers.read(2);
+    let count = control & CPU_SET_COUNT_MASK;
+    let fixed = control & CPU_SET_FIXED_SOURCE != 0;
+    let words = control & CPU_SET_32BIT != 0;
+    cpu_set(cpu, memory, count, fixed, words)
+}
+
+/// SWI 0x0C - CpuFastSet
+///
+/// Same as CpuSet but always transfers words and works in blocks of 8 words. The
+/// word count in r2 is rounded up to a multiple of 8.
+fn swi_cpu_fast_set(cpu: &mut Cpu, memory: &mut dyn Memory) -> Cycles {
+    let control = cpu.registers.read(2);
+    let count = ((control & CPU_SET_COUNT_MASK) + 7) & !7;
+    let fixed = control & CPU_SET_FIXED_SOURCE != 0;
+    cpu_set(cpu, memory, count, fixed, true)
+}
+
+/// Transfers `count` words, or halfwords if `words` is false, from r0 to r1.
+fn cpu_set(cpu: &mut Cpu, memory: &mut dyn Memory, count: u32, fixed: bool, words: bool) -> Cycles {
+    let size = if words { 4 } else { 2 };
+    let mut source = cpu.registers.read(0) & !(size - 1);
+    let mut destination = cpu.registers.read(1) & !(size - 1);
+    let mut wait = Waitstates::zero();
+
+    // The BIOS refuses to read from its own region.
+    if source & 0x0E000000 == 0 {
+        return Cycles::one();
+    }
+
+    for _ in 0..count {
+        if words {
+            let (value, load_wait) = memory.load32(source, cpu);
+            wait += load_wait + memory.store32(destination, value, cpu);
+        } else {
+            let (value, load_wait) = memory.load16(source, cpu);
+            wait += load_wait + memory.store16(destination, value, cpu);
+        }
+        if !fixed {
+            source = source.wrapping_add(size);
+        }
+        destination = destination.wrapping_add(size);
+    }
+
+    Cycles::from(count * 2) + wait
+}
+
+/// The BIOS's sine table: sin(2 * PI * i / 256) as signed 1.14 fixed point numbers,
+/// rounded towards zero. The cosine of an index is the sine of the index + 64.
+#[rustfmt::skip]
+const SIN_TABLE: [i16; 256] = [
+    0x0000, 0x0192, 0x0323, 0x04B5, 0x0645, 0x07D5, 0x0964, 0x0AF1,
+    0x0C7C, 0x0E05, 0x0F8C, 0x1111, 0x1294, 0x1413, 0x158F, 0x1708,
+    0x187D, 0x19EF, 0x1B5D, 0x1CC6, 0x1E2B, 0x1F8B, 0x20E7, 0x223D,
+    0x238E, 0x24DA, 0x261F, 0x275F, 0x2899, 0x29CD, 0x2AFA, 0x2C21,
+    0x2D41, 0x2E5A, 0x2F6B, 0x3076, 0x3179, 0x3274, 0x3367, 0x3453,
+    0x3536, 0x3612, 0x36E5, 0x37AF, 0x3871, 0x392A, 0x39DA, 0x3A82,
+    0x3B20, 0x3BB6, 0x3C42, 0x3CC5, 0x3D3E, 0x3DAE, 0x3E14, 0x3E71,
+    0x3EC5, 0x3F0E, 0x3F4E, 0x3F84, 0x3FB1, 0x3FD3, 0x3FEC, 0x3FFB,
+    0x4000, 0x3FFB, 0x3FEC, 0x3FD3, 0x3FB1, 0x3F84, 0x3F4E, 0x3F0E,
+    0x3EC5, 0x3E71, 0x3E14, 0x3DAE, 0x3D3E, 0x3CC5, 0x3C42, 0x3BB6,
+    0x3B20, 0x3A82, 0x39DA, 0x392A, 0x3871, 0x37AF, 0x36E5, 0x3612,
+    0x3536, 0x3453, 0x3367, 0x3274, 0x3179, 0x3076, 0x2F6B, 0x2E5A,
+    0x2D41, 0x2C21, 0x2AFA, 0x29CD, 0x2899, 0x275F, 0x261F, 0x24DA,
+    0x238E, 0x223D, 0x20E7, 0x1F8B, 0x1E2B, 0x1CC6, 0x1B5D, 0x19EF,
+    0x187D, 0x1708, 0x158F, 0x1413, 0x1294, 0x1111, 0x0F8C, 0x0E05,
+    0x0C7C, 0x0AF1, 0x0964, 0x07D5, 0x0645, 0x04B5, 0x0323, 0x0192,
+    0x0000, -0x0192, -0x0323, -0x04B5, -0x0645, -0x07D5, -0x0964, -0x0AF1,
+    -0x0C7C, -0x0E05, -0x0F8C, -0x1111, -0x1294, -0x1413, -0x158F, -0x1708,
+    -0x187D, -0x19EF, -0x1B5D, -0x1CC6, -0x1E2B, -0x1F8B, -0x20E7, -0x223D,
+    -0x238E, -0x24DA, -0x261F, -0x275F, -0x2899, -0x29CD, -0x2AFA, -0x2C21,
+    -0x2D41, -0x2E5A, -0x2F6B, -0x3076, -0x3179, -0x3274, -0x3367, -0x3453,
+    -0x3536, -0x3612, -0x36E5, -0x37AF, -0x3871, -0x392A, -0x39DA, -0x3A82,
+    -0x3B20, -0x3BB6, -0x3C42, -0x3CC5, -0x3D3E, -0x3DAE, -0x3E14, -0x3E71,
+    -0x3EC5, -0x3F0E, -0x3F4E, -0x3F84, -0x3FB1, -0x3FD3, -0x3FEC, -0x3FFB,
+    -0x4000, -0x3FFB, -0x3FEC, -0x3FD3, -0x3FB1, -0x3F84, -0x3F4E, -0x3F0E,
+    -0x3EC5, -0x3E71, -0x3E14, -0x3DAE, -0x3D3E, -0x3CC5, -0x3C42, -0x3BB6,
+    -0x3B20, -0x3A82, -0x39DA, -0x392A, -0x3871, -0x37AF, -0x36E5, -0x3612,
+    -0x3536, -0x3453, -0x3367, -0x3274, -0x3179, -0x3076, -0x2F6B, -0x2E5A,
+    -0x2D41, -0x2C21, -0x2AFA, -0x29CD, -0x2899, -0x275F, -0x261F, -0x24DA,
+    -0x238E, -0x223D, -0x20E7, -0x1F8B, -0x1E2B, -0x1CC6, -0x1B5D, -0x19EF,
+    -0x187D, -0x1708, -0x158F, -0x1413, -0x1294, -0x1111, -0x0F8C, -0x0E05,
+    -0x0C7C, -0x0AF1, -0x0964, -0x07D5, -0x0645, -0x04B5, -0x0323, -0x0192,
+];
+
+/// Returns the sine and cosine of the top 8 bits of a BIOS angle (0x0000 to 0xFFFF
+/// for 0 to 2PI) as 1.14 fixed point numbers.
+fn sin_cos(angle: u16) -> (i32, i32) {
+    let index = (angle >> 8) as usize;
+    (
+        SIN_TABLE[index] as i32,
+        SIN_TABLE[(index + 64) & 0xFF] as i32,
+    )
+}
+
+/// Builds the 8.8 fixed point rotation/scaling matrix (PA, PB, PC, PD) for the
+/// given 8.8 scaling ratios and angle.
+fn affine_matrix(scale_x: i32, scale_y: i32, angle: u16) -> [i32; 4] {
+    let (sin, cos) = sin_cos(angle);
+    [
+        (scale_x * cos) >> 14,
+        (-scale_x * sin) >> 14,
+        (scale_y * sin) >> 14,
+        (scale_y * cos) >> 14,
+    ]
+}
+
+/// SWI 0x0E - BgAffineSet
+///
+/// Calculates the rotation/scaling parameters for `r2` backgrounds. r0 points to
+/// 20 byte entries that contain the center of rotation in the background as two
+/// signed 24.8 words, the center of rotation on the screen as two signed halfwords,
+/// the X and Y scaling ratios as signed 8.8 halfwords and the angle as a halfword
+/// (only the top 8 bits are used), followed by 2 bytes of padding. r1 points to 16
+/// byte entries that receive BGxPA-BGxPD followed by BGxX and BGxY.
+fn swi_bg_affine_set(cpu: &mut Cpu, memory: &mut dyn Memory) -> Cycles {
+    let mut source = cpu.registers.read(0);
+    let mut destination = cpu.registers.read(1);
+    let count = cpu.registers.read(2);
+    let mut wait = Waitstates::zero();
+
+    for _ in 0..count {
+        let mut load16 = |offset: u32| {
+            let (value, load_wait) = memory.load16(source.wrapping_add(offset), cpu);
+            wait += load_wait;
+            value as i16 as i32
+        };
+        let center_x = load16(8);
+        let center_y = load16(10);
+        let scale_x = load16(12);
+        let scale_y = load16(14);
+        let angle = load16(16) as u16;
+        let (origin_x, origin_x_wait) = memory.load32(source, cpu);
+        let (origin_y, origin_y_wait) = memory.load32(source.wrapping_add(4), cpu);
+        wait += origin_x_wait + origin_y_wait;
+
+        let [pa, pb, pc, pd] = affine_matrix(scale_x, scale_y, angle);
+        let x = (origin_x as i32).wrapping_sub(pa * center_x + pb * center_y);
+        let y = (origin_y as i32).wrapping_sub(pc * center_x + pd * center_y);
+        for (offset, parameter) in [pa, pb, pc, pd].into_iter().enumerate() {
+            let address = destination.wrapping_add(offset as u32 * 2);
+            wait += memory.store16(address, parameter as u16, cpu);
+        }
+        wait += memory.store32(destination.wrapping_add(8), x as u32, cpu);
+        wait += memory.store32(destination.wrapping_add(12), y as u32, cpu);
+
+        source = source.wrapping_add(20);
+        destination = destination.wrapping_add(16);
+    }
+
+    Cycles::from(count) + wait
+}
+
+/// SWI 0x0F - ObjAffineSet
+///
+/// Calculates the rotation/scaling parameters for `r2` sprites. r0 points to 8
+/// byte entries that contain the X and Y scaling ratios as signed 8.8 halfwords and
+/// the angle as a halfword (only the top 8 bits are used), followed by 2 bytes of
+/// padding. PA, PB, PC and PD are written as halfwords starting at r1, r3 bytes
+/// apart: 2 to write them next to each other or 8 to write them into OAM.
+fn swi_obj_affine_set(cpu: &mut Cpu, memory: &mut dyn Memory) -> Cycles {
+    let mut source = cpu.registers.read(0);
+    let mut destination = cpu.registers.read(1);
+    let count = cpu.registers.read(2);
+    let stride = cpu.registers.read(3);
+    let mut wait = Waitstates::zero();
+
+    for _ in 0..count {
+        let mut load16 = |offset: u32| {
+            let (value, load_wait) = memory.load16(source.wrapping_add(offset), cpu);
+            wait += load_wait;
+            value
+        };
+        let scale_x = load16(0) as i16 as i32;
+        let scale_y = load16(2) as i16 as i32;
+        let angle = load16(4);
+
+        for parameter in affine_matrix(scale_x, scale_y, angle) {
+            wait += memory.store16(destination, parameter as u16, cpu);
+            destination = destination.wrapping_add(stride);
+        }
+        source = source.wrapping_add(8);
+    }
+
+    Cycles::from(count) + wait
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum TransferWidth {
+    Byte,
+    Halfword,
+}
+
+/// SWI 0x11 - LZ77UnCompWram
+/// SWI 0x12 - LZ77UnCompVram
+///
+/// r0 is the source address, pointing to the compressed data, and r1 is the
+/// destination address. The data begins with a header word containing the type
+/// in bits 4-7 and the decompressed size in bits 8-31.
+fn swi_lz77_uncomp(cpu: &mut Cpu, memory: &mut dyn Memory, width: TransferWidth) -> Cycles {
+    let source = cpu.registers.read(0);
+    let destination = cpu.registers.read(1);
+    let mut wait = Waitstates::zero();
+
+    let mut reader = SourceReader::new(source);
+    let header = reader.header(cpu, memory, &mut wait);
+    let size = (header >> 8) as usize;
+    let mut output = Vec::with_capacity(size);
+
+    'blocks: while output.len() < size {
+        let flags = reader.next(cpu, memory, &mut wait);
+        for block in (0..8).rev() {
+            if output.len() >= size {
+                break 'blocks;
+            }
+
+            if (flags >> block) & 1 == 0 {
+                output.push(reader.next(cpu, memory, &mut wait));
+                continue;
+            }
+
+            let hi = reader.next(cpu, memory, &mut wait) as usize;
+            let lo = reader.next(cpu, memory, &mut wait) as usize;
+            let length = (hi >> 4) + 3;
+            let displacement = (((hi & 0xF) << 8) | lo) + 1;
+            for _ in 0..length {
+                let byte = output
+                    .len()
+                    .checked_sub(displacement)
+                    .map(|offset| output[offset])
+                    .unwrap_or(0);
+                output.push(byte);
+            }
+        }
+    }
+    output.truncate(size);
+
+    write_output(destination, &output, width, cpu, memory, &mut wait);
+    Cycles::from(output.len() as u32) + wait
+}
+
+/// SWI 0x13 - HuffUnComp
+///
+/// r0 is the source address and r1 is the destination address. The header word
+/// contains the size of each decoded value in bits 0-3 (4 or 8 bits), the type in
+/// bits 4-7 and the decompressed size in bits 8-31. It is followed by the tree
+/// table and then the bitstream as 32-bit words read from the most significant bit.
+///
+/// The first byte of the tree table is its size / 2 - 1 and the root node follows
+/// it. Each node has the offset to its children in bits 0-5 and flags in bits 6 and
+/// 7 that are set when its right or left child, respectively, is a data value.
+fn swi_huff_uncomp(cpu: &mut Cpu, memory: &mut dyn Memory) -> Cycles {
+    let source = cpu.registers.read(0);
+    let destination = cpu.registers.read(1);
+    let mut wait = Waitstates::zero();
+
+    let mut reader = SourceReader::new(source);
+    let header = reader.header(cpu, memory, &mut wait);
+    let bits = if header & 0xF == 4 { 4 } else { 8 };
+    let size = (header >> 8) as usize;
+    let tree_size = reader.next(cpu, memory, &mut wait) as u32;
+    let root_address = reader.address;
+    let (root, root_wait) = memory.load8(root_address, cpu);
+    wait += root_wait;
+
+    let mut stream = source.wrapping_add(4 + (tree_size + 1) * 2);
+    let mut output = Vec::with_capacity(size);
+    let mut unit = 0u32;
+    let mut unit_bits = 0;
+    let (mut node_address, mut node) = (root_address, root);
+
+    'stream: while output.len() < size {
+        let (word, word_wait) = memory.load32(stream, cpu);
+        wait += word_wait;
+        stream = stream.wrapping_add(4);
+
+        for bit in (0..32).rev() {
+            let direction = (word >> bit) & 1;
+            let child_address = (node_address & !1)
+                .wrapping_add((node as u32 & 0x3F) * 2 + 2)
+                .wrapping_add(direction);
+            let (child, child_wait) = memory.load8(child_address, cpu);
+            wait += child_wait;
+
+            if node & (0x80 >> direction) == 0 {
+                (node_address, node) = (child_address, child);
+                continue;
+            }
+
+            // Decoded values are packed into words starting from the least
+            // significant bits.
+            unit |= (child as u32 & ((1 << bits) - 1)) << unit_bits;
+            unit_bits += bits;
+            if unit_bits == 32 {
+                output.extend_from_slice(&unit.to_le_bytes());
+                unit = 0;
+                unit_bits = 0;
+                if output.len() >= size {
+                    break 'stream;
+                }
+            }
+            (node_address, node) = (root_address, root);
+        }
+    }
+    output.truncate(size);
+
+    write_output(
+        destination,
+        &output,
+        TransferWidth::Halfword,
+        cpu,
+        memory,
+        &mut wait,
+    );
+    Cycles::from(output.len() as u32) + wait
+}
+
+/// SWI 0x14 - RLUnCompWram
+/// SWI 0x15 - RLUnCompVram
+///
+/// r0 is the source address and r1 is the destination address. The data begins
+/// with a header word containing the type in bits 4-7 and the decompressed size in
+/// bits 8-31. Each run starts with a flag byte: if bit 7 is set the next byte is
+/// repeated (bits 0-6) + 3 times, otherwise the next (bits 0-6) + 1 bytes are copied.
+fn swi_rl_uncomp(cpu: &mut Cpu, memory: &mut dyn Memory, width: TransferWidth) -> Cycles {
+    let source = cpu.registers.read(0);
+    let destination = cpu.registers.read(1);
+    let mut wait = Waitstates::zero();
+
+    let mut reader = SourceReader::new(source);
+    let header = reader.header(cpu, memory, &mut wait);
+    let size = (header >> 8) as usize;
+    let mut output = Vec::with_capacity(size);
+
+    while output.len() < size {
+        let flag = reader.next(cpu, memory, &mut wait) as usize;
+        if flag & 0x80 != 0 {
+            let byte = reader.next(cpu, memory, &mut wait);
+            output.resize(output.len() + (flag & 0x7F) + 3, byte);
+        } else {
+            for _ in 0..(flag & 0x7F) + 1 {
+                output.push(reader.next(cpu, memory, &mut wait));
+            }
+        }
+    }
+    output.truncate(size);
+
+    write_output(destination, &output, width, cpu, memory, &mut wait);
+    Cycles::from(output.len() as u32) + wait
+}
+
+/// Writes decompressed data to memory. VRAM does not support 8-bit writes so the
+/// VRAM variants of the decompression functions write in 16-bit units instead.
+fn write_output(
+    destination: u32,
+    data: &[u8],
+    width: TransferWidth,
+    cpu: &mut Cpu,
+    memory: &mut dyn Memory,
+    wait: &mut Waitstates,
+) {
+    match width {
+        TransferWidth::Byte => {
+            for (offset, &byte) in data.iter().enumerate() {
+                *wait += memory.store8(destination.wrapping_add(offset as u32), byte, cpu);
+            }
+        }
+        TransferWidth::Halfword => {
+            for (offset, pair) in data.chunks(2).enumerate() {
+                let lo = pair[0] as u16;
+                let hi = pair.get(1).copied().unwrap_or(0) as u16;
+                let address = destination.wrapping_add(offset as u32 * 2);
+                *wait += memory.store16(address, lo | (hi << 8), cpu);
+            }
+        }
+    }
+}
+
+/// Sequential byte reader for compressed data.
+struct SourceReader {
+    address: u32,
+}
+
+impl SourceReader {
+    fn new(address: u32) -> Self {
+        Self { address }
+    }
+
+    fn header(&mut self, cpu: &mut Cpu, memory: &mut dyn Memory, wait: &mut Waitstates) -> u32 {
+        let (header, header_wait) = memory.load32(self.address, cpu);
+        *wait += header_wait;
+        self.address = self.address.wrapping_add(4);
+        header
+    }
+
+    fn next(&mut self, cpu: &mut Cpu, memory: &mut dyn Memory, wait: &mut Waitstates) -> u8 {
+        let (value, byte_wait) = memory.load8(self.address, cpu);
+        *wait += byte_wait;
+        self.address = self.address.wrapping_add(1);
+        value
+    }
+}
@@ -20,6 +20,18 @@ impl SharedGbaScheduler {
     pub fn clear(&mut self) {
         self.inner.borrow_mut().clear();
     }
+
+    pub fn pending(&self) -> Vec<(Cycles, GbaEvent)> {
+        self.inner.borrow().pending().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.borrow().entries.len()
+    }
+
+    pub fn cycles_until_next(&self) -> Option<Cycles> {
+        self.inner.borrow().cycles_until_next()
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -27,10 +39,18 @@ pub enum GbaEvent {
     HDraw,
     HBlank,
 
-    // FIXME replace this with something else once we have
-    //       another event. Right now it's only used in tests.
-    #[allow(dead_code)]
-    Test,
+    /// An event scheduled by [`crate::Gba::schedule_user_event`].
+    User(u32),
+}
+
+impl GbaEvent {
+    pub fn name(self) -> &'static str {
+        match self {
+            GbaEvent::HDraw => "HDraw",
+            GbaEvent::HBlank => "HBlank",
+            GbaEvent::User(_) => "User",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -82,6 +102,23 @@ impl GbaScheduler {
     pub fn clear(&mut self) {
         self.entries.clear();
     }
+
+    /// Returns the number of cycles until the next event fires without ticking.
+    pub fn cycles_until_next(&self) -> Option<Cycles> {
+        self.entries.last().map(|entry| entry.cycles)
+    }
+
+    /// Returns the pending events in the order that they will fire along with the
+    /// number of cycles until each one fires.
+    pub fn pending(&self) -> impl '_ + Iterator<Item = (Cycles, GbaEvent)> {
+        self.entries
+            .iter()
+            .rev()
+            .scan(Cycles::zero(), |cycles, entry| {
+                *cycles += entry.cycles;
+                Some((*cycles, entry.event))
+            })
+    }
 }
 
 #[cfg(test)]
@@ -156,7 +193,7 @@ mod test {
         let mut scheduler = GbaScheduler::default();
         scheduler.schedule(GbaEvent::HBlank, Cycles::from(16));
         scheduler.schedule(GbaEvent::HDraw, Cycles::from(12));
-        scheduler.schedule(GbaEvent::Test, Cycles::from(14));
+        scheduler.schedule(GbaEvent::User(0), Cycles::from(14));
 
         assert_eq!(
             scheduler.entries.get(2),
@@ -169,7 +206,7 @@ mod test {
         assert_eq!(
             scheduler.entries.get(1),
             Some(&Entry {
-                event: GbaEvent::Test,
+                event: GbaEvent::User(0),
                 cycles: Cycles::from(2)
             })
         );
@@ -188,7 +225,7 @@ mod test {
         let mut scheduler = GbaScheduler::default();
         scheduler.schedule(GbaEvent::HBlank, Cycles::from(16));
         scheduler.schedule(GbaEvent::HDraw, Cycles::from(12));
-        scheduler.schedule(GbaEvent::Test, Cycles::from(14));
+        scheduler.schedule(GbaEvent::User(0), Cycles::from(14));
 
         let mut cycles = Cycles::from(1);
         assert_eq!(scheduler.tick(&mut cycles), None);
@@ -198,7 +235,7 @@ mod test {
         assert_eq!(cycles, Cycles::zero());
 
         let mut cycles = Cycles::from(4);
-        assert_eq!(scheduler.tick(&mut cycles), Some(GbaEvent::Test));
+        assert_eq!(scheduler.tick(&mut cycles), Some(GbaEvent::User(0)));
         assert_eq!(cycles, Cycles::from(2));
         assert_eq!(scheduler.tick(&mut cycles), Some(GbaEvent::HBlank));
         assert_eq!(cycles, Cycles::zero());
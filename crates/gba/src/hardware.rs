@@ -1,17 +1,32 @@
+pub mod backup;
+pub mod gpio;
 pub mod keypad;
 pub mod palette;
+pub mod prefetch;
+pub mod serial;
 pub mod system_control;
 pub mod video;
 
 use crate::{
+    bios::BiosMode,
     events::SharedGbaScheduler,
-    memory::{BIOS_SIZE, EWRAM_SIZE, IWRAM_SIZE, OAM_SIZE, VRAM_SIZE},
+    header::CartridgeHeader,
+    memory::{
+        Fill, MemoryRegion, MemoryStats, BIOS_SIZE, EWRAM_SIZE, GAMEPAK_HEADER_SIZE,
+        GAMEPAK_MAX_SIZE, IWRAM_SIZE, OAM_SIZE, REGION_GAMEPAK2_HI, VRAM_SIZE,
+    },
 };
 
 use self::{
+    backup::{Backup, SaveType},
+    gpio::Gpio,
     keypad::Keypad,
     palette::Palette,
-    system_control::{RegInternalMemoryControl, SystemControl},
+    prefetch::GamepakPrefetch,
+    serial::Serial,
+    system_control::{
+        RegInternalMemoryControl, RegInterruptMasterEnable, RegInterrupts, SystemControl,
+    },
     video::GbaVideo,
 };
 
@@ -23,6 +38,7 @@ pub struct GbaMemoryMappedHardware {
     pub video: Box<GbaVideo>,
     pub system_control: SystemControl,
     pub keypad: Keypad,
+    pub serial: Serial,
 
     pub palram: Box<Palette>,
     pub vram: Box<[u8; VRAM_SIZE]>,
@@ -30,11 +46,23 @@ pub struct GbaMemoryMappedHardware {
 
     pub(crate) gamepak_mask: usize,
     pub(crate) gamepak: Vec<u8>,
+    pub(crate) prefetch: GamepakPrefetch,
+    pub gpio: Gpio,
+    pub backup: Backup,
+
+    pub(crate) bios_mode: BiosMode,
+    pub(crate) uninitialized_fill: Fill,
 
-    /// The last value ready from memory.
-    pub(crate) last_read_value: u32,
     /// The last value read from BIOS.
     pub(crate) last_bios_value: u32,
+    /// Whether reads from the BIOS are blocked while the CPU is running code
+    /// outside of it.
+    pub(crate) bios_read_protection: bool,
+    /// Access counts, only allocated while they are enabled.
+    pub(crate) memory_stats: Option<Box<MemoryStats>>,
+    /// Counts the accesses that can change memory or hardware state: every write
+    /// and reads from EEPROM. Used to tell whether a loop has side effects.
+    pub(crate) side_effects: u64,
 }
 
 impl GbaMemoryMappedHardware {
@@ -47,6 +75,7 @@ impl GbaMemoryMappedHardware {
             video: Box::new(GbaVideo::new(scheduler)),
             system_control: SystemControl::default(),
             keypad: Keypad::default(),
+            serial: Serial::default(),
 
             palram: Box::default(),
             vram: Box::new([0; VRAM_SIZE]),
@@ -54,28 +83,151 @@ impl GbaMemoryMappedHardware {
 
             gamepak_mask: 0,
             gamepak: vec![0; 4],
+            prefetch: GamepakPrefetch::default(),
+            gpio: Gpio::default(),
+            backup: Backup::default(),
+
+            bios_mode: BiosMode::default(),
+            uninitialized_fill: Fill::default(),
 
-            last_read_value: 0,
             last_bios_value: 0,
+            bios_read_protection: true,
+            memory_stats: None,
+            side_effects: 0,
         }
     }
 
     /// Called after a hard reset of the GBA.
     pub(crate) fn reset(&mut self) {
         tracing::debug!("resetting GBA hardware");
+        self.uninitialized_fill
+            .apply(&mut [&mut self.ewram[..], &mut self.vram[..]]);
         self.system_control
             .write_internal_memory_control(RegInternalMemoryControl::DEFAULT);
+        self.system_control.interrupt_enable = RegInterrupts::default();
+        self.system_control.interrupt_request = RegInterrupts::default();
+        self.system_control.interrupt_master_enable = RegInterruptMasterEnable::default();
         self.video.reset();
         self.keypad.reset();
+        self.serial.reset();
+        self.prefetch.flush();
+        self.gpio.reset();
+        self.backup.reset();
+    }
+
+    /// Same as [`GbaMemoryMappedHardware::set_gamepak`] but rejects images that are
+    /// smaller than the cartridge header or larger than the 32MB gamepak region.
+    pub fn try_set_gamepak(&mut self, new_gamepak: Vec<u8>) -> Result<(), GamepakError> {
+        if new_gamepak.len() < GAMEPAK_HEADER_SIZE {
+            return Err(GamepakError::TooSmall(new_gamepak.len()));
+        }
+        if new_gamepak.len() > GAMEPAK_MAX_SIZE {
+            return Err(GamepakError::TooLarge(new_gamepak.len()));
+        }
+        if CartridgeHeader::parse(&new_gamepak).is_some_and(|header| !header.logo_valid()) {
+            tracing::warn!(
+                "gamepak header is missing the Nintendo logo, real hardware would not boot it"
+            );
+        }
+        self.set_gamepak(new_gamepak);
+        Ok(())
     }
 
+    /// Replaces the gamepak without validating it. The image is padded with zeroes
+    /// to the next power of two (and at least one word) so that it can be mirrored
+    /// across the gamepak region. The save memory is replaced with an erased one of
+    /// the type detected by [`SaveType::detect`].
     pub fn set_gamepak(&mut self, mut new_gamepak: Vec<u8>) {
         assert!(!new_gamepak.is_empty());
-        let gamepak_size = new_gamepak.len().next_power_of_two();
+        self.backup.set_save_type(SaveType::detect(&new_gamepak));
+        let gamepak_size = new_gamepak.len().max(4).next_power_of_two();
         new_gamepak.resize(gamepak_size, 0);
         self.gamepak = new_gamepak;
         self.gamepak_mask = gamepak_size - 1;
     }
+
+    /// Replaces the contents of the BIOS region. Images smaller than the BIOS
+    /// region are padded with zeroes and larger ones are rejected.
+    pub fn set_bios(&mut self, new_bios: Vec<u8>) -> Result<(), BiosError> {
+        if new_bios.len() > BIOS_SIZE {
+            return Err(BiosError::TooLarge(new_bios.len()));
+        }
+        self.bios.fill(0);
+        self.bios[..new_bios.len()].copy_from_slice(&new_bios);
+        Ok(())
+    }
+
+    pub fn bios_mode(&self) -> BiosMode {
+        self.bios_mode
+    }
+
+    /// Returns the header of the current gamepak, or `None` if it is too small to
+    /// have one (e.g. the no-op gamepak).
+    pub fn cartridge_header(&self) -> Option<CartridgeHeader> {
+        CartridgeHeader::parse(&self.gamepak)
+    }
+
+    /// Returns true if `address` goes to the EEPROM instead of ROM. EEPROM is
+    /// mapped across the whole 0Dxxxxxxh region for gamepaks of up to 16MB and at
+    /// 0DFFFF00h-0DFFFFFFh for larger ones.
+    pub(crate) fn is_eeprom_mapped(&self, address: u32) -> bool {
+        self.backup.save_type().is_eeprom()
+            && address >> 24 == REGION_GAMEPAK2_HI
+            && (self.gamepak.len() <= 0x1000000 || address & 0xFFFFFF >= 0xFFFF00)
+    }
+
+    /// Returns true if `address` is in a region that code can be run from. See
+    /// [`MemoryRegion::is_executable`].
+    pub fn is_executable(&self, address: u32) -> bool {
+        MemoryRegion::from_address(address).is_executable()
+    }
+}
+
+/// Returned by [`GbaMemoryMappedHardware::try_set_gamepak`] for images that can't be a GBA gamepak.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GamepakError {
+    /// The image is smaller than the cartridge header.
+    TooSmall(usize),
+    /// The image is larger than the gamepak region.
+    TooLarge(usize),
+}
+
+impl std::fmt::Display for GamepakError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GamepakError::TooSmall(size) => write!(
+                f,
+                "gamepak is {size} bytes but must be at least {GAMEPAK_HEADER_SIZE} bytes"
+            ),
+            GamepakError::TooLarge(size) => write!(
+                f,
+                "gamepak is {size} bytes but must be at most {GAMEPAK_MAX_SIZE} bytes"
+            ),
+        }
+    }
 }
 
+impl std::error::Error for GamepakError {}
+
+/// Returned by [`GbaMemoryMappedHardware::set_bios`] for images that don't fit in the
+/// BIOS region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BiosError {
+    /// The image is larger than the BIOS region.
+    TooLarge(usize),
+}
+
+impl std::fmt::Display for BiosError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BiosError::TooLarge(size) => write!(
+                f,
+                "BIOS image is {size} bytes but must be at most {BIOS_SIZE} bytes"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BiosError {}
+
 pub const CUSTOM_BIOS: &[u8] = include_bytes!("../../../roms/custom/custom-bios.bin");
@@ -0,0 +1,274 @@
+/// The kind of save memory (backup) on the gamepak. This is normally detected
+/// from the ID string that the SDK library for it leaves in the ROM, see
+/// [`SaveType::detect`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SaveType {
+    #[default]
+    None,
+    /// 32KB of battery backed SRAM at 0E000000h.
+    Sram32K,
+    /// 64KB of flash memory at 0E000000h.
+    Flash64K,
+    /// 128KB of flash memory at 0E000000h in two 64KB banks.
+    Flash128K,
+    /// 512 bytes of EEPROM accessed serially through 0D000000h.
+    Eeprom512,
+    /// 8KB of EEPROM accessed serially through 0D000000h.
+    Eeprom8K,
+}
+
+impl SaveType {
+    /// The ID strings and the save type that each one means. Games with EEPROM
+    /// don't say which size they have so those are assumed to be 8KB.
+    const MARKERS: [(&'static [u8], SaveType); 5] = [
+        (b"EEPROM_V", SaveType::Eeprom8K),
+        (b"SRAM_V", SaveType::Sram32K),
+        (b"FLASH_V", SaveType::Flash64K),
+        (b"FLASH512_V", SaveType::Flash64K),
+        (b"FLASH1M_V", SaveType::Flash128K),
+    ];
+
+    /// The size of the save memory in bytes.
+    pub const fn size(self) -> usize {
+        match self {
+            SaveType::None => 0,
+            SaveType::Sram32K => 0x8000,
+            SaveType::Flash64K => 0x10000,
+            SaveType::Flash128K => 0x20000,
+            SaveType::Eeprom512 => 0x200,
+            SaveType::Eeprom8K => 0x2000,
+        }
+    }
+
+    pub const fn is_eeprom(self) -> bool {
+        matches!(self, SaveType::Eeprom512 | SaveType::Eeprom8K)
+    }
+
+    /// Looks for one of the word aligned ID strings (e.g. "FLASH1M_V") in the
+    /// gamepak. Homebrew often doesn't have one, in which case this returns
+    /// [`SaveType::None`].
+    pub fn detect(gamepak: &[u8]) -> SaveType {
+        (0..gamepak.len())
+            .step_by(4)
+            .find_map(|offset| {
+                Self::MARKERS
+                    .iter()
+                    .find(|(marker, _)| gamepak[offset..].starts_with(marker))
+                    .map(|&(_, save_type)| save_type)
+            })
+            .unwrap_or(SaveType::None)
+    }
+}
+
+/// Flash chips used by 64KB and 128KB carts. Reads of the first two bytes in ID
+/// mode return the manufacturer and device codes.
+const FLASH64K_ID: [u8; 2] = [0x32, 0x1B]; // Panasonic MN63F805MNP
+const FLASH128K_ID: [u8; 2] = [0x62, 0x13]; // Sanyo LE26FV10N1TS
+
+const FLASH_BANK_SIZE: usize = 0x10000;
+const FLASH_SECTOR_SIZE: usize = 0x1000;
+
+/// The save memory on the gamepak. SRAM and flash are mapped at 0E000000h and
+/// mirrored across that region. EEPROM is accessed one bit at a time through
+/// 0D000000h, see [`Backup::eeprom_load16`].
+#[derive(Default)]
+pub struct Backup {
+    save_type: SaveType,
+    data: Vec<u8>,
+    flash: FlashState,
+    eeprom: EepromState,
+}
+
+/// Progress through the flash command sequence (AAh to 5555h, 55h to 2AAAh, then
+/// the command to 5555h) and the effects of previous commands.
+#[derive(Default)]
+struct FlashState {
+    /// The number of bytes of the command sequence that have been written.
+    sequence: u8,
+    id_mode: bool,
+    /// Set by command 80h, the next command can erase the chip or a sector.
+    erase_armed: bool,
+    /// Set by command A0h, the next write programs a byte.
+    write_armed: bool,
+    /// Set by command B0h, the next write to 0000h selects the bank.
+    bank_armed: bool,
+    bank: usize,
+}
+
+#[derive(Default)]
+struct EepromState {
+    /// The bits of the current request, the first one in the highest position.
+    incoming: u128,
+    incoming_len: u8,
+    /// The 64 bits of a block being read and how many of the 68 bits of the
+    /// reply have been read so far.
+    outgoing: Option<(u64, u8)>,
+}
+
+impl Backup {
+    /// Called after a hard reset of the GBA. The save data is kept.
+    pub(crate) fn reset(&mut self) {
+        self.flash = FlashState::default();
+        self.eeprom = EepromState::default();
+    }
+
+    pub fn save_type(&self) -> SaveType {
+        self.save_type
+    }
+
+    /// Replaces the save memory with an erased one of the given type. Any save data
+    /// that was there is thrown away.
+    pub fn set_save_type(&mut self, save_type: SaveType) {
+        self.save_type = save_type;
+        self.data = vec![0xFF; save_type.size()];
+        self.reset();
+    }
+
+    /// The contents of the save memory. EEPROM blocks are stored with the first
+    /// bit that is transferred in the highest bit of the first byte.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    /// Reads a byte from the SRAM region. Carts without SRAM or flash read as FFh.
+    pub(crate) fn read8(&self, address: u32) -> u8 {
+        let offset = address as usize & 0xFFFF;
+        match self.save_type {
+            SaveType::Sram32K => self.data[offset & 0x7FFF],
+            SaveType::Flash64K | SaveType::Flash128K => {
+                if self.flash.id_mode && offset < 2 {
+                    if self.save_type == SaveType::Flash64K {
+                        FLASH64K_ID[offset]
+                    } else {
+                        FLASH128K_ID[offset]
+                    }
+                } else {
+                    self.data[self.flash.bank * FLASH_BANK_SIZE + offset]
+                }
+            }
+            _ => 0xFF,
+        }
+    }
+
+    /// Writes a byte to the SRAM region.
+    pub(crate) fn write8(&mut self, address: u32, value: u8) {
+        let offset = address as usize & 0xFFFF;
+        match self.save_type {
+            SaveType::Sram32K => self.data[offset & 0x7FFF] = value,
+            SaveType::Flash64K | SaveType::Flash128K => self.flash_write(offset, value),
+            _ => {}
+        }
+    }
+
+    fn flash_write(&mut self, offset: usize, value: u8) {
+        let flash = &mut self.flash;
+        if flash.write_armed {
+            // Programming can only clear bits, setting them requires an erase.
+            flash.write_armed = false;
+            self.data[flash.bank * FLASH_BANK_SIZE + offset] &= value;
+            return;
+        }
+        if flash.bank_armed && offset == 0 {
+            flash.bank_armed = false;
+            flash.bank = usize::from(value & 0x1);
+            return;
+        }
+
+        match (flash.sequence, offset, value) {
+            (0, 0x5555, 0xAA) => flash.sequence = 1,
+            (1, 0x2AAA, 0x55) => flash.sequence = 2,
+            (2, _, 0x30) if flash.erase_armed && offset.is_multiple_of(FLASH_SECTOR_SIZE) => {
+                let start = flash.bank * FLASH_BANK_SIZE + offset;
+                self.data[start..start + FLASH_SECTOR_SIZE].fill(0xFF);
+                flash.sequence = 0;
+                flash.erase_armed = false;
+            }
+            (2, 0x5555, command) => {
+                flash.sequence = 0;
+                let erase_armed = std::mem::take(&mut flash.erase_armed);
+                match command {
+                    0x90 => flash.id_mode = true,
+                    0xF0 => flash.id_mode = false,
+                    0x80 => flash.erase_armed = true,
+                    0x10 if erase_armed => self.data.fill(0xFF),
+                    0xA0 => flash.write_armed = true,
+                    0xB0 if self.save_type == SaveType::Flash128K => flash.bank_armed = true,
+                    _ => tracing::debug!("unknown flash command: 0x{command:02X}"),
+                }
+            }
+            // Some chips leave ID mode when F0h is written without the sequence.
+            (_, _, 0xF0) => {
+                flash.sequence = 0;
+                flash.id_mode = false;
+            }
+            _ => flash.sequence = 0,
+        }
+    }
+
+    /// The number of address bits in an EEPROM request.
+    fn eeprom_address_bits(&self) -> u8 {
+        if self.save_type == SaveType::Eeprom512 {
+            6
+        } else {
+            14
+        }
+    }
+
+    /// Reads the next bit of the reply to a read request, or 1 (ready) if there
+    /// isn't one. A reply is 4 bits that should be ignored followed by the 64 bits
+    /// of the block.
+    pub(crate) fn eeprom_load16(&mut self) -> u16 {
+        let Some((block, read)) = self.eeprom.outgoing.as_mut() else {
+            return 1;
+        };
+        let bit = if *read < 4 {
+            0
+        } else {
+            (*block >> (63 - (*read - 4))) as u16 & 0x1
+        };
+        *read += 1;
+        if *read == 68 {
+            self.eeprom.outgoing = None;
+        }
+        bit
+    }
+
+    /// Shifts in bit 0 of `value` as the next bit of a request. A request is 11b
+    /// (read) or 10b (write), the block address, 64 bits of data for a write,
+    /// and a 0 bit. Writes finish immediately.
+    pub(crate) fn eeprom_store16(&mut self, value: u16) {
+        let address_bits = self.eeprom_address_bits();
+        let eeprom = &mut self.eeprom;
+        eeprom.incoming = (eeprom.incoming << 1) | u128::from(value & 0x1);
+        eeprom.incoming_len += 1;
+        if eeprom.incoming_len == 1 {
+            // Anything but a 1 can't be the start of a request.
+            eeprom.incoming_len = eeprom.incoming as u8;
+            return;
+        }
+
+        let read = eeprom.incoming >> (eeprom.incoming_len - 2) == 0b11;
+        let request_len = if read { 3 } else { 67 } + address_bits;
+        if eeprom.incoming_len < request_len {
+            return;
+        }
+
+        let data_bits = if read { 0 } else { 64 };
+        let address_mask = (self.data.len() / 8 - 1) as u128;
+        let block = ((eeprom.incoming >> (data_bits + 1)) & address_mask) as usize;
+        let range = block * 8..block * 8 + 8;
+        if read {
+            let value = u64::from_be_bytes(self.data[range].try_into().unwrap());
+            eeprom.outgoing = Some((value, 0));
+        } else {
+            let value = (eeprom.incoming >> 1) as u64;
+            self.data[range].copy_from_slice(&value.to_be_bytes());
+        }
+        eeprom.incoming = 0;
+        eeprom.incoming_len = 0;
+    }
+}
@@ -0,0 +1,130 @@
+pub mod rtc;
+
+use self::rtc::Rtc;
+
+/// 80000C4h - I/O Port Data (4 bits, R/W)
+pub const GPIO_DATA: u32 = 0xC4;
+/// 80000C6h - I/O Port Direction (4 bits, W). A set bit makes the pin an output.
+pub const GPIO_DIRECTION: u32 = 0xC6;
+/// 80000C8h - I/O Port Control (1 bit, W). Bit 0 makes the port readable.
+pub const GPIO_CONTROL: u32 = 0xC8;
+
+/// GPIO pin that drives the rumble motor on carts that have one.
+pub const PIN_RUMBLE: u8 = 0x8;
+
+/// Called with the new state of the rumble motor whenever it turns on or off.
+/// See [`Gpio::set_rumble_handler`].
+pub type RumbleHandler = Box<dyn Send + Sync + FnMut(bool)>;
+
+/// The general purpose I/O port that some gamepaks map over their ROM at
+/// 80000C4h-80000C9h. The devices that are emulated on it are the S-3511
+/// real-time clock on pins 0-2 and a rumble motor on pin 3. Either one can
+/// be left disconnected.
+pub struct Gpio {
+    /// The last value written to the data register.
+    data: u8,
+    direction: u8,
+    readable: bool,
+    pub rtc: Rtc,
+    rtc_enabled: bool,
+    rumble: bool,
+    rumble_handler: Option<RumbleHandler>,
+}
+
+impl Default for Gpio {
+    fn default() -> Self {
+        Gpio {
+            data: 0,
+            direction: 0,
+            readable: false,
+            rtc: Rtc::default(),
+            rtc_enabled: true,
+            rumble: false,
+            rumble_handler: None,
+        }
+    }
+}
+
+impl Gpio {
+    /// Called after a hard reset of the GBA. The RTC keeps its time.
+    pub(crate) fn reset(&mut self) {
+        self.data = 0;
+        self.direction = 0;
+        self.readable = false;
+        self.rtc.reset_serial();
+        self.update_rumble();
+    }
+
+    /// Connects or disconnects the RTC. A disconnected RTC ignores the port and
+    /// doesn't drive any pins. The RTC is connected by default.
+    pub fn set_rtc_enabled(&mut self, enabled: bool) {
+        if !enabled {
+            self.rtc.reset_serial();
+        }
+        self.rtc_enabled = enabled;
+    }
+
+    /// Sets the handler that is called when the rumble pin turns on or off and
+    /// returns the previous one. Carts without a rumble motor never do either.
+    pub fn set_rumble_handler(&mut self, handler: Option<RumbleHandler>) -> Option<RumbleHandler> {
+        std::mem::replace(&mut self.rumble_handler, handler)
+    }
+
+    /// Returns true if the GBA is driving the rumble pin high.
+    pub fn rumble(&self) -> bool {
+        self.rumble
+    }
+
+    fn update_rumble(&mut self) {
+        let rumble = self.data & self.direction & PIN_RUMBLE != 0;
+        if rumble != self.rumble {
+            self.rumble = rumble;
+            if let Some(handler) = self.rumble_handler.as_mut() {
+                handler(rumble);
+            }
+        }
+    }
+
+    /// Returns true if `address` is in the gamepak region and reads from it
+    /// should go to [`Gpio::read16`] instead of ROM.
+    #[inline]
+    pub(crate) fn is_mapped(&self, address: u32) -> bool {
+        self.readable && (GPIO_DATA..=GPIO_CONTROL + 1).contains(&(address & 0x1FFFFFF))
+    }
+
+    /// Reads the halfword register at `address`. Only valid if [`Gpio::is_mapped`]
+    /// returned true for the address.
+    pub(crate) fn read16(&self, address: u32) -> u16 {
+        match address & 0x1FFFFFE {
+            GPIO_DATA => {
+                let device = if self.rtc_enabled { self.rtc.pins() } else { 0 };
+                let input = device & !self.direction;
+                u16::from((self.data & self.direction) | input)
+            }
+            GPIO_DIRECTION => u16::from(self.direction),
+            GPIO_CONTROL => u16::from(self.readable),
+            _ => 0,
+        }
+    }
+
+    /// Writes to the GPIO port if `address` is one of its registers. Returns false
+    /// if the address is plain ROM.
+    pub(crate) fn write16(&mut self, address: u32, value: u16) -> bool {
+        match address & 0x1FFFFFE {
+            GPIO_DATA => {
+                self.data = value as u8 & 0xF;
+                if self.rtc_enabled {
+                    self.rtc.write_pins(self.data & self.direction);
+                }
+                self.update_rumble();
+            }
+            GPIO_DIRECTION => {
+                self.direction = value as u8 & 0xF;
+                self.update_rumble();
+            }
+            GPIO_CONTROL => self.readable = value & 0x1 != 0,
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,347 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// GPIO pin connected to the RTC's serial clock.
+pub const PIN_SCK: u8 = 0x1;
+/// GPIO pin connected to the RTC's serial data line.
+pub const PIN_SIO: u8 = 0x2;
+/// GPIO pin connected to the RTC's chip select.
+pub const PIN_CS: u8 = 0x4;
+
+/// Status register bit that selects 24 hour time instead of 12 hour time.
+pub const STATUS_24_HOUR: u8 = 0x40;
+/// Status register bits that can be written. The power failure flag (bit 7) is
+/// never set because the clock is backed by the host's clock.
+const STATUS_WRITABLE: u8 = 0x6A;
+
+/// Seconds from the Unix epoch to 2000-01-01 00:00:00, which is what the RTC is
+/// set to when it is reset.
+const RESET_TIME: i64 = 946_684_800;
+
+/// The Seiko S-3511 real-time clock found on the GPIO port of gamepaks like the
+/// Pokémon Ruby, Sapphire, and Emerald carts.
+///
+/// Transfers start when CS goes high. The 8-bit command is clocked in MSB first
+/// on rising edges of SCK, followed by the register's data bytes, LSB first,
+/// which are either written by the GBA or driven onto SIO by the RTC. Commands
+/// are `0110_RRR_D` where `RRR` selects a register and `D` is set for reads.
+///
+/// Time is read from the host's clock plus an offset that changes when the game
+/// sets the time. See [`Rtc::offset`].
+#[derive(Default)]
+pub struct Rtc {
+    offset: i64,
+    status: u8,
+
+    state: State,
+    sck: bool,
+    cs: bool,
+    /// The level that the RTC is driving SIO to.
+    sio: bool,
+}
+
+#[derive(Default, Clone, Copy)]
+enum State {
+    #[default]
+    Idle,
+    Command {
+        byte: u8,
+        bits: u8,
+    },
+    Write {
+        register: Register,
+        data: [u8; 7],
+        bit: usize,
+    },
+    Read {
+        data: [u8; 7],
+        len: usize,
+        bit: usize,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Register {
+    Reset,
+    DateTime,
+    Status,
+    Time,
+}
+
+impl Register {
+    fn from_command(command: u8) -> Option<Self> {
+        match command {
+            0 => Some(Register::Reset),
+            2 => Some(Register::DateTime),
+            4 => Some(Register::Status),
+            6 => Some(Register::Time),
+            _ => None,
+        }
+    }
+
+    fn len(self) -> usize {
+        match self {
+            Register::Reset => 0,
+            Register::DateTime => 7,
+            Register::Status => 1,
+            Register::Time => 3,
+        }
+    }
+}
+
+impl Rtc {
+    /// The number of seconds between the host's clock and the RTC's clock. Frontends
+    /// can store this alongside the save file and restore it with [`Rtc::set_offset`]
+    /// to keep the time that the game set.
+    pub fn offset(&self) -> i64 {
+        self.offset
+    }
+
+    pub fn set_offset(&mut self, offset: i64) {
+        self.offset = offset;
+    }
+
+    /// The RTC's current time as seconds since the Unix epoch.
+    pub fn now(&self) -> i64 {
+        let host = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs() as i64)
+            .unwrap_or(0);
+        host + self.offset
+    }
+
+    /// Aborts any transfer that is in progress.
+    pub(crate) fn reset_serial(&mut self) {
+        self.state = State::Idle;
+        self.sck = false;
+        self.cs = false;
+        self.sio = false;
+    }
+
+    /// The pins that are being driven by the RTC.
+    pub(crate) fn pins(&self) -> u8 {
+        if self.sio {
+            PIN_SIO
+        } else {
+            0
+        }
+    }
+
+    /// Called with the levels of the GPIO pins that are outputs whenever the GBA
+    /// writes to the data register.
+    pub(crate) fn write_pins(&mut self, pins: u8) {
+        let sck = pins & PIN_SCK != 0;
+        let cs = pins & PIN_CS != 0;
+
+        if !cs {
+            self.state = State::Idle;
+        } else if !self.cs {
+            self.state = State::Command { byte: 0, bits: 0 };
+        } else if sck && !self.sck {
+            self.clock(pins & PIN_SIO != 0);
+        }
+
+        self.sck = sck;
+        self.cs = cs;
+    }
+
+    /// Handles a rising edge of SCK while CS is high.
+    fn clock(&mut self, sio: bool) {
+        self.state = match self.state {
+            State::Idle => State::Idle,
+
+            State::Command { byte, bits } => {
+                let byte = (byte << 1) | sio as u8;
+                if bits < 7 {
+                    State::Command {
+                        byte,
+                        bits: bits + 1,
+                    }
+                } else {
+                    self.begin_command(byte)
+                }
+            }
+
+            State::Write {
+                register,
+                mut data,
+                bit,
+            } => {
+                data[bit / 8] |= (sio as u8) << (bit % 8);
+                if bit + 1 < register.len() * 8 {
+                    State::Write {
+                        register,
+                        data,
+                        bit: bit + 1,
+                    }
+                } else {
+                    self.write_register(register, &data[..register.len()]);
+                    State::Idle
+                }
+            }
+
+            State::Read { data, len, bit } => {
+                self.sio = (data[bit / 8] >> (bit % 8)) & 0x1 != 0;
+                if bit + 1 < len * 8 {
+                    State::Read {
+                        data,
+                        len,
+                        bit: bit + 1,
+                    }
+                } else {
+                    State::Idle
+                }
+            }
+        };
+    }
+
+    fn begin_command(&mut self, byte: u8) -> State {
+        if byte >> 4 != 0x6 {
+            tracing::debug!("invalid RTC command: 0x{byte:02X}");
+            return State::Idle;
+        }
+
+        let Some(register) = Register::from_command((byte >> 1) & 0x7) else {
+            tracing::debug!("unimplemented RTC command: 0x{byte:02X}");
+            return State::Idle;
+        };
+
+        if register == Register::Reset {
+            self.status = 0;
+            self.offset = RESET_TIME - (self.now() - self.offset);
+            State::Idle
+        } else if byte & 0x1 != 0 {
+            State::Read {
+                data: self.read_register(register),
+                len: register.len(),
+                bit: 0,
+            }
+        } else {
+            State::Write {
+                register,
+                data: [0; 7],
+                bit: 0,
+            }
+        }
+    }
+
+    fn read_register(&self, register: Register) -> [u8; 7] {
+        let time = DateTime::from_unix(self.now());
+        let hour = if self.status & STATUS_24_HOUR != 0 {
+            bcd(time.hour)
+        } else if time.hour >= 12 {
+            bcd(time.hour - 12) | 0x80
+        } else {
+            bcd(time.hour)
+        };
+
+        match register {
+            Register::Reset => [0; 7],
+            Register::DateTime => [
+                bcd(time.year.rem_euclid(100) as u8),
+                bcd(time.month),
+                bcd(time.day),
+                time.weekday,
+                hour,
+                bcd(time.minute),
+                bcd(time.second),
+            ],
+            Register::Status => [self.status, 0, 0, 0, 0, 0, 0],
+            Register::Time => [hour, bcd(time.minute), bcd(time.second), 0, 0, 0, 0],
+        }
+    }
+
+    fn write_register(&mut self, register: Register, data: &[u8]) {
+        let mut time = DateTime::from_unix(self.now());
+        let time_of_day = match register {
+            Register::Reset => return,
+            Register::Status => {
+                self.status = data[0] & STATUS_WRITABLE;
+                return;
+            }
+            Register::DateTime => {
+                time.year = 2000 + i64::from(from_bcd(data[0]));
+                time.month = from_bcd(data[1]).clamp(1, 12);
+                time.day = from_bcd(data[2]).clamp(1, 31);
+                &data[4..]
+            }
+            Register::Time => data,
+        };
+
+        let pm = time_of_day[0] & 0x80 != 0;
+        time.hour = from_bcd(time_of_day[0] & 0x3F);
+        if self.status & STATUS_24_HOUR == 0 && pm {
+            time.hour += 12;
+        }
+        time.minute = from_bcd(time_of_day[1]);
+        time.second = from_bcd(time_of_day[2]);
+
+        self.offset += time.to_unix() - self.now();
+    }
+}
+
+/// A calendar date and time in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DateTime {
+    year: i64,
+    month: u8,
+    day: u8,
+    /// 0 is Sunday.
+    weekday: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+}
+
+impl DateTime {
+    // Date conversions use Howard Hinnant's `civil_from_days` and `days_from_civil`.
+
+    fn from_unix(seconds: i64) -> Self {
+        let days = seconds.div_euclid(86400);
+        let secs = seconds.rem_euclid(86400);
+
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + (month <= 2) as i64;
+
+        DateTime {
+            year,
+            month: month as u8,
+            day: day as u8,
+            // 1970-01-01 was a Thursday.
+            weekday: (days + 4).rem_euclid(7) as u8,
+            hour: (secs / 3600) as u8,
+            minute: (secs / 60 % 60) as u8,
+            second: (secs % 60) as u8,
+        }
+    }
+
+    fn to_unix(self) -> i64 {
+        let month = i64::from(self.month);
+        let year = if month <= 2 { self.year - 1 } else { self.year };
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let mp = if month > 2 { month - 3 } else { month + 9 };
+        let doy = (153 * mp + 2) / 5 + i64::from(self.day) - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        let days = era * 146097 + doe - 719468;
+
+        days * 86400
+            + i64::from(self.hour) * 3600
+            + i64::from(self.minute) * 60
+            + i64::from(self.second)
+    }
+}
+
+fn bcd(value: u8) -> u8 {
+    ((value / 10) << 4) | (value % 10)
+}
+
+fn from_bcd(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0xF)
+}
@@ -71,6 +71,40 @@ impl RegKeyInput {
     pub fn release_all(&mut self) {
         self.reset();
     }
+
+    /// Presses every button in `buttons` and releases the rest.
+    pub fn set_buttons(&mut self, buttons: Buttons) {
+        self.value = !buttons.pressed & Buttons::ALL.pressed;
+    }
+}
+
+/// A set of pressed buttons. See [`crate::Gba::set_buttons`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Buttons {
+    /// One bit per button in KEYINPUT order, set if it is pressed.
+    pressed: u16,
+}
+
+impl Buttons {
+    pub const NONE: Buttons = Buttons { pressed: 0 };
+    pub const ALL: Buttons = Buttons { pressed: 0x3FF };
+
+    /// Returns a copy of this set with `key` pressed.
+    pub fn with(self, key: Key) -> Self {
+        Buttons {
+            pressed: self.pressed | (1 << u8::from(key)),
+        }
+    }
+
+    pub fn is_pressed(self, key: Key) -> bool {
+        self.pressed & (1 << u8::from(key)) != 0
+    }
+}
+
+impl FromIterator<Key> for Buttons {
+    fn from_iter<I: IntoIterator<Item = Key>>(keys: I) -> Self {
+        keys.into_iter().fold(Buttons::NONE, Buttons::with)
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq)]
@@ -0,0 +1,106 @@
+use arm::emu::Waitstates;
+
+/// Number of halfwords that fit in the prefetch buffer.
+const PREFETCH_CAPACITY: u32 = 8;
+
+/// Game Pak Prefetch Buffer
+///
+/// When enabled in WAITCNT the gamepak keeps reading sequential halfwords into a
+/// buffer of up to 8 halfwords whenever the CPU is not using the gamepak bus. Reads
+/// from the front of the buffer complete without any waitstates while any other
+/// gamepak access empties the buffer.
+#[derive(Default)]
+pub struct GamepakPrefetch {
+    /// Whether the buffer is currently reading halfwords from the gamepak.
+    active: bool,
+    /// Address of the halfword at the front of the buffer.
+    head: u32,
+    /// Number of halfwords in the buffer.
+    count: u32,
+    /// Cycles spent so far reading the next halfword into the buffer.
+    progress: u32,
+    /// Cycles that the CPU used the gamepak bus since the last call to [`GamepakPrefetch::idle`].
+    busy: u32,
+}
+
+impl GamepakPrefetch {
+    /// Returns the waitstates for reading `halfwords` halfwords starting at `address`.
+    /// `first` and `second` are the non-sequential and sequential waitstates of the
+    /// gamepak region that is being read.
+    pub(crate) fn read(
+        &mut self,
+        address: u32,
+        halfwords: u32,
+        sequential: bool,
+        enabled: bool,
+        (first, second): (Waitstates, Waitstates),
+    ) -> Waitstates {
+        if enabled && self.active && address == self.head {
+            let buffered = self.count.min(halfwords);
+            let missing = halfwords - buffered;
+
+            // Halfwords that aren't in the buffer yet are read at sequential speed,
+            // minus whatever progress was already made on the first one.
+            let mut wait = 0;
+            if missing > 0 {
+                wait = (missing * u32::from(second)).saturating_sub(self.progress);
+                self.busy += wait + 1;
+                self.progress = 0;
+            }
+            self.count -= buffered;
+            self.head = address.wrapping_add(halfwords * 2);
+            return Waitstates::from(wait);
+        }
+
+        let mut wait = if sequential { second } else { first };
+        for _ in 1..halfwords {
+            wait += second;
+        }
+
+        self.active = enabled;
+        self.head = address.wrapping_add(halfwords * 2);
+        self.count = 0;
+        self.progress = 0;
+        self.busy += u32::from(wait) + 1;
+        wait
+    }
+
+    /// Stops the prefetch buffer and empties it. This happens when writing to the
+    /// gamepak or when the buffer is disabled.
+    pub(crate) fn flush(&mut self) {
+        self.active = false;
+        self.count = 0;
+        self.progress = 0;
+    }
+
+    /// Advances the prefetch buffer after the CPU has run for `cycles` cycles. Any
+    /// of those cycles where the gamepak bus was not in use are spent reading the
+    /// next halfwords into the buffer. `second` is the sequential waitstates of the
+    /// region that the buffer is reading from.
+    pub(crate) fn idle(&mut self, cycles: u32, second: Waitstates) {
+        let mut idle = cycles.saturating_sub(self.busy);
+        self.busy = 0;
+
+        if !self.active {
+            return;
+        }
+
+        let halfword_cycles = u32::from(second) + 1;
+        while idle > 0 && self.count < PREFETCH_CAPACITY {
+            let remaining = halfword_cycles - self.progress;
+            if idle >= remaining {
+                idle -= remaining;
+                self.count += 1;
+                self.progress = 0;
+            } else {
+                self.progress += idle;
+                idle = 0;
+            }
+        }
+    }
+
+    /// The address that the prefetch buffer will read next.
+    pub(crate) fn next_address(&self) -> u32 {
+        self.head.wrapping_add(self.count * 2)
+    }
+}
@@ -0,0 +1,225 @@
+use pyrite_derive::IoRegister;
+
+use crate::memory::IoRegister as _;
+
+use super::system_control::RegInterrupts;
+
+/// What a GBA that isn't connected sends in multiplayer mode. The SIOMULTI
+/// registers of missing players read as this after a transfer.
+pub const MULTIPLAYER_NO_DATA: u16 = 0xFFFF;
+
+/// The other end of the link cable, e.g. a second emulator instance.
+pub trait LinkCablePeer: Send + Sync {
+    /// Called when this GBA starts a multiplayer transfer as the parent. `data` is
+    /// the value from SIOMLT_SEND. Returns the values sent by children 1 to 3 in
+    /// order, with [`MULTIPLAYER_NO_DATA`] for the ones that aren't connected.
+    fn transfer_multiplayer(&mut self, data: u16) -> [u16; 3];
+}
+
+/// The mode selected by RCNT and SIOCNT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerialMode {
+    Normal8,
+    Normal32,
+    Multiplayer,
+    Uart,
+    GeneralPurpose,
+    JoyBus,
+}
+
+/// The serial port (SIO). Only this GBA's side of the cable is emulated. With no
+/// [`LinkCablePeer`] connected it behaves like a GBA without a cable: multiplayer
+/// transfers complete immediately with [`MULTIPLAYER_NO_DATA`] from every child,
+/// and normal mode transfers using the internal clock shift in all ones. Normal
+/// mode transfers using an external clock wait for a clock that never comes,
+/// just like on hardware.
+#[derive(Default)]
+pub struct Serial {
+    pub siocnt: RegSioCnt,
+    pub rcnt: RegRcnt,
+    /// SIOMULTI0-3, which double as SIODATA32 in 32-bit normal mode.
+    data: [u16; 4],
+    /// SIOMLT_SEND, which doubles as SIODATA8 in 8-bit normal mode.
+    send: u16,
+    peer: Option<Box<dyn LinkCablePeer>>,
+}
+
+impl Serial {
+    /// Called after a hard reset of the GBA. The peer stays connected.
+    pub(crate) fn reset(&mut self) {
+        self.siocnt = RegSioCnt::default();
+        self.rcnt = RegRcnt::default();
+        self.data = [0; 4];
+        self.send = 0;
+    }
+
+    /// Connects or disconnects the other end of the link cable and returns the
+    /// previous one.
+    pub fn set_peer(
+        &mut self,
+        peer: Option<Box<dyn LinkCablePeer>>,
+    ) -> Option<Box<dyn LinkCablePeer>> {
+        let previous = std::mem::replace(&mut self.peer, peer);
+        self.update_terminals();
+        previous
+    }
+
+    pub fn mode(&self) -> SerialMode {
+        match (self.rcnt.mode(), self.siocnt.mode()) {
+            (0 | 1, 0) => SerialMode::Normal8,
+            (0 | 1, 1) => SerialMode::Normal32,
+            (0 | 1, 2) => SerialMode::Multiplayer,
+            (0 | 1, _) => SerialMode::Uart,
+            (2, _) => SerialMode::GeneralPurpose,
+            _ => SerialMode::JoyBus,
+        }
+    }
+
+    /// Reads SIOMULTI0-3 (or SIODATA32), `index` is the halfword index from 4000120h.
+    pub(crate) fn read_data(&self, index: usize) -> u16 {
+        self.data[index]
+    }
+
+    pub(crate) fn write_data(&mut self, index: usize, value: u16) {
+        self.data[index] = value;
+    }
+
+    /// Reads SIOMLT_SEND (or SIODATA8).
+    pub(crate) fn read_send(&self) -> u16 {
+        self.send
+    }
+
+    pub(crate) fn write_send(&mut self, value: u16) {
+        self.send = value;
+    }
+
+    /// Writes SIOCNT and starts a transfer if the start bit was set. Returns the
+    /// interrupts requested by transfers that complete immediately.
+    pub(crate) fn write_siocnt(&mut self, value: u16) -> RegInterrupts {
+        let was_active = self.siocnt.start();
+        self.siocnt.write(value);
+        self.update_terminals();
+        if !was_active && self.siocnt.start() {
+            self.start_transfer()
+        } else {
+            RegInterrupts::default()
+        }
+    }
+
+    pub(crate) fn write_rcnt(&mut self, value: u16) {
+        self.rcnt.write(value);
+        self.update_terminals();
+    }
+
+    /// Updates the read only bits of SIOCNT. This GBA is always the parent in
+    /// multiplayer mode and the SD terminal only reports that every GBA is ready
+    /// if something is connected.
+    fn update_terminals(&mut self) {
+        if self.mode() == SerialMode::Multiplayer {
+            self.siocnt.set_si_terminal(false);
+            self.siocnt.set_sd_terminal(self.peer.is_some());
+            self.siocnt.set_multiplayer_id(0);
+        }
+    }
+
+    fn start_transfer(&mut self) -> RegInterrupts {
+        match self.mode() {
+            SerialMode::Multiplayer => {
+                let children = match self.peer.as_mut() {
+                    Some(peer) => peer.transfer_multiplayer(self.send),
+                    None => [MULTIPLAYER_NO_DATA; 3],
+                };
+                self.data[0] = self.send;
+                self.data[1..].copy_from_slice(&children);
+                self.siocnt.set_multiplayer_error(false);
+            }
+            SerialMode::Normal8 | SerialMode::Normal32 if !self.siocnt.internal_clock() => {
+                // Nothing on the other end is going to supply the clock.
+                return RegInterrupts::default();
+            }
+            SerialMode::Normal8 => self.send = (self.send & 0xFF00) | 0xFF,
+            SerialMode::Normal32 => self.data[..2].fill(0xFFFF),
+            mode => {
+                tracing::debug!("unimplemented serial transfer in {mode:?} mode");
+                return RegInterrupts::default();
+            }
+        }
+
+        self.siocnt.set_start(false);
+        let mut interrupts = RegInterrupts::default();
+        interrupts.set_serial(self.siocnt.irq_enabled());
+        interrupts
+    }
+}
+
+/// 4000128h - SIOCNT - SIO Control Register (R/W)
+///
+/// The meaning of the low bits depends on the mode. In Normal Mode:
+///
+/// ```ignore
+///   Bit   Expl.
+///   0     Shift Clock (SC)        (0=External, 1=Internal)
+///   1     Internal Shift Clock    (0=256KHz, 1=2MHz)
+///   2     SI State (opponents SO) (0=Low, 1=High/None) --- (Read Only)
+///   3     SO during inactivity    (0=Low, 1=High) (applied ONLY when Bit7=0)
+///   4-6   Not used                (Read only, always 0 ?)
+///   7     Start Bit               (0=Inactive/Ready, 1=Start/Active)
+///   8-11  Not used                (R/W, should be 0)
+///   12    Transfer Length         (0=8bit, 1=32bit)
+///   13    Must be "0" for Normal Mode
+///   14    IRQ Enable              (0=Disable, 1=Want IRQ upon completion)
+///   15    Not used                (Read only, always 0)
+/// ```
+///
+/// In Multi-Player Mode:
+///
+/// ```ignore
+///   Bit   Expl.
+///   0-1   Baud Rate     (0-3: 9600,38400,57600,115200 bps)
+///   2     SI-Terminal   (0=Parent, 1=Child)                  (Read Only)
+///   3     SD-Terminal   (0=Bad connection, 1=All GBAs Ready) (Read Only)
+///   4-5   Multi-Player ID     (0=Parent, 1-3=1st-3rd child)  (Read Only)
+///   6     Multi-Player Error  (0=Normal, 1=Error)            (Read Only)
+///   7     Start/Busy Bit      (0=Inactive, 1=Start/Busy) (Read Only for Slaves)
+///   8-11  Not used            (R/W, should be 0)
+///   12    Must be "0" for Multi-Player mode
+///   13    Must be "1" for Multi-Player mode
+///   14    IRQ Enable          (0=Disable, 1=Want IRQ upon completion)
+///   15    Not used            (Read only, always 0)
+/// ```
+#[derive(IoRegister, Copy, Clone)]
+#[repr(C)]
+#[field(internal_clock: bool = 0)]
+#[field(baud_rate: u16 = 0..=1, overlapping)]
+#[field(si_terminal: readonly<bool> = 2)]
+#[field(sd_terminal: bool = 3)]
+#[field(multiplayer_id: readonly<u16> = 4..=5)]
+#[field(multiplayer_error: readonly<bool> = 6)]
+#[field(start: bool = 7)]
+#[field(mode: u16 = 12..=13)]
+#[field(irq_enabled: bool = 14)]
+#[field(not_used_bit_15: readonly<bool> = 15)]
+pub struct RegSioCnt {
+    value: u16,
+}
+
+/// 4000134h - RCNT - Mode Selection (R/W)
+///
+/// ```ignore
+///   Bit   Expl.
+///   0-3   General purpose data for the SC, SD, SI and SO terminals
+///   4-7   General purpose direction for the SC, SD, SI and SO terminals
+///   8     SI interrupt enable     (General Purpose Mode)
+///   9-13  Not used
+///   14-15 Mode Selection          (0-1=SIOCNT selects the mode,
+///                                  2=General Purpose, 3=JOY Bus)
+/// ```
+#[derive(IoRegister, Copy, Clone)]
+#[repr(C)]
+#[field(data: u16 = 0..=3)]
+#[field(direction: u16 = 4..=7)]
+#[field(si_irq_enabled: bool = 8)]
+#[field(mode: u16 = 14..=15)]
+pub struct RegRcnt {
+    value: u16,
+}
@@ -1,14 +1,53 @@
+use crate::memory::IoRegister;
 use arm::emu::Waitstates;
 use pyrite_derive::IoRegister;
 
+/// How closely memory access timings are emulated. This only changes how many
+/// cycles memory accesses take and never the values that are read or written.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum AccuracyMode {
+    /// Memory accesses use the fixed waitstates that the GBA starts with. WAITCNT,
+    /// the EWRAM wait control and the gamepak prefetch buffer are ignored.
+    Fast,
+    /// Memory accesses use the waitstates configured in WAITCNT and the EWRAM wait
+    /// control and gamepak reads go through the prefetch buffer.
+    #[default]
+    Cycle,
+}
+
 #[derive(Default)]
 pub struct SystemControl {
     pub waitcnt: RegWaitcnt,
     pub internal_memory_control: RegInternalMemoryControl,
     pub waitstates: SystemWaitstates,
+    accuracy: AccuracyMode,
+
+    pub interrupt_enable: RegInterrupts,
+    pub interrupt_request: RegInterrupts,
+    pub interrupt_master_enable: RegInterruptMasterEnable,
 }
 
 impl SystemControl {
+    /// Sets the bits in IF for every interrupt in `interrupts`.
+    pub fn request_interrupts(&mut self, interrupts: RegInterrupts) {
+        let requested = self.interrupt_request.read() | interrupts.read();
+        self.interrupt_request.write(requested);
+    }
+
+    /// Clears the bits in IF that are set in `value`. This is what happens when
+    /// IF is written to.
+    pub fn acknowledge_interrupts(&mut self, value: u16) {
+        let requested = self.interrupt_request.read() & !value;
+        self.interrupt_request.write(requested);
+    }
+
+    /// Returns true if an interrupt that is enabled in IE has been requested and
+    /// interrupts are enabled in IME.
+    pub fn irq_pending(&self) -> bool {
+        self.interrupt_master_enable.enabled()
+            && (self.interrupt_enable.read() & self.interrupt_request.read()) != 0
+    }
+
     pub fn write_waitcnt(&mut self, waitcnt: RegWaitcnt) {
         self.waitcnt = waitcnt;
         self.update_waitstates();
@@ -22,8 +61,27 @@ impl SystemControl {
         self.update_waitstates();
     }
 
+    pub fn accuracy(&self) -> AccuracyMode {
+        self.accuracy
+    }
+
+    pub fn set_accuracy(&mut self, accuracy: AccuracyMode) {
+        self.accuracy = accuracy;
+        self.update_waitstates();
+    }
+
+    /// Returns true if gamepak reads should go through the prefetch buffer.
+    pub fn prefetch_enabled(&self) -> bool {
+        self.accuracy == AccuracyMode::Cycle && self.waitcnt.gamepak_prefetch_buffer_enabled()
+    }
+
     pub fn update_waitstates(&mut self) {
-        self.waitstates.sram = match self.waitcnt.sram_wait_control() {
+        let (waitcnt, internal_memory_control) = match self.accuracy {
+            AccuracyMode::Cycle => (self.waitcnt, self.internal_memory_control),
+            AccuracyMode::Fast => (RegWaitcnt::new(0), RegInternalMemoryControl::DEFAULT),
+        };
+
+        self.waitstates.sram = match waitcnt.sram_wait_control() {
             0 => Waitstates::from(4u32),
             1 => Waitstates::from(3u32),
             2 => Waitstates::from(2u32),
@@ -31,7 +89,7 @@ impl SystemControl {
             _ => unreachable!(),
         };
 
-        self.waitstates.gamepak[0].0 = match self.waitcnt.waitstate_0_first_access() {
+        self.waitstates.gamepak[0].0 = match waitcnt.waitstate_0_first_access() {
             0 => Waitstates::from(4u32),
             1 => Waitstates::from(3u32),
             2 => Waitstates::from(2u32),
@@ -39,13 +97,13 @@ impl SystemControl {
             _ => unreachable!(),
         };
 
-        self.waitstates.gamepak[0].1 = match self.waitcnt.waitstate_0_second_access() {
+        self.waitstates.gamepak[0].1 = match waitcnt.waitstate_0_second_access() {
             0 => Waitstates::from(2u32),
             1 => Waitstates::from(1u32),
             _ => unreachable!(),
         };
 
-        self.waitstates.gamepak[1].0 = match self.waitcnt.waitstate_1_first_access() {
+        self.waitstates.gamepak[1].0 = match waitcnt.waitstate_1_first_access() {
             0 => Waitstates::from(4u32),
             1 => Waitstates::from(3u32),
             2 => Waitstates::from(2u32),
@@ -53,13 +111,13 @@ impl SystemControl {
             _ => unreachable!(),
         };
 
-        self.waitstates.gamepak[1].1 = match self.waitcnt.waitstate_1_second_access() {
+        self.waitstates.gamepak[1].1 = match waitcnt.waitstate_1_second_access() {
             0 => Waitstates::from(4u32),
             1 => Waitstates::from(1u32),
             _ => unreachable!(),
         };
 
-        self.waitstates.gamepak[2].0 = match self.waitcnt.waitstate_2_first_access() {
+        self.waitstates.gamepak[2].0 = match waitcnt.waitstate_2_first_access() {
             0 => Waitstates::from(4u32),
             1 => Waitstates::from(3u32),
             2 => Waitstates::from(2u32),
@@ -67,13 +125,13 @@ impl SystemControl {
             _ => unreachable!(),
         };
 
-        self.waitstates.gamepak[2].1 = match self.waitcnt.waitstate_2_second_access() {
+        self.waitstates.gamepak[2].1 = match waitcnt.waitstate_2_second_access() {
             0 => Waitstates::from(8u32),
             1 => Waitstates::from(1u32),
             _ => unreachable!(),
         };
 
-        self.waitstates.ewram = match self.internal_memory_control.wait_control_ewram() {
+        self.waitstates.ewram = match internal_memory_control.wait_control_ewram() {
             value @ 0..=14 => Waitstates::from(15u32 - value),
             15 => {
                 tracing::warn!("EWRAM waitstates set to 0, this would lock up real hardware");
@@ -138,6 +196,65 @@ pub struct RegWaitcnt {
     value: u32,
 }
 
+/// 4000200h - IE - Interrupt Enable Register (R/W)
+/// 4000202h - IF - Interrupt Request Flags / IRQ Acknowledge (R/W, see below)
+///
+/// ```ignore
+///   Bit   Expl.
+///   0     LCD V-Blank                    (0=Disable)
+///   1     LCD H-Blank                    (etc.)
+///   2     LCD V-Counter Match            (etc.)
+///   3     Timer 0 Overflow               (etc.)
+///   4     Timer 1 Overflow               (etc.)
+///   5     Timer 2 Overflow               (etc.)
+///   6     Timer 3 Overflow               (etc.)
+///   7     Serial Communication           (etc.)
+///   8     DMA 0                          (etc.)
+///   9     DMA 1                          (etc.)
+///   10    DMA 2                          (etc.)
+///   11    DMA 3                          (etc.)
+///   12    Keypad                         (etc.)
+///   13    Game Pak (external IRQ source) (etc.)
+///   14-15 Not used
+/// ```
+///
+/// Interrupts must be enabled in IE and IME before they are serviced. IF bits are set
+/// by hardware when an interrupt is requested and are acknowledged (cleared) by writing
+/// a 1 to them.
+#[derive(IoRegister, Copy, Clone)]
+#[repr(C)]
+#[field(vblank: bool = 0)]
+#[field(hblank: bool = 1)]
+#[field(v_counter: bool = 2)]
+#[field(timer0: bool = 3)]
+#[field(timer1: bool = 4)]
+#[field(timer2: bool = 5)]
+#[field(timer3: bool = 6)]
+#[field(serial: bool = 7)]
+#[field(dma0: bool = 8)]
+#[field(dma1: bool = 9)]
+#[field(dma2: bool = 10)]
+#[field(dma3: bool = 11)]
+#[field(keypad: bool = 12)]
+#[field(gamepak: bool = 13)]
+pub struct RegInterrupts {
+    value: u16,
+}
+
+/// 4000208h - IME - Interrupt Master Enable Register (R/W)
+///
+/// ```ignore
+///   Bit   Expl.
+///   0     Disable all interrupts         (0=Disable All, 1=See IE register)
+///   1-31  Not used
+/// ```
+#[derive(IoRegister, Copy, Clone)]
+#[repr(C)]
+#[field(enabled: bool = 0)]
+pub struct RegInterruptMasterEnable {
+    value: u16,
+}
+
 /// 4000800h - 32bit - Undocumented - Internal Memory Control (R/W)
 /// Supported by GBA and GBA SP only - NOT supported by DS (even in GBA mode).
 /// Also supported by GBA Micro - but crashes on "overclocked" WRAM setting.
@@ -1,3 +1,5 @@
+mod affine;
+mod dirty;
 pub mod line;
 mod mode3;
 mod mode4;
@@ -16,7 +18,9 @@ use self::{
     registers::{BgMode, GbaVideoRegisters},
 };
 
-use super::palette::Palette;
+use super::{palette::Palette, system_control::RegInterrupts};
+
+pub use dirty::DirtyLineOutput;
 
 pub const VISIBLE_LINE_WIDTH: usize = 240;
 pub const VISIBLE_LINE_COUNT: usize = 160;
@@ -24,15 +28,97 @@ pub const LINE_COUNT: usize = 228;
 pub const VISIBLE_PIXELS: usize = VISIBLE_LINE_WIDTH * VISIBLE_LINE_COUNT;
 pub const HDRAW_CYCLES: Cycles = Cycles::new(960);
 pub const HBLANK_CYCLES: Cycles = Cycles::new(272);
+pub const FRAME_CYCLES: Cycles = Cycles::new(LINE_COUNT as u32 * (960 + 272));
 
 pub type LineBuffer = [u16; VISIBLE_LINE_WIDTH];
 pub type ScreenBuffer = [u16; VISIBLE_PIXELS];
 
+/// A complete 240x160 frame of 1555 pixels.
+#[derive(Clone)]
+pub struct FrameBuffer {
+    pixels: Box<ScreenBuffer>,
+}
+
+impl FrameBuffer {
+    pub fn new() -> Self {
+        Self {
+            pixels: Box::new([0; VISIBLE_PIXELS]),
+        }
+    }
+
+    pub fn pixels(&self) -> &ScreenBuffer {
+        &self.pixels
+    }
+
+    pub fn line(&self, line: usize) -> &LineBuffer {
+        let start = line * VISIBLE_LINE_WIDTH;
+        (&self.pixels[start..start + VISIBLE_LINE_WIDTH])
+            .try_into()
+            .unwrap()
+    }
+
+    fn line_mut(&mut self, line: usize) -> &mut LineBuffer {
+        let start = line * VISIBLE_LINE_WIDTH;
+        (&mut self.pixels[start..start + VISIBLE_LINE_WIDTH])
+            .try_into()
+            .unwrap()
+    }
+}
+
+impl Default for FrameBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A layer that is composited into each line. See [`GbaVideo::set_layer_enabled`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Layer {
+    Bg0,
+    Bg1,
+    Bg2,
+    Bg3,
+    Obj,
+}
+
+impl Layer {
+    pub const ALL: [Layer; 5] = [Layer::Bg0, Layer::Bg1, Layer::Bg2, Layer::Bg3, Layer::Obj];
+
+    /// The layer for background `bg`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bg` is not in the range 0-3.
+    pub fn bg(bg: usize) -> Layer {
+        match bg {
+            0 => Layer::Bg0,
+            1 => Layer::Bg1,
+            2 => Layer::Bg2,
+            3 => Layer::Bg3,
+            _ => panic!("invalid background {bg}"),
+        }
+    }
+
+    fn mask(self) -> u8 {
+        1 << self as u8
+    }
+}
+
 pub struct GbaVideo {
     pub(crate) line: GbaLine,
     scheduler: SharedGbaScheduler,
     pub(crate) registers: GbaVideoRegisters,
     pub(crate) frame: u64,
+
+    /// The frame that is currently being drawn.
+    back_buffer: FrameBuffer,
+    /// The last completed frame.
+    pub(crate) frame_buffer: FrameBuffer,
+
+    /// Bitmask of the layers that were hidden with [`GbaVideo::set_layer_enabled`].
+    hidden_layers: u8,
+    /// Set with [`GbaVideo::set_rendering_suppressed`].
+    rendering_suppressed: bool,
 }
 
 impl GbaVideo {
@@ -42,36 +128,88 @@ impl GbaVideo {
             scheduler,
             registers: GbaVideoRegisters::default(),
             frame: 0,
+            back_buffer: FrameBuffer::new(),
+            frame_buffer: FrameBuffer::new(),
+            hidden_layers: 0,
+            rendering_suppressed: false,
         }
     }
 
+    /// Hides or shows a layer without changing DISPCNT. A hidden layer is never drawn,
+    /// but a layer that is shown is still only drawn if DISPCNT enables it. This is
+    /// meant for debugging and is not changed by a reset.
+    pub fn set_layer_enabled(&mut self, layer: Layer, enabled: bool) {
+        if enabled {
+            self.hidden_layers &= !layer.mask();
+        } else {
+            self.hidden_layers |= layer.mask();
+        }
+    }
+
+    /// Returns false if the layer was hidden with [`GbaVideo::set_layer_enabled`].
+    pub fn layer_enabled(&self, layer: Layer) -> bool {
+        self.hidden_layers & layer.mask() == 0
+    }
+
+    /// Stops visible lines from being drawn, e.g. for frames that are skipped.
+    /// Frames still end at the same time but nothing is sent to the
+    /// [`GbaVideoOutput`] and the frame buffer keeps the last frame that was drawn.
+    /// This is not changed by a reset.
+    pub fn set_rendering_suppressed(&mut self, suppressed: bool) {
+        self.rendering_suppressed = suppressed;
+    }
+
+    pub fn rendering_suppressed(&self) -> bool {
+        self.rendering_suppressed
+    }
+
     fn render_line(&mut self, line: u16, video: &mut dyn GbaVideoOutput, context: HBlankContext) {
+        #[cfg(feature = "puffin")]
+        puffin::profile_function!();
+
+        let buffer = self.back_buffer.line_mut(line as usize);
+        if self.registers.dispcnt.forced_blank() {
+            buffer.fill(rgb5(0x1F, 0x1F, 0x1F));
+            video.gba_line_ready(line as usize, buffer);
+            self.end_line(line, video);
+            return;
+        }
+
         let mut unhandled_mode = false;
 
-        let render_context = RenderContext::new(line, &self.registers, context.vram);
+        let render_context =
+            RenderContext::new(line, &self.registers, context.vram, self.hidden_layers);
+        let bitmap_enabled = !render_context.is_hidden(Layer::Bg2);
         self.line.clear(context.palette);
         match self.registers.dispcnt.bg_mode() {
             BgMode::Mode0 => unhandled_mode = true,
-            BgMode::Mode1 => unhandled_mode = true,
-            BgMode::Mode3 => mode3::render(&mut self.line, render_context),
-            BgMode::Mode2 => unhandled_mode = true,
-            BgMode::Mode4 => mode4::render(&mut self.line, render_context),
+            // FIXME BG0 and BG1 are text backgrounds which aren't drawn yet.
+            BgMode::Mode1 => affine::render_backgrounds(&mut self.line, render_context, &[2]),
+            BgMode::Mode3 if bitmap_enabled => mode3::render(&mut self.line, render_context),
+            BgMode::Mode2 => affine::render_backgrounds(&mut self.line, render_context, &[2, 3]),
+            BgMode::Mode4 if bitmap_enabled => mode4::render(&mut self.line, render_context),
+            BgMode::Mode3 | BgMode::Mode4 => {}
             BgMode::Mode5 => unhandled_mode = true,
             BgMode::Invalid6 => unhandled_mode = true,
             BgMode::Invalid7 => unhandled_mode = true,
         }
 
-        let mut buffer = [0u16; VISIBLE_LINE_WIDTH];
         if unhandled_mode {
             buffer.fill(rgb5(0x1F, 0, 0x1F));
         } else {
             let context = BlendContext::with_hblank(&self.registers, context);
-            self.line.blend(&mut buffer, context);
+            self.line.blend(buffer, context);
         }
-        video.gba_line_ready(line as usize, &buffer);
+        video.gba_line_ready(line as usize, buffer);
+        self.end_line(line, video);
+    }
 
+    /// Swaps the frame buffers after the last visible line has been drawn.
+    fn end_line(&mut self, line: u16, video: &mut dyn GbaVideoOutput) {
         if line == (VISIBLE_LINE_COUNT - 1) as u16 {
             self.frame += 1;
+            std::mem::swap(&mut self.back_buffer, &mut self.frame_buffer);
+            video.gba_frame_ready(&self.frame_buffer);
         }
     }
 
@@ -82,7 +220,10 @@ impl GbaVideo {
         self.begin_hdraw();
     }
 
-    pub(crate) fn begin_hdraw(&mut self) {
+    /// Moves on to the next scanline and returns the interrupts that were requested
+    /// by entering VBlank or by matching the VCount setting in DISPSTAT.
+    pub(crate) fn begin_hdraw(&mut self) -> RegInterrupts {
+        let mut interrupts = RegInterrupts::default();
         self.scheduler.schedule(GbaEvent::HBlank, HDRAW_CYCLES);
 
         let mut current_scanline = self.registers.vcount.current_scanline();
@@ -97,22 +238,68 @@ impl GbaVideo {
             .set_vblank_flag(current_scanline >= VISIBLE_LINE_COUNT as u16);
         self.registers.dispstat.set_hblank_flag(false);
         self.registers.vcount.set_current_scanline(current_scanline);
+
+        if current_scanline == VISIBLE_LINE_COUNT as u16 {
+            self.registers
+                .bg_affine
+                .iter_mut()
+                .for_each(|affine| affine.latch_reference_point());
+            interrupts.set_vblank(self.registers.dispstat.vblank_irq_enable());
+        }
+
+        let v_counter_match = current_scanline == self.registers.dispstat.v_count_setting();
+        self.registers.dispstat.set_v_counter_flag(v_counter_match);
+        if v_counter_match {
+            interrupts.set_v_counter(self.registers.dispstat.v_counter_irq_enable());
+        }
+
+        interrupts
     }
 
-    pub(crate) fn begin_hblank(&mut self, video: &mut dyn GbaVideoOutput, context: HBlankContext) {
+    /// Renders the current scanline if it is visible and rendering isn't suppressed,
+    /// and returns the interrupts that were requested by entering HBlank.
+    pub(crate) fn begin_hblank(
+        &mut self,
+        video: &mut dyn GbaVideoOutput,
+        context: HBlankContext,
+    ) -> RegInterrupts {
         self.scheduler.schedule(GbaEvent::HDraw, HBLANK_CYCLES);
 
         self.registers.dispstat.set_hblank_flag(true);
         let current_scanline = self.registers.vcount.current_scanline();
         if current_scanline < VISIBLE_LINE_COUNT as _ {
-            self.render_line(current_scanline, video, context);
+            if self.rendering_suppressed {
+                if current_scanline == (VISIBLE_LINE_COUNT - 1) as u16 {
+                    self.frame += 1;
+                }
+            } else {
+                self.render_line(current_scanline, video, context);
+            }
+            self.registers
+                .bg_affine
+                .iter_mut()
+                .for_each(|affine| affine.next_line());
         }
+
+        let mut interrupts = RegInterrupts::default();
+        interrupts.set_hblank(self.registers.dispstat.hblank_irq_enable());
+        interrupts
     }
 
     #[inline]
     pub fn current_scanline(&self) -> u16 {
         self.registers.vcount.current_scanline()
     }
+
+    #[inline]
+    pub fn in_vblank(&self) -> bool {
+        self.registers.dispstat.vblank_flag()
+    }
+
+    #[inline]
+    pub fn in_hblank(&self) -> bool {
+        self.registers.dispstat.hblank_flag()
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -126,19 +313,86 @@ struct RenderContext<'a> {
     pub vram: &'a [u8; VRAM_SIZE],
     pub line: u16,
     pub registers: &'a GbaVideoRegisters,
+    hidden_layers: u8,
 }
 
 impl<'a> RenderContext<'a> {
-    pub fn new(line: u16, registers: &'a GbaVideoRegisters, vram: &'a [u8; VRAM_SIZE]) -> Self {
+    pub fn new(
+        line: u16,
+        registers: &'a GbaVideoRegisters,
+        vram: &'a [u8; VRAM_SIZE],
+        hidden_layers: u8,
+    ) -> Self {
         Self {
             line,
             vram,
             registers,
+            hidden_layers,
         }
     }
+
+    /// Returns true if the layer was hidden for debugging, whatever DISPCNT says.
+    pub fn is_hidden(&self, layer: Layer) -> bool {
+        self.hidden_layers & layer.mask() != 0
+    }
 }
 
 #[inline]
 pub const fn rgb5(r: u16, g: u16, b: u16) -> u16 {
     (r & 0x1F) | ((g & 0x1F) << 5) | ((b & 0x1F) << 10) | 0x8000
 }
+
+/// Expands a 1555 color into an RGB8 triple. The top bits of each channel are
+/// replicated into the low bits so that 0x1F maps to 0xFF.
+#[inline]
+pub const fn rgb8(color: u16) -> [u8; 3] {
+    const fn expand(c: u16) -> u8 {
+        let c = (c & 0x1F) as u8;
+        (c << 3) | (c >> 2)
+    }
+    [expand(color), expand(color >> 5), expand(color >> 10)]
+}
+
+/// Conversions from buffers of 1555 pixels to 8-bit color channels.
+pub trait ToRgb8 {
+    /// Writes 3 bytes for each pixel into `out`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `out` is too small to hold every pixel.
+    fn to_rgb8(&self, out: &mut [u8]);
+
+    /// Writes 4 bytes for each pixel into `out`. The alpha channel is always 0xFF.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `out` is too small to hold every pixel.
+    fn to_rgba8(&self, out: &mut [u8]);
+}
+
+impl ToRgb8 for [u16] {
+    fn to_rgb8(&self, out: &mut [u8]) {
+        assert!(out.len() >= self.len() * 3, "RGB8 buffer is too small");
+        for (out, &pixel) in out.chunks_exact_mut(3).zip(self) {
+            out.copy_from_slice(&rgb8(pixel));
+        }
+    }
+
+    fn to_rgba8(&self, out: &mut [u8]) {
+        assert!(out.len() >= self.len() * 4, "RGBA8 buffer is too small");
+        for (out, &pixel) in out.chunks_exact_mut(4).zip(self) {
+            let [r, g, b] = rgb8(pixel);
+            out.copy_from_slice(&[r, g, b, 0xFF]);
+        }
+    }
+}
+
+impl ToRgb8 for FrameBuffer {
+    fn to_rgb8(&self, out: &mut [u8]) {
+        self.pixels[..].to_rgb8(out)
+    }
+
+    fn to_rgba8(&self, out: &mut [u8]) {
+        self.pixels[..].to_rgba8(out)
+    }
+}
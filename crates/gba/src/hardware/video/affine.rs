@@ -0,0 +1,80 @@
+use arrayvec::ArrayVec;
+
+use crate::video::line::{Pixel, PixelAttrs};
+
+use super::{line::GbaLine, Layer, RenderContext, VISIBLE_LINE_WIDTH};
+
+/// Draws the enabled affine backgrounds from back to front so that the background
+/// with the highest priority ends up on top.
+pub(super) fn render_backgrounds(
+    line: &mut GbaLine,
+    context: RenderContext,
+    backgrounds: &[usize],
+) {
+    let dispcnt = context.registers.dispcnt;
+    let mut enabled = backgrounds
+        .iter()
+        .copied()
+        .filter(|&bg| match bg {
+            2 => dispcnt.screen_display_bg2(),
+            _ => dispcnt.screen_display_bg3(),
+        })
+        .filter(|&bg| !context.is_hidden(Layer::bg(bg)))
+        .collect::<ArrayVec<usize, 2>>();
+    enabled.sort_by_key(|&bg| std::cmp::Reverse((context.registers.bg_cnt[bg].priority(), bg)));
+
+    for bg in enabled {
+        render(line, context, bg);
+    }
+}
+
+/// Renders one of the rotation/scaling backgrounds (BG2 or BG3) into the line.
+/// Affine backgrounds always use 8bpp tiles and a map of 1 byte tile numbers.
+fn render(line: &mut GbaLine, context: RenderContext, bg: usize) {
+    #[cfg(feature = "puffin")]
+    puffin::profile_function!();
+
+    debug_assert!(bg == 2 || bg == 3);
+
+    let control = context.registers.bg_cnt[bg];
+    let affine = &context.registers.bg_affine[bg - 2];
+    let attrs = PixelAttrs::default();
+
+    let size = 128i32 << control.screen_size();
+    let tiles_per_row = (size / 8) as usize;
+    let map_base = control.screen_base_block() as usize * 0x800;
+    let char_base = control.character_base_block() as usize * 0x4000;
+    let wraparound = control.display_area_overflow();
+
+    // With vertical mosaic every line of a block is drawn using the reference point
+    // of the first line in the block.
+    let mosaic = context.registers.bg_mosaic(bg);
+    let lines_into_block = (context.line - mosaic.source_line(context.line)) as i32;
+    let origin_x = affine
+        .internal_x
+        .wrapping_sub(affine.pb.fixed().wrapping_mul(lines_into_block));
+    let origin_y = affine
+        .internal_y
+        .wrapping_sub(affine.pd.fixed().wrapping_mul(lines_into_block));
+    let (pa, pc) = (affine.pa.fixed(), affine.pc.fixed());
+
+    for x in 0..VISIBLE_LINE_WIDTH {
+        let source_x = mosaic.source_x(x) as i32;
+        let mut tx = origin_x.wrapping_add(pa.wrapping_mul(source_x)) >> 8;
+        let mut ty = origin_y.wrapping_add(pc.wrapping_mul(source_x)) >> 8;
+
+        if wraparound {
+            tx &= size - 1;
+            ty &= size - 1;
+        } else if tx < 0 || ty < 0 || tx >= size || ty >= size {
+            continue;
+        }
+
+        let (tx, ty) = (tx as usize, ty as usize);
+        let tile = context.vram[map_base + (ty / 8) * tiles_per_row + (tx / 8)] as usize;
+        let entry = context.vram[char_base + tile * 64 + (ty % 8) * 8 + (tx % 8)];
+        if entry != 0 {
+            line.push(x, Pixel::new(attrs, entry));
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use crate::{GbaVideoOutput, NoopGbaVideoOutput};
+
+use super::{FrameBuffer, LineBuffer, VISIBLE_LINE_COUNT, VISIBLE_LINE_WIDTH};
+
+/// A [`GbaVideoOutput`] that compares every line against the same line of the
+/// previous frame and keeps track of which lines changed. Lines are forwarded to
+/// an inner output as they arrive.
+///
+/// The changed lines are available from [`DirtyLineOutput::dirty_lines`] once
+/// [`GbaVideoOutput::gba_frame_ready`] has been called. Every line of the first
+/// frame is considered dirty.
+pub struct DirtyLineOutput<V: GbaVideoOutput = NoopGbaVideoOutput> {
+    inner: V,
+    previous: Box<[LineBuffer; VISIBLE_LINE_COUNT]>,
+    has_previous: bool,
+
+    /// Lines of the frame being drawn that differ from the previous frame.
+    pending: Vec<usize>,
+    /// Lines of the last completed frame that differ from the frame before it.
+    dirty: Vec<usize>,
+}
+
+impl<V: GbaVideoOutput> DirtyLineOutput<V> {
+    pub fn new(inner: V) -> Self {
+        DirtyLineOutput {
+            inner,
+            previous: Box::new([[0; VISIBLE_LINE_WIDTH]; VISIBLE_LINE_COUNT]),
+            has_previous: false,
+            pending: Vec::with_capacity(VISIBLE_LINE_COUNT),
+            dirty: Vec::with_capacity(VISIBLE_LINE_COUNT),
+        }
+    }
+
+    /// Indices of the lines in the last completed frame that changed, in the
+    /// order that they were drawn.
+    pub fn dirty_lines(&self) -> &[usize] {
+        &self.dirty
+    }
+
+    /// Forgets the previous frame so that every line of the next frame is dirty.
+    pub fn invalidate(&mut self) {
+        self.has_previous = false;
+    }
+
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut V {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> V {
+        self.inner
+    }
+}
+
+impl Default for DirtyLineOutput {
+    fn default() -> Self {
+        Self::new(NoopGbaVideoOutput)
+    }
+}
+
+impl<V: GbaVideoOutput> GbaVideoOutput for DirtyLineOutput<V> {
+    fn gba_line_ready(&mut self, line: usize, data: &LineBuffer) {
+        let previous = &mut self.previous[line];
+        if !self.has_previous || previous != data {
+            previous.copy_from_slice(data);
+            self.pending.push(line);
+        }
+        self.inner.gba_line_ready(line, data);
+    }
+
+    fn gba_frame_ready(&mut self, frame: &FrameBuffer) {
+        std::mem::swap(&mut self.dirty, &mut self.pending);
+        self.pending.clear();
+        self.has_previous = true;
+        self.inner.gba_frame_ready(frame);
+    }
+}
@@ -13,9 +13,11 @@ pub(super) fn render(line: &mut GbaLine, context: RenderContext) {
     //      of some bounds checks ???
     assert!(context.line < 160);
 
+    let mosaic = context.registers.bg_mosaic(2);
+    let source_line = mosaic.source_line(context.line);
     let frame_buffer = Mode3FrameBuffer::new(context.vram);
     for x in 0..VISIBLE_LINE_WIDTH {
-        let pixel = frame_buffer.get_pixel(context.line, x);
+        let pixel = frame_buffer.get_pixel(source_line, mosaic.source_x(x));
         line.push(x, Pixel::new_bitmap(pixel));
     }
 }
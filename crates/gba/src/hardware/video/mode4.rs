@@ -17,9 +17,11 @@ pub(super) fn render(line: &mut GbaLine, context: RenderContext) {
     let frame = context.registers.dispcnt.display_frame_select();
     let frame_buffer = Mode4FrameBuffer::new(context.vram, frame);
     let attrs = PixelAttrs::default();
+    let mosaic = context.registers.bg_mosaic(2);
+    let source_line = mosaic.source_line(context.line);
 
     for x in 0..VISIBLE_LINE_WIDTH {
-        let pixel = frame_buffer.get_pixel(context.line, x);
+        let pixel = frame_buffer.get_pixel(source_line, mosaic.source_x(x));
         if pixel != 0 {
             line.push(x, Pixel::new(attrs, pixel));
         }
@@ -1,6 +1,6 @@
 use pyrite_derive::IoRegister;
 
-use crate::hardware::keypad::RegKeyInput;
+use crate::{hardware::keypad::RegKeyInput, memory::IoRegister};
 
 #[derive(Default)]
 pub struct GbaVideoRegisters {
@@ -9,6 +9,22 @@ pub struct GbaVideoRegisters {
     pub(crate) dispstat: RegDispstat,
     pub(crate) vcount: RegVcount,
     pub(crate) keyinput: RegKeyInput,
+    pub(crate) bg_cnt: [RegBgControl; 4],
+    /// Affine parameters of BG2 and BG3.
+    pub(crate) bg_affine: [AffineBgRegisters; 2],
+    pub(crate) mosaic: RegMosaic,
+}
+
+impl GbaVideoRegisters {
+    /// The mosaic block used by a background, which is [`MosaicBlock::NONE`] if mosaic
+    /// is disabled in its BGxCNT register.
+    pub(crate) fn bg_mosaic(&self, bg: usize) -> MosaicBlock {
+        if self.bg_cnt[bg].mosaic() {
+            self.mosaic.bg_block()
+        } else {
+            MosaicBlock::NONE
+        }
+    }
 }
 
 /// 4000000h - DISPCNT - LCD Control (Read/Write)
@@ -64,8 +80,8 @@ pub struct RegDispcnt {
 #[field(hblank_flag: readonly<bool> = 1)]
 #[field(v_counter_flag: readonly<bool> = 2)]
 #[field(vblank_irq_enable: bool = 3)]
-#[field(hblank_irq_enable: bool = 3)]
-#[field(v_counter_irq_enable: bool = 3)]
+#[field(hblank_irq_enable: bool = 4)]
+#[field(v_counter_irq_enable: bool = 5)]
 #[field(v_count_setting: u16 = 8..=15)]
 pub struct RegDispstat {
     value: u16,
@@ -85,6 +101,200 @@ pub struct RegVcount {
     value: u16,
 }
 
+/// 4000008h - BG0CNT - BG0 Control (R/W) (BG Modes 0,1 only)
+/// 400000Ah - BG1CNT - BG1 Control (R/W) (BG Modes 0,1 only)
+/// 400000Ch - BG2CNT - BG2 Control (R/W) (BG Modes 0,1,2 only)
+/// 400000Eh - BG3CNT - BG3 Control (R/W) (BG Modes 0,2 only)
+///   Bit   Expl.
+///   0-1   BG Priority           (0-3, 0=Highest)
+///   2-3   Character Base Block  (0-3, in units of 16 KBytes) (=BG Tile Data)
+///   4-5   Not used (must be zero) (except in NDS mode: MSBs of char base)
+///   6     Mosaic                (0=Disable, 1=Enable)
+///   7     Colors/Palettes       (0=16/16, 1=256/1)
+///   8-12  Screen Base Block     (0-31, in units of 2 KBytes) (=BG Map Data)
+///   13    BG0/BG1: Not used (except in NDS mode: Ext Palette Slot for BG0/BG1)
+///   13    BG2/BG3: Display Area Overflow (0=Transparent, 1=Wraparound)
+///   14-15 Screen Size (0-3)
+/// Internal Screen Size (dots) and size of BG Map (bytes):
+///   Value  Text Mode      Rotation/Scaling Mode
+///   0      256x256 (2K)   128x128   (256 bytes)
+///   1      512x256 (4K)   256x256   (1K)
+///   2      256x512 (4K)   512x512   (4K)
+///   3      512x512 (8K)   1024x1024 (16K)
+#[derive(IoRegister, Copy, Clone)]
+#[field(priority: u16 = 0..=1)]
+#[field(character_base_block: u16 = 2..=3)]
+#[field(mosaic: bool = 6)]
+#[field(palette_256: bool = 7)]
+#[field(screen_base_block: u16 = 8..=12)]
+#[field(display_area_overflow: bool = 13)]
+#[field(screen_size: u16 = 14..=15)]
+pub struct RegBgControl {
+    value: u16,
+}
+
+/// 4000020h - BG2PA - BG2 Rotation/Scaling Parameter A (alias dx) (W)
+/// 4000022h - BG2PB - BG2 Rotation/Scaling Parameter B (alias dmx) (W)
+/// 4000024h - BG2PC - BG2 Rotation/Scaling Parameter C (alias dy) (W)
+/// 4000026h - BG2PD - BG2 Rotation/Scaling Parameter D (alias dmy) (W)
+/// (and the same for BG3 at 4000030h..4000036h)
+///   Bit   Expl.
+///   0-7   Fractional portion (8 bits)
+///   8-14  Integer portion    (7 bits)
+///   15    Sign               (1 bit)
+#[derive(IoRegister, Copy, Clone)]
+pub struct RegBgAffineParam {
+    value: u16,
+}
+
+impl RegBgAffineParam {
+    /// The parameter as a signed 8.8 fixed point number.
+    pub fn fixed(self) -> i32 {
+        self.value as i16 as i32
+    }
+}
+
+/// 4000028h - BG2X_L - BG2 Reference Point X-Coordinate, lower 16 bit (W)
+/// 400002Ah - BG2X_H - BG2 Reference Point X-Coordinate, upper 12 bit (W)
+/// 400002Ch - BG2Y_L - BG2 Reference Point Y-Coordinate, lower 16 bit (W)
+/// 400002Eh - BG2Y_H - BG2 Reference Point Y-Coordinate, upper 12 bit (W)
+/// (and the same for BG3 at 4000038h..400003Eh)
+///   Bit   Expl.
+///   0-7   Fractional portion (8 bits)
+///   8-26  Integer portion    (19 bits)
+///   27    Sign               (1 bit)
+///   28-31 Not used
+#[derive(IoRegister, Copy, Clone)]
+#[field(point: u32 = 0..=27)]
+pub struct RegBgReference {
+    value: u32,
+}
+
+impl RegBgReference {
+    /// The reference point as a signed 20.8 fixed point number.
+    pub fn fixed(self) -> i32 {
+        ((self.point() << 4) as i32) >> 4
+    }
+}
+
+/// The rotation/scaling registers of BG2 or BG3.
+#[derive(Default, Copy, Clone)]
+pub struct AffineBgRegisters {
+    pub(crate) pa: RegBgAffineParam,
+    pub(crate) pb: RegBgAffineParam,
+    pub(crate) pc: RegBgAffineParam,
+    pub(crate) pd: RegBgAffineParam,
+    pub(crate) x: RegBgReference,
+    pub(crate) y: RegBgReference,
+
+    /// The reference point of the current scanline. These are copied from the
+    /// reference point registers when those are written and at the start of every
+    /// VBlank, and are incremented by PB and PD after every scanline.
+    pub(crate) internal_x: i32,
+    pub(crate) internal_y: i32,
+}
+
+impl AffineBgRegisters {
+    pub(crate) fn write_x_lo(&mut self, value: u16) {
+        self.x.write16_lo(value);
+        self.internal_x = self.x.fixed();
+    }
+
+    pub(crate) fn write_x_hi(&mut self, value: u16) {
+        self.x.write16_hi(value);
+        self.internal_x = self.x.fixed();
+    }
+
+    pub(crate) fn write_y_lo(&mut self, value: u16) {
+        self.y.write16_lo(value);
+        self.internal_y = self.y.fixed();
+    }
+
+    pub(crate) fn write_y_hi(&mut self, value: u16) {
+        self.y.write16_hi(value);
+        self.internal_y = self.y.fixed();
+    }
+
+    /// Reloads the internal reference point from the reference point registers.
+    pub(crate) fn latch_reference_point(&mut self) {
+        self.internal_x = self.x.fixed();
+        self.internal_y = self.y.fixed();
+    }
+
+    /// Moves the internal reference point to the start of the next scanline.
+    pub(crate) fn next_line(&mut self) {
+        self.internal_x = self.internal_x.wrapping_add(self.pb.fixed());
+        self.internal_y = self.internal_y.wrapping_add(self.pd.fixed());
+    }
+}
+
+/// 400004Ch - MOSAIC - Mosaic Size (W)
+/// The Mosaic function can be separately enabled/disabled for BG0-BG3 by BG0CNT-BG3CNT
+/// Registers, as well as for each OBJ0-127 by OBJ attributes in OAM memory. Also,
+/// setting all of the bits below to zero effectively disables the mosaic function.
+///   Bit   Expl.
+///   0-3   BG Mosaic H-Size  (minus 1)
+///   4-7   BG Mosaic V-Size  (minus 1)
+///   8-11  OBJ Mosaic H-Size (minus 1)
+///   12-15 OBJ Mosaic V-Size (minus 1)
+///   16-31 Not used
+/// Example: When setting H-Size to 5, then pixels 0-5 of each display row are colorized
+/// as pixel 0, pixels 6-11 as pixel 6, pixels 12-17 as pixel 12, and so on.
+#[derive(IoRegister, Copy, Clone)]
+#[field(bg_h_size: u16 = 0..=3)]
+#[field(bg_v_size: u16 = 4..=7)]
+#[field(obj_h_size: u16 = 8..=11)]
+#[field(obj_v_size: u16 = 12..=15)]
+pub struct RegMosaic {
+    value: u16,
+}
+
+impl RegMosaic {
+    /// The width and height in pixels of a background mosaic block.
+    pub fn bg_block(self) -> MosaicBlock {
+        MosaicBlock {
+            width: self.bg_h_size() as usize + 1,
+            height: self.bg_v_size() as usize + 1,
+        }
+    }
+
+    /// The width and height in pixels of an object mosaic block.
+    pub fn obj_block(self) -> MosaicBlock {
+        MosaicBlock {
+            width: self.obj_h_size() as usize + 1,
+            height: self.obj_v_size() as usize + 1,
+        }
+    }
+}
+
+/// The size of a mosaic block. Every pixel in a block is replaced by the pixel in the
+/// top left corner of the block.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct MosaicBlock {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl MosaicBlock {
+    /// A block that leaves pixels unchanged.
+    pub const NONE: MosaicBlock = MosaicBlock {
+        width: 1,
+        height: 1,
+    };
+
+    /// The x coordinate that a pixel at `x` is sampled from.
+    #[inline]
+    pub fn source_x(self, x: usize) -> usize {
+        x - (x % self.width)
+    }
+
+    /// The line that a pixel on `line` is sampled from.
+    #[inline]
+    pub fn source_line(self, line: u16) -> u16 {
+        line - (line % self.height as u16)
+    }
+}
+
 /// 4000002h - Undocumented - Green Swap (R/W)
 /// Normally, red green blue intensities for a group of two pixels is output as BGRbgr
 /// (uppercase for left pixel at even xloc, lowercase for right pixel at odd xloc).
@@ -0,0 +1,81 @@
+use crate::memory::GAMEPAK_HEADER_SIZE;
+
+/// The compressed Nintendo logo that the BIOS expects at 0x04-0x9F of every
+/// cartridge header. The BIOS refuses to boot cartridges where it doesn't match.
+pub const NINTENDO_LOGO: [u8; 156] = [
+    0x24, 0xFF, 0xAE, 0x51, 0x69, 0x9A, 0xA2, 0x21, 0x3D, 0x84, 0x82, 0x0A, 0x84, 0xE4, 0x09, 0xAD,
+    0x11, 0x24, 0x8B, 0x98, 0xC0, 0x81, 0x7F, 0x21, 0xA3, 0x52, 0xBE, 0x19, 0x93, 0x09, 0xCE, 0x20,
+    0x10, 0x46, 0x4A, 0x4A, 0xF8, 0x27, 0x31, 0xEC, 0x58, 0xC7, 0xE8, 0x33, 0x82, 0xE3, 0xCE, 0xBF,
+    0x85, 0xF4, 0xDF, 0x94, 0xCE, 0x4B, 0x09, 0xC1, 0x94, 0x56, 0x8A, 0xC0, 0x13, 0x72, 0xA7, 0xFC,
+    0x9F, 0x84, 0x4D, 0x73, 0xA3, 0xCA, 0x9A, 0x61, 0x58, 0x97, 0xA3, 0x27, 0xFC, 0x03, 0x98, 0x76,
+    0x23, 0x1D, 0xC7, 0x61, 0x03, 0x04, 0xAE, 0x56, 0xBF, 0x38, 0x84, 0x00, 0x40, 0xA7, 0x0E, 0xFD,
+    0xFF, 0x52, 0xFE, 0x03, 0x6F, 0x95, 0x30, 0xF1, 0x97, 0xFB, 0xC0, 0x85, 0x60, 0xD6, 0x80, 0x25,
+    0xA9, 0x63, 0xBE, 0x03, 0x01, 0x4E, 0x38, 0xE2, 0xF9, 0xA2, 0x34, 0xFF, 0xBB, 0x3E, 0x03, 0x44,
+    0x78, 0x00, 0x90, 0xCB, 0x88, 0x11, 0x3A, 0x94, 0x65, 0xC0, 0x7C, 0x63, 0x87, 0xF0, 0x3C, 0xAF,
+    0xD6, 0x25, 0xE4, 0x8B, 0x38, 0x0A, 0xAC, 0x72, 0x21, 0xD4, 0xF8, 0x07,
+];
+
+const LOGO_START: usize = 0x04;
+const TITLE_START: usize = 0xA0;
+const GAME_CODE_START: usize = 0xAC;
+const MAKER_CODE_START: usize = 0xB0;
+
+/// The 192 byte header at the start of every GBA cartridge.
+///
+/// ```ignore
+///   Address Bytes Expl.
+///   000h    4     ROM Entry Point  (32bit ARM branch opcode, eg. "B rom_start")
+///   004h    156   Nintendo Logo    (compressed bitmap, required!)
+///   0A0h    12    Game Title       (uppercase ascii, max 12 characters)
+///   0ACh    4     Game Code        (uppercase ascii, 4 characters)
+///   0B0h    2     Maker Code       (uppercase ascii, 2 characters)
+///   0B2h    1     Fixed value      (must be 96h, required!)
+///   0B3h    1     Main unit code   (00h for current GBA models)
+///   0B4h    1     Device type      (usually 00h)
+///   0B5h    7     Reserved Area    (should be zero filled)
+///   0BCh    1     Software version (usually 00h)
+///   0BDh    1     Complement check (header checksum, required!)
+///   0BEh    2     Reserved Area    (should be zero filled)
+/// ```
+#[derive(Clone)]
+pub struct CartridgeHeader {
+    bytes: [u8; GAMEPAK_HEADER_SIZE],
+}
+
+impl CartridgeHeader {
+    /// Reads the header from the start of a ROM image. Returns `None` if the image
+    /// is too small to contain a header.
+    pub fn parse(rom: &[u8]) -> Option<Self> {
+        Some(CartridgeHeader {
+            bytes: rom.get(..GAMEPAK_HEADER_SIZE)?.try_into().unwrap(),
+        })
+    }
+
+    pub fn logo(&self) -> &[u8] {
+        &self.bytes[LOGO_START..LOGO_START + NINTENDO_LOGO.len()]
+    }
+
+    /// Returns true if the header contains the Nintendo logo. Dumps where it doesn't
+    /// are corrupt or are homebrew that was never fixed up for real hardware.
+    pub fn logo_valid(&self) -> bool {
+        self.logo() == NINTENDO_LOGO
+    }
+
+    /// The game title with any trailing padding removed.
+    pub fn title(&self) -> String {
+        ascii_field(&self.bytes[TITLE_START..GAME_CODE_START])
+    }
+
+    pub fn game_code(&self) -> String {
+        ascii_field(&self.bytes[GAME_CODE_START..MAKER_CODE_START])
+    }
+
+    pub fn maker_code(&self) -> String {
+        ascii_field(&self.bytes[MAKER_CODE_START..MAKER_CODE_START + 2])
+    }
+}
+
+fn ascii_field(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).trim_end().to_owned()
+}
@@ -0,0 +1,80 @@
+//! Detection of loops that can't make progress until the next event, such as
+//! `b .` or a loop that polls VCOUNT. Everything outside of the CPU only changes
+//! when an event fires, so once an iteration of a loop ends in exactly the same
+//! state that it started in without having written to memory or an event having
+//! fired, every iteration until the next event will do the same and they can be
+//! skipped.
+
+use arm::emu::Cpu;
+
+/// The largest distance in bytes of a backwards branch that is considered to be
+/// the end of a loop.
+const IDLE_LOOP_MAX_SIZE: u32 = 64;
+
+#[derive(Default)]
+pub(crate) struct IdleLoopDetector {
+    enabled: bool,
+    /// The state of the CPU the last time that it branched back to the start of a
+    /// loop.
+    last_iteration: Option<LoopIteration>,
+}
+
+#[derive(PartialEq, Eq)]
+struct LoopIteration {
+    /// r0-r15 and the CPSR.
+    registers: [u32; 17],
+    /// See [`crate::GbaMemoryMappedHardware::side_effects`].
+    side_effects: u64,
+    /// The number of events that had fired. An iteration that an event fired in
+    /// might have read a value from before the event and would exit on the next one.
+    events: u64,
+}
+
+impl IdleLoopDetector {
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.last_iteration = None;
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Called after every step with the address of the instruction that was
+    /// executed and the number of events fired so far. Returns true if the CPU just
+    /// finished an iteration of an idle loop.
+    pub(crate) fn check(
+        &mut self,
+        address: u32,
+        cpu: &Cpu,
+        side_effects: u64,
+        events: u64,
+    ) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        let next = cpu.next_execution_address();
+        if next > address || address - next > IDLE_LOOP_MAX_SIZE {
+            return false;
+        }
+
+        let mut registers = [0; 17];
+        for (register, value) in registers[..16].iter_mut().enumerate() {
+            *value = cpu.registers.read(register as u32);
+        }
+        registers[16] = cpu.registers.read_cpsr();
+        let iteration = LoopIteration {
+            registers,
+            side_effects,
+            events,
+        };
+
+        if self.last_iteration.as_ref() == Some(&iteration) {
+            true
+        } else {
+            self.last_iteration = Some(iteration);
+            false
+        }
+    }
+}
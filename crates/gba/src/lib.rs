@@ -1,18 +1,38 @@
+pub mod bios;
 mod events;
 mod hardware;
+pub mod header;
+mod idle_loop;
 pub mod memory;
 
-use arm::emu::{Cpu, CpuMode, Cycles, InstructionSet};
+use std::collections::VecDeque;
+
+use arm::emu::{CpsrFlag, Cpu, CpuException, CpuMode, Cycles, InstructionSet};
+use bios::BiosMode;
 use events::{GbaEvent, SharedGbaScheduler};
-pub use hardware::{keypad, video, GbaMemoryMappedHardware};
+pub use hardware::{
+    backup, gpio, keypad, palette, serial, system_control::AccuracyMode, video, BiosError,
+    GamepakError, GbaMemoryMappedHardware,
+};
 use hardware::{keypad::Keypad, video::HBlankContext, CUSTOM_BIOS};
+use idle_loop::IdleLoopDetector;
 
 pub const NOP_ROM: [u8; 4] = [0xFE, 0xFF, 0xFF, 0xEA];
 
+/// Called with the ID of a user event when it fires. See [`Gba::schedule_user_event`].
+pub type UserEventHandler =
+    Box<dyn Send + Sync + FnMut(&mut Cpu, &mut GbaMemoryMappedHardware, u32)>;
+
 pub struct Gba {
     pub cpu: Cpu,
     pub mapped: GbaMemoryMappedHardware,
     scheduler: SharedGbaScheduler,
+    user_event_handler: Option<UserEventHandler>,
+    /// Button changes that haven't been applied yet, ordered by frame.
+    input_script: VecDeque<(u64, keypad::Buttons)>,
+    idle_loops: IdleLoopDetector,
+    /// The number of events that have fired, used to detect idle loops.
+    events_fired: u64,
 }
 
 impl Gba {
@@ -23,59 +43,541 @@ impl Gba {
         assert!(CUSTOM_BIOS.len() <= memory::BIOS_SIZE);
         mmh.bios[..CUSTOM_BIOS.len()].copy_from_slice(CUSTOM_BIOS);
 
-        let cpu = Cpu::new(InstructionSet::Arm, CpuMode::System, &mut mmh);
+        let mut cpu = Cpu::new(InstructionSet::Arm, CpuMode::System, &mut mmh);
+        cpu.set_exception_handler(bios::exception_handler);
         Self {
             cpu,
             mapped: mmh,
             scheduler,
+            user_event_handler: None,
+            input_script: VecDeque::new(),
+            idle_loops: IdleLoopDetector::default(),
+            events_fired: 0,
         }
     }
 
     /// Hard reset.
     pub fn reset(&mut self) {
-        self.cpu.branch(0, &mut self.mapped);
+        match self.mapped.bios_mode {
+            BiosMode::Real => {
+                self.cpu.branch(0, &mut self.mapped);
+            }
+            BiosMode::Hle => bios::hle_boot(&mut self.cpu, &mut self.mapped),
+        }
         self.scheduler.clear();
         self.mapped.reset();
     }
 
-    pub fn step(&mut self, video_out: &mut dyn GbaVideoOutput, audio_out: &mut dyn GbaAudioOutput) {
+    /// Steps the CPU forward by one instruction and handles any events that occur
+    /// in the meantime. Nothing happens while the CPU is halted.
+    pub fn step(
+        &mut self,
+        video_out: &mut dyn GbaVideoOutput,
+        audio_out: &mut dyn GbaAudioOutput,
+    ) -> GbaStepResult {
+        self.step_with_cycles(video_out, audio_out).0
+    }
+
+    /// Same as [`Gba::step`] but also reports the instruction that was executed, see
+    /// [`InstructionStep`]. Returns `None` without doing anything if the CPU is halted.
+    #[cfg(feature = "arm-disassembler")]
+    pub fn step_instruction(
+        &mut self,
+        video_out: &mut dyn GbaVideoOutput,
+        audio_out: &mut dyn GbaAudioOutput,
+    ) -> Option<InstructionStep> {
+        use arm::disasm::{arm as disasm_arm, thumb as disasm_thumb, MemoryView as _};
+
+        if self.cpu.is_halted() {
+            return None;
+        }
+
+        // Only one half of a THUMB bl pair executes per step so the halves are
+        // decoded separately.
+        let address = self.cpu.next_execution_address();
+        let instr = if self.cpu.registers.get_flag(CpsrFlag::T) {
+            disasm_thumb::disasm(self.mapped.view16(address), address).into()
+        } else {
+            disasm_arm::disasm(self.mapped.view32(address), address).into()
+        };
+
+        self.cpu.take_exception();
+        let (result, cycles) = self.step_with_cycles(video_out, audio_out);
+        Some(InstructionStep {
+            address,
+            instr,
+            cycles,
+            exception: self.cpu.take_exception(),
+            result,
+        })
+    }
+
+    /// Steps the GBA until the current frame is complete and returns it. This doesn't
+    /// need a [`GbaVideoOutput`]. If the CPU halts before the end of the frame the last
+    /// completed frame is returned instead.
+    pub fn run_frame(&mut self, audio_out: &mut dyn GbaAudioOutput) -> &video::FrameBuffer {
+        let frame = self.frame_count();
+        while self.frame_count() == frame {
+            if self.step(&mut NoopGbaVideoOutput, audio_out) == GbaStepResult::Halted {
+                break;
+            }
+        }
+        self.frame_buffer()
+    }
+
+    /// Steps the GBA until the next VBlank period begins at the start of scanline
+    /// 160. If the GBA is already in VBlank this runs until the next one. Returns
+    /// [`GbaStepResult::Halted`] if the CPU halts first.
+    pub fn run_to_vblank(
+        &mut self,
+        video_out: &mut dyn GbaVideoOutput,
+        audio_out: &mut dyn GbaAudioOutput,
+    ) -> GbaStepResult {
+        while self.in_vblank() {
+            if self.step(video_out, audio_out) == GbaStepResult::Halted {
+                return GbaStepResult::Halted;
+            }
+        }
+        while !self.in_vblank() {
+            if self.step(video_out, audio_out) == GbaStepResult::Halted {
+                return GbaStepResult::Halted;
+            }
+        }
+        GbaStepResult::Running
+    }
+
+    /// Steps the CPU and returns the number of cycles that the step took.
+    fn step_with_cycles(
+        &mut self,
+        video_out: &mut dyn GbaVideoOutput,
+        audio_out: &mut dyn GbaAudioOutput,
+    ) -> (GbaStepResult, Cycles) {
         let _unused = audio_out;
 
-        let mut cycles = self.cpu.step(&mut self.mapped);
+        if self.cpu.is_halted() {
+            return (GbaStepResult::Halted, Cycles::zero());
+        }
+
+        let address = self.cpu.next_execution_address();
+        let mut cycles = {
+            #[cfg(feature = "puffin")]
+            puffin::profile_scope!("cpu_step");
+
+            if self.mapped.system_control.irq_pending() && !self.cpu.registers.get_flag(CpsrFlag::I)
+            {
+                self.cpu.exception(CpuException::Irq, &mut self.mapped)
+            } else {
+                self.cpu.step(&mut self.mapped)
+            }
+        };
+        self.mapped.step_prefetch(cycles);
+        let mut step_cycles = cycles;
+
+        {
+            #[cfg(feature = "puffin")]
+            puffin::profile_scope!("scheduler_tick");
+
+            while let Some(event) = self.scheduler.tick(&mut cycles) {
+                self.handle_event(event, cycles, video_out);
+            }
+        }
+
+        if self.idle_loops.check(
+            address,
+            &self.cpu,
+            self.mapped.side_effects,
+            self.events_fired,
+        ) {
+            step_cycles += self.skip_to_next_event(video_out);
+        }
+
+        self.apply_input_script();
+
+        let result = if self.cpu.is_halted() {
+            GbaStepResult::Halted
+        } else {
+            GbaStepResult::Running
+        };
+        (result, step_cycles)
+    }
+
+    /// Fires the next pending event without running the CPU and returns the number
+    /// of cycles that were skipped. Used when the CPU is in an idle loop.
+    fn skip_to_next_event(&mut self, video_out: &mut dyn GbaVideoOutput) -> Cycles {
+        let Some(skipped) = self.scheduler.cycles_until_next() else {
+            return Cycles::zero();
+        };
+        self.mapped.step_prefetch(skipped);
+        let mut cycles = skipped;
         while let Some(event) = self.scheduler.tick(&mut cycles) {
             self.handle_event(event, cycles, video_out);
         }
+        skipped
+    }
+
+    /// Applies the entries of the input script whose frame has been reached.
+    fn apply_input_script(&mut self) {
+        while let Some(&(frame, buttons)) = self.input_script.front() {
+            if frame > self.frame_count() {
+                break;
+            }
+            self.input_script.pop_front();
+            self.set_buttons(buttons);
+        }
     }
 
     fn handle_event(&mut self, event: GbaEvent, _late: Cycles, video_out: &mut dyn GbaVideoOutput) {
+        self.events_fired = self.events_fired.wrapping_add(1);
         match event {
-            GbaEvent::HDraw => self.mapped.video.begin_hdraw(),
+            GbaEvent::HDraw => {
+                let interrupts = self.mapped.video.begin_hdraw();
+                self.mapped.system_control.request_interrupts(interrupts);
+            }
             GbaEvent::HBlank => {
                 let context = HBlankContext {
                     palette: &self.mapped.palram,
                     vram: &self.mapped.vram,
                 };
-                self.mapped.video.begin_hblank(video_out, context);
+                let interrupts = self.mapped.video.begin_hblank(video_out, context);
+                self.mapped.system_control.request_interrupts(interrupts);
+            }
+            GbaEvent::User(id) => {
+                if let Some(handler) = self.user_event_handler.as_mut() {
+                    handler(&mut self.cpu, &mut self.mapped, id);
+                }
+            }
+        }
+    }
+
+    /// Schedules a user event with the given ID to fire once `after` more cycles have
+    /// passed. The event is delivered to the handler set with
+    /// [`Gba::set_user_event_handler`] during the [`Gba::step`] in which it fires.
+    /// Pending user events are dropped by [`Gba::reset`].
+    pub fn schedule_user_event(&mut self, after: Cycles, id: u32) {
+        self.scheduler.schedule(GbaEvent::User(id), after);
+    }
+
+    /// Sets the handler that user events are delivered to and returns the previous one.
+    /// User events that fire while there is no handler are ignored.
+    pub fn set_user_event_handler<F>(&mut self, handler: F) -> Option<UserEventHandler>
+    where
+        F: 'static + Send + Sync + FnMut(&mut Cpu, &mut GbaMemoryMappedHardware, u32),
+    {
+        self.user_event_handler.replace(Box::new(handler))
+    }
+
+    /// Sets the handler that is called with the new state of the gamepak's rumble
+    /// motor whenever the game turns it on or off through the GPIO port, and returns
+    /// the previous one.
+    pub fn set_rumble_handler<F>(&mut self, handler: F) -> Option<gpio::RumbleHandler>
+    where
+        F: 'static + Send + Sync + FnMut(bool),
+    {
+        self.mapped.gpio.set_rumble_handler(Some(Box::new(handler)))
+    }
+
+    /// Connects something to the other end of the link cable, or disconnects it with
+    /// `None`, and returns the previous peer. See [`serial::Serial`].
+    pub fn set_link_cable_peer(
+        &mut self,
+        peer: Option<Box<dyn serial::LinkCablePeer>>,
+    ) -> Option<Box<dyn serial::LinkCablePeer>> {
+        self.mapped.serial.set_peer(peer)
+    }
+
+    /// Lists the events that are waiting to fire in the order that they will fire, along
+    /// with the number of cycles until each one fires.
+    pub fn pending_events(&self) -> Vec<(Cycles, &'static str)> {
+        self.scheduler
+            .pending()
+            .into_iter()
+            .map(|(cycles, event)| (cycles, event.name()))
+            .collect()
+    }
+
+    /// The number of events that are waiting to fire.
+    pub fn pending_event_count(&self) -> usize {
+        self.scheduler.len()
+    }
+
+    /// The number of cycles until the next pending event fires, or zero if there
+    /// are no pending events.
+    pub fn cycles_until_next_event(&self) -> Cycles {
+        self.scheduler
+            .cycles_until_next()
+            .unwrap_or_else(Cycles::zero)
+    }
+
+    /// Steps the GBA until the next pending event has fired and returns the number
+    /// of cycles that were run. Instructions can't be split so this may run past the
+    /// event by part of an instruction, any extra cycles are already accounted for by
+    /// the events scheduled after it. Stops early if the CPU halts.
+    pub fn advance_to_next_event(
+        &mut self,
+        video_out: &mut dyn GbaVideoOutput,
+        audio_out: &mut dyn GbaAudioOutput,
+    ) -> Cycles {
+        let target = self.cycles_until_next_event();
+        let mut elapsed = Cycles::zero();
+        while elapsed < target || elapsed.is_zero() {
+            let (result, cycles) = self.step_with_cycles(video_out, audio_out);
+            elapsed += cycles;
+            if result == GbaStepResult::Halted {
+                break;
             }
-            GbaEvent::Test => unreachable!(),
         }
+        elapsed
+    }
+
+    /// Writes an objdump style listing of the `len` bytes starting at `start` to `out`,
+    /// one instruction per line with its address, opcode, disassembly, and comment.
+    /// Memory is read without side effects so unmapped regions read as zero. In THUMB
+    /// both halves of a `bl` are listed together if they are both in the range. The
+    /// listing stops at the end of the address space. Memory is read in chunks so
+    /// that large ranges don't have to be copied all at once.
+    #[cfg(feature = "arm-disassembler")]
+    pub fn write_disassembly(
+        &self,
+        out: &mut dyn std::io::Write,
+        start: u32,
+        len: u32,
+        isa: arm::disasm::InstructionSet,
+    ) -> std::io::Result<()> {
+        use arm::disasm::{Disassembler, InstructionSet as Isa, MemoryView as _};
+
+        /// The number of bytes that are disassembled at a time.
+        const CHUNK_SIZE: u64 = 0x1000;
+
+        let memory = &self.mapped;
+        let width = match isa {
+            Isa::Arm => 4,
+            Isa::Thumb => 2,
+        };
+        let end = (u64::from(start) + u64::from(len)).min(1 << 32);
+        let aligned_start = start & !(width - 1);
+        // Whole instructions are listed even if only part of the last one is in range.
+        let size = (end - u64::from(aligned_start)).next_multiple_of(u64::from(width));
+        let range_end = u64::from(aligned_start) + size;
+
+        let mut chunk_start = u64::from(aligned_start);
+        while chunk_start < range_end {
+            let chunk_end = (chunk_start + CHUNK_SIZE).min(range_end);
+            // One more instruction is read so that a THUMB `bl` pair that starts at
+            // the end of the chunk is listed whole. The next chunk starts after it.
+            let read_end = (chunk_end + u64::from(width)).min(range_end);
+            let bytes = memory.read_range(chunk_start as u32, (read_end - chunk_start) as usize);
+
+            let mut next_chunk_start = chunk_end;
+            let mut instructions = Disassembler::new(isa, &bytes, chunk_start as u32).peekable();
+            while let Some((addr, instr)) = instructions.next() {
+                if u64::from(addr) >= chunk_end {
+                    break;
+                }
+                let offset = (u64::from(addr) - chunk_start) as usize;
+                let instr_end = instructions
+                    .peek()
+                    .map(|&(next, _)| (u64::from(next) - chunk_start) as usize)
+                    .unwrap_or(bytes.len());
+                next_chunk_start = chunk_start + instr_end as u64;
+
+                let opcode = bytes[offset..instr_end]
+                    .chunks(width as usize)
+                    .map(|unit| match isa {
+                        Isa::Arm => format!("{:08x}", u32::from_le_bytes(unit.try_into().unwrap())),
+                        Isa::Thumb => {
+                            format!("{:04x}", u16::from_le_bytes(unit.try_into().unwrap()))
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                writeln!(
+                    out,
+                    "{addr:08x}:  {opcode:<9}  {}",
+                    instr.full(addr, Some(memory))
+                )?;
+            }
+            chunk_start = next_chunk_start;
+        }
+        Ok(())
+    }
+
+    /// Moves execution to `address` the same way that `bx` does: an odd address
+    /// switches to THUMB and an even one switches to ARM. The address is aligned
+    /// for the new instruction set and the pipeline is refilled from it.
+    pub fn set_pc(&mut self, address: u32) {
+        self.cpu.registers.put_flag(CpsrFlag::T, address & 0x1 != 0);
+        self.cpu.branch(address, &mut self.mapped);
     }
 
     pub fn set_gamepak(&mut self, gamepak: Vec<u8>) {
         self.mapped.set_gamepak(gamepak);
     }
 
+    /// Replaces the gamepak, rejecting images that are too small or too large
+    /// to be a GBA gamepak. See [`GamepakError`].
+    pub fn try_set_gamepak(&mut self, gamepak: Vec<u8>) -> Result<(), GamepakError> {
+        self.mapped.try_set_gamepak(gamepak)
+    }
+
+    /// Replaces the save memory with an erased one of the given type, overriding
+    /// the type detected when the gamepak was loaded. This is needed for gamepaks
+    /// without an ID string, e.g. homebrew. Loading another gamepak detects the
+    /// save type again.
+    pub fn set_save_type(&mut self, save_type: backup::SaveType) {
+        self.mapped.backup.set_save_type(save_type);
+    }
+
+    /// The type of the gamepak's save memory.
+    pub fn save_type(&self) -> backup::SaveType {
+        self.mapped.backup.save_type()
+    }
+
     pub fn set_noop_gamepak(&mut self) {
         self.mapped.set_gamepak(NOP_ROM.to_vec());
     }
 
+    /// Replaces the BIOS with the given image (e.g. a dump of the real GBA BIOS),
+    /// rejecting images that are larger than the BIOS region. The image is copied
+    /// into memory immediately, call [`Gba::reset`] afterwards to boot from it.
+    pub fn set_bios(&mut self, bios: Vec<u8>) -> Result<(), BiosError> {
+        self.mapped.set_bios(bios)
+    }
+
+    /// Selects how BIOS calls are serviced. This takes effect for the boot
+    /// sequence after the next [`Gba::reset`] and immediately for SWIs.
+    pub fn set_bios_mode(&mut self, mode: BiosMode) {
+        self.mapped.bios_mode = mode;
+    }
+
+    /// Turns the BIOS read protection on or off. With protection on (the default)
+    /// reads from the BIOS by code running outside of it return the last word that
+    /// the BIOS read instead of the BIOS's contents, like real hardware. Turning it
+    /// off is mostly useful for debugging.
+    pub fn set_bios_read_protection(&mut self, enabled: bool) {
+        self.mapped.bios_read_protection = enabled;
+    }
+
+    /// Starts or stops counting memory accesses per region. Enabling this clears the
+    /// counts and disabling it throws them away. There is no overhead beyond a check
+    /// per access while it is disabled.
+    pub fn set_memory_stats_enabled(&mut self, enabled: bool) {
+        self.mapped.memory_stats = enabled.then(Box::default);
+    }
+
+    /// The memory access counts since they were enabled, or `None` if they aren't.
+    pub fn memory_stats(&self) -> Option<&memory::MemoryStats> {
+        self.mapped.memory_stats.as_deref()
+    }
+
+    /// Sets the memory access counts back to zero if they are enabled.
+    pub fn clear_memory_stats(&mut self) {
+        if let Some(stats) = self.mapped.memory_stats.as_deref_mut() {
+            stats.clear();
+        }
+    }
+
+    /// Selects what EWRAM and VRAM contain after the next [`Gba::reset`].
+    pub fn set_uninitialized_fill(&mut self, fill: memory::Fill) {
+        self.mapped.uninitialized_fill = fill;
+    }
+
+    pub fn bios_mode(&self) -> BiosMode {
+        self.mapped.bios_mode
+    }
+
+    /// Turns idle loop skipping on or off. While it is on, a short loop that ends an
+    /// iteration in the same state that it started in without writing to memory
+    /// (e.g. `b .` or a loop polling VCOUNT) skips straight to the next event
+    /// instead of running until it fires. This is off by default.
+    pub fn set_idle_loop_skipping(&mut self, enabled: bool) {
+        self.idle_loops.set_enabled(enabled);
+    }
+
+    pub fn idle_loop_skipping(&self) -> bool {
+        self.idle_loops.enabled()
+    }
+
+    /// Selects how closely memory access timings are emulated. This can be
+    /// changed at any time and persists across resets.
+    pub fn set_accuracy_mode(&mut self, mode: AccuracyMode) {
+        self.mapped.system_control.set_accuracy(mode);
+        self.mapped.prefetch.flush();
+    }
+
+    pub fn accuracy_mode(&self) -> AccuracyMode {
+        self.mapped.system_control.accuracy()
+    }
+
     pub fn frame_count(&self) -> u64 {
         self.mapped.video.frame
     }
 
+    /// The scanline that is currently being drawn (VCOUNT). Lines 160 to 227
+    /// are in VBlank.
+    pub fn current_scanline(&self) -> u16 {
+        self.mapped.video.current_scanline()
+    }
+
+    /// Returns true if the current scanline is one of the lines after the visible
+    /// part of the frame.
+    pub fn in_vblank(&self) -> bool {
+        self.mapped.video.in_vblank()
+    }
+
+    /// Returns true if the current scanline has finished drawing its visible pixels.
+    pub fn in_hblank(&self) -> bool {
+        self.mapped.video.in_hblank()
+    }
+
+    /// The last completed frame.
+    pub fn frame_buffer(&self) -> &video::FrameBuffer {
+        &self.mapped.video.frame_buffer
+    }
+
+    /// Hides or shows a video layer for debugging. DISPCNT is not modified, so
+    /// the game can't tell that a layer is hidden.
+    pub fn set_layer_enabled(&mut self, layer: video::Layer, enabled: bool) {
+        self.mapped.video.set_layer_enabled(layer, enabled);
+    }
+
+    /// Returns false if the layer was hidden with [`Gba::set_layer_enabled`].
+    pub fn layer_enabled(&self, layer: video::Layer) -> bool {
+        self.mapped.video.layer_enabled(layer)
+    }
+
+    /// Skips drawing visible lines while `suppressed` is true. Frames still end on
+    /// time but no lines are sent to the [`GbaVideoOutput`] and the frame buffer keeps
+    /// the last frame that was drawn. This is meant for frame skipping.
+    pub fn set_rendering_suppressed(&mut self, suppressed: bool) {
+        self.mapped.video.set_rendering_suppressed(suppressed);
+    }
+
+    pub fn rendering_suppressed(&self) -> bool {
+        self.mapped.video.rendering_suppressed()
+    }
+
     pub fn keypad_mut(&mut self) -> &mut Keypad {
         &mut self.mapped.keypad
     }
+
+    /// Describes every emulated I/O register with its current value and fields.
+    pub fn io_registers(&self) -> Vec<memory::IoRegDescription> {
+        self.mapped.io_registers()
+    }
+
+    /// Presses every button in `buttons` and releases the rest.
+    pub fn set_buttons(&mut self, buttons: keypad::Buttons) {
+        self.mapped.keypad.keyinput.set_buttons(buttons);
+    }
+
+    /// Replaces the input script with a list of frame numbers and the buttons that
+    /// are held from the start of that frame on, i.e. from the step in which
+    /// [`Gba::frame_count`] reaches it. Entries for frames that have already started
+    /// are applied in order on the next step.
+    pub fn set_input_script(&mut self, mut script: Vec<(u64, keypad::Buttons)>) {
+        script.sort_by_key(|&(frame, _)| frame);
+        self.input_script = script.into();
+    }
 }
 
 impl Default for Gba {
@@ -88,10 +590,47 @@ impl Default for Gba {
 unsafe impl Send for Gba {}
 unsafe impl Sync for Gba {}
 
+/// The state of the GBA after a call to [`Gba::step`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GbaStepResult {
+    Running,
+
+    /// An exception handler returned [`arm::emu::ExceptionHandlerResult::Halt`].
+    /// Call [`arm::emu::Cpu::resume`] to continue execution.
+    Halted,
+}
+
+/// An instruction executed by [`Gba::step_instruction`].
+#[cfg(feature = "arm-disassembler")]
+#[derive(Debug)]
+pub struct InstructionStep {
+    /// The address of the instruction.
+    pub address: u32,
+
+    /// The instruction at `address`. If an IRQ was taken instead of executing it,
+    /// this is the instruction that the IRQ handler will return to.
+    pub instr: arm::disasm::AnyInstr,
+
+    /// The cycles taken by the instruction, including any exception it raised.
+    pub cycles: Cycles,
+
+    /// The exception that was raised during the step, if any.
+    pub exception: Option<CpuException>,
+
+    /// The state of the GBA after the step.
+    pub result: GbaStepResult,
+}
+
 pub struct NoopGbaAudioOutput;
 
 pub trait GbaVideoOutput {
     fn gba_line_ready(&mut self, line: usize, data: &video::LineBuffer);
+
+    /// Called after the last visible line of a frame has been passed to
+    /// [`GbaVideoOutput::gba_line_ready`].
+    fn gba_frame_ready(&mut self, frame: &video::FrameBuffer) {
+        let _unused = frame;
+    }
 }
 
 pub struct NoopGbaVideoOutput;
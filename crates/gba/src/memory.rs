@@ -1,27 +1,49 @@
 mod io_registers;
 
+use std::ops::Range;
+
 #[cfg(feature = "arm-disassembler")]
 use arm::disasm::MemoryView;
-use arm::emu::{AccessType, Cpu, Memory, Waitstates};
+use arm::emu::{AccessType, CpsrFlag, Cpu, Cycles, Memory, Waitstates};
 use byteorder::{ByteOrder, LittleEndian};
-use util::bits::BitOps;
+use util::{bits::BitOps, wyhash::WyHash};
 
-use crate::hardware::GbaMemoryMappedHardware;
+use crate::{hardware::GbaMemoryMappedHardware, AccuracyMode};
 
 impl GbaMemoryMappedHardware {
+    /// Converts an address in any of the three gamepak regions into an offset into
+    /// the gamepak. Each region is a mirror of the same gamepak and gamepaks that are
+    /// smaller than a region are mirrored within it.
+    #[inline]
+    fn gamepak_offset(&self, address: u32) -> usize {
+        address as usize & self.gamepak_mask
+    }
+
     fn gamepak_load32<const AREA: usize>(
         &mut self,
         address: u32,
         access_type: AccessType,
         wait: &mut Waitstates,
     ) -> u32 {
-        *wait += if access_type == AccessType::Sequential {
-            self.system_control.waitstates.gamepak[AREA].1
-        } else {
-            self.system_control.waitstates.gamepak[AREA].0
-        };
-        *wait += self.system_control.waitstates.gamepak[AREA].1;
-        LittleEndian::read_u32(&self.gamepak[(address as usize & self.gamepak_mask)..])
+        *wait += self.prefetch.read(
+            address,
+            2,
+            access_type == AccessType::Sequential,
+            self.system_control.prefetch_enabled(),
+            self.system_control.waitstates.gamepak[AREA],
+        );
+        if self.is_eeprom_mapped(address) {
+            self.side_effects = self.side_effects.wrapping_add(1);
+            let lo = self.backup.eeprom_load16();
+            let hi = self.backup.eeprom_load16();
+            return u32::from(lo) | (u32::from(hi) << 16);
+        }
+        if self.gpio.is_mapped(address) {
+            let lo = self.gamepak_read16(address);
+            let hi = self.gamepak_read16(address.wrapping_add(2));
+            return u32::from(lo) | (u32::from(hi) << 16);
+        }
+        LittleEndian::read_u32(&self.gamepak[self.gamepak_offset(address)..])
     }
 
     fn gamepak_load16<const AREA: usize>(
@@ -30,12 +52,27 @@ impl GbaMemoryMappedHardware {
         access_type: AccessType,
         wait: &mut Waitstates,
     ) -> u16 {
-        *wait += if access_type == AccessType::Sequential {
-            self.system_control.waitstates.gamepak[AREA].1
-        } else {
-            self.system_control.waitstates.gamepak[AREA].0
-        };
-        LittleEndian::read_u16(&self.gamepak[(address as usize & self.gamepak_mask)..])
+        *wait += self.prefetch.read(
+            address,
+            1,
+            access_type == AccessType::Sequential,
+            self.system_control.prefetch_enabled(),
+            self.system_control.waitstates.gamepak[AREA],
+        );
+        if self.is_eeprom_mapped(address) {
+            self.side_effects = self.side_effects.wrapping_add(1);
+            return self.backup.eeprom_load16();
+        }
+        self.gamepak_read16(address)
+    }
+
+    /// Reads a halfword from the gamepak without any waitstates, going to the GPIO
+    /// port instead of ROM if it is readable.
+    fn gamepak_read16(&self, address: u32) -> u16 {
+        if self.gpio.is_mapped(address) {
+            return self.gpio.read16(address);
+        }
+        LittleEndian::read_u16(&self.gamepak[self.gamepak_offset(address)..])
     }
 
     fn gamepak_load8<const AREA: usize>(
@@ -56,61 +93,219 @@ impl GbaMemoryMappedHardware {
         &mut self,
         address: u32,
         value: u32,
-        _access_type: AccessType,
-        _wait: &mut Waitstates,
+        access_type: AccessType,
+        wait: &mut Waitstates,
     ) {
-        tracing::debug!("unimplemented gamepak store32: [0x{address:08X}] = 0x{value:08X}");
+        *wait += if access_type == AccessType::Sequential {
+            self.system_control.waitstates.gamepak[AREA].1
+        } else {
+            self.system_control.waitstates.gamepak[AREA].0
+        };
+        *wait += self.system_control.waitstates.gamepak[AREA].1;
+        self.prefetch.flush();
+        if self.is_eeprom_mapped(address) {
+            self.backup.eeprom_store16(value as u16);
+            self.backup.eeprom_store16((value >> 16) as u16);
+            return;
+        }
+        let lo = self.gpio.write16(address, value as u16);
+        let hi = self
+            .gpio
+            .write16(address.wrapping_add(2), (value >> 16) as u16);
+        if !(lo || hi) {
+            tracing::debug!("unimplemented gamepak store32: [0x{address:08X}] = 0x{value:08X}");
+        }
     }
 
     fn gamepak_store16<const AREA: usize>(
         &mut self,
         address: u32,
         value: u16,
-        _access_type: AccessType,
-        _wait: &mut Waitstates,
+        access_type: AccessType,
+        wait: &mut Waitstates,
     ) {
-        tracing::debug!("unimplemented gamepak store16: [0x{address:08X}] = 0x{value:04X}");
+        *wait += if access_type == AccessType::Sequential {
+            self.system_control.waitstates.gamepak[AREA].1
+        } else {
+            self.system_control.waitstates.gamepak[AREA].0
+        };
+        self.prefetch.flush();
+        if self.is_eeprom_mapped(address) {
+            self.backup.eeprom_store16(value);
+            return;
+        }
+        if !self.gpio.write16(address, value) {
+            tracing::debug!("unimplemented gamepak store16: [0x{address:08X}] = 0x{value:04X}");
+        }
     }
 
     fn gamepak_store8<const AREA: usize>(
         &mut self,
         address: u32,
         value: u8,
-        _access_type: AccessType,
-        _wait: &mut Waitstates,
+        access_type: AccessType,
+        wait: &mut Waitstates,
     ) {
-        tracing::debug!("unimplemented gamepak store8: [0x{address:08X}] = 0x{value:02X}");
+        *wait += if access_type == AccessType::Sequential {
+            self.system_control.waitstates.gamepak[AREA].1
+        } else {
+            self.system_control.waitstates.gamepak[AREA].0
+        };
+        self.prefetch.flush();
+        if !self.gpio.write16(address, u16::from(value)) {
+            tracing::debug!("unimplemented gamepak store8: [0x{address:08X}] = 0x{value:02X}");
+        }
+    }
+
+    /// Lets the gamepak prefetch buffer use any cycles out of the last `cycles` cycles
+    /// where the CPU was not accessing the gamepak.
+    pub(crate) fn step_prefetch(&mut self, cycles: Cycles) {
+        if !self.system_control.prefetch_enabled() {
+            self.prefetch.flush();
+        }
+        let area = match self.prefetch.next_address() >> 24 {
+            REGION_GAMEPAK1_LO | REGION_GAMEPAK1_HI => 1,
+            REGION_GAMEPAK2_LO | REGION_GAMEPAK2_HI => 2,
+            _ => 0,
+        };
+        let second = self.system_control.waitstates.gamepak[area].1;
+        self.prefetch.idle(u32::from(cycles), second);
+    }
+
+    /// Returns the value that is read from unused memory. This is the most recently
+    /// prefetched opcode, which for THUMB code depends on where the code is running from.
+    fn open_bus(&self, cpu: &Cpu) -> u32 {
+        let prefetched = cpu.prefetched_opcode();
+        if !cpu.registers.get_flag(CpsrFlag::T) {
+            return prefetched;
+        }
+
+        // In THUMB state the PC points to the prefetched opcode ($+4) and the
+        // decoded opcode is at $+2.
+        let pc = cpu.registers.read(15);
+        let decoded = cpu.decoded_opcode();
+        let aligned = pc & 0x2 == 0;
+        let (lo, hi) = match pc >> 24 {
+            REGION_BIOS | REGION_OAM if aligned => (prefetched, self.code16(pc.wrapping_add(2))),
+            REGION_BIOS | REGION_OAM => (decoded, prefetched),
+            REGION_IWRAM if aligned => (prefetched, decoded),
+            REGION_IWRAM => (decoded, prefetched),
+            _ => (prefetched, prefetched),
+        };
+        (lo & 0xFFFF) | (hi << 16)
+    }
+
+    /// Reads a THUMB opcode from BIOS or OAM without side effects.
+    fn code16(&self, address: u32) -> u32 {
+        match address >> 24 {
+            REGION_BIOS if address < 0x4000 => {
+                LittleEndian::read_u16(&self.bios[address as usize & !0x1..]) as u32
+            }
+            REGION_OAM => {
+                LittleEndian::read_u16(&self.oam[(address & OAM_MASK & !0x1) as usize..]) as u32
+            }
+            _ => 0,
+        }
+    }
+
+    /// Returns the `len` bytes of EWRAM or IWRAM starting at `address` and the
+    /// waitstates for a single byte access, if they are contiguous in one of them.
+    fn work_ram_slice(&mut self, address: u32, len: usize) -> Option<(&mut [u8], Waitstates)> {
+        let (ram, offset, wait): (&mut [u8], usize, Waitstates) = match address >> 24 {
+            REGION_EWRAM => (
+                &mut self.ewram[..],
+                (address & EWRAM_MASK) as usize,
+                self.system_control.waitstates.ewram,
+            ),
+            REGION_IWRAM => (
+                &mut self.iwram[..],
+                (address & IWRAM_MASK) as usize,
+                Waitstates::zero(),
+            ),
+            _ => return None,
+        };
+        let slice = ram.get_mut(offset..offset.checked_add(len)?)?;
+        Some((slice, wait))
+    }
+
+    /// Reads the BIOS word containing `address`. The BIOS can only be read while
+    /// the CPU is executing it, otherwise this returns the last word that was read
+    /// from the BIOS unless the protection is turned off.
+    fn bios_load32(&mut self, address: u32, cpu: &Cpu) -> u32 {
+        if !self.bios_read_protection || cpu.next_execution_address() < 0x4000 {
+            let value = LittleEndian::read_u32(&self.bios[(address & !0x3) as usize..]);
+            self.last_bios_value = value;
+            value
+        } else {
+            self.last_bios_value
+        }
     }
 
     fn load_sram8<T>(&mut self, address: u32, wait: &mut Waitstates) -> T
     where
         T: From<u8>,
     {
-        tracing::debug!("unimplemented sram load8: [0x{address:08X}]");
         *wait += self.system_control.waitstates.sram;
-        (0u8).into()
+        self.backup.read8(address).into()
+    }
+
+    /// Counts an access for [`MemoryStats`] if they are enabled.
+    #[inline]
+    fn record_access(&mut self, address: u32, write: bool) {
+        self.record_accesses(address, write, 1);
+    }
+
+    /// Counts `count` accesses to the region containing `address`, one for each
+    /// byte of a slice copy.
+    #[inline]
+    fn record_accesses(&mut self, address: u32, write: bool, count: u64) {
+        if write {
+            self.side_effects = self.side_effects.wrapping_add(count);
+        }
+        if let Some(stats) = self.memory_stats.as_deref_mut() {
+            stats.record(MemoryRegion::from_address(address), write, count);
+        }
+    }
+
+    /// Extra waitstates for a CPU access to palette RAM, VRAM or OAM while the video
+    /// hardware is using them to draw a line. OAM is also in use during the HBlank of
+    /// visible lines unless DISPCNT has H-Blank Interval Free set. This is only
+    /// emulated in [`AccuracyMode::Cycle`].
+    fn video_bus_waitstates(&self, address: u32) -> Waitstates {
+        let region = address >> 24;
+        if !matches!(region, REGION_PAL | REGION_VRAM | REGION_OAM)
+            || self.system_control.accuracy() != AccuracyMode::Cycle
+        {
+            return Waitstates::zero();
+        }
+
+        let registers = &self.video.registers;
+        if registers.dispcnt.forced_blank() || registers.dispstat.vblank_flag() {
+            return Waitstates::zero();
+        }
+        let in_hblank = registers.dispstat.hblank_flag();
+        let busy =
+            !in_hblank || (region == REGION_OAM && !registers.dispcnt.hblank_interval_free());
+        if busy {
+            Waitstates::one()
+        } else {
+            Waitstates::zero()
+        }
     }
 
     fn store_sram8(&mut self, address: u32, value: u8, wait: &mut Waitstates) {
-        tracing::debug!("unimplemented sram store8: [0x{address:08X}] = 0x{value:02X}");
         *wait += self.system_control.waitstates.sram;
+        self.backup.write8(address, value);
     }
 }
 
 impl Memory for GbaMemoryMappedHardware {
     fn load32(&mut self, address: u32, cpu: &mut Cpu) -> (u32, arm::emu::Waitstates) {
+        self.record_access(address, false);
         let address = address & !0x3;
         let mut wait = Waitstates::zero();
         let value = match address >> 24 {
-            REGION_BIOS if address < 0x4000 => {
-                if cpu.next_execution_address() < 0x4000 {
-                    let value = LittleEndian::read_u32(&self.bios[address as usize..]);
-                    self.last_bios_value = value;
-                    value
-                } else {
-                    self.last_bios_value
-                }
-            }
+            REGION_BIOS if address < 0x4000 => self.bios_load32(address, cpu),
             // FIXME implement enable/disable from SystemControl
             REGION_EWRAM => {
                 wait += self.system_control.waitstates.ewram + self.system_control.waitstates.ewram;
@@ -145,26 +340,21 @@ impl Memory for GbaMemoryMappedHardware {
 
             _ => {
                 tracing::debug!("32-bit read from unused memory: [0x{address:08X}]");
-                self.last_read_value
+                self.open_bus(cpu)
             }
         };
-        self.last_read_value = value;
+        wait += self.video_bus_waitstates(address);
         (value, wait)
     }
 
     fn load16(&mut self, address: u32, cpu: &mut Cpu) -> (u16, arm::emu::Waitstates) {
+        self.record_access(address, false);
         let address = address & !0x1;
         let mut wait = Waitstates::zero();
         let value = match address >> 24 {
             REGION_BIOS if address < 0x4000 => {
-                if cpu.next_execution_address() < 0x4000 {
-                    let value = LittleEndian::read_u32(&self.bios[(address & !0x3) as usize..]);
-                    self.last_bios_value = value;
-                    value.rotate_right(((address & 0x2) >> 1) * 16) as u16
-                } else {
-                    self.last_bios_value
-                        .rotate_right(((address & 0x2) >> 1) * 16) as u16
-                }
+                self.bios_load32(address, cpu)
+                    .rotate_right(((address & 0x2) >> 1) * 16) as u16
             }
             // FIXME implement enable/disable from SystemControl
             REGION_EWRAM => {
@@ -192,27 +382,24 @@ impl Memory for GbaMemoryMappedHardware {
                 .wrapping_mul(0x0101u16),
             _ => {
                 tracing::debug!("16-bit read from unused memory: [0x{address:08X}]");
-                self.last_read_value as u16
+                (self.open_bus(cpu) >> ((address & 0x2) * 8)) as u16
             }
         };
+        wait += self.video_bus_waitstates(address);
         (value, wait)
     }
 
     fn load8(&mut self, address: u32, cpu: &mut Cpu) -> (u8, arm::emu::Waitstates) {
+        self.record_access(address, false);
         let mut wait = Waitstates::zero();
         let value = match address >> 24 {
-            0x0 if address < 0x4000 => {
-                if cpu.next_execution_address() < 0x4000 {
-                    let value = LittleEndian::read_u32(&self.bios[(address & !0x3) as usize..]);
-                    self.last_bios_value = value;
-                    value.rotate_right((address & 0x3) * 8) as u8
-                } else {
-                    self.last_bios_value.rotate_right((address & 0x3) * 8) as u8
-                }
+            REGION_BIOS if address < 0x4000 => {
+                self.bios_load32(address, cpu)
+                    .rotate_right((address & 0x3) * 8) as u8
             }
             // FIXME implement enable/disable from SystemControl
             REGION_EWRAM => {
-                wait += self.system_control.waitstates.ewram + self.system_control.waitstates.ewram;
+                wait += self.system_control.waitstates.ewram;
                 self.ewram[(address & EWRAM_MASK) as usize]
             }
             // FIXME implement enable/disable from SystemControl
@@ -233,14 +420,15 @@ impl Memory for GbaMemoryMappedHardware {
             REGION_SRAM => self.load_sram8::<u8>(address, &mut wait),
             _ => {
                 tracing::debug!("8-bit read from unused memory: [0x{address:08X}]");
-                self.last_read_value as u8
+                (self.open_bus(cpu) >> ((address & 0x3) * 8)) as u8
             }
         };
-        self.last_read_value = value as u32;
+        wait += self.video_bus_waitstates(address);
         (value, wait)
     }
 
     fn store32(&mut self, address: u32, value: u32, cpu: &mut Cpu) -> arm::emu::Waitstates {
+        self.record_access(address, true);
         let address = address & !0x3;
         let mut wait = Waitstates::zero();
         match address >> 24 {
@@ -283,10 +471,12 @@ impl Memory for GbaMemoryMappedHardware {
                 tracing::debug!("32-bit write to unused memory: [0x{address:08X}] = 0x{value:08X}");
             }
         }
+        wait += self.video_bus_waitstates(address);
         wait
     }
 
     fn store16(&mut self, address: u32, value: u16, cpu: &mut Cpu) -> arm::emu::Waitstates {
+        self.record_access(address, true);
         let address = address & !0x1;
         let mut wait = Waitstates::zero();
         match address >> 24 {
@@ -323,10 +513,12 @@ impl Memory for GbaMemoryMappedHardware {
                 tracing::debug!("16-bit write to unused memory: [0x{address:08X}] = 0x{value:04X}");
             }
         }
+        wait += self.video_bus_waitstates(address);
         wait
     }
 
     fn store8(&mut self, address: u32, value: u8, cpu: &mut Cpu) -> arm::emu::Waitstates {
+        self.record_access(address, true);
         let mut wait = Waitstates::zero();
         match address >> 24 {
             // FIXME implement enable/disable from SystemControl
@@ -370,9 +562,28 @@ impl Memory for GbaMemoryMappedHardware {
                 tracing::debug!("8-bit write to unused memory: [0x{address:08X}] = 0x{value:02X}");
             }
         }
+        wait += self.video_bus_waitstates(address);
         wait
     }
 
+    fn load_slice(&mut self, address: u32, buffer: &mut [u8], cpu: &mut Cpu) -> Waitstates {
+        if let Some((ram, wait)) = self.work_ram_slice(address, buffer.len()) {
+            buffer.copy_from_slice(ram);
+            self.record_accesses(address, false, buffer.len() as u64);
+            return Waitstates::from(u32::from(wait) * buffer.len() as u32);
+        }
+        arm::emu::load_slice_bytewise(self, address, buffer, cpu)
+    }
+
+    fn store_slice(&mut self, address: u32, data: &[u8], cpu: &mut Cpu) -> Waitstates {
+        if let Some((ram, wait)) = self.work_ram_slice(address, data.len()) {
+            ram.copy_from_slice(data);
+            self.record_accesses(address, true, data.len() as u64);
+            return Waitstates::from(u32::from(wait) * data.len() as u32);
+        }
+        arm::emu::store_slice_bytewise(self, address, data, cpu)
+    }
+
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
@@ -382,10 +593,13 @@ impl Memory for GbaMemoryMappedHardware {
     }
 }
 
+/// Reads memory the same way the CPU would but without any side effects: there
+/// are no waitstates, the BIOS read protection and open bus values are not updated,
+/// and unmapped memory reads as zero.
 #[cfg(feature = "arm-disassembler")]
 impl MemoryView for GbaMemoryMappedHardware {
-    fn view8(&self, _address: u32) -> u8 {
-        0
+    fn view8(&self, address: u32) -> u8 {
+        (self.view16(address & !0x1) >> ((address & 0x1) * 8)) as u8
     }
 
     fn view16(&self, address: u32) -> u16 {
@@ -398,21 +612,12 @@ impl MemoryView for GbaMemoryMappedHardware {
             REGION_EWRAM => LittleEndian::read_u16(&self.ewram[(address & EWRAM_MASK) as usize..]),
             // FIXME implement enable/disable from SystemControl
             REGION_IWRAM => LittleEndian::read_u16(&self.iwram[(address & IWRAM_MASK) as usize..]),
-            REGION_IOREGS => 0,
+            REGION_IOREGS => self.ioreg_load16(address),
             REGION_PAL => self.palram.load16(address),
             REGION_VRAM => LittleEndian::read_u16(&self.vram[vram_offset(address)..]),
             REGION_OAM => LittleEndian::read_u16(&self.oam[(address & OAM_MASK) as usize..]),
-
-            REGION_GAMEPAK0_LO | REGION_GAMEPAK0_HI => {
-                LittleEndian::read_u16(&self.gamepak[(address as usize & self.gamepak_mask)..])
-            }
-            REGION_GAMEPAK1_LO | REGION_GAMEPAK1_HI => {
-                LittleEndian::read_u16(&self.gamepak[(address as usize & self.gamepak_mask)..])
-            }
-            REGION_GAMEPAK2_LO | REGION_GAMEPAK2_HI => {
-                LittleEndian::read_u16(&self.gamepak[(address as usize & self.gamepak_mask)..])
-            }
-            REGION_SRAM => 0,
+            REGION_GAMEPAK0_LO..=REGION_GAMEPAK2_HI => self.gamepak_read16(address),
+            REGION_SRAM => u16::from(self.backup.read8(address)) * 0x0101,
             _ => 0,
         }
     }
@@ -427,21 +632,16 @@ impl MemoryView for GbaMemoryMappedHardware {
             REGION_EWRAM => LittleEndian::read_u32(&self.ewram[(address & EWRAM_MASK) as usize..]),
             // FIXME implement enable/disable from SystemControl
             REGION_IWRAM => LittleEndian::read_u32(&self.iwram[(address & IWRAM_MASK) as usize..]),
-            REGION_IOREGS => 0,
+            REGION_IOREGS => self.ioreg_load32(address),
             REGION_PAL => self.palram.load32(address),
             REGION_VRAM => LittleEndian::read_u32(&self.vram[vram_offset(address)..]),
             REGION_OAM => LittleEndian::read_u32(&self.oam[(address & OAM_MASK) as usize..]),
-
-            REGION_GAMEPAK0_LO | REGION_GAMEPAK0_HI => {
-                LittleEndian::read_u32(&self.gamepak[(address as usize & self.gamepak_mask)..])
-            }
-            REGION_GAMEPAK1_LO | REGION_GAMEPAK1_HI => {
-                LittleEndian::read_u32(&self.gamepak[(address as usize & self.gamepak_mask)..])
-            }
-            REGION_GAMEPAK2_LO | REGION_GAMEPAK2_HI => {
-                LittleEndian::read_u32(&self.gamepak[(address as usize & self.gamepak_mask)..])
+            REGION_GAMEPAK0_LO..=REGION_GAMEPAK2_HI => {
+                let lo = self.gamepak_read16(address);
+                let hi = self.gamepak_read16(address.wrapping_add(2));
+                u32::from(lo) | (u32::from(hi) << 16)
             }
-            REGION_SRAM => 0,
+            REGION_SRAM => u32::from(self.backup.read8(address)) * 0x01010101,
             _ => 0,
         }
     }
@@ -486,6 +686,8 @@ pub const PAL_SIZE: usize = 0x400;
 pub const VRAM_SIZE: usize = 0x18000;
 pub const OAM_SIZE: usize = 0x400;
 pub const IOREGS_SIZE: usize = 0x20A;
+pub const GAMEPAK_MAX_SIZE: usize = 0x2000000;
+pub const GAMEPAK_HEADER_SIZE: usize = 0xC0;
 
 pub const EWRAM_MASK: u32 = 0x3FFFF;
 pub const IWRAM_MASK: u32 = 0x7FFF;
@@ -493,7 +695,232 @@ pub const PAL_MASK: u32 = 0x3FF;
 pub const OAM_MASK: u32 = 0x3FF;
 pub const ROM_MAX_MASK: u32 = 0xFFFFFF;
 
+/// The contents of EWRAM and VRAM after a hard reset. Real hardware leaves them
+/// uninitialized so games that read them before writing them behave differently
+/// from one boot to the next. A seeded fill makes that reproducible.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fill {
+    #[default]
+    Zero,
+
+    /// Fill memory with pseudo-random bytes generated from the seed.
+    Seeded(u64),
+}
+
+impl Fill {
+    pub(crate) fn apply(self, buffers: &mut [&mut [u8]]) {
+        match self {
+            Fill::Zero => buffers.iter_mut().for_each(|buffer| buffer.fill(0)),
+            Fill::Seeded(seed) => {
+                let mut rand = WyHash::new(seed);
+                for buffer in buffers.iter_mut() {
+                    for chunk in buffer.chunks_mut(8) {
+                        let bytes = rand.next_rand().to_le_bytes();
+                        chunk.copy_from_slice(&bytes[..chunk.len()]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The regions of the GBA's address space that [`MemoryStats`] counts accesses to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemoryRegion {
+    Bios,
+    Ewram,
+    Iwram,
+    IoRegisters,
+    Palette,
+    Vram,
+    Oam,
+    /// All three of the gamepak ROM regions.
+    Gamepak,
+    Sram,
+    /// Anything that isn't mapped to memory, including the BIOS region past the end
+    /// of the BIOS.
+    Unused,
+}
+
+impl MemoryRegion {
+    pub const ALL: [MemoryRegion; 10] = [
+        MemoryRegion::Bios,
+        MemoryRegion::Ewram,
+        MemoryRegion::Iwram,
+        MemoryRegion::IoRegisters,
+        MemoryRegion::Palette,
+        MemoryRegion::Vram,
+        MemoryRegion::Oam,
+        MemoryRegion::Gamepak,
+        MemoryRegion::Sram,
+        MemoryRegion::Unused,
+    ];
+
+    pub fn from_address(address: u32) -> Self {
+        match address >> 24 {
+            REGION_BIOS if (address as usize) < BIOS_SIZE => MemoryRegion::Bios,
+            REGION_EWRAM => MemoryRegion::Ewram,
+            REGION_IWRAM => MemoryRegion::Iwram,
+            REGION_IOREGS => MemoryRegion::IoRegisters,
+            REGION_PAL => MemoryRegion::Palette,
+            REGION_VRAM => MemoryRegion::Vram,
+            REGION_OAM => MemoryRegion::Oam,
+            REGION_GAMEPAK0_LO..=REGION_GAMEPAK2_HI => MemoryRegion::Gamepak,
+            REGION_SRAM => MemoryRegion::Sram,
+            _ => MemoryRegion::Unused,
+        }
+    }
+
+    /// Returns true for the regions that programs run code from: the BIOS, both
+    /// work RAMs, VRAM and the gamepak. The CPU can technically fetch instructions
+    /// from palette RAM and OAM too but nothing does in practice, so jumping to
+    /// them (or to I/O, SRAM or unmapped memory) almost certainly means the
+    /// program has gone off the rails.
+    pub fn is_executable(self) -> bool {
+        matches!(
+            self,
+            MemoryRegion::Bios
+                | MemoryRegion::Ewram
+                | MemoryRegion::Iwram
+                | MemoryRegion::Vram
+                | MemoryRegion::Gamepak
+        )
+    }
+}
+
+/// Read and write counts for each [`MemoryRegion`]. Every byte, halfword or word
+/// loaded or stored through [`Memory`] counts as one access, including instruction
+/// fetches and DMA transfers, so slice copies count one access per byte. See
+/// [`crate::Gba::set_memory_stats_enabled`].
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct MemoryStats {
+    reads: [u64; MemoryRegion::ALL.len()],
+    writes: [u64; MemoryRegion::ALL.len()],
+}
+
+impl MemoryStats {
+    #[inline]
+    fn record(&mut self, region: MemoryRegion, write: bool, count: u64) {
+        let counts = if write {
+            &mut self.writes
+        } else {
+            &mut self.reads
+        };
+        counts[region as usize] += count;
+    }
+
+    pub fn reads(&self, region: MemoryRegion) -> u64 {
+        self.reads[region as usize]
+    }
+
+    pub fn writes(&self, region: MemoryRegion) -> u64 {
+        self.writes[region as usize]
+    }
+
+    /// Sets every count back to zero.
+    pub fn clear(&mut self) {
+        *self = MemoryStats::default();
+    }
+}
+
+/// A field of an [`IoRegister`] as declared with `#[field(...)]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IoRegisterField {
+    pub name: &'static str,
+    pub bits: Range<u32>,
+    pub readable: bool,
+    pub writable: bool,
+}
+
+/// The current state of an I/O register for debuggers. See [`crate::Gba::io_registers`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IoRegDescription {
+    /// The name used by GBATEK, e.g. "DISPCNT".
+    pub name: &'static str,
+    pub address: u32,
+    /// The value stored in the register, including bits that can't be read.
+    pub value: u32,
+    /// Every field of the register with its value.
+    pub fields: Vec<(IoRegisterField, u32)>,
+}
+
+impl IoRegDescription {
+    pub fn new<R, T>(name: &'static str, address: u32, register: R) -> Self
+    where
+        R: IoRegister<T>,
+        T: BitOps + From<R> + Into<u32>,
+    {
+        let value: u32 = T::from(register).into();
+        let fields = R::FIELDS
+            .iter()
+            .map(|field| (field.clone(), value.get_bit_range(field.bits.clone())))
+            .collect();
+        IoRegDescription {
+            name,
+            address,
+            value,
+            fields,
+        }
+    }
+
+    /// The value of the field called `name`, if the register has one.
+    pub fn field(&self, name: &str) -> Option<u32> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field.name == name)
+            .map(|&(_, value)| value)
+    }
+}
+
+/// Implemented with `#[derive(IoRegister)]`, which also generates a getter and
+/// setter for every `#[field(...)]`:
+///
+/// ```
+/// # mod memory { pub use gba::memory::*; }
+/// use pyrite_derive::IoRegister;
+///
+/// #[derive(IoRegister, Copy, Clone)]
+/// #[field(mode: u16 = 0..=2)]
+/// #[field(enabled: bool = 15)]
+/// struct RegExample {
+///     value: u16,
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Fields can't share bits unless one of them is marked `overlapping`:
+///
+/// ```compile_fail
+/// # mod memory { pub use gba::memory::*; }
+/// use pyrite_derive::IoRegister;
+///
+/// #[derive(IoRegister, Copy, Clone)]
+/// #[field(mode: u16 = 0..=2)]
+/// #[field(enabled: bool = 2)]
+/// struct RegExample {
+///     value: u16,
+/// }
+/// # fn main() {}
+/// ```
+///
+/// And they have to fit in the register:
+///
+/// ```compile_fail
+/// # mod memory { pub use gba::memory::*; }
+/// use pyrite_derive::IoRegister;
+///
+/// #[derive(IoRegister, Copy, Clone)]
+/// #[field(mode: u16 = 0..=2)]
+/// #[field(enabled: bool = 16)]
+/// struct RegExample {
+///     value: u16,
+/// }
+/// # fn main() {}
+/// ```
 pub trait IoRegister<T: BitOps>: Copy + From<T> {
+    /// The fields of the register in the order that they were declared.
+    const FIELDS: &'static [IoRegisterField];
+
     fn read(self) -> T;
     fn write(&mut self, value: T);
 
@@ -537,3 +964,40 @@ pub trait IoRegister<T: BitOps>: Copy + From<T> {
         self.write(original.put_bit_range(16..32, value.into()));
     }
 }
+
+#[cfg(test)]
+mod test {
+    use pyrite_derive::IoRegister;
+
+    use super::IoRegister as _;
+
+    #[derive(IoRegister, Copy, Clone)]
+    #[field(mode: u8 = 0..=2)]
+    #[field(enabled: bool = 7)]
+    struct RegTest {
+        value: u8,
+    }
+
+    #[test]
+    fn test_field_setters_mask_values() {
+        let mut register = RegTest::new(0);
+        register.set_mode(0xFF);
+        assert_eq!(register.mode(), 0x7);
+        assert!(!register.enabled());
+        assert_eq!(register.read(), 0x07);
+
+        register.set_enabled(true);
+        register.set_mode(0x0A);
+        assert_eq!(register.mode(), 0x2);
+        assert!(register.enabled());
+        assert_eq!(register.read(), 0x82);
+
+        let register = RegTest::new(0x7D);
+        assert_eq!(register.mode(), 0x5);
+        assert!(!register.enabled());
+        assert_eq!(
+            format!("{register:?}"),
+            "RegTest { mode: 5, enabled: false }"
+        );
+    }
+}
@@ -3,16 +3,37 @@ use util::display::hex;
 
 use crate::GbaMemoryMappedHardware;
 
-use super::IoRegister;
+use super::{IoRegDescription, IoRegister};
 
 impl GbaMemoryMappedHardware {
-    pub(super) fn ioreg_load16(&mut self, address: u32) -> u16 {
+    pub(super) fn ioreg_load16(&self, address: u32) -> u16 {
         match address {
             self::DISPCNT => self.video.registers.dispcnt.read(),
             self::GREENSWAP => self.video.registers.green_swap.read(),
             self::DISPSTAT => self.video.registers.dispstat.read(),
             self::VCOUNT => self.video.registers.vcount.read(),
+            self::BG0CNT => self.video.registers.bg_cnt[0].read(),
+            self::BG1CNT => self.video.registers.bg_cnt[1].read(),
+            self::BG2CNT => self.video.registers.bg_cnt[2].read(),
+            self::BG3CNT => self.video.registers.bg_cnt[3].read(),
+            self::BG2PA..=self::BG3Y_H => 0,     // write only
+            self::MOSAIC | self::MOSAIC_HI => 0, // write only
+            self::SIOMULTI0..=self::SIOMULTI3 => {
+                self.serial.read_data(((address - SIOMULTI0) / 2) as usize)
+            }
+            self::SIOCNT => self.serial.siocnt.read(),
+            self::SIOMLT_SEND => self.serial.read_send(),
             self::KEYINPUT => self.keypad.keyinput.read(),
+            self::RCNT => self.serial.rcnt.read(),
+            self::IE => self.system_control.interrupt_enable.read(),
+            self::IF => self.system_control.interrupt_request.read(),
+            self::WAITCNT => self.system_control.waitcnt.read() as u16,
+            self::IME => self.system_control.interrupt_master_enable.read(),
+            self::IME_HI => 0,
+            address if is_imc(address) => self.system_control.internal_memory_control.read() as u16,
+            address if is_imc_hi(address) => {
+                (self.system_control.internal_memory_control.read() >> 16) as u16
+            }
             _ => {
                 tracing::debug!(address = hex(address), "unimplemented read from IO");
                 0
@@ -26,6 +47,56 @@ impl GbaMemoryMappedHardware {
             self::GREENSWAP => self.video.registers.green_swap.write(value),
             self::DISPSTAT => self.video.registers.dispstat.write(value),
             self::VCOUNT => self.video.registers.vcount.write(value),
+            self::BG0CNT => self.video.registers.bg_cnt[0].write(value),
+            self::BG1CNT => self.video.registers.bg_cnt[1].write(value),
+            self::BG2CNT => self.video.registers.bg_cnt[2].write(value),
+            self::BG3CNT => self.video.registers.bg_cnt[3].write(value),
+            self::BG2PA => self.video.registers.bg_affine[0].pa.write(value),
+            self::BG2PB => self.video.registers.bg_affine[0].pb.write(value),
+            self::BG2PC => self.video.registers.bg_affine[0].pc.write(value),
+            self::BG2PD => self.video.registers.bg_affine[0].pd.write(value),
+            self::BG2X => self.video.registers.bg_affine[0].write_x_lo(value),
+            self::BG2X_H => self.video.registers.bg_affine[0].write_x_hi(value),
+            self::BG2Y => self.video.registers.bg_affine[0].write_y_lo(value),
+            self::BG2Y_H => self.video.registers.bg_affine[0].write_y_hi(value),
+            self::BG3PA => self.video.registers.bg_affine[1].pa.write(value),
+            self::BG3PB => self.video.registers.bg_affine[1].pb.write(value),
+            self::BG3PC => self.video.registers.bg_affine[1].pc.write(value),
+            self::BG3PD => self.video.registers.bg_affine[1].pd.write(value),
+            self::BG3X => self.video.registers.bg_affine[1].write_x_lo(value),
+            self::BG3X_H => self.video.registers.bg_affine[1].write_x_hi(value),
+            self::BG3Y => self.video.registers.bg_affine[1].write_y_lo(value),
+            self::BG3Y_H => self.video.registers.bg_affine[1].write_y_hi(value),
+            self::MOSAIC => self.video.registers.mosaic.write(value),
+            self::MOSAIC_HI => { /* unused */ }
+            self::SIOMULTI0..=self::SIOMULTI3 => self
+                .serial
+                .write_data(((address - SIOMULTI0) / 2) as usize, value),
+            self::SIOCNT => {
+                let interrupts = self.serial.write_siocnt(value);
+                self.system_control.request_interrupts(interrupts);
+            }
+            self::SIOMLT_SEND => self.serial.write_send(value),
+            self::RCNT => self.serial.write_rcnt(value),
+            self::IE => self.system_control.interrupt_enable.write(value),
+            self::IF => self.system_control.acknowledge_interrupts(value),
+            self::IME => self.system_control.interrupt_master_enable.write(value),
+            self::IME_HI => { /* unused */ }
+            self::WAITCNT => {
+                let mut waitcnt = self.system_control.waitcnt;
+                waitcnt.write16_lo(value);
+                self.system_control.write_waitcnt(waitcnt);
+            }
+            address if is_imc(address) => {
+                let mut imc = self.system_control.internal_memory_control;
+                imc.write16_lo(value);
+                self.system_control.write_internal_memory_control(imc);
+            }
+            address if is_imc_hi(address) => {
+                let mut imc = self.system_control.internal_memory_control;
+                imc.write16_hi(value);
+                self.system_control.write_internal_memory_control(imc);
+            }
             _ => {
                 tracing::debug!(
                     address = hex(address),
@@ -36,7 +107,7 @@ impl GbaMemoryMappedHardware {
         }
     }
 
-    pub(super) fn ioreg_load32(&mut self, address: u32) -> u32 {
+    pub(super) fn ioreg_load32(&self, address: u32) -> u32 {
         let lo = self.ioreg_load16(address) as u32;
         let hi = self.ioreg_load16(address.wrapping_add(2)) as u32;
         lo | (hi << 16)
@@ -47,13 +118,22 @@ impl GbaMemoryMappedHardware {
         self.ioreg_store16(address.wrapping_add(2), (value >> 16) as u16);
     }
 
-    pub(super) fn ioreg_load8(&mut self, address: u32) -> u8 {
+    pub(super) fn ioreg_load8(&self, address: u32) -> u8 {
         (self.ioreg_load16(address & !0x1) >> ((address & 1) * 8)) as u8
     }
 
     pub(super) fn ioreg_store8(&mut self, address: u32, value: u8) {
+        // Writing a 1 to a bit of IF acknowledges that interrupt so the other byte
+        // can't be written back with its old value.
+        if address & !0x1 == IF {
+            let shift = (address & 1) * 8;
+            self.system_control
+                .acknowledge_interrupts((value as u16) << shift);
+            return;
+        }
+
         let old = self.ioreg_load16(address & !0x1);
-        if (value & 1) == 0 {
+        if (address & 1) == 0 {
             // write low
             self.ioreg_store16(address & !0x1, (old & 0xFF00) | (value as u16))
         } else {
@@ -63,6 +143,54 @@ impl GbaMemoryMappedHardware {
     }
 }
 
+impl GbaMemoryMappedHardware {
+    /// Describes every I/O register that is emulated, ordered by address.
+    pub fn io_registers(&self) -> Vec<IoRegDescription> {
+        let video = &self.video.registers;
+        let system_control = &self.system_control;
+        vec![
+            IoRegDescription::new("DISPCNT", DISPCNT, video.dispcnt),
+            IoRegDescription::new("GREENSWAP", GREENSWAP, video.green_swap),
+            IoRegDescription::new("DISPSTAT", DISPSTAT, video.dispstat),
+            IoRegDescription::new("VCOUNT", VCOUNT, video.vcount),
+            IoRegDescription::new("BG0CNT", BG0CNT, video.bg_cnt[0]),
+            IoRegDescription::new("BG1CNT", BG1CNT, video.bg_cnt[1]),
+            IoRegDescription::new("BG2CNT", BG2CNT, video.bg_cnt[2]),
+            IoRegDescription::new("BG3CNT", BG3CNT, video.bg_cnt[3]),
+            IoRegDescription::new("BG2PA", BG2PA, video.bg_affine[0].pa),
+            IoRegDescription::new("BG2PB", BG2PB, video.bg_affine[0].pb),
+            IoRegDescription::new("BG2PC", BG2PC, video.bg_affine[0].pc),
+            IoRegDescription::new("BG2PD", BG2PD, video.bg_affine[0].pd),
+            IoRegDescription::new("BG2X", BG2X, video.bg_affine[0].x),
+            IoRegDescription::new("BG2Y", BG2Y, video.bg_affine[0].y),
+            IoRegDescription::new("BG3PA", BG3PA, video.bg_affine[1].pa),
+            IoRegDescription::new("BG3PB", BG3PB, video.bg_affine[1].pb),
+            IoRegDescription::new("BG3PC", BG3PC, video.bg_affine[1].pc),
+            IoRegDescription::new("BG3PD", BG3PD, video.bg_affine[1].pd),
+            IoRegDescription::new("BG3X", BG3X, video.bg_affine[1].x),
+            IoRegDescription::new("BG3Y", BG3Y, video.bg_affine[1].y),
+            IoRegDescription::new("MOSAIC", MOSAIC, video.mosaic),
+            IoRegDescription::new("SIOCNT", SIOCNT, self.serial.siocnt),
+            IoRegDescription::new("KEYINPUT", KEYINPUT, self.keypad.keyinput),
+            IoRegDescription::new("RCNT", RCNT, self.serial.rcnt),
+            IoRegDescription::new("IE", IE, system_control.interrupt_enable),
+            IoRegDescription::new("IF", IF, system_control.interrupt_request),
+            IoRegDescription::new("WAITCNT", WAITCNT, system_control.waitcnt),
+            IoRegDescription::new("IME", IME, system_control.interrupt_master_enable),
+            IoRegDescription::new("IMC", IMC, system_control.internal_memory_control),
+        ]
+    }
+}
+
+/// Internal Memory Control is mirrored across the whole I/O area in increments of 64K.
+fn is_imc(address: u32) -> bool {
+    address & 0xFFFF == IMC & 0xFFFF
+}
+
+fn is_imc_hi(address: u32) -> bool {
+    address & 0xFFFF == IMC_H & 0xFFFF
+}
+
 #[derive(IoRegister, Copy, Clone)]
 #[repr(C)]
 pub struct UnimplementedIo16 {
@@ -80,10 +208,10 @@ pub const DISPCNT: u32 = 0x04000000;
 pub const GREENSWAP: u32 = 0x04000002;
 pub const DISPSTAT: u32 = 0x04000004;
 pub const VCOUNT: u32 = 0x04000006;
-// pub const BG0CNT: u32 = 0x04000008;
-// pub const BG1CNT: u32 = 0x0400000A;
-// pub const BG2CNT: u32 = 0x0400000C;
-// pub const BG3CNT: u32 = 0x0400000E;
+pub const BG0CNT: u32 = 0x04000008;
+pub const BG1CNT: u32 = 0x0400000A;
+pub const BG2CNT: u32 = 0x0400000C;
+pub const BG3CNT: u32 = 0x0400000E;
 // pub const BG0HOFS: u32 = 0x04000010;
 // pub const BG0VOFS: u32 = 0x04000012;
 // pub const BG1HOFS: u32 = 0x04000014;
@@ -92,30 +220,30 @@ pub const VCOUNT: u32 = 0x04000006;
 // pub const BG2VOFS: u32 = 0x0400001A;
 // pub const BG3HOFS: u32 = 0x0400001C;
 // pub const BG3VOFS: u32 = 0x0400001E;
-// pub const BG2PA: u32 = 0x04000020;
-// pub const BG2PB: u32 = 0x04000022;
-// pub const BG2PC: u32 = 0x04000024;
-// pub const BG2PD: u32 = 0x04000026;
-// pub const BG2X: u32 = 0x04000028;
-// pub const BG2X_H: u32 = 0x0400002A;
-// pub const BG2Y: u32 = 0x0400002C;
-// pub const BG2Y_H: u32 = 0x0400002E;
-// pub const BG3PA: u32 = 0x04000030;
-// pub const BG3PB: u32 = 0x04000032;
-// pub const BG3PC: u32 = 0x04000034;
-// pub const BG3PD: u32 = 0x04000036;
-// pub const BG3X: u32 = 0x04000038;
-// pub const BG3X_H: u32 = 0x0400003A;
-// pub const BG3Y: u32 = 0x0400003C;
-// pub const BG3Y_H: u32 = 0x0400003E;
+pub const BG2PA: u32 = 0x04000020;
+pub const BG2PB: u32 = 0x04000022;
+pub const BG2PC: u32 = 0x04000024;
+pub const BG2PD: u32 = 0x04000026;
+pub const BG2X: u32 = 0x04000028;
+pub const BG2X_H: u32 = 0x0400002A;
+pub const BG2Y: u32 = 0x0400002C;
+pub const BG2Y_H: u32 = 0x0400002E;
+pub const BG3PA: u32 = 0x04000030;
+pub const BG3PB: u32 = 0x04000032;
+pub const BG3PC: u32 = 0x04000034;
+pub const BG3PD: u32 = 0x04000036;
+pub const BG3X: u32 = 0x04000038;
+pub const BG3X_H: u32 = 0x0400003A;
+pub const BG3Y: u32 = 0x0400003C;
+pub const BG3Y_H: u32 = 0x0400003E;
 // pub const WIN0H: u32 = 0x04000040;
 // pub const WIN1H: u32 = 0x04000042;
 // pub const WIN0V: u32 = 0x04000044;
 // pub const WIN1V: u32 = 0x04000046;
 // pub const WININ: u32 = 0x04000048;
 // pub const WINOUT: u32 = 0x0400004A;
-// pub const MOSAIC: u32 = 0x0400004C;
-// pub const MOSAIC_HI: u32 = 0x0400004E;
+pub const MOSAIC: u32 = 0x0400004C;
+pub const MOSAIC_HI: u32 = 0x0400004E;
 // pub const BLDCNT: u32 = 0x04000050;
 // pub const BLDALPHA: u32 = 0x04000052;
 // pub const BLDY: u32 = 0x04000054;
@@ -224,14 +352,14 @@ pub const VCOUNT: u32 = 0x04000006;
 // pub const TM3CNT_L: u32 = 0x0400010C;
 // pub const TM3CNT_H: u32 = 0x0400010E;
 
-// // Serial Communication (1)_
+// Serial Communication (1)
 // pub const SIODATA32: u32 = 0x04000120;
-// pub const SIOMULTI0: u32 = 0x04000120;
+pub const SIOMULTI0: u32 = 0x04000120;
 // pub const SIOMULTI1: u32 = 0x04000122;
 // pub const SIOMULTI2: u32 = 0x04000124;
-// pub const SIOMULTI3: u32 = 0x04000126;
-// pub const SIOCNT: u32 = 0x04000128;
-// pub const SIOMLT_SEND: u32 = 0x0400012A;
+pub const SIOMULTI3: u32 = 0x04000126;
+pub const SIOCNT: u32 = 0x04000128;
+pub const SIOMLT_SEND: u32 = 0x0400012A;
 // pub const SIODATA8: u32 = 0x0400012A;
 
 // // Keypad Input
@@ -239,7 +367,7 @@ pub const KEYINPUT: u32 = 0x04000130;
 // pub const KEYCNT: u32 = 0x04000132;
 
 // // Serial Communication (2)
-// pub const RCNT: u32 = 0x04000134;
+pub const RCNT: u32 = 0x04000134;
 // pub const IR: u32 = 0x04000136;
 // pub const JOYCNT: u32 = 0x04000140;
 // pub const JOY_RECV: u32 = 0x04000150;
@@ -249,14 +377,14 @@ pub const KEYINPUT: u32 = 0x04000130;
 // pub const JOYSTAT: u32 = 0x04000158;
 
 // // Interrupt, Waitstate, and Power-Down Control
-// pub const IE: u32 = 0x04000200;
-// pub const IF: u32 = 0x04000202;
+pub const IE: u32 = 0x04000200;
+pub const IF: u32 = 0x04000202;
 // pub const IF_HI: u32 = 0x04000203;
-// pub const WAITCNT: u32 = 0x04000204;
-// pub const IME: u32 = 0x04000208;
-// pub const IME_HI: u32 = 0x0400020A;
+pub const WAITCNT: u32 = 0x04000204;
+pub const IME: u32 = 0x04000208;
+pub const IME_HI: u32 = 0x0400020A;
 // pub const POSTFLG: u32 = 0x04000300;
 // pub const HALTCNT: u32 = 0x04000301;
 // pub const BUG410: u32 = 0x04000410;
-// pub const IMC: u32 = 0x04000800;
-// pub const IMC_H: u32 = 0x04000802;
+pub const IMC: u32 = 0x04000800;
+pub const IMC_H: u32 = 0x04000802;
@@ -0,0 +1,233 @@
+use arm::emu::Memory as _;
+use gba::{backup::SaveType, Gba, NOP_ROM};
+
+#[macro_use]
+mod common;
+
+#[test]
+fn test_save_type_detection() {
+    let mut gamepak = vec![0; 0x200];
+    gamepak[0x100..0x10C].copy_from_slice(b"FLASH1M_V103");
+    let mut gba = Gba::new();
+    gba.set_gamepak(gamepak);
+    assert_eq!(gba.save_type(), SaveType::Flash128K);
+    assert_eq!(gba.mapped.backup.data().len(), 0x20000);
+
+    gba.set_gamepak(NOP_ROM.to_vec());
+    assert_eq!(gba.save_type(), SaveType::None);
+    assert!(gba.mapped.backup.data().is_empty());
+}
+
+/// Without an ID string there is no save memory until the save type is forced.
+/// Forcing 128KB flash makes both banks addressable.
+#[test]
+fn test_forced_flash128k_is_fully_addressable() {
+    let mut gba = common::load(
+        "
+        ldr r4, =#0x0E005555
+        ldr r5, =#0x0E002AAA
+        ldr r6, =#0x0E000000
+        ldr r7, =#0x0E00FFFF
+        mov r0, #0xAA
+        mov r1, #0x55
+
+        @ Write 5Ah to the last byte of bank 1.
+        strb r0, [r4]
+        strb r1, [r5]
+        mov r2, #0xB0
+        strb r2, [r4]
+        mov r2, #1
+        strb r2, [r6]
+        strb r0, [r4]
+        strb r1, [r5]
+        mov r2, #0xA0
+        strb r2, [r4]
+        mov r2, #0x5A
+        strb r2, [r7]
+        ldrb r8, [r7]
+
+        @ Write A5h to the last byte of bank 0.
+        strb r0, [r4]
+        strb r1, [r5]
+        mov r2, #0xB0
+        strb r2, [r4]
+        mov r2, #0
+        strb r2, [r6]
+        ldrb r9, [r7]
+        strb r0, [r4]
+        strb r1, [r5]
+        mov r2, #0xA0
+        strb r2, [r4]
+        mov r2, #0xA5
+        strb r2, [r7]
+        ldrb r10, [r7]
+
+        @ Read the manufacturer and device codes.
+        strb r0, [r4]
+        strb r1, [r5]
+        mov r2, #0x90
+        strb r2, [r4]
+        ldrb r11, [r6]
+        ldrb r12, [r6, #1]
+        strb r0, [r4]
+        strb r1, [r5]
+        mov r2, #0xF0
+        strb r2, [r4]
+        swi #0xCE
+        ",
+    );
+    assert_eq!(gba.save_type(), SaveType::None);
+    gba.set_save_type(SaveType::Flash128K);
+    assert_eq!(gba.save_type(), SaveType::Flash128K);
+    let gba = common::run_until_halted(gba);
+
+    assert_eq!(gba.cpu.registers.read(8), 0x5A);
+    assert_eq!(gba.cpu.registers.read(9), 0xFF, "bank 0 is still erased");
+    assert_eq!(gba.cpu.registers.read(10), 0xA5);
+    assert_eq!(gba.cpu.registers.read(11), 0x62);
+    assert_eq!(gba.cpu.registers.read(12), 0x13);
+
+    let data = gba.mapped.backup.data();
+    assert_eq!(data.len(), 0x20000);
+    assert_eq!(data[0x0FFFF], 0xA5);
+    assert_eq!(data[0x1FFFF], 0x5A);
+}
+
+#[test]
+fn test_forced_sram_is_mirrored() {
+    let mut gba = common::load(
+        "
+        ldr r4, =#0x0E000000
+        ldr r5, =#0x0E008000
+        mov r0, #0x42
+        strb r0, [r4, #0x10]
+        ldrb r1, [r5, #0x10]
+        ldr r2, [r4, #0x10]
+        swi #0xCE
+        ",
+    );
+    gba.set_save_type(SaveType::Sram32K);
+    let gba = common::run_until_halted(gba);
+    assert_eq!(gba.cpu.registers.read(1), 0x42);
+    assert_eq!(gba.cpu.registers.read(2), 0x42424242);
+    assert_eq!(gba.mapped.backup.data()[0x10], 0x42);
+}
+
+/// Sends an EEPROM request one bit at a time, first bit first.
+fn eeprom_send(gba: &mut Gba, bits: &[(u64, u32)]) {
+    for &(value, len) in bits {
+        for bit in (0..len).rev() {
+            let bit = ((value >> bit) & 0x1) as u16;
+            gba.mapped.store16(0x0D000000, bit, &mut gba.cpu);
+        }
+    }
+}
+
+#[test]
+fn test_forced_eeprom512() {
+    let mut gba = Gba::new();
+    gba.set_noop_gamepak();
+    gba.set_save_type(SaveType::Eeprom512);
+    assert_eq!(gba.mapped.backup.data().len(), 0x200);
+
+    let block = 0x0123456789ABCDEF;
+    eeprom_send(&mut gba, &[(0b10, 2), (0x3F, 6), (block, 64), (0, 1)]);
+    assert_eq!(gba.mapped.load16(0x0D000000, &mut gba.cpu).0, 1, "ready");
+
+    eeprom_send(&mut gba, &[(0b11, 2), (0x3F, 6), (0, 1)]);
+    let mut reply = 0u128;
+    for _ in 0..68 {
+        let (bit, _) = gba.mapped.load16(0x0D000000, &mut gba.cpu);
+        reply = (reply << 1) | u128::from(bit & 0x1);
+    }
+    assert_eq!(reply, u128::from(block));
+    assert_eq!(&gba.mapped.backup.data()[0x1F8..], &block.to_be_bytes());
+}
+
+/// Sends AAh to 5555h, 55h to 2AAAh and then `command` to 5555h.
+fn flash_command(gba: &mut Gba, command: u8) {
+    gba.mapped.store8(0x0E005555, 0xAA, &mut gba.cpu);
+    gba.mapped.store8(0x0E002AAA, 0x55, &mut gba.cpu);
+    gba.mapped.store8(0x0E005555, command, &mut gba.cpu);
+}
+
+fn flash_select_bank(gba: &mut Gba, bank: u8) {
+    flash_command(gba, 0xB0);
+    gba.mapped.store8(0x0E000000, bank, &mut gba.cpu);
+}
+
+fn flash_pattern(bank: u8, offset: u32) -> u8 {
+    (offset as u8 ^ (offset >> 8) as u8) ^ (bank * 0x5F)
+}
+
+#[test]
+fn test_flash128k_banks_are_independent() {
+    let mut gba = Gba::new();
+    gba.set_noop_gamepak();
+    gba.set_save_type(SaveType::Flash128K);
+
+    let offsets = (0..0x10000).step_by(0x0FF1);
+    for bank in 0..2 {
+        flash_select_bank(&mut gba, bank);
+        for offset in offsets.clone() {
+            flash_command(&mut gba, 0xA0);
+            let value = flash_pattern(bank, offset);
+            gba.mapped.store8(0x0E000000 + offset, value, &mut gba.cpu);
+        }
+    }
+    for bank in 0..2 {
+        flash_select_bank(&mut gba, bank);
+        for offset in offsets.clone() {
+            let (value, _) = gba.mapped.load8(0x0E000000 + offset, &mut gba.cpu);
+            assert_eq!(
+                value,
+                flash_pattern(bank, offset),
+                "bank {bank} 0x{offset:04X}"
+            );
+        }
+    }
+
+    // Erasing a sector only erases it in the selected bank.
+    flash_select_bank(&mut gba, 1);
+    flash_command(&mut gba, 0x80);
+    gba.mapped.store8(0x0E005555, 0xAA, &mut gba.cpu);
+    gba.mapped.store8(0x0E002AAA, 0x55, &mut gba.cpu);
+    gba.mapped.store8(0x0E000000, 0x30, &mut gba.cpu);
+    let data = gba.mapped.backup.data();
+    assert!(data[0x10000..0x11000].iter().all(|&byte| byte == 0xFF));
+    assert_eq!(data[0x0000], flash_pattern(0, 0));
+    assert_eq!(data[0x0FF1], flash_pattern(0, 0x0FF1));
+}
+
+/// Writes `block` to an EEPROM block and reads it back. Only the low 10 bits of
+/// the 14 address bits are used by the 8KB EEPROM.
+#[test]
+fn test_eeprom8k_write_then_read() {
+    let mut gba = Gba::new();
+    gba.set_noop_gamepak();
+    gba.set_save_type(SaveType::Eeprom8K);
+    assert_eq!(gba.mapped.backup.data().len(), 0x2000);
+
+    let block = 0xFEDCBA9876543210;
+    eeprom_send(&mut gba, &[(0b10, 2), (0x3C01, 14), (block, 64), (0, 1)]);
+    eeprom_send(&mut gba, &[(0b11, 2), (0x0001, 14), (0, 1)]);
+    let mut reply = 0u128;
+    for _ in 0..68 {
+        let (bit, _) = gba.mapped.load16(0x0DFFFF00, &mut gba.cpu);
+        reply = (reply << 1) | u128::from(bit & 0x1);
+    }
+    assert_eq!(reply, u128::from(block));
+    assert_eq!(&gba.mapped.backup.data()[0x8..0x10], &block.to_be_bytes());
+}
+
+/// EEPROM only takes over the top 256 bytes of 0Dxxxxxxh on gamepaks that are
+/// larger than 16MB.
+#[test]
+fn test_eeprom_mapping_with_large_gamepak() {
+    let mut gba = Gba::new();
+    gba.set_gamepak(vec![0xAB; 0x1000001]);
+    gba.set_save_type(SaveType::Eeprom8K);
+    // The gamepak is padded with zeroes after its last byte.
+    assert_eq!(gba.mapped.load16(0x0D000000, &mut gba.cpu).0, 0x00AB);
+    assert_eq!(gba.mapped.load16(0x0DFFFF00, &mut gba.cpu).0, 1);
+}
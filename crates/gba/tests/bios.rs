@@ -0,0 +1,528 @@
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+use arm::disasm::MemoryView as _;
+use gba::bios::BiosMode;
+use tracing_subscriber::fmt::MakeWriter;
+
+#[macro_use]
+mod common;
+
+#[test]
+fn test_hle_boot_skips_bios() {
+    let gba = emu_arm_hle! {"
+        mov r0, sp
+        swi #0xCE
+    "};
+    assert_eq!(gba.bios_mode(), BiosMode::Hle);
+    assert_eq!(gba.cpu.registers.read_mode(), arm::emu::CpuMode::System);
+    assert_eq!(gba.cpu.registers.read(0), 0x03007F00);
+}
+
+#[test]
+fn test_hle_div() {
+    let gba = emu_arm_hle! {"
+        ldr r0, =#-1234
+        ldr r1, =#10
+        swi #0x60000
+        swi #0xCE
+    "};
+    assert_eq!(gba.cpu.registers.read(0) as i32, -123);
+    assert_eq!(gba.cpu.registers.read(1) as i32, -4);
+    assert_eq!(gba.cpu.registers.read(3), 123);
+}
+
+#[test]
+fn test_hle_div_thumb() {
+    let gba = emu_arm_hle! {"
+        adr r0, thumb_start + 1
+        bx r0
+    .thumb
+    thumb_start:
+        ldr r0, =#1000
+        ldr r1, =#-7
+        swi #0x06
+        swi #0xCE
+    "};
+    assert_eq!(gba.cpu.registers.read(0) as i32, -142);
+    assert_eq!(gba.cpu.registers.read(1) as i32, 6);
+    assert_eq!(gba.cpu.registers.read(3), 142);
+}
+
+#[test]
+fn test_hle_lz77_uncomp_wram() {
+    // Decompresses to "ABCABCABCABC": three literal bytes followed by a single
+    // back-reference of length 9 with a displacement of 3.
+    let gba = emu_arm_hle! {"
+        adr r0, compressed
+        ldr r1, =#0x02000000
+        swi #0x110000
+        swi #0xCE
+    .align 2
+    compressed:
+        .byte 0x10, 0x0C, 0x00, 0x00
+        .byte 0x10, 0x41, 0x42, 0x43, 0x60, 0x02
+    "};
+    let output: Vec<u8> = (0..3)
+        .flat_map(|word| gba.mapped.view32(0x02000000 + word * 4).to_le_bytes())
+        .collect();
+    assert_eq!(&output, b"ABCABCABCABC");
+}
+
+#[test]
+fn test_hle_lz77_uncomp_vram() {
+    let gba = emu_arm_hle! {"
+        adr r0, compressed
+        ldr r1, =#0x06000000
+        swi #0x120000
+        swi #0xCE
+    .align 2
+    compressed:
+        .byte 0x10, 0x0C, 0x00, 0x00
+        .byte 0x10, 0x41, 0x42, 0x43, 0x60, 0x02
+    "};
+    let output: Vec<u8> = (0..3)
+        .flat_map(|word| gba.mapped.view32(0x06000000 + word * 4).to_le_bytes())
+        .collect();
+    assert_eq!(&output, b"ABCABCABCABC");
+}
+
+#[test]
+fn test_hle_cpu_set_fill_and_copy() {
+    let gba = emu_arm_hle! {"
+        @ Fill 5 words with 0x11223344.
+        adr r0, fill_word
+        ldr r1, =#0x02000000
+        ldr r2, =#0x05000005
+        swi #0x0B0000
+
+        @ Copy 3 halfwords. The destination is realigned to a halfword.
+        adr r0, halfwords
+        ldr r1, =#0x02000101
+        mov r2, #3
+        swi #0x0B0000
+        swi #0xCE
+    .align 2
+    fill_word:
+        .word 0x11223344
+    halfwords:
+        .hword 0xAAAA, 0xBBBB, 0xCCCC, 0xDDDD
+    "};
+    for word in 0..5 {
+        assert_eq!(gba.mapped.view32(0x02000000 + word * 4), 0x11223344);
+    }
+    assert_eq!(gba.mapped.view32(0x02000014), 0);
+    assert_eq!(gba.mapped.view16(0x02000100), 0xAAAA);
+    assert_eq!(gba.mapped.view16(0x02000102), 0xBBBB);
+    assert_eq!(gba.mapped.view16(0x02000104), 0xCCCC);
+    assert_eq!(gba.mapped.view16(0x02000106), 0);
+}
+
+#[test]
+fn test_hle_cpu_fast_set_fill_and_copy() {
+    let gba = emu_arm_hle! {"
+        @ Fill 3 words, which is rounded up to a block of 8.
+        adr r0, fill_word
+        ldr r1, =#0x02000000
+        ldr r2, =#0x01000003
+        swi #0x0C0000
+
+        @ Copy 8 words.
+        adr r0, words
+        ldr r1, =#0x02000100
+        mov r2, #8
+        swi #0x0C0000
+        swi #0xCE
+    .align 2
+    fill_word:
+        .word 0xCAFEBABE
+    words:
+        .word 1, 2, 3, 4, 5, 6, 7, 8, 9
+    "};
+    for word in 0..8 {
+        assert_eq!(gba.mapped.view32(0x02000000 + word * 4), 0xCAFEBABE);
+    }
+    assert_eq!(gba.mapped.view32(0x02000020), 0);
+    for word in 0..8 {
+        assert_eq!(gba.mapped.view32(0x02000100 + word * 4), word + 1);
+    }
+    assert_eq!(gba.mapped.view32(0x02000120), 0);
+}
+
+/// Angle 0 gives the scaling matrix and angle 4000h (90 degrees) rotates it.
+#[test]
+fn test_hle_obj_affine_set() {
+    let gba = emu_arm_hle! {"
+        adr r0, parameters
+        ldr r1, =#0x02000000
+        mov r2, #3
+        mov r3, #2
+        swi #0x0F0000
+
+        @ Write the first matrix into the first OAM parameter group.
+        adr r0, parameters
+        ldr r1, =#0x07000006
+        mov r2, #1
+        mov r3, #8
+        swi #0x0F0000
+        swi #0xCE
+    .align 2
+    parameters:
+        .hword 0x0100, 0x0100, 0x0000, 0
+        .hword 0x0100, 0x0100, 0x4000, 0
+        .hword 0x0200, 0x0080, 0x0000, 0
+    "};
+    let matrix = |address: u32, stride: u32| -> [u16; 4] {
+        std::array::from_fn(|index| gba.mapped.view16(address + index as u32 * stride))
+    };
+    assert_eq!(matrix(0x02000000, 2), [0x0100, 0x0000, 0x0000, 0x0100]);
+    assert_eq!(matrix(0x02000008, 2), [0x0000, 0xFF00, 0x0100, 0x0000]);
+    assert_eq!(matrix(0x02000010, 2), [0x0200, 0x0000, 0x0000, 0x0080]);
+    assert_eq!(matrix(0x07000006, 8), [0x0100, 0x0000, 0x0000, 0x0100]);
+}
+
+/// The reference point is the center of rotation in the background (16.0, 32.0)
+/// minus the matrix applied to the center of rotation on the screen (8, 4).
+#[test]
+fn test_hle_bg_affine_set() {
+    let gba = emu_arm_hle! {"
+        adr r0, parameters
+        ldr r1, =#0x02000000
+        mov r2, #2
+        swi #0x0E0000
+        swi #0xCE
+    .align 2
+    parameters:
+        .word 0x1000, 0x2000
+        .hword 8, 4, 0x0100, 0x0100, 0x0000, 0
+        .word 0x1000, 0x2000
+        .hword 8, 4, 0x0100, 0x0100, 0x4000, 0
+    "};
+    let parameters = |address: u32| {
+        let matrix: [u16; 4] =
+            std::array::from_fn(|index| gba.mapped.view16(address + index as u32 * 2));
+        let x = gba.mapped.view32(address + 8);
+        let y = gba.mapped.view32(address + 12);
+        (matrix, x, y)
+    };
+    assert_eq!(
+        parameters(0x02000000),
+        ([0x0100, 0x0000, 0x0000, 0x0100], 0x0800, 0x1C00)
+    );
+    assert_eq!(
+        parameters(0x02000010),
+        ([0x0000, 0xFF00, 0x0100, 0x0000], 0x1400, 0x1800)
+    );
+}
+
+#[test]
+fn test_hle_rl_uncomp_wram() {
+    // Decompresses to "AAAAAABCD": a run of 6 'A's followed by 3 literal bytes.
+    let gba = emu_arm_hle! {"
+        adr r0, compressed
+        ldr r1, =#0x02000000
+        swi #0x140000
+        swi #0xCE
+    .align 2
+    compressed:
+        .byte 0x30, 0x09, 0x00, 0x00
+        .byte 0x83, 0x41, 0x02, 0x42, 0x43, 0x44
+    "};
+    let output: Vec<u8> = (0..9)
+        .map(|byte| gba.mapped.view8(0x02000000 + byte))
+        .collect();
+    assert_eq!(&output, b"AAAAAABCD");
+    assert_eq!(gba.mapped.view8(0x02000009), 0);
+}
+
+#[test]
+fn test_hle_rl_uncomp_vram() {
+    let gba = emu_arm_hle! {"
+        adr r0, compressed
+        ldr r1, =#0x06000000
+        swi #0x150000
+        swi #0xCE
+    .align 2
+    compressed:
+        .byte 0x30, 0x08, 0x00, 0x00
+        .byte 0x83, 0x41, 0x01, 0x42, 0x43
+    "};
+    let output: Vec<u8> = (0..2)
+        .flat_map(|word| gba.mapped.view32(0x06000000 + word * 4).to_le_bytes())
+        .collect();
+    assert_eq!(&output, b"AAAAAABC");
+}
+
+#[test]
+fn test_hle_huff_uncomp() {
+    // A tree with a single node whose children are the values 'A' (0) and 'B' (1),
+    // decoding the bits 0110.
+    let gba = emu_arm_hle! {"
+        adr r0, compressed
+        ldr r1, =#0x02000000
+        swi #0x130000
+        swi #0xCE
+    .align 2
+    compressed:
+        .byte 0x28, 0x04, 0x00, 0x00
+        .byte 0x01, 0xC0, 0x41, 0x42
+        .word 0x60000000
+    "};
+    assert_eq!(gba.mapped.view32(0x02000000).to_le_bytes(), *b"ABBA");
+}
+
+#[test]
+fn test_hle_div_matches_native_division() {
+    // A fixed xorshift sequence so that failures are reproducible.
+    let mut state = 0x2545F491u32;
+    let mut random = move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as i32
+    };
+
+    let mut operands = vec![(i32::MIN, -1), (i32::MIN, 1), (7, -7), (-1, i32::MAX)];
+    while operands.len() < 256 {
+        let numerator = random();
+        // Mix in small denominators so that the quotients aren't all 0 or -1.
+        let denominator = match random() {
+            denominator if denominator & 1 == 0 => denominator >> 20,
+            denominator => denominator,
+        };
+        if denominator != 0 {
+            operands.push((numerator, denominator));
+        }
+    }
+
+    let table: String = operands
+        .iter()
+        .map(|&(numerator, denominator)| format!(".word {numerator}, {denominator}\n"))
+        .collect();
+    let source = format!(
+        "
+        adr r4, operands
+        ldr r5, =#0x02000000
+        ldr r6, =#{count}
+    next:
+        ldmia r4!, {{r0, r1}}
+        swi #0x60000
+        stmia r5!, {{r0, r1, r3}}
+        subs r6, r6, #1
+        bne next
+        swi #0xCE
+    .ltorg
+    .align 2
+    operands:
+        {table}
+    ",
+        count = operands.len(),
+    );
+    let gba = common::execute_with_bios_mode(&source, BiosMode::Hle);
+
+    for (index, &(numerator, denominator)) in operands.iter().enumerate() {
+        let result = 0x02000000 + index as u32 * 12;
+        let quotient = numerator.wrapping_div(denominator);
+        assert_eq!(
+            (
+                gba.mapped.view32(result) as i32,
+                gba.mapped.view32(result + 4) as i32,
+                gba.mapped.view32(result + 8),
+            ),
+            (
+                quotient,
+                numerator.wrapping_rem(denominator),
+                quotient.unsigned_abs()
+            ),
+            "{numerator} / {denominator}"
+        );
+    }
+}
+
+#[test]
+fn test_hle_div_arm() {
+    let gba = emu_arm_hle! {"
+        ldr r0, =#10
+        ldr r1, =#-1234
+        swi #0x70000
+        swi #0xCE
+    "};
+    assert_eq!(gba.cpu.registers.read(0) as i32, -123);
+    assert_eq!(gba.cpu.registers.read(1) as i32, -4);
+    assert_eq!(gba.cpu.registers.read(3), 123);
+}
+
+#[test]
+fn test_hle_sqrt() {
+    let gba = emu_arm_hle! {"
+        ldr r0, =#1000000
+        swi #0x80000
+        mov r4, r0
+        ldr r0, =#15
+        swi #0x80000
+        mov r5, r0
+        ldr r0, =#0xFFFFFFFF
+        swi #0x80000
+        swi #0xCE
+    "};
+    assert_eq!(gba.cpu.registers.read(4), 1000);
+    assert_eq!(gba.cpu.registers.read(5), 3);
+    assert_eq!(gba.cpu.registers.read(0), 0xFFFF);
+}
+
+#[test]
+fn test_hle_arctan() {
+    let gba = emu_arm_hle! {"
+        @ tan = 1.0
+        ldr r0, =#0x4000
+        swi #0x90000
+        mov r4, r0
+        @ tan = 0.5
+        ldr r0, =#0x2000
+        swi #0x90000
+        mov r5, r0
+        @ tan = -1.0
+        ldr r0, =#-0x4000
+        swi #0x90000
+        swi #0xCE
+    "};
+    assert_eq!(gba.cpu.registers.read(4), 0x2000);
+    assert_eq!(gba.cpu.registers.read(5), 0x12E4);
+    assert_eq!(gba.cpu.registers.read(0) as i32, -0x2000);
+}
+
+#[test]
+fn test_hle_arctan2() {
+    let gba = emu_arm_hle! {"
+        ldr r0, =#0x4000
+        ldr r1, =#0x4000
+        swi #0xA0000
+        mov r4, r0
+        ldr r0, =#-0x4000
+        mov r1, #0
+        swi #0xA0000
+        mov r5, r0
+        ldr r0, =#0x4000
+        ldr r1, =#-0x4000
+        swi #0xA0000
+        mov r6, r0
+        ldr r0, =#-0x4000
+        ldr r1, =#0x2000
+        swi #0xA0000
+        swi #0xCE
+    "};
+    assert_eq!(gba.cpu.registers.read(4), 0x2000);
+    assert_eq!(gba.cpu.registers.read(5), 0x8000);
+    assert_eq!(gba.cpu.registers.read(6), 0xE000);
+    assert_eq!(gba.cpu.registers.read(0), 0x6D1C);
+}
+
+#[test]
+fn test_hle_arctan2_min_x() {
+    let gba = emu_arm_hle! {"
+        ldr r0, =#0x80000000
+        ldr r1, =#0x4000
+        swi #0xA0000
+        swi #0xCE
+    "};
+    assert_eq!(gba.cpu.registers.read(0), 0x8000);
+}
+
+#[test]
+fn test_hle_arctan2_min_y() {
+    let gba = emu_arm_hle! {"
+        ldr r0, =#0x4000
+        ldr r1, =#0x80000000
+        swi #0xA0000
+        swi #0xCE
+    "};
+    assert_eq!(gba.cpu.registers.read(0), 0xC000);
+}
+
+/// Collects everything written by a tracing subscriber.
+#[derive(Clone, Default)]
+struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for CapturedLogs {
+    type Writer = CapturedLogs;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+#[test]
+fn test_swi_logging() {
+    let logs = CapturedLogs::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(logs.clone())
+        .with_max_level(tracing::Level::TRACE)
+        .finish();
+
+    tracing::subscriber::with_default(subscriber, || {
+        emu_arm_hle! {"
+            ldr r0, =#100
+            ldr r1, =#7
+            swi #0x60000
+            ldr r0, =#81
+            swi #0x80000
+            swi #0xCE
+        "}
+    });
+
+    let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    let swis: Vec<&str> = logs
+        .lines()
+        .filter(|line| line.contains(gba::bios::SWI_LOG_TARGET))
+        .collect();
+    assert_eq!(swis.len(), 2, "{logs}");
+    assert!(swis[0].contains("SWI 0x06"), "{}", swis[0]);
+    assert!(
+        swis[0].contains("r0=0x00000064 r1=0x00000007"),
+        "{}",
+        swis[0]
+    );
+    assert!(swis[1].contains("SWI 0x08"), "{}", swis[1]);
+    assert!(swis[1].contains("r0=0x00000051"), "{}", swis[1]);
+}
+
+/// Logging SWIs must not change emulation, e.g. by reading the comment through the
+/// gamepak prefetch buffer.
+#[test]
+fn test_swi_logging_has_no_side_effects() {
+    let run = || {
+        let mut gba = common::load(
+            "
+            ldr r0, =#100
+            ldr r1, =#7
+            swi #0x60000
+            swi #0xCE
+            ",
+        );
+        gba.set_bios_mode(BiosMode::Hle);
+        gba.reset();
+        gba.set_memory_stats_enabled(true);
+        let gba = common::run_until_halted(gba);
+        gba.memory_stats().unwrap().clone()
+    };
+
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(CapturedLogs::default())
+        .with_max_level(tracing::Level::TRACE)
+        .finish();
+    let logged = tracing::subscriber::with_default(subscriber, run);
+    assert_eq!(logged, run());
+}
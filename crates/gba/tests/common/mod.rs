@@ -1,23 +1,54 @@
-use std::{
-    path::Path,
-    sync::{
-        atomic::{self, AtomicBool},
-        Arc, Mutex,
-    },
-};
+pub mod operands;
+
+use std::{path::Path, sync::Mutex};
 
 use arm::{
     disasm::MemoryView as _,
-    emu::{CpuException, Cycles, ExceptionHandlerResult},
+    emu::{CpuException, ExceptionHandlerResult},
 };
 use arm_devkit::{LinkerScript, LinkerScriptWeakRef};
 use gba::{
-    video::LineBuffer, Gba, GbaMemoryMappedHardware, GbaVideoOutput, NoopGbaAudioOutput,
+    bios::BiosMode,
+    video::{FrameBuffer, LineBuffer},
+    Gba, GbaMemoryMappedHardware, GbaStepResult, GbaVideoOutput, NoopGbaAudioOutput,
     NoopGbaVideoOutput,
 };
 
 #[allow(dead_code)]
 pub fn execute(original_source: &str) -> Gba {
+    execute_with_bios_mode(original_source, BiosMode::Real)
+}
+
+#[allow(dead_code)]
+pub fn execute_with_bios_mode(original_source: &str, bios_mode: BiosMode) -> Gba {
+    let mut gba = Gba::new();
+    gba.set_gamepak(assemble_arm(original_source));
+    gba.set_bios_mode(bios_mode);
+    gba.reset();
+    run_until_halted(gba)
+}
+
+/// Same as [`execute`] but replaces the BIOS with `bios_source`. The BIOS is linked
+/// at the same address as the ROM so it must only use PC relative addressing.
+#[allow(dead_code)]
+pub fn execute_with_bios(original_source: &str, bios_source: &str) -> Gba {
+    let mut gba = Gba::new();
+    gba.set_gamepak(assemble_arm(original_source));
+    gba.set_bios(assemble_arm(bios_source)).unwrap();
+    gba.reset();
+    run_until_halted(gba)
+}
+
+/// Loads the program into a freshly reset GBA without running anything.
+#[allow(dead_code)]
+pub fn load(original_source: &str) -> Gba {
+    let mut gba = Gba::new();
+    gba.set_gamepak(assemble_arm(original_source));
+    gba.reset();
+    gba
+}
+
+fn assemble_arm(original_source: &str) -> Vec<u8> {
     let preamble = ".text\n.arm\n.global _start\n_start:\n";
     let mut source = String::with_capacity(original_source.len() + preamble.len());
     source.push_str(preamble);
@@ -28,12 +59,12 @@ pub fn execute(original_source: &str) -> Gba {
     // where you're likely to have your code ignored by your antivirus (e.g. Windows Defender)
     arm_devkit::set_internal_tempfile_directory(env!("CARGO_TARGET_TMPDIR"));
 
-    let mut gba = Gba::new();
-    gba.set_gamepak(arm_devkit::arm::assemble(&source, simple_linker_script()).unwrap());
-    gba.reset();
+    arm_devkit::arm::assemble(&source, simple_linker_script()).unwrap()
+}
 
-    let execution_ended: Arc<AtomicBool> = Arc::default();
-    let execution_ended_from_handler = execution_ended.clone();
+/// Makes the CPU halt when the program executes `swi #0xCE`.
+#[allow(dead_code)]
+pub fn halt_on_swi_ce(gba: &mut Gba) {
     gba.cpu
         .set_exception_handler(move |cpu, memory, exception| {
             if exception == CpuException::Swi {
@@ -50,21 +81,22 @@ pub fn execute(original_source: &str) -> Gba {
                 };
 
                 if comment == 0xCE {
-                    execution_ended_from_handler.store(true, atomic::Ordering::Release);
-                    return ExceptionHandlerResult::Handled(Cycles::from(1));
+                    return ExceptionHandlerResult::Halt;
                 }
             }
-            ExceptionHandlerResult::Ignored
+            gba::bios::exception_handler(cpu, memory, exception)
         });
+}
+
+/// Runs the GBA until the program executes `swi #0xCE`.
+#[allow(dead_code)]
+pub fn run_until_halted(mut gba: Gba) -> Gba {
+    halt_on_swi_ce(&mut gba);
 
     let start_time = std::time::Instant::now();
     let mut steps_since_time_chek = 0;
 
     loop {
-        if execution_ended.load(atomic::Ordering::Acquire) {
-            break;
-        }
-
         if steps_since_time_chek >= 1024 {
             if start_time.elapsed() > std::time::Duration::from_secs(5) {
                 let next_pc = gba.cpu.next_execution_address();
@@ -75,7 +107,9 @@ pub fn execute(original_source: &str) -> Gba {
             steps_since_time_chek += 1;
         }
 
-        gba.step(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput);
+        if gba.step(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput) == GbaStepResult::Halted {
+            break;
+        }
     }
 
     gba
@@ -131,6 +165,103 @@ where
     }
 }
 
+/// Runs the GBA until `frames` more frames have been rendered and returns the
+/// [`frame_hash`] of each one.
+#[allow(dead_code)]
+pub fn run_frame_hashes(gba: &mut Gba, frames: usize) -> Vec<u32> {
+    let mut output = FrameHashOutput::default();
+
+    let execution_started = std::time::Instant::now();
+    while output.hashes.len() < frames {
+        if execution_started.elapsed() > std::time::Duration::from_secs(30) {
+            let next_pc = gba.cpu.next_execution_address();
+            panic!("emulator timeout: 0x{next_pc:08X}");
+        }
+        gba.step(&mut output, &mut NoopGbaAudioOutput);
+    }
+
+    output.hashes
+}
+
+/// Loads a ROM and returns the [`frame_hash`] of each of the first `frames` frames.
+#[allow(dead_code)]
+pub fn rom_frame_hashes<P: AsRef<Path>>(rom_path: P, frames: usize) -> Vec<u32> {
+    let mut gba = Gba::new();
+    gba.set_gamepak(std::fs::read(rom_path.as_ref()).expect("error reading ROM file"));
+    gba.reset();
+    run_frame_hashes(&mut gba, frames)
+}
+
+/// Compares frame hashes against the golden hashes stored in
+/// `tests/data/frames/<name>.txt`. Setting the `PYRITE_BLESS` environment variable
+/// writes `hashes` to the golden file instead.
+#[allow(dead_code)]
+pub fn assert_frame_hashes(name: &str, hashes: &[u32]) {
+    let golden_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/data/frames")
+        .join(format!("{name}.txt"));
+
+    if std::env::var_os("PYRITE_BLESS").is_some() {
+        let contents: String = hashes.iter().map(|hash| format!("{hash:08X}\n")).collect();
+        std::fs::create_dir_all(golden_path.parent().unwrap())
+            .expect("error creating golden frame directory");
+        std::fs::write(&golden_path, contents).expect("error writing golden frame hashes");
+        return;
+    }
+
+    let contents = std::fs::read_to_string(&golden_path).unwrap_or_else(|err| {
+        panic!("error reading golden frame hashes (path: {golden_path:?}): {err}")
+    });
+    let golden: Vec<u32> = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| u32::from_str_radix(line, 16).expect("invalid golden frame hash"))
+        .collect();
+
+    assert_eq!(
+        golden.len(),
+        hashes.len(),
+        "frame count mismatch for `{name}`"
+    );
+    for (frame, (&expected, &actual)) in golden.iter().zip(hashes).enumerate() {
+        assert_eq!(
+            expected, actual,
+            "frame {frame} of `{name}` does not match: expected=0x{expected:08X}, found=0x{actual:08X}"
+        );
+    }
+}
+
+/// CRC-32 (IEEE) of the little-endian bytes of every pixel in a frame.
+#[allow(dead_code)]
+pub fn frame_hash(frame: &FrameBuffer) -> u32 {
+    let mut crc = !0u32;
+    for byte in frame.pixels().iter().flat_map(|pixel| pixel.to_le_bytes()) {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[derive(Default)]
+struct FrameHashOutput {
+    hashes: Vec<u32>,
+}
+
+impl GbaVideoOutput for FrameHashOutput {
+    fn gba_line_ready(&mut self, _line: usize, _data: &LineBuffer) {}
+
+    fn gba_frame_ready(&mut self, frame: &FrameBuffer) {
+        self.hashes.push(frame_hash(frame));
+    }
+}
+
 #[allow(dead_code)]
 pub fn video_noop(_: usize, _: &LineBuffer) {}
 #[allow(dead_code)]
@@ -164,3 +295,10 @@ macro_rules! emu_arm {
         $crate::common::execute(&format!($source))
     };
 }
+
+#[macro_export]
+macro_rules! emu_arm_hle {
+    ($source:expr) => {
+        $crate::common::execute_with_bios_mode(&format!($source), ::gba::bios::BiosMode::Hle)
+    };
+}
@@ -0,0 +1,83 @@
+#![allow(dead_code)]
+
+use gba::keypad::{Key, KeyInputState};
+use rand::Rng;
+
+/// Word values that commonly break things, like sign and carry boundaries.
+pub fn edge_words() -> impl Iterator<Item = u32> {
+    [
+        0, 1, 0x7F, 0x80, 0xFF, 0x7FFF, 0x8000, 0xFFFF, 0x7FFFFFFF, 0x80000000, 0xFFFFFFFF,
+    ]
+    .into_iter()
+}
+
+pub fn rand_operand<T>(mut count: usize) -> impl Iterator<Item = T>
+where
+    rand::distributions::Standard: rand::distributions::Distribution<T>,
+{
+    std::iter::from_fn(move || {
+        if count == 0 {
+            return None;
+        }
+
+        count -= 1;
+        let mut rng = rand::thread_rng();
+        Some(rng.gen())
+    })
+}
+
+/// The edge words followed by `count` random words. These are also useful as
+/// register values.
+pub fn words(count: usize) -> impl Iterator<Item = u32> {
+    edge_words().chain(rand_operand(count))
+}
+
+/// `count` random addresses in `range` that are aligned to `align` bytes.
+pub fn rand_aligned_address(
+    mut count: usize,
+    range: std::ops::Range<u32>,
+    align: u32,
+) -> impl Iterator<Item = u32> {
+    std::iter::from_fn(move || {
+        if count == 0 {
+            return None;
+        }
+
+        count -= 1;
+        let mut rng = rand::thread_rng();
+        Some(rng.gen_range(range.clone()) & !(align - 1))
+    })
+}
+
+/// `count` blocks of random bytes with a random length of 1 to `max_len` bytes.
+pub fn rand_memory_block(mut count: usize, max_len: usize) -> impl Iterator<Item = Vec<u8>> {
+    std::iter::from_fn(move || {
+        if count == 0 {
+            return None;
+        }
+
+        count -= 1;
+        let mut rng = rand::thread_rng();
+        let len = rng.gen_range(1..=max_len);
+        Some((0..len).map(|_| rng.gen()).collect())
+    })
+}
+
+/// `count` random states of every key, indexed by [`Key`].
+pub fn rand_key_states(mut count: usize) -> impl Iterator<Item = [KeyInputState; Key::COUNT]> {
+    std::iter::from_fn(move || {
+        if count == 0 {
+            return None;
+        }
+
+        count -= 1;
+        let mut rng = rand::thread_rng();
+        Some(std::array::from_fn(|_| {
+            if rng.gen() {
+                KeyInputState::Pressed
+            } else {
+                KeyInputState::Released
+            }
+        }))
+    })
+}
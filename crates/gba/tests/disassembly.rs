@@ -0,0 +1,140 @@
+use arm::{
+    disasm::{arm as disasm_arm, thumb as disasm_thumb, InstructionSet, MemoryView},
+    emu::Memory as _,
+};
+
+#[macro_use]
+mod common;
+
+fn dump(gba: &gba::Gba, start: u32, len: u32, isa: InstructionSet) -> String {
+    let mut out = Vec::new();
+    gba.write_disassembly(&mut out, start, len, isa).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn test_dump_disassembly() {
+    let gba = common::load(
+        "
+        mov r0, #1
+        add r0, r0, #2
+        str r0, [r1]
+        bx lr
+    ",
+    );
+
+    let listing = dump(&gba, 0x08000000, 16, InstructionSet::Arm);
+    let lines: Vec<&str> = listing.lines().collect();
+    assert_eq!(lines.len(), 4, "{listing}");
+    assert!(lines[0].starts_with("08000000:  e3a00001"), "{listing}");
+    assert!(lines[3].starts_with("0800000c:  e12fff1e"), "{listing}");
+
+    let mnemonics: Vec<&str> = lines
+        .iter()
+        .map(|line| line.split_whitespace().nth(2).unwrap())
+        .collect();
+    assert_eq!(mnemonics, ["mov", "add", "str", "bx"]);
+}
+
+#[test]
+fn test_dump_disassembly_at_region_boundaries() {
+    let gba = common::load("mov r0, #1");
+
+    // Unaligned starts are aligned down and unmapped memory reads as zero.
+    let listing = dump(&gba, 0x00FFFFFE, 4, InstructionSet::Thumb);
+    let lines: Vec<&str> = listing.lines().collect();
+    assert_eq!(lines.len(), 2, "{listing}");
+    assert!(lines[0].starts_with("00fffffe:  0000"), "{listing}");
+    assert!(lines[1].starts_with("01000000:  0000"), "{listing}");
+
+    // The listing stops at the end of the address space.
+    let listing = dump(&gba, 0xFFFFFFFC, 0x100, InstructionSet::Arm);
+    assert_eq!(listing.lines().count(), 1, "{listing}");
+    assert!(listing.starts_with("fffffffc:  "), "{listing}");
+}
+
+/// Both halves of a THUMB `bl` are listed on one line with both opcodes.
+#[test]
+fn test_dump_thumb_bl_pair() {
+    let mut gba = gba::Gba::new();
+    gba.reset();
+    gba.mapped.store16(0x03000000, 0xF000, &mut gba.cpu);
+    gba.mapped.store16(0x03000002, 0xF802, &mut gba.cpu);
+    gba.mapped.store16(0x03000004, 0x2105, &mut gba.cpu);
+
+    let listing = dump(&gba, 0x03000000, 6, InstructionSet::Thumb);
+    let lines: Vec<&str> = listing.lines().collect();
+    assert_eq!(lines.len(), 2, "{listing}");
+    assert!(
+        lines[0].starts_with("03000000:  f000 f802  bl"),
+        "{listing}"
+    );
+    assert!(
+        lines[1].starts_with("03000004:  2105       mov"),
+        "{listing}"
+    );
+
+    // The second half is decoded on its own if it is out of range.
+    let listing = dump(&gba, 0x03000000, 2, InstructionSet::Thumb);
+    assert_eq!(listing.lines().count(), 1, "{listing}");
+    assert!(listing.starts_with("03000000:  f000 "), "{listing}");
+}
+
+/// Large listings are read from memory in 4KB chunks. A `bl` pair that starts at the
+/// end of one is still listed whole and the listing continues after it.
+#[test]
+fn test_dump_thumb_bl_pair_across_chunks() {
+    let mut gba = gba::Gba::new();
+    gba.reset();
+    gba.mapped.store16(0x03000FFE, 0xF000, &mut gba.cpu);
+    gba.mapped.store16(0x03001000, 0xF802, &mut gba.cpu);
+    gba.mapped.store16(0x03001002, 0x2105, &mut gba.cpu);
+
+    let listing = dump(&gba, 0x03000000, 0x2000, InstructionSet::Thumb);
+    let lines: Vec<&str> = listing.lines().collect();
+    assert_eq!(lines.len(), 0x1000 - 1, "{listing}");
+    assert!(
+        lines[0x7FF].starts_with("03000ffe:  f000 f802  bl"),
+        "{listing}"
+    );
+    assert!(
+        lines[0x800].starts_with("03001002:  2105       mov"),
+        "{listing}"
+    );
+    assert!(
+        lines.last().unwrap().starts_with("03001ffe:  "),
+        "{listing}"
+    );
+}
+
+#[test]
+fn test_disassemble_iwram_through_memory_view() {
+    let mut gba = gba::Gba::new();
+    gba.reset();
+
+    // ldr r0, [pc, #0] followed by the word that it loads.
+    gba.mapped.store32(0x03000100, 0xE59F0000, &mut gba.cpu);
+    gba.mapped.store32(0x03000108, 0xDEADBEEF, &mut gba.cpu);
+    // mov r1, #5
+    gba.mapped.store16(0x03000200, 0x2105, &mut gba.cpu);
+
+    let memory: &dyn MemoryView = &gba.mapped;
+    assert_eq!(memory.view32(0x03000100), 0xE59F0000);
+    assert_eq!(memory.view16(0x03000102), 0xE59F);
+    assert_eq!(memory.view8(0x03000103), 0xE5);
+    // IWRAM is mirrored every 32KB.
+    assert_eq!(memory.view32(0x03008100), 0xE59F0000);
+
+    let instr = disasm_arm::disasm(memory.view32(0x03000100), 0x03000100);
+    assert_eq!(instr.mnemonic().to_string(), "ldr");
+    // The comment shows the value that is loaded, which is read through the view.
+    let text = instr.full(0x03000100, Some(memory)).to_string();
+    assert!(text.ends_with("r0 = 0xdeadbeef"), "{text}");
+
+    let instr = disasm_thumb::disasm(memory.view16(0x03000200), 0x03000200);
+    assert_eq!(instr.mnemonic().to_string(), "mov");
+    assert_eq!(
+        instr.arguments(0x03000200, Some(memory)).to_string(),
+        "r1, #0x5"
+    );
+}
@@ -0,0 +1,263 @@
+use std::sync::{Arc, Mutex};
+
+use arm::emu::Cycles;
+use gba::{
+    video::{HBLANK_CYCLES, HDRAW_CYCLES},
+    Gba, GbaStepResult, NoopGbaAudioOutput, NoopGbaVideoOutput,
+};
+
+#[macro_use]
+mod common;
+
+#[test]
+pub fn user_events_fire_at_the_scheduled_cycle() {
+    let mut gba = Gba::new();
+    gba.set_noop_gamepak();
+    gba.reset();
+
+    let fired = Arc::new(Mutex::new(Vec::new()));
+    let fired_in_handler = fired.clone();
+    gba.set_user_event_handler(move |_cpu, mapped, id| {
+        let video = &mapped.video;
+        fired_in_handler
+            .lock()
+            .unwrap()
+            .push((id, video.current_scanline(), video.in_hblank()));
+    });
+
+    let line_5 = Cycles::new(5 * u32::from(HDRAW_CYCLES + HBLANK_CYCLES));
+    // scheduled out of order to make sure that they are still delivered in order
+    gba.schedule_user_event(line_5 + HDRAW_CYCLES + Cycles::one(), 2);
+    gba.schedule_user_event(line_5 + Cycles::new(100), 1);
+    gba.schedule_user_event(line_5 + Cycles::new(100), 3);
+
+    while gba.current_scanline() < 7 {
+        gba.step(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput);
+    }
+
+    assert_eq!(
+        *fired.lock().unwrap(),
+        vec![(1, 5, false), (3, 5, false), (2, 5, true)]
+    );
+}
+
+#[test]
+pub fn pending_events_in_order() {
+    let mut gba = Gba::new();
+    gba.set_noop_gamepak();
+    gba.reset();
+
+    assert_eq!(gba.pending_events(), vec![(HDRAW_CYCLES, "HBlank")]);
+
+    gba.schedule_user_event(Cycles::new(2000), 0);
+    let pending = gba.pending_events();
+    assert_eq!(
+        pending,
+        vec![(HDRAW_CYCLES, "HBlank"), (Cycles::new(2000), "User")]
+    );
+    assert_eq!(gba.pending_events(), pending);
+    assert_eq!(gba.pending_event_count(), 2);
+
+    while !gba.in_hblank() {
+        gba.step(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput);
+    }
+    let pending = gba.pending_events();
+    assert_eq!(pending.len(), 2);
+    assert_eq!(pending[0].1, "HDraw");
+    assert_eq!(pending[1].1, "User");
+    assert!(pending[0].0 <= HBLANK_CYCLES);
+    assert_eq!(
+        u32::from(pending[1].0) - u32::from(pending[0].0),
+        2000 - u32::from(HDRAW_CYCLES + HBLANK_CYCLES)
+    );
+}
+
+#[test]
+pub fn advance_to_next_event_lands_on_hblank() {
+    let mut gba = Gba::new();
+    gba.set_noop_gamepak();
+    gba.reset();
+
+    assert_eq!(gba.cycles_until_next_event(), HDRAW_CYCLES);
+    let elapsed = gba.advance_to_next_event(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput);
+    assert!(gba.in_hblank());
+    assert_eq!(gba.current_scanline(), 0);
+
+    // The last instruction may run past HBlank but those cycles count towards HDraw.
+    assert!(elapsed >= HDRAW_CYCLES);
+    let overshoot = elapsed - HDRAW_CYCLES;
+    assert!(overshoot < Cycles::new(16), "overshoot: {overshoot:?}");
+    assert_eq!(gba.cycles_until_next_event(), HBLANK_CYCLES - overshoot);
+    assert_eq!(
+        gba.pending_events(),
+        vec![(HBLANK_CYCLES - overshoot, "HDraw")]
+    );
+
+    let until_hdraw = gba.cycles_until_next_event();
+    let elapsed = gba.advance_to_next_event(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput);
+    assert!(elapsed >= until_hdraw);
+    assert!(!gba.in_hblank());
+    assert_eq!(gba.current_scanline(), 1);
+}
+
+#[test]
+pub fn idle_loop_skips_to_the_next_event() {
+    let mut gba = common::load("b .");
+    gba.set_idle_loop_skipping(true);
+    gba.set_pc(0x08000000);
+
+    // The first iteration records the state at the start of the loop and the second
+    // one matches it.
+    let until_hblank = gba.cycles_until_next_event();
+    gba.step(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput);
+    assert!(!gba.in_hblank());
+    assert!(gba.cycles_until_next_event() < until_hblank);
+    gba.step(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput);
+    assert!(gba.in_hblank());
+    assert_eq!(gba.pending_events(), vec![(HBLANK_CYCLES, "HDraw")]);
+
+    // An event fired since the last iteration so this one only records the state.
+    gba.step(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput);
+    assert!(gba.in_hblank());
+    gba.step(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput);
+    assert!(!gba.in_hblank());
+    assert_eq!(gba.current_scanline(), 1);
+}
+
+/// Counts the steps it takes to reach `swi #0xCE`.
+fn steps_until_halted(mut gba: Gba) -> (Gba, usize) {
+    common::halt_on_swi_ce(&mut gba);
+    let mut steps = 1;
+    while gba.step(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput) != GbaStepResult::Halted {
+        steps += 1;
+    }
+    (gba, steps)
+}
+
+const WAIT_FOR_VCOUNT_160: &str = "
+    ldr r4, =#0x04000006
+wait:
+    ldrh r0, [r4]
+    cmp r0, #160
+    bne wait
+    swi #0xCE
+";
+
+#[test]
+pub fn idle_loop_skipping_polling_vcount() {
+    let mut gba = common::load(WAIT_FOR_VCOUNT_160);
+    gba.set_pc(0x08000000);
+    let (gba, steps) = steps_until_halted(gba);
+    assert_eq!(gba.current_scanline(), 160);
+
+    let mut skipping = common::load(WAIT_FOR_VCOUNT_160);
+    skipping.set_idle_loop_skipping(true);
+    skipping.set_pc(0x08000000);
+    let (skipping, skipping_steps) = steps_until_halted(skipping);
+    assert_eq!(skipping.current_scanline(), 160);
+    assert_eq!(skipping.frame_count(), gba.frame_count());
+
+    // Each event takes two iterations of the loop to be detected.
+    assert!(
+        skipping_steps < 160 * 2 * 8,
+        "{skipping_steps} steps with skipping, {steps} without"
+    );
+}
+
+/// Polls VCOUNT with enough instructions after the load that the line can change in
+/// the middle of an iteration.
+const WAIT_FOR_VCOUNT_1: &str = "
+    ldr r4, =#0x04000006
+wait:
+    ldrh r0, [r4]
+    mov r1, r1
+    mov r1, r1
+    mov r1, r1
+    mov r1, r1
+    mov r1, r1
+    mov r1, r1
+    cmp r0, #1
+    bne wait
+    swi #0xCE
+";
+
+/// The address of `wait` in [`WAIT_FOR_VCOUNT_1`].
+const WAIT_FOR_VCOUNT_1_LOOP: u32 = 0x08000004;
+
+/// An iteration that VCOUNT changed in ends with the same registers as the one
+/// before it, because it read VCOUNT before the change, but it must not be skipped.
+#[test]
+pub fn idle_loop_skipping_ignores_iterations_with_events() {
+    // Find the iteration that VCOUNT changes in without skipping.
+    let mut gba = common::load(WAIT_FOR_VCOUNT_1);
+    gba.set_pc(0x08000000);
+    common::halt_on_swi_ce(&mut gba);
+    let mut iterations = Vec::new();
+    let mut changed_at = None;
+    let mut steps = 0;
+    loop {
+        if gba.cpu.next_execution_address() == WAIT_FOR_VCOUNT_1_LOOP {
+            iterations.push(steps);
+        }
+        let line = gba.current_scanline();
+        steps += 1;
+        if gba.step(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput) == GbaStepResult::Halted {
+            break;
+        }
+        if changed_at.is_none() && gba.current_scanline() != line {
+            changed_at = Some((steps - 1, iterations.len() - 1));
+        }
+    }
+    let (changed_at, iteration) = changed_at.unwrap();
+    assert!(
+        changed_at > iterations[iteration],
+        "VCOUNT must change after it was read"
+    );
+
+    // Start skipping at the beginning of the iteration before that one.
+    let mut skipping = common::load(WAIT_FOR_VCOUNT_1);
+    skipping.set_pc(0x08000000);
+    common::halt_on_swi_ce(&mut skipping);
+    let mut skipping_steps = iterations[iteration - 1];
+    for _ in 0..skipping_steps {
+        skipping.step(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput);
+    }
+    skipping.set_idle_loop_skipping(true);
+    loop {
+        skipping_steps += 1;
+        if skipping.step(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput) == GbaStepResult::Halted
+        {
+            break;
+        }
+    }
+
+    assert_eq!(skipping_steps, steps);
+    assert_eq!(skipping.current_scanline(), 1);
+    assert_eq!(skipping.in_hblank(), gba.in_hblank());
+    assert_eq!(
+        skipping.cycles_until_next_event(),
+        gba.cycles_until_next_event()
+    );
+}
+
+/// Loops that write to memory are never skipped even if the registers are the
+/// same after every iteration.
+#[test]
+pub fn idle_loop_skipping_ignores_loops_with_side_effects() {
+    let source = "
+        ldr r4, =#0x04000006
+        ldr r5, =#0x03000000
+    wait:
+        str r4, [r5]
+        ldrh r0, [r4]
+        cmp r0, #160
+        bne wait
+        swi #0xCE
+    ";
+    let (_, steps) = steps_until_halted(common::load(source));
+    let mut skipping = common::load(source);
+    skipping.set_idle_loop_skipping(true);
+    let (skipping, skipping_steps) = steps_until_halted(skipping);
+    assert_eq!(skipping.current_scanline(), 160);
+    assert_eq!(skipping_steps, steps);
+}
@@ -0,0 +1,195 @@
+use std::sync::{Arc, Mutex};
+
+use arm::emu::Memory as _;
+use gba::{
+    gpio::{
+        rtc::{PIN_CS, PIN_SCK, PIN_SIO, STATUS_24_HOUR},
+        PIN_RUMBLE,
+    },
+    Gba,
+};
+
+const GPIO_DATA: u32 = 0x080000C4;
+const GPIO_DIRECTION: u32 = 0x080000C6;
+const GPIO_CONTROL: u32 = 0x080000C8;
+
+const CMD_RESET: u8 = 0x60;
+const CMD_DATETIME: u8 = 0x64;
+const CMD_STATUS: u8 = 0x68;
+const READ: u8 = 0x1;
+
+fn gba_with_rtc() -> Gba {
+    let mut gba = Gba::new();
+    gba.set_gamepak((0..0x200).map(|byte| byte as u8).collect());
+    gba.reset();
+    gba.mapped.store16(GPIO_CONTROL, 1, &mut gba.cpu);
+    gba
+}
+
+fn write_pins(gba: &mut Gba, pins: u8) {
+    gba.mapped.store16(GPIO_DATA, u16::from(pins), &mut gba.cpu);
+}
+
+/// Starts a transfer and sends the command MSB first.
+fn send_command(gba: &mut Gba, command: u8) {
+    gba.mapped.store16(
+        GPIO_DIRECTION,
+        u16::from(PIN_SCK | PIN_SIO | PIN_CS),
+        &mut gba.cpu,
+    );
+    write_pins(gba, PIN_SCK);
+    write_pins(gba, PIN_SCK | PIN_CS);
+    for bit in (0..8).rev() {
+        let sio = if (command >> bit) & 1 != 0 {
+            PIN_SIO
+        } else {
+            0
+        };
+        write_pins(gba, PIN_CS | sio);
+        write_pins(gba, PIN_CS | PIN_SCK | sio);
+    }
+}
+
+fn end_transfer(gba: &mut Gba) {
+    write_pins(gba, PIN_SCK);
+}
+
+fn read_bytes(gba: &mut Gba, command: u8, len: usize) -> Vec<u8> {
+    send_command(gba, command | READ);
+    gba.mapped
+        .store16(GPIO_DIRECTION, u16::from(PIN_SCK | PIN_CS), &mut gba.cpu);
+
+    let bytes = (0..len)
+        .map(|_| {
+            (0..8).fold(0, |byte, bit| {
+                write_pins(gba, PIN_CS);
+                write_pins(gba, PIN_CS | PIN_SCK);
+                let data = gba.mapped.load16(GPIO_DATA, &mut gba.cpu).0 as u8;
+                byte | (((data & PIN_SIO) >> 1) << bit)
+            })
+        })
+        .collect();
+    end_transfer(gba);
+    bytes
+}
+
+fn write_bytes(gba: &mut Gba, command: u8, bytes: &[u8]) {
+    send_command(gba, command);
+    for byte in bytes {
+        for bit in 0..8 {
+            let sio = if (byte >> bit) & 1 != 0 { PIN_SIO } else { 0 };
+            write_pins(gba, PIN_CS | sio);
+            write_pins(gba, PIN_CS | PIN_SCK | sio);
+        }
+    }
+    end_transfer(gba);
+}
+
+fn is_bcd(value: u8) -> bool {
+    value & 0xF < 10 && value >> 4 < 10
+}
+
+#[test]
+fn test_gpio_is_only_readable_when_enabled() {
+    let mut gba = gba_with_rtc();
+    gba.mapped.store16(GPIO_DIRECTION, 0x5, &mut gba.cpu);
+    assert_eq!(gba.mapped.load16(GPIO_DIRECTION, &mut gba.cpu).0, 0x5);
+    assert_eq!(gba.mapped.load16(GPIO_CONTROL, &mut gba.cpu).0, 0x1);
+
+    gba.mapped.store16(GPIO_CONTROL, 0, &mut gba.cpu);
+    assert_eq!(gba.mapped.load16(GPIO_DATA, &mut gba.cpu).0, 0xC5C4);
+    assert_eq!(gba.mapped.load32(GPIO_DATA, &mut gba.cpu).0, 0xC7C6C5C4);
+}
+
+#[test]
+fn test_rtc_read_status() {
+    let mut gba = gba_with_rtc();
+    assert_eq!(read_bytes(&mut gba, CMD_STATUS, 1), [0x00]);
+
+    write_bytes(&mut gba, CMD_STATUS, &[STATUS_24_HOUR | 0x81]);
+    assert_eq!(read_bytes(&mut gba, CMD_STATUS, 1), [STATUS_24_HOUR]);
+}
+
+#[test]
+fn test_rtc_read_date_time() {
+    let mut gba = gba_with_rtc();
+    write_bytes(&mut gba, CMD_STATUS, &[STATUS_24_HOUR]);
+
+    let date_time = read_bytes(&mut gba, CMD_DATETIME, 7);
+    let [year, month, day, weekday, hour, minute, second] = date_time[..] else {
+        unreachable!()
+    };
+    assert!(
+        date_time.iter().all(|&value| is_bcd(value)),
+        "{date_time:02X?}"
+    );
+    assert!(year >= 0x24, "year: {year:02X}");
+    assert!((0x01..=0x12).contains(&month), "month: {month:02X}");
+    assert!((0x01..=0x31).contains(&day), "day: {day:02X}");
+    assert!(weekday < 7, "weekday: {weekday}");
+    assert!(hour < 0x24, "hour: {hour:02X}");
+    assert!(minute < 0x60, "minute: {minute:02X}");
+    assert!(second < 0x60, "second: {second:02X}");
+}
+
+#[test]
+fn test_rtc_set_date_time() {
+    let mut gba = gba_with_rtc();
+    write_bytes(&mut gba, CMD_STATUS, &[STATUS_24_HOUR]);
+
+    // Tuesday, 2021-06-15 13:45:30
+    write_bytes(
+        &mut gba,
+        CMD_DATETIME,
+        &[0x21, 0x06, 0x15, 0x02, 0x13, 0x45, 0x30],
+    );
+    let date_time = read_bytes(&mut gba, CMD_DATETIME, 7);
+    assert_eq!(date_time[..6], [0x21, 0x06, 0x15, 0x02, 0x13, 0x45]);
+    assert!(date_time[6] == 0x30 || date_time[6] == 0x31);
+
+    // The time that the game set is kept across a reset of the GBA.
+    let offset = gba.mapped.gpio.rtc.offset();
+    gba.reset();
+    assert_eq!(gba.mapped.gpio.rtc.offset(), offset);
+
+    // Resetting the RTC goes back to 2000-01-01 00:00:00 in 12 hour mode.
+    gba.mapped.store16(GPIO_CONTROL, 1, &mut gba.cpu);
+    write_bytes(&mut gba, CMD_RESET, &[]);
+    assert_eq!(read_bytes(&mut gba, CMD_STATUS, 1), [0x00]);
+    let date_time = read_bytes(&mut gba, CMD_DATETIME, 7);
+    assert_eq!(date_time[..6], [0x00, 0x01, 0x01, 0x06, 0x00, 0x00]);
+}
+
+#[test]
+fn test_rumble_handler_observes_transitions() {
+    let mut gba = gba_with_rtc();
+    let transitions = Arc::new(Mutex::new(Vec::new()));
+    let observed = transitions.clone();
+    gba.set_rumble_handler(move |rumble| observed.lock().unwrap().push(rumble));
+
+    // The pin only drives the motor while it is an output.
+    write_pins(&mut gba, PIN_RUMBLE);
+    assert!(transitions.lock().unwrap().is_empty());
+    gba.mapped
+        .store16(GPIO_DIRECTION, u16::from(PIN_RUMBLE), &mut gba.cpu);
+    write_pins(&mut gba, PIN_RUMBLE);
+    write_pins(&mut gba, 0);
+    write_pins(&mut gba, 0);
+    write_pins(&mut gba, PIN_RUMBLE);
+    assert_eq!(*transitions.lock().unwrap(), [true, false, true]);
+    assert!(gba.mapped.gpio.rumble());
+
+    // A reset turns the motor off.
+    gba.reset();
+    assert_eq!(*transitions.lock().unwrap(), [true, false, true, false]);
+}
+
+#[test]
+fn test_disconnected_rtc_ignores_the_port() {
+    let mut gba = gba_with_rtc();
+    write_bytes(&mut gba, CMD_STATUS, &[STATUS_24_HOUR]);
+    gba.mapped.gpio.set_rtc_enabled(false);
+    assert_eq!(read_bytes(&mut gba, CMD_STATUS, 1), [0x00]);
+    gba.mapped.gpio.set_rtc_enabled(true);
+    assert_eq!(read_bytes(&mut gba, CMD_STATUS, 1), [STATUS_24_HOUR]);
+}
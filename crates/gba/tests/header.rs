@@ -0,0 +1,39 @@
+use gba::{
+    header::{CartridgeHeader, NINTENDO_LOGO},
+    memory::GAMEPAK_HEADER_SIZE,
+    Gba,
+};
+
+#[test]
+fn test_header_logo() {
+    let rom = std::fs::read("../../roms/custom/mode3-test.gba").unwrap();
+    let header = CartridgeHeader::parse(&rom).unwrap();
+    assert_eq!(header.logo(), NINTENDO_LOGO);
+    assert!(header.logo_valid());
+    assert_eq!(header.title(), "mode3-test");
+    assert_eq!(header.game_code(), "0000");
+    assert_eq!(header.maker_code(), "00");
+
+    let mut corrupted = rom.clone();
+    corrupted[0x04 + 100] ^= 0x01;
+    assert!(!CartridgeHeader::parse(&corrupted).unwrap().logo_valid());
+
+    // The last byte of the logo is checked too.
+    let mut corrupted = rom;
+    corrupted[0x9F] = 0;
+    assert!(!CartridgeHeader::parse(&corrupted).unwrap().logo_valid());
+}
+
+#[test]
+fn test_header_too_small() {
+    assert!(CartridgeHeader::parse(&[0; GAMEPAK_HEADER_SIZE - 1]).is_none());
+
+    let mut gba = Gba::new();
+    gba.set_noop_gamepak();
+    assert!(gba.mapped.cartridge_header().is_none());
+
+    gba.set_gamepak(vec![0; GAMEPAK_HEADER_SIZE]);
+    let header = gba.mapped.cartridge_header().unwrap();
+    assert!(!header.logo_valid());
+    assert_eq!(header.title(), "");
+}
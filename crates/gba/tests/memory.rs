@@ -1,4 +1,15 @@
-use arm::disasm::MemoryView as _;
+use arm::{
+    disasm::MemoryView as _,
+    emu::{Memory as _, Waitstates},
+};
+use common::operands::{rand_aligned_address, rand_key_states, rand_memory_block, words};
+use gba::{
+    bios::BiosMode,
+    keypad::{Buttons, Key, KeyInputState},
+    memory::{Fill, MemoryRegion, BIOS_SIZE, GAMEPAK_HEADER_SIZE, GAMEPAK_MAX_SIZE},
+    AccuracyMode, BiosError, GamepakError, Gba, GbaStepResult, NoopGbaAudioOutput,
+    NoopGbaVideoOutput,
+};
 
 #[macro_use]
 mod common;
@@ -12,6 +23,29 @@ fn test_simple_gba_emulation_run() {
     assert_eq!(gba.cpu.registers.read(0), 0xDEADBEEF);
 }
 
+#[test]
+fn test_swi_halt() {
+    let mut gba = emu_arm! {"
+        mov r0, #1
+        swi #0xCE
+        mov r0, #2
+    "};
+    let halted_at = gba.cpu.next_execution_address();
+    assert!(gba.cpu.is_halted());
+
+    for _ in 0..4 {
+        let result = gba.step(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput);
+        assert_eq!(result, GbaStepResult::Halted);
+    }
+    assert_eq!(gba.cpu.registers.read(0), 1);
+    assert_eq!(gba.cpu.next_execution_address(), halted_at);
+
+    gba.cpu.resume();
+    let result = gba.step(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput);
+    assert_eq!(result, GbaStepResult::Running);
+    assert_eq!(gba.cpu.registers.read(0), 2);
+}
+
 #[test]
 fn test_32bit_read_from_bios() {
     // Reading from BIOS Memory (00000000-00003FFF)
@@ -31,10 +65,81 @@ fn test_32bit_read_from_bios() {
 }
 
 #[test]
-fn test_32bit_read_from_unused_memory() {
-    // FIXME For now I only (crudely) emulate reading from unused memory. Will need something a bit more involved
-    //       to emulate the other behaviors, but will do that a later time. -- Marc
+fn test_bios_read_protection() {
+    let source = "
+        ldr r1, =#0x4
+        ldr r0, [r1]
+        ldrh r2, [r1]
+        ldrb r3, [r1, #3]
+        swi #0xCE
+    ";
+
+    // Code in ROM only sees the last opcode that was fetched from the BIOS.
+    let protected = common::run_until_halted(common::load(source));
+    let real = protected.mapped.view32(0x4);
+    assert_eq!(protected.cpu.registers.read(0), 0xE12FFF10);
+    assert_ne!(protected.cpu.registers.read(0), real);
+    assert_eq!(protected.cpu.registers.read(2), 0xFF10);
+    assert_eq!(protected.cpu.registers.read(3), 0xE1);
+
+    let mut gba = common::load(source);
+    gba.set_bios_read_protection(false);
+    let unprotected = common::run_until_halted(gba);
+    assert_eq!(unprotected.cpu.registers.read(0), real);
+    assert_eq!(unprotected.cpu.registers.read(2), real & 0xFFFF);
+    assert_eq!(unprotected.cpu.registers.read(3), real >> 24);
+}
+
+#[test]
+fn test_memory_stats() {
+    let mut gba = common::load(
+        "
+        ldr r1, =#0x02000000
+        ldr r2, =#0x03000000
+        ldr r0, [r1]
+        ldrh r0, [r1, #2]
+        ldrb r0, [r1, #3]
+        ldr r0, [r2]
+        ldr r0, [r2, #4]
+        str r0, [r2, #8]
+        swi #0xCE
+    ",
+    );
+    assert!(gba.memory_stats().is_none());
+
+    // Skip the BIOS boot sequence so that it doesn't touch IWRAM.
+    gba.set_bios_mode(BiosMode::Hle);
+    gba.reset();
+    gba.set_memory_stats_enabled(true);
+    let mut gba = common::run_until_halted(gba);
+
+    let stats = gba.memory_stats().unwrap();
+    assert_eq!(stats.reads(MemoryRegion::Ewram), 3);
+    assert_eq!(stats.writes(MemoryRegion::Ewram), 0);
+    assert_eq!(stats.reads(MemoryRegion::Iwram), 2);
+    assert_eq!(stats.writes(MemoryRegion::Iwram), 1);
+    assert!(stats.reads(MemoryRegion::Gamepak) > 0);
+    assert_eq!(stats.reads(MemoryRegion::Vram), 0);
 
+    // Slice copies count every byte, with or without the work RAM fast path.
+    gba.clear_memory_stats();
+    let mut buffer = [0; 8];
+    gba.mapped.load_slice(0x02000000, &mut buffer, &mut gba.cpu);
+    gba.mapped.store_slice(0x03000000, &buffer, &mut gba.cpu);
+    gba.mapped.load_slice(0x06000000, &mut buffer, &mut gba.cpu);
+    let stats = gba.memory_stats().unwrap();
+    assert_eq!(stats.reads(MemoryRegion::Ewram), 8);
+    assert_eq!(stats.writes(MemoryRegion::Iwram), 8);
+    assert_eq!(stats.reads(MemoryRegion::Vram), 8);
+
+    gba.clear_memory_stats();
+    assert_eq!(gba.memory_stats().unwrap().reads(MemoryRegion::Ewram), 0);
+    gba.set_memory_stats_enabled(false);
+    assert!(gba.memory_stats().is_none());
+}
+
+#[test]
+fn test_32bit_read_from_unused_memory() {
     // Reading from Unused Memory (00004000-01FFFFFF,10000000-FFFFFFFF)
     //      Accessing unused memory at 00004000h-01FFFFFFh, and 10000000h-FFFFFFFFh (and 02000000h-03FFFFFFh
     //      when RAM is disabled via Port 4000800h) returns the recently pre-fetched opcode. For ARM code this is simply:
@@ -72,6 +177,39 @@ fn test_32bit_read_from_unused_memory() {
     assert_eq!(gba.cpu.registers.read(0), 0xE3A00000);
 }
 
+#[test]
+fn test_16bit_and_8bit_read_from_unused_memory() {
+    // Narrow reads return the part of the prefetched opcode selected by the address.
+    let gba = emu_arm! {"
+        ldr r1, =#0x10000002
+        ldrh r0, [r1]
+        ldr r1, =#0x10000003
+        ldrb r2, [r1]           @ <-- fetched by the ldrh
+        swi #0xCE
+        mov r0, #0              @ <-- fetched by the ldrb
+        bx  r0
+    "};
+    assert_eq!(gba.cpu.registers.read(0), 0xE5D1);
+    assert_eq!(gba.cpu.registers.read(2), 0xE3);
+}
+
+#[test]
+fn test_thumb_read_from_unused_memory() {
+    // For THUMB code in cartridge ROM the prefetched opcode is repeated in both halves
+    // of the word: LSW = [$+4], MSW = [$+4]
+    let gba = emu_arm! {"
+        adr r0, thumb_start + 1
+        bx r0
+    .thumb
+    thumb_start:
+        ldr r1, =#0x10000000
+        ldr r0, [r1]
+        swi #0xCE               @ <-- decoded
+        mov r2, #0x55           @ <-- fetched
+    "};
+    assert_eq!(gba.cpu.registers.read(0), 0x22552255);
+}
+
 #[test]
 fn test_ewram_mirror_32bit() {
     let gba = emu_arm! {"
@@ -371,3 +509,577 @@ fn test_swi_using_custom_bios() {
     "};
     assert_eq!(gba.mapped.view32(0x02000000), 0xDEADBEEF);
 }
+
+/// Runs a loop of 32-bit loads from `address` and returns the number of scanlines
+/// that it took to complete.
+fn scanlines_for_loads(address: u32) -> u32 {
+    let gba = emu_arm! {"
+        ldr r4, =#0x04000006
+        ldr r1, =#{address}
+        ldr r2, =#2000
+        ldrh r5, [r4]
+    loop:
+        ldr r3, [r1]
+        subs r2, r2, #1
+        bne loop
+        ldrh r6, [r4]
+        subs r0, r6, r5
+        addmi r0, r0, #228
+        swi #0xCE
+    "};
+    gba.cpu.registers.read(0)
+}
+
+#[test]
+fn test_ewram_waitstates() {
+    // EWRAM has 2 waitstates by default (3/3/6 cycles for 8/16/32-bit accesses) while
+    // IWRAM is accessed in a single cycle.
+    let ewram = scanlines_for_loads(0x02000000);
+    let iwram = scanlines_for_loads(0x03000000);
+    assert!(ewram > iwram, "ewram={ewram}, iwram={iwram}");
+}
+
+#[test]
+fn test_waitcnt_read_write() {
+    let gba = emu_arm! {"
+        ldr r1, =#0x04000204
+        ldr r2, =#0x4317
+        strh r2, [r1]
+        ldrh r0, [r1]
+        swi #0xCE
+    "};
+    assert_eq!(gba.cpu.registers.read(0), 0x4317);
+    assert_eq!(gba.mapped.system_control.waitstates.sram, 8.into());
+    assert_eq!(
+        gba.mapped.system_control.waitstates.gamepak[0],
+        (3.into(), 1.into())
+    );
+}
+
+/// Runs a loop from ROM that leaves the gamepak bus idle for a few cycles every
+/// iteration and returns the number of scanlines that it took to complete.
+fn scanlines_for_rom_loop(waitcnt: u32) -> u32 {
+    let gba = emu_arm! {"
+        ldr r1, =#0x04000204
+        ldr r2, =#{waitcnt}
+        strh r2, [r1]
+        ldr r4, =#0x04000006
+        ldr r1, =#0x03000000
+        ldr r2, =#2000
+        ldrh r5, [r4]
+    loop:
+        ldr r3, [r1]
+        ldr r3, [r1]
+        ldr r3, [r1]
+        subs r2, r2, #1
+        bne loop
+        ldrh r6, [r4]
+        subs r0, r6, r5
+        addmi r0, r0, #228
+        swi #0xCE
+    "};
+    gba.cpu.registers.read(0)
+}
+
+#[test]
+fn test_gamepak_prefetch() {
+    // WS0 = 3,1 with and without the prefetch buffer enabled.
+    let prefetch_on = scanlines_for_rom_loop(0x4014);
+    let prefetch_off = scanlines_for_rom_loop(0x0014);
+    assert!(
+        prefetch_on < prefetch_off,
+        "prefetch_on={prefetch_on}, prefetch_off={prefetch_off}"
+    );
+}
+
+/// Runs a program that copies words out of ROM in the given accuracy mode until it
+/// reaches the branch to itself at the end. Returns the GBA and the number of
+/// cycles that it took.
+fn run_copy_loop(mode: AccuracyMode) -> (Gba, u64) {
+    let mut gba = common::load(
+        "
+        ldr r1, =#0x04000204
+        ldr r2, =#0x4317
+        strh r2, [r1]
+        ldr r1, =#0x08000000
+        ldr r2, =#0x02000000
+        ldr r3, =#0x03000100
+        mov r0, #0
+        mov r4, #64
+    loop:
+        ldr r5, [r1], #4
+        add r0, r0, r5
+        str r5, [r2], #4
+        str r0, [r3], #4
+        subs r4, r4, #1
+        bne loop
+    end:
+        b end
+    ",
+    );
+    gba.set_accuracy_mode(mode);
+    assert_eq!(gba.accuracy_mode(), mode);
+
+    let mut cycles = 0u64;
+    for _ in 0..1_000_000 {
+        let step = gba
+            .step_instruction(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput)
+            .expect("CPU halted");
+        cycles += u64::from(u32::from(step.cycles));
+        if step.address >= 0x08000000 && gba.cpu.next_execution_address() == step.address {
+            return (gba, cycles);
+        }
+    }
+    panic!("copy loop did not finish");
+}
+
+#[test]
+fn test_accuracy_modes() {
+    let (fast, fast_cycles) = run_copy_loop(AccuracyMode::Fast);
+    let (cycle, cycle_cycles) = run_copy_loop(AccuracyMode::Cycle);
+
+    for register in 0..16 {
+        assert_eq!(
+            fast.cpu.registers.read(register),
+            cycle.cpu.registers.read(register),
+            "r{register}"
+        );
+    }
+    for offset in (0..0x100).step_by(4) {
+        assert_eq!(
+            fast.mapped.view32(0x02000000 + offset),
+            cycle.mapped.view32(0x02000000 + offset)
+        );
+        assert_eq!(
+            fast.mapped.view32(0x03000100 + offset),
+            cycle.mapped.view32(0x03000100 + offset)
+        );
+    }
+    assert_eq!(
+        cycle.mapped.view32(0x02000000),
+        cycle.mapped.view32(0x08000000)
+    );
+
+    // WAITCNT makes the gamepak faster than its startup timings and the
+    // prefetch buffer is ignored in fast mode.
+    assert!(
+        cycle_cycles < fast_cycles,
+        "cycle={cycle_cycles}, fast={fast_cycles}"
+    );
+}
+
+#[test]
+fn test_gamepak_size_validation() {
+    let mut gba = Gba::new();
+
+    assert_eq!(
+        gba.try_set_gamepak(vec![0; GAMEPAK_HEADER_SIZE - 1]),
+        Err(GamepakError::TooSmall(GAMEPAK_HEADER_SIZE - 1))
+    );
+    assert_eq!(
+        gba.try_set_gamepak(vec![0; GAMEPAK_MAX_SIZE + 1]),
+        Err(GamepakError::TooLarge(GAMEPAK_MAX_SIZE + 1))
+    );
+
+    // 0xC0 bytes is padded to 0x100 bytes and mirrored from there.
+    let mut gamepak = vec![0; GAMEPAK_HEADER_SIZE];
+    gamepak[..4].copy_from_slice(&0xDEADBEEFu32.to_le_bytes());
+    assert_eq!(gba.try_set_gamepak(gamepak), Ok(()));
+    assert_eq!(gba.mapped.view32(0x08000000), 0xDEADBEEF);
+    assert_eq!(gba.mapped.view32(0x080000C0), 0);
+    assert_eq!(gba.mapped.view32(0x08000100), 0xDEADBEEF);
+
+    // The no-op gamepak is smaller than a header but is still allowed.
+    gba.set_noop_gamepak();
+    assert_eq!(gba.mapped.view32(0x08000000), 0xEAFFFFFE);
+}
+
+#[test]
+fn test_bios_size_validation() {
+    let mut gba = Gba::new();
+
+    assert_eq!(
+        gba.set_bios(vec![0; BIOS_SIZE + 1]),
+        Err(BiosError::TooLarge(BIOS_SIZE + 1))
+    );
+
+    // Smaller images are padded with zeroes and take effect immediately.
+    assert_eq!(gba.set_bios(0xDEADBEEFu32.to_le_bytes().to_vec()), Ok(()));
+    assert_eq!(gba.mapped.view32(0x00000000), 0xDEADBEEF);
+    assert_eq!(gba.mapped.view32(0x00000004), 0);
+}
+
+#[test]
+fn test_gamepak_mirroring() {
+    let mut gba = emu_arm! {"
+        ldr     r4, =#0x08000000
+        ldr     r0, [r4, #0x10]
+        add     r5, r4, #0x02000000
+        ldr     r1, [r5, #0x10]
+        add     r5, r4, #0x04000000
+        ldr     r2, [r5, #0x10]
+        ldrh    r3, [r5, #0x12]
+        swi     #0xCE
+    "};
+
+    // The same word through each of the three waitstate regions.
+    let word = gba.mapped.view32(0x08000010);
+    assert_ne!(word, 0);
+    assert_eq!(gba.cpu.registers.read(0), word);
+    assert_eq!(gba.cpu.registers.read(1), word);
+    assert_eq!(gba.cpu.registers.read(2), word);
+    assert_eq!(gba.cpu.registers.read(3), word >> 16);
+
+    // Gamepaks that are smaller than a region are mirrored within it.
+    let mut gamepak = vec![0; 0x100];
+    gamepak[0x10..0x14].copy_from_slice(&0xDEADBEEFu32.to_le_bytes());
+    gba.set_gamepak(gamepak);
+    for address in [0x08000010, 0x08000110, 0x09FFFF10, 0x0A000010, 0x0DFFFF10] {
+        assert_eq!(gba.mapped.load32(address, &mut gba.cpu).0, 0xDEADBEEF);
+        assert_eq!(gba.mapped.load16(address + 2, &mut gba.cpu).0, 0xDEAD);
+        assert_eq!(gba.mapped.load8(address + 1, &mut gba.cpu).0, 0xBE);
+    }
+}
+
+#[test]
+fn test_uninitialized_fill() {
+    let boot = |fill: Fill| {
+        let mut gba = Gba::new();
+        gba.set_noop_gamepak();
+        gba.set_uninitialized_fill(fill);
+        gba.reset();
+        (gba.mapped.ewram.to_vec(), gba.mapped.vram.to_vec())
+    };
+
+    let (ewram, vram) = boot(Fill::Zero);
+    assert!(ewram.iter().chain(&vram).all(|&byte| byte == 0));
+
+    let (ewram_a, vram_a) = boot(Fill::Seeded(0x5EED));
+    let (ewram_b, vram_b) = boot(Fill::Seeded(0x5EED));
+    let (ewram_c, vram_c) = boot(Fill::Seeded(0xFEED));
+    assert_eq!(ewram_a, ewram_b);
+    assert_eq!(vram_a, vram_b);
+    assert_ne!(ewram_a, ewram_c);
+    assert_ne!(vram_a, vram_c);
+    assert_ne!(ewram_a, ewram);
+}
+
+#[test]
+fn test_slice_access_matches_byte_access() {
+    let data: Vec<u8> = (0..0x40u8).map(|b| b.wrapping_mul(37)).collect();
+
+    // EWRAM and IWRAM take the fast path. The rest falls back to byte accesses,
+    // including ranges that wrap around a mirror.
+    for address in [0x02000010, 0x0203FFE0, 0x03001000, 0x03007FE0, 0x06000100] {
+        let mut fast = Gba::new();
+        let mut slow = Gba::new();
+
+        let fast_wait = fast.mapped.store_slice(address, &data, &mut fast.cpu);
+        let mut slow_wait = Waitstates::zero();
+        for (offset, &byte) in data.iter().enumerate() {
+            slow_wait += slow
+                .mapped
+                .store8(address + offset as u32, byte, &mut slow.cpu);
+        }
+        assert_eq!(fast_wait, slow_wait, "store at 0x{address:08X}");
+
+        let mut fast_data = vec![0; data.len()];
+        let fast_wait = fast
+            .mapped
+            .load_slice(address, &mut fast_data, &mut fast.cpu);
+        let mut slow_data = vec![0; data.len()];
+        let mut slow_wait = Waitstates::zero();
+        for (offset, byte) in slow_data.iter_mut().enumerate() {
+            let (value, wait) = slow.mapped.load8(address + offset as u32, &mut slow.cpu);
+            *byte = value;
+            slow_wait += wait;
+        }
+        assert_eq!(fast_wait, slow_wait, "load at 0x{address:08X}");
+        assert_eq!(fast_data, slow_data, "load at 0x{address:08X}");
+        assert_eq!(fast.mapped.ewram[..], slow.mapped.ewram[..]);
+        assert_eq!(fast.mapped.iwram[..], slow.mapped.iwram[..]);
+        assert_eq!(fast.mapped.vram[..], slow.mapped.vram[..]);
+    }
+}
+
+#[test]
+fn test_16bit_bus_regions() {
+    let mut gba = Gba::new();
+    gba.reset();
+    // Forced blank keeps the video hardware off of the bus so that only the bus
+    // width affects the waitstates.
+    gba.mapped.store16(0x04000000, 0x0080, &mut gba.cpu);
+
+    // EWRAM, palette RAM and VRAM have a 16-bit bus, so a 32-bit access is split
+    // into two 16-bit accesses and pays the waitstates for both.
+    let ewram_wait = gba.mapped.load16(0x02000000, &mut gba.cpu).1;
+    assert_ne!(ewram_wait, Waitstates::zero());
+    assert_eq!(
+        gba.mapped.load32(0x02000000, &mut gba.cpu).1,
+        ewram_wait + ewram_wait
+    );
+    assert_eq!(
+        gba.mapped.store32(0x02000000, 0, &mut gba.cpu),
+        ewram_wait + ewram_wait
+    );
+    for address in [0x05000000, 0x06000000] {
+        assert_eq!(
+            gba.mapped.load16(address, &mut gba.cpu).1,
+            Waitstates::zero()
+        );
+        assert_eq!(
+            gba.mapped.load32(address, &mut gba.cpu).1,
+            Waitstates::one()
+        );
+        assert_eq!(
+            gba.mapped.store32(address, 0, &mut gba.cpu),
+            Waitstates::one()
+        );
+    }
+
+    // IWRAM has a 32-bit bus.
+    assert_eq!(
+        gba.mapped.load32(0x03000000, &mut gba.cpu).1,
+        Waitstates::zero()
+    );
+
+    // Misaligned accesses are forced to the bus width of the access.
+    gba.mapped.store32(0x02000102, 0xDEADBEEF, &mut gba.cpu);
+    assert_eq!(gba.mapped.view32(0x02000100), 0xDEADBEEF);
+    assert_eq!(gba.mapped.load16(0x02000103, &mut gba.cpu).0, 0xDEAD);
+}
+
+#[test]
+fn test_video_memory_access_during_rendering() {
+    let mut gba = Gba::new();
+    gba.set_noop_gamepak();
+    gba.reset();
+
+    let step_until = |gba: &mut Gba, done: fn(&Gba) -> bool| {
+        while !done(gba) {
+            gba.step(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput);
+        }
+    };
+
+    // Line 0 is being drawn right after a reset.
+    step_until(&mut gba, |gba| !gba.in_vblank() && !gba.in_hblank());
+    let hdraw_wait = gba.mapped.store16(0x06000000, 0, &mut gba.cpu);
+    assert_eq!(hdraw_wait, Waitstates::one());
+    assert_eq!(
+        gba.mapped.load16(0x05000000, &mut gba.cpu).1,
+        Waitstates::one()
+    );
+    assert_eq!(
+        gba.mapped.load32(0x06000000, &mut gba.cpu).1,
+        Waitstates::from(2u32)
+    );
+
+    // Only OAM is in use during HBlank, unless H-Blank Interval Free is set.
+    step_until(&mut gba, |gba| gba.in_hblank() && !gba.in_vblank());
+    assert_eq!(
+        gba.mapped.store16(0x06000000, 0, &mut gba.cpu),
+        Waitstates::zero()
+    );
+    assert_eq!(
+        gba.mapped.store16(0x07000000, 0, &mut gba.cpu),
+        Waitstates::one()
+    );
+    gba.mapped.store16(0x04000000, 0x0020, &mut gba.cpu);
+    assert_eq!(
+        gba.mapped.store16(0x07000000, 0, &mut gba.cpu),
+        Waitstates::zero()
+    );
+    gba.mapped.store16(0x04000000, 0x0000, &mut gba.cpu);
+
+    step_until(&mut gba, |gba| gba.in_vblank());
+    let vblank_wait = gba.mapped.store16(0x06000000, 0, &mut gba.cpu);
+    assert_eq!(vblank_wait, Waitstates::zero());
+    assert!(hdraw_wait > vblank_wait);
+
+    // The fast accuracy mode ignores the video hardware.
+    step_until(&mut gba, |gba| !gba.in_vblank() && !gba.in_hblank());
+    gba.set_accuracy_mode(AccuracyMode::Fast);
+    assert_eq!(
+        gba.mapped.store16(0x06000000, 0, &mut gba.cpu),
+        Waitstates::zero()
+    );
+}
+
+#[test]
+fn test_iwram_access_widths() {
+    let mut gba = Gba::new();
+    gba.reset();
+
+    let addresses = rand_aligned_address(usize::MAX, 0x03000000..0x03008000, 4);
+    for (address, value) in addresses.zip(words(64)) {
+        gba.mapped.store32(address, value, &mut gba.cpu);
+        assert_eq!(gba.mapped.load32(address, &mut gba.cpu).0, value);
+        for (offset, half) in [(0, value as u16), (2, (value >> 16) as u16)] {
+            assert_eq!(gba.mapped.load16(address + offset, &mut gba.cpu).0, half);
+        }
+        for (offset, &byte) in value.to_le_bytes().iter().enumerate() {
+            let address = address + offset as u32;
+            assert_eq!(gba.mapped.load8(address, &mut gba.cpu).0, byte);
+        }
+
+        let value = !value;
+        gba.mapped.store16(address, value as u16, &mut gba.cpu);
+        gba.mapped
+            .store16(address + 2, (value >> 16) as u16, &mut gba.cpu);
+        assert_eq!(gba.mapped.load32(address, &mut gba.cpu).0, value);
+
+        let value = value.rotate_left(8);
+        for (offset, &byte) in value.to_le_bytes().iter().enumerate() {
+            gba.mapped
+                .store8(address + offset as u32, byte, &mut gba.cpu);
+        }
+        assert_eq!(gba.mapped.load32(address, &mut gba.cpu).0, value);
+    }
+}
+
+#[test]
+fn test_ewram_memory_blocks() {
+    let mut gba = Gba::new();
+    gba.reset();
+
+    let addresses = rand_aligned_address(usize::MAX, 0x02000000..0x02030000, 1);
+    for (address, block) in addresses.zip(rand_memory_block(32, 0x1000)) {
+        gba.mapped.store_slice(address, &block, &mut gba.cpu);
+        let mut read = vec![0; block.len()];
+        gba.mapped.load_slice(address, &mut read, &mut gba.cpu);
+        assert_eq!(read, block, "block at 0x{address:08X}");
+        assert_eq!(gba.mapped.view8(address), block[0]);
+    }
+}
+
+#[test]
+fn test_keyinput_reflects_key_states() {
+    let mut gba = Gba::new();
+    gba.reset();
+
+    for states in rand_key_states(64) {
+        let mut expected = 0;
+        for (index, &state) in states.iter().enumerate() {
+            let key = Key::try_from(index).unwrap();
+            gba.keypad_mut().keyinput.set_key_state(key, state);
+            if matches!(state, KeyInputState::Released) {
+                expected |= 1 << index;
+            }
+        }
+        assert_eq!(gba.mapped.load16(0x04000130, &mut gba.cpu).0, expected);
+    }
+}
+
+/// The ROM waits for A to be pressed and then records the scanline. The script
+/// presses A at the start of frame 10, when the frame counter reaches it in the
+/// HBlank of the last visible line.
+#[test]
+fn test_input_script_presses_buttons_on_its_frame() {
+    let mut gba = common::load(
+        "
+        ldr r4, =#0x04000130
+        ldr r5, =#0x04000006
+    wait_for_a:
+        ldrh r0, [r4]
+        tst r0, #0x1
+        bne wait_for_a
+        ldrh r1, [r5]
+        swi #0xCE
+        ",
+    );
+    gba.set_input_script(vec![
+        (10, [Key::A, Key::Start].into_iter().collect()),
+        (5, Buttons::NONE.with(Key::B)),
+    ]);
+    let gba = common::run_until_halted(gba);
+
+    assert_eq!(gba.frame_count(), 10);
+    assert_eq!(gba.cpu.registers.read(1), 159);
+    let keyinput = gba.mapped.view16(0x04000130);
+    assert_eq!(keyinput, 0x3FF & !0x9, "only A and Start are pressed");
+}
+
+#[test]
+fn test_io_register_fields() {
+    let mut gba = Gba::new();
+    gba.reset();
+    // BG mode 3, frame 1, forced blank, BG2 and OBJ enabled.
+    gba.mapped.store16(0x04000000, 0x1493, &mut gba.cpu);
+
+    let registers = gba.io_registers();
+    let dispcnt = registers.iter().find(|reg| reg.name == "DISPCNT").unwrap();
+    assert_eq!(dispcnt.address, 0x04000000);
+    assert_eq!(dispcnt.value, 0x1493);
+    assert_eq!(dispcnt.field("bg_mode"), Some(3));
+    assert_eq!(dispcnt.field("display_frame_select"), Some(1));
+    assert_eq!(dispcnt.field("hblank_interval_free"), Some(0));
+    assert_eq!(dispcnt.field("forced_blank"), Some(1));
+    assert_eq!(dispcnt.field("screen_display_bg0"), Some(0));
+    assert_eq!(dispcnt.field("screen_display_bg2"), Some(1));
+    assert_eq!(dispcnt.field("screen_display_obj"), Some(1));
+    assert_eq!(dispcnt.field("not_a_field"), None);
+
+    let (bg_mode, _) = &dispcnt.fields[0];
+    assert_eq!(bg_mode.name, "bg_mode");
+    assert_eq!(bg_mode.bits, 0..3);
+    assert!(bg_mode.readable && bg_mode.writable);
+    let dispstat = registers.iter().find(|reg| reg.name == "DISPSTAT").unwrap();
+    let (vblank_flag, _) = &dispstat.fields[0];
+    assert_eq!(vblank_flag.name, "vblank_flag");
+    assert!(vblank_flag.readable && !vblank_flag.writable);
+
+    // Every field's bits come from the register's value.
+    for register in &registers {
+        for (field, value) in &register.fields {
+            let mask = (1u64 << field.bits.len()) - 1;
+            let expected = (u64::from(register.value) >> field.bits.start) & mask;
+            assert_eq!(
+                u64::from(*value),
+                expected,
+                "{}.{}",
+                register.name,
+                field.name
+            );
+        }
+    }
+    assert!(registers
+        .windows(2)
+        .all(|pair| pair[0].address < pair[1].address));
+}
+
+#[test]
+fn test_executable_regions() {
+    let mut gba = Gba::new();
+    gba.reset();
+    let memory = &gba.mapped;
+
+    // The first and last word of each region.
+    let executable = [
+        ("BIOS", 0x00000000, 0x00003FFC),
+        ("EWRAM", 0x02000000, 0x0203FFFC),
+        ("IWRAM", 0x03000000, 0x03007FFC),
+        ("VRAM", 0x06000000, 0x06017FFC),
+        ("gamepak waitstate 0", 0x08000000, 0x09FFFFFC),
+        ("gamepak waitstate 1", 0x0A000000, 0x0BFFFFFC),
+        ("gamepak waitstate 2", 0x0C000000, 0x0DFFFFFC),
+    ];
+    for (region, first, last) in executable {
+        for address in [first, last] {
+            assert!(memory.is_executable(address), "{region} 0x{address:08X}");
+        }
+    }
+
+    let not_executable = [
+        0x00004000, // past the end of the BIOS
+        0x01000000, // unmapped
+        0x04000000, // I/O registers
+        0x05000000, // palette RAM
+        0x07000000, // OAM
+        0x0E000000, // SRAM
+        0x10000000, // unmapped
+        0xFFFFFFFC,
+    ];
+    for address in not_executable {
+        assert!(!memory.is_executable(address), "0x{address:08X}");
+    }
+}
@@ -0,0 +1,102 @@
+use std::sync::{Arc, Mutex};
+
+use gba::serial::{LinkCablePeer, MULTIPLAYER_NO_DATA};
+
+#[macro_use]
+mod common;
+
+/// Starts a multiplayer transfer of 0x1234 as the parent with the serial IRQ
+/// enabled, waits for it to finish, and then reads SIOCNT into r0, SIOMULTI0-3
+/// into r1-r3 and r6, and IF into r7.
+const MULTIPLAYER_TRANSFER: &str = "
+    ldr r4, =#0x04000100
+    mov r0, #0
+    strh r0, [r4, #0x34]
+    ldr r0, =#0x6003
+    strh r0, [r4, #0x28]
+    ldr r0, =#0x1234
+    strh r0, [r4, #0x2A]
+    ldrh r0, [r4, #0x28]
+    orr r0, r0, #0x80
+    strh r0, [r4, #0x28]
+wait:
+    ldrh r0, [r4, #0x28]
+    tst r0, #0x80
+    bne wait
+    ldrh r1, [r4, #0x20]
+    ldrh r2, [r4, #0x22]
+    ldrh r3, [r4, #0x24]
+    ldrh r6, [r4, #0x26]
+    ldr r4, =#0x04000200
+    ldrh r7, [r4, #0x02]
+    swi #0xCE
+";
+
+#[test]
+fn test_multiplayer_transfer_disconnected() {
+    let gba = common::execute(MULTIPLAYER_TRANSFER);
+    let siocnt = gba.cpu.registers.read(0);
+    assert_eq!(siocnt & 0x80, 0, "transfer is no longer busy");
+    assert_eq!(siocnt & 0x4, 0, "this GBA is the parent");
+    assert_eq!(siocnt & 0x8, 0, "nothing is connected");
+    assert_eq!(siocnt & 0x30, 0, "multiplayer ID");
+
+    let no_data = MULTIPLAYER_NO_DATA as u32;
+    assert_eq!(gba.cpu.registers.read(1), 0x1234);
+    assert_eq!(gba.cpu.registers.read(2), no_data);
+    assert_eq!(gba.cpu.registers.read(3), no_data);
+    assert_eq!(gba.cpu.registers.read(6), no_data);
+    assert_eq!(
+        gba.cpu.registers.read(7) & 0x80,
+        0x80,
+        "serial IRQ requested"
+    );
+}
+
+struct RecordingPeer {
+    received: Arc<Mutex<Vec<u16>>>,
+}
+
+impl LinkCablePeer for RecordingPeer {
+    fn transfer_multiplayer(&mut self, data: u16) -> [u16; 3] {
+        self.received.lock().unwrap().push(data);
+        [0xBEEF, MULTIPLAYER_NO_DATA, MULTIPLAYER_NO_DATA]
+    }
+}
+
+#[test]
+fn test_multiplayer_transfer_with_peer() {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let mut gba = common::load(MULTIPLAYER_TRANSFER);
+    let previous = gba.set_link_cable_peer(Some(Box::new(RecordingPeer {
+        received: received.clone(),
+    })));
+    assert!(previous.is_none());
+    let gba = common::run_until_halted(gba);
+
+    assert_eq!(*received.lock().unwrap(), vec![0x1234]);
+    assert_eq!(gba.cpu.registers.read(0) & 0x8, 0x8, "all GBAs ready");
+    assert_eq!(gba.cpu.registers.read(1), 0x1234);
+    assert_eq!(gba.cpu.registers.read(2), 0xBEEF);
+    assert_eq!(gba.cpu.registers.read(3), MULTIPLAYER_NO_DATA as u32);
+}
+
+#[test]
+fn test_normal_transfer_disconnected() {
+    let gba = emu_arm! {"
+        ldr r4, =#0x04000100
+        mov r0, #0
+        strh r0, [r4, #0x34]
+        ldr r0, =#0x12345678
+        str r0, [r4, #0x20]
+        ldr r0, =#0x1081
+        strh r0, [r4, #0x28]
+    wait:
+        ldrh r0, [r4, #0x28]
+        tst r0, #0x80
+        bne wait
+        ldr r1, [r4, #0x20]
+        swi #0xCE
+    "};
+    assert_eq!(gba.cpu.registers.read(1), 0xFFFFFFFF);
+}
@@ -0,0 +1,88 @@
+use arm::{
+    disasm::AnyInstr,
+    emu::{CpsrFlag, CpuException, Cycles},
+};
+use gba::{GbaStepResult, NoopGbaAudioOutput, NoopGbaVideoOutput};
+
+#[macro_use]
+mod common;
+
+#[test]
+fn test_step_instruction_reports_executed_instructions() {
+    let mut gba = common::load(
+        "
+        mov r0, #1
+        add r0, r0, #2
+        swi #0x0
+    ",
+    );
+
+    // Run through the BIOS until the first instruction of the ROM is next.
+    while gba.cpu.next_execution_address() != 0x08000000 {
+        gba.step(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput);
+    }
+
+    let mov = gba
+        .step_instruction(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput)
+        .unwrap();
+    assert_eq!(mov.address, 0x08000000);
+    assert!(matches!(mov.instr, AnyInstr::Arm(_)));
+    assert_eq!(mov.instr.mnemonic().to_string(), "mov");
+    assert_eq!(
+        mov.instr.arguments(mov.address, None).to_string(),
+        "r0, #0x1"
+    );
+    assert_eq!(mov.exception, None);
+    assert_eq!(mov.result, GbaStepResult::Running);
+    assert_eq!(gba.cpu.registers.read(0), 1);
+
+    let add = gba
+        .step_instruction(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput)
+        .unwrap();
+    assert_eq!(add.address, 0x08000004);
+    assert_eq!(add.instr.mnemonic().to_string(), "add");
+    assert_eq!(
+        add.instr.arguments(add.address, None).to_string(),
+        "r0, r0, #0x2"
+    );
+    assert_eq!(add.exception, None);
+    assert_eq!(gba.cpu.registers.read(0), 3);
+
+    // Both are a sequential opcode fetch from ROM with no other memory accesses.
+    assert_eq!(mov.cycles, add.cycles);
+    assert!(mov.cycles > Cycles::from(1u32));
+
+    let swi = gba
+        .step_instruction(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput)
+        .unwrap();
+    assert_eq!(swi.address, 0x08000008);
+    assert_eq!(swi.exception, Some(CpuException::Swi));
+}
+
+#[test]
+fn test_set_pc_matches_bx() {
+    let mut bx = common::load(
+        "
+        ldr r0, =0x08000101
+        bx r0
+    ",
+    );
+    while !bx.cpu.registers.get_flag(CpsrFlag::T) {
+        bx.step(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput);
+    }
+
+    let mut gba = common::load("mov r0, #0");
+    gba.set_pc(0x08000101);
+    assert!(gba.cpu.registers.get_flag(CpsrFlag::T));
+    assert_eq!(gba.cpu.next_execution_address(), 0x08000100);
+    assert_eq!(
+        gba.cpu.next_execution_address(),
+        bx.cpu.next_execution_address()
+    );
+    assert_eq!(gba.cpu.registers.read(15), bx.cpu.registers.read(15));
+
+    // An even address goes back to ARM and is word aligned.
+    gba.set_pc(0x08000006);
+    assert!(!gba.cpu.registers.get_flag(CpsrFlag::T));
+    assert_eq!(gba.cpu.next_execution_address(), 0x08000004);
+}
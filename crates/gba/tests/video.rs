@@ -1,8 +1,11 @@
-use arm::disasm::MemoryView as _;
-use common::{audio_noop, execute_until};
+use arm::{disasm::MemoryView as _, emu::Memory as _};
+use common::{assert_frame_hashes, audio_noop, execute_until, execute_with_bios, run_frame_hashes};
 use gba::{
-    video::{rgb5, LineBuffer, VISIBLE_LINE_COUNT, VISIBLE_LINE_WIDTH},
-    Gba,
+    video::{
+        rgb5, rgb8, DirtyLineOutput, FrameBuffer, Layer, LineBuffer, ToRgb8, VISIBLE_LINE_COUNT,
+        VISIBLE_LINE_WIDTH, VISIBLE_PIXELS,
+    },
+    Gba, GbaStepResult, GbaVideoOutput, NoopGbaAudioOutput, NoopGbaVideoOutput,
 };
 
 #[macro_use]
@@ -37,3 +40,499 @@ pub fn simple_mode3_test() {
         }
     }
 }
+
+#[test]
+pub fn frame_ready_after_last_line() {
+    #[derive(Default)]
+    struct CountingOutput {
+        lines: Vec<usize>,
+        frames: usize,
+        last_line: Option<LineBuffer>,
+    }
+
+    impl GbaVideoOutput for CountingOutput {
+        fn gba_line_ready(&mut self, line: usize, data: &LineBuffer) {
+            self.lines.push(line);
+            self.last_line = Some(*data);
+        }
+
+        fn gba_frame_ready(&mut self, frame: &FrameBuffer) {
+            assert_eq!(self.lines.len(), VISIBLE_LINE_COUNT);
+            assert_eq!(
+                Some(frame.line(VISIBLE_LINE_COUNT - 1)),
+                self.last_line.as_ref()
+            );
+            self.frames += 1;
+        }
+    }
+
+    let mut gba = Gba::new();
+    gba.set_noop_gamepak();
+    gba.reset();
+
+    let mut output = CountingOutput::default();
+    while gba.frame_count() < 1 {
+        gba.step(&mut output, &mut NoopGbaAudioOutput);
+    }
+
+    assert_eq!(output.frames, 1);
+    assert_eq!(output.lines, (0..VISIBLE_LINE_COUNT).collect::<Vec<_>>());
+    assert_eq!(
+        gba.frame_buffer().line(VISIBLE_LINE_COUNT - 1),
+        &output.last_line.unwrap()
+    );
+}
+
+#[test]
+pub fn suppressed_rendering_skips_lines() {
+    #[derive(Default)]
+    struct CountingOutput {
+        lines: usize,
+        frames: usize,
+    }
+
+    impl GbaVideoOutput for CountingOutput {
+        fn gba_line_ready(&mut self, _line: usize, _data: &LineBuffer) {
+            self.lines += 1;
+        }
+
+        fn gba_frame_ready(&mut self, _frame: &FrameBuffer) {
+            self.frames += 1;
+        }
+    }
+
+    let mut gba = Gba::new();
+    gba.set_noop_gamepak();
+    gba.reset();
+
+    let mut output = CountingOutput::default();
+    while gba.frame_count() < 1 {
+        gba.step(&mut output, &mut NoopGbaAudioOutput);
+    }
+    assert_eq!((output.lines, output.frames), (VISIBLE_LINE_COUNT, 1));
+    let drawn = gba.frame_buffer().pixels().to_vec();
+
+    gba.set_rendering_suppressed(true);
+    let mut output = CountingOutput::default();
+    while gba.frame_count() < 3 {
+        gba.step(&mut output, &mut NoopGbaAudioOutput);
+    }
+    assert_eq!((output.lines, output.frames), (0, 0));
+    assert_eq!(gba.frame_buffer().pixels()[..], drawn[..]);
+
+    gba.set_rendering_suppressed(false);
+    let mut output = CountingOutput::default();
+    while gba.frame_count() < 4 {
+        gba.step(&mut output, &mut NoopGbaAudioOutput);
+    }
+    assert_eq!((output.lines, output.frames), (VISIBLE_LINE_COUNT, 1));
+}
+
+#[test]
+pub fn noop_boot_frame_hashes() {
+    let mut gba = Gba::new();
+    gba.set_noop_gamepak();
+    gba.reset();
+
+    let hashes = run_frame_hashes(&mut gba, 4);
+    assert_frame_hashes("noop-boot", &hashes);
+}
+
+#[test]
+pub fn dirty_lines_of_identical_frames() {
+    let mut gba = Gba::new();
+    gba.set_noop_gamepak();
+    gba.reset();
+
+    let mut output = DirtyLineOutput::default();
+    while gba.frame_count() < 1 {
+        gba.step(&mut output, &mut NoopGbaAudioOutput);
+    }
+    assert_eq!(
+        output.dirty_lines(),
+        (0..VISIBLE_LINE_COUNT).collect::<Vec<_>>()
+    );
+
+    while gba.frame_count() < 2 {
+        gba.step(&mut output, &mut NoopGbaAudioOutput);
+    }
+    assert_eq!(output.dirty_lines(), &[] as &[usize]);
+}
+
+#[test]
+pub fn scanline_and_blanking_accessors() {
+    let mut gba = Gba::new();
+    gba.set_noop_gamepak();
+    gba.reset();
+
+    assert_eq!(gba.current_scanline(), 0);
+    assert!(!gba.in_vblank());
+    assert!(!gba.in_hblank());
+
+    while !gba.in_hblank() {
+        gba.step(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput);
+    }
+    assert_eq!(gba.current_scanline(), 0);
+
+    while gba.current_scanline() != VISIBLE_LINE_COUNT as u16 {
+        gba.step(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput);
+    }
+    assert!(gba.in_vblank());
+    assert!(!gba.in_hblank());
+    assert_eq!(gba.frame_count(), 1);
+
+    while gba.current_scanline() != 0 {
+        gba.step(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput);
+    }
+    assert!(!gba.in_vblank());
+}
+
+/// A BIOS that only implements the reset vector and the IRQ dispatcher of the real
+/// BIOS which calls the handler stored at 0x03FFFFFC.
+const IRQ_BIOS: &str = r#"
+    b       reset
+    b       .
+    b       .
+    b       .
+    b       .
+    b       .
+    b       irq
+    b       .
+reset:
+    mov     r0, #0x08000000
+    bx      r0
+irq:
+    stmfd   sp!, {r0-r3, r12, lr}
+    mov     r0, #0x04000000
+    add     lr, pc, #0
+    ldr     pc, [r0, #-4]
+    ldmfd   sp!, {r0-r3, r12, lr}
+    subs    pc, lr, #4
+"#;
+
+#[test]
+pub fn vcount_match_interrupt() {
+    let gba = execute_with_bios(
+        r#"
+        msr     cpsr_c, #0xD2
+        ldr     sp, =0x03007FA0
+        msr     cpsr_c, #0x9F
+        ldr     sp, =0x03007F00
+
+        adr     r0, irq_handler
+        ldr     r1, =0x03FFFFFC
+        str     r0, [r1]
+
+        mov     r5, #0
+        ldr     r1, =0x04000000
+        ldr     r0, =0x5020     @ VCount setting = 80, VCounter IRQ enable
+        strh    r0, [r1, #4]
+        add     r2, r1, #0x200
+        mov     r0, #4          @ IE = VCounter
+        strh    r0, [r2]
+        mov     r0, #1          @ IME = 1
+        strh    r0, [r2, #8]
+        msr     cpsr_c, #0x1F
+
+    wait:
+        cmp     r5, #2
+        bne     wait
+        swi     #0xCE
+
+    irq_handler:
+        ldrh    r1, [r0, #6]    @ VCOUNT
+        cmp     r5, #0
+        moveq   r6, r1
+        movne   r7, r1
+        add     r5, r5, #1
+        add     r2, r0, #0x200
+        mov     r1, #4          @ acknowledge VCounter
+        strh    r1, [r2, #2]
+        bx      lr
+        .pool
+        "#,
+        IRQ_BIOS,
+    );
+
+    assert_eq!(gba.cpu.registers.read(5), 2);
+    assert_eq!(gba.cpu.registers.read(6), 80);
+    assert_eq!(gba.cpu.registers.read(7), 80);
+    assert!(gba.frame_count() >= 1);
+}
+
+/// The palette entry of pixel (x, y) in the 128x128 affine background that is set up
+/// by [`setup_affine_background`].
+fn affine_texel(x: usize, y: usize) -> usize {
+    let tile = 1 + (x / 8 + y / 8) % 15;
+    1 + (tile + x % 8 + 2 * (y % 8)) % 15
+}
+
+fn affine_color(entry: usize) -> u16 {
+    if entry == 0 {
+        0
+    } else {
+        let entry = entry as u16;
+        rgb5(entry, 31 - entry, entry * 2)
+    }
+}
+
+/// Sets up BG2 in mode 2 as a 128x128 background with its map at screen base block 8
+/// and tiles at character base block 0.
+fn setup_affine_background(gba: &mut Gba, wraparound: bool) {
+    for entry in 1..16 {
+        let address = 0x05000000 + entry as u32 * 2;
+        gba.mapped
+            .store16(address, affine_color(entry), &mut gba.cpu);
+    }
+
+    let mut tiles = vec![0u8; 16 * 64];
+    for tile in 1..16 {
+        for y in 0..8 {
+            for x in 0..8 {
+                tiles[tile * 64 + y * 8 + x] = (1 + (tile + x + 2 * y) % 15) as u8;
+            }
+        }
+    }
+    let mut map = vec![0u8; 16 * 16];
+    for y in 0..16 {
+        for x in 0..16 {
+            map[y * 16 + x] = (1 + (x + y) % 15) as u8;
+        }
+    }
+    for (base, data) in [(0x06000000, &tiles), (0x06004000, &map)] {
+        for (offset, pair) in data.chunks(2).enumerate() {
+            let value = u16::from_le_bytes([pair[0], pair[1]]);
+            gba.mapped
+                .store16(base + offset as u32 * 2, value, &mut gba.cpu);
+        }
+    }
+
+    let bg2cnt = (8 << 8) | ((wraparound as u16) << 13);
+    gba.mapped.store16(0x0400000C, bg2cnt, &mut gba.cpu);
+    gba.mapped.store16(0x04000000, 0x0402, &mut gba.cpu);
+}
+
+fn set_bg2_affine(gba: &mut Gba, pa: u16, pd: u16, x: u32, y: u32) {
+    gba.mapped.store16(0x04000020, pa, &mut gba.cpu);
+    gba.mapped.store16(0x04000022, 0, &mut gba.cpu);
+    gba.mapped.store16(0x04000024, 0, &mut gba.cpu);
+    gba.mapped.store16(0x04000026, pd, &mut gba.cpu);
+    gba.mapped.store32(0x04000028, x, &mut gba.cpu);
+    gba.mapped.store32(0x0400002C, y, &mut gba.cpu);
+}
+
+#[test]
+pub fn run_frame_returns_completed_frames() {
+    let mut gba = Gba::new();
+    gba.set_noop_gamepak();
+    gba.reset();
+
+    let start = gba.frame_count();
+    for _ in 0..3 {
+        let frame = gba.run_frame(&mut NoopGbaAudioOutput);
+        assert_eq!(frame.pixels().len(), VISIBLE_PIXELS);
+        assert_eq!(frame.line(VISIBLE_LINE_COUNT - 1).len(), VISIBLE_LINE_WIDTH);
+    }
+    assert_eq!(gba.frame_count(), start + 3);
+}
+
+#[test]
+pub fn run_to_vblank_stops_at_the_start_of_vblank() {
+    let mut gba = Gba::new();
+    gba.set_noop_gamepak();
+    gba.reset();
+
+    for frame in 0..2 {
+        let result = gba.run_to_vblank(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput);
+        assert_eq!(result, GbaStepResult::Running);
+        assert_eq!(gba.current_scanline(), VISIBLE_LINE_COUNT as u16);
+        assert!(gba.in_vblank());
+        assert!(!gba.in_hblank());
+        assert_eq!(gba.frame_count(), frame + 1);
+    }
+}
+
+/// Runs for two frames so that the second one uses the reference point latched at VBlank.
+fn run_two_frames(gba: &mut Gba) -> &FrameBuffer {
+    while gba.frame_count() < 2 {
+        gba.step(&mut NoopGbaVideoOutput, &mut NoopGbaAudioOutput);
+    }
+    gba.frame_buffer()
+}
+
+#[test]
+pub fn affine_background_identity() {
+    let mut gba = Gba::new();
+    gba.set_noop_gamepak();
+    gba.reset();
+    setup_affine_background(&mut gba, true);
+    set_bg2_affine(&mut gba, 0x100, 0x100, 0, 0);
+
+    let frame = run_two_frames(&mut gba);
+    for y in 0..VISIBLE_LINE_COUNT {
+        for x in 0..VISIBLE_LINE_WIDTH {
+            let expected = affine_color(affine_texel(x % 128, y % 128));
+            assert_eq!(frame.line(y)[x], expected, "pixel ({x}, {y})");
+        }
+    }
+}
+
+#[test]
+pub fn affine_background_scaled() {
+    let mut gba = Gba::new();
+    gba.set_noop_gamepak();
+    gba.reset();
+    setup_affine_background(&mut gba, false);
+    // Each texel is drawn as 2x2 pixels starting from (64, 16) in the background. Anything
+    // past the right edge of the background is transparent.
+    set_bg2_affine(&mut gba, 0x80, 0x80, 64 << 8, 16 << 8);
+
+    let frame = run_two_frames(&mut gba);
+    for y in 0..VISIBLE_LINE_COUNT {
+        for x in 0..VISIBLE_LINE_WIDTH {
+            let (tx, ty) = (64 + x / 2, 16 + y / 2);
+            let expected = if tx < 128 {
+                affine_color(affine_texel(tx, ty))
+            } else {
+                affine_color(0)
+            };
+            assert_eq!(frame.line(y)[x], expected, "pixel ({x}, {y})");
+        }
+    }
+}
+
+#[test]
+pub fn hidden_layers_are_not_drawn() {
+    let mut gba = Gba::new();
+    gba.set_noop_gamepak();
+    gba.reset();
+    setup_affine_background(&mut gba, true);
+    set_bg2_affine(&mut gba, 0x100, 0x100, 0, 0);
+
+    // BG3 uses the same map and tiles as BG2 but is scrolled 8 pixels to the right
+    // and drawn behind it.
+    gba.mapped
+        .store16(0x0400000E, (8 << 8) | (1 << 13) | 1, &mut gba.cpu);
+    gba.mapped.store16(0x04000030, 0x100, &mut gba.cpu);
+    gba.mapped.store16(0x04000032, 0, &mut gba.cpu);
+    gba.mapped.store16(0x04000034, 0, &mut gba.cpu);
+    gba.mapped.store16(0x04000036, 0x100, &mut gba.cpu);
+    gba.mapped.store32(0x04000038, 8 << 8, &mut gba.cpu);
+    gba.mapped.store32(0x0400003C, 0, &mut gba.cpu);
+    gba.mapped.store16(0x04000000, 0x0C02, &mut gba.cpu); // mode 2, BG2 and BG3
+
+    let bg2 = |x: usize, y: usize| affine_color(affine_texel(x % 128, y % 128));
+    let bg3 = |x: usize, y: usize| affine_color(affine_texel((x + 8) % 128, y % 128));
+    let check = |frame: &FrameBuffer, expected: &dyn Fn(usize, usize) -> u16| {
+        for y in 0..VISIBLE_LINE_COUNT {
+            for x in 0..VISIBLE_LINE_WIDTH {
+                assert_eq!(frame.line(y)[x], expected(x, y), "pixel ({x}, {y})");
+            }
+        }
+    };
+
+    check(run_two_frames(&mut gba), &bg2);
+
+    gba.set_layer_enabled(Layer::Bg2, false);
+    assert!(!gba.layer_enabled(Layer::Bg2));
+    check(gba.run_frame(&mut NoopGbaAudioOutput), &bg3);
+    assert_eq!(gba.mapped.view16(0x04000000), 0x0C02);
+
+    gba.set_layer_enabled(Layer::Bg3, false);
+    check(gba.run_frame(&mut NoopGbaAudioOutput), &|_, _| {
+        affine_color(0)
+    });
+
+    gba.set_layer_enabled(Layer::Bg2, true);
+    gba.set_layer_enabled(Layer::Bg3, true);
+    check(gba.run_frame(&mut NoopGbaAudioOutput), &bg2);
+}
+
+#[test]
+pub fn forced_blank_lines_are_white() {
+    let mut gba = Gba::new();
+    gba.set_noop_gamepak();
+    gba.reset();
+    setup_affine_background(&mut gba, true);
+    set_bg2_affine(&mut gba, 0x100, 0x100, 0, 0);
+    gba.mapped.store16(0x04000000, 0x0482, &mut gba.cpu); // mode 2, forced blank, BG2
+
+    let white = rgb5(0x1F, 0x1F, 0x1F);
+    let frame = run_two_frames(&mut gba);
+    for y in 0..VISIBLE_LINE_COUNT {
+        assert!(
+            frame.line(y).iter().all(|&pixel| pixel == white),
+            "line {y}"
+        );
+    }
+
+    // Mode 0 isn't drawn yet but forced blank still turns it white.
+    gba.mapped.store16(0x04000000, 0x0080, &mut gba.cpu);
+    let frame = gba.run_frame(&mut NoopGbaAudioOutput);
+    assert!(frame.pixels().iter().all(|&pixel| pixel == white));
+
+    gba.mapped.store16(0x04000000, 0x0402, &mut gba.cpu);
+    let frame = gba.run_frame(&mut NoopGbaAudioOutput);
+    assert_eq!(frame.line(0)[0], affine_color(affine_texel(0, 0)));
+}
+
+#[test]
+pub fn bg_mosaic_4x4() {
+    fn gradient(x: usize, y: usize) -> u16 {
+        rgb5(x as u16 % 32, y as u16 % 32, (x / 32) as u16)
+    }
+
+    let mut gba = Gba::new();
+    gba.set_noop_gamepak();
+    gba.reset();
+
+    for y in 0..VISIBLE_LINE_COUNT {
+        for x in 0..VISIBLE_LINE_WIDTH {
+            let address = 0x06000000 + ((y * VISIBLE_LINE_WIDTH + x) * 2) as u32;
+            gba.mapped.store16(address, gradient(x, y), &mut gba.cpu);
+        }
+    }
+    gba.mapped.store16(0x0400004C, 0x0033, &mut gba.cpu); // 4x4 BG mosaic
+    gba.mapped.store16(0x0400000C, 1 << 6, &mut gba.cpu); // BG2 mosaic enable
+    gba.mapped.store16(0x04000000, 0x0403, &mut gba.cpu); // mode 3, BG2
+
+    let frame = run_two_frames(&mut gba);
+    for y in 0..VISIBLE_LINE_COUNT {
+        let line: &LineBuffer = frame.line(y);
+        for (x, &pixel) in line.iter().enumerate() {
+            let expected = gradient(x - x % 4, y - y % 4);
+            assert_eq!(pixel, expected, "pixel ({x}, {y})");
+        }
+    }
+}
+
+#[test]
+pub fn rgb8_conversion() {
+    assert_eq!(rgb8(rgb5(0, 0, 0)), [0x00, 0x00, 0x00]);
+    assert_eq!(rgb8(rgb5(31, 31, 31)), [0xFF, 0xFF, 0xFF]);
+    assert_eq!(rgb8(rgb5(31, 0, 0)), [0xFF, 0x00, 0x00]);
+    assert_eq!(rgb8(rgb5(0, 16, 1)), [0x00, 0x84, 0x08]);
+    // The alpha bit is ignored.
+    assert_eq!(rgb8(0x7FFF), rgb8(0xFFFF));
+
+    let mut line: LineBuffer = [0; VISIBLE_LINE_WIDTH];
+    line[0] = rgb5(31, 0, 0);
+    line[1] = rgb5(0, 31, 0);
+    line[239] = rgb5(0, 0, 31);
+
+    let mut rgb = vec![0u8; VISIBLE_LINE_WIDTH * 3];
+    line.to_rgb8(&mut rgb);
+    assert_eq!(rgb[..6], [0xFF, 0x00, 0x00, 0x00, 0xFF, 0x00]);
+    assert_eq!(rgb[6..9], [0x00, 0x00, 0x00]);
+    assert_eq!(rgb[717..], [0x00, 0x00, 0xFF]);
+
+    let mut rgba = vec![0u8; VISIBLE_LINE_WIDTH * 4];
+    line.to_rgba8(&mut rgba);
+    assert_eq!(rgba[..8], [0xFF, 0x00, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF]);
+    assert_eq!(rgba[956..], [0x00, 0x00, 0xFF, 0xFF]);
+
+    let frame = FrameBuffer::new();
+    let mut rgba = vec![0u8; VISIBLE_PIXELS * 4];
+    frame.to_rgba8(&mut rgba);
+    assert!(rgba.chunks_exact(4).all(|pixel| pixel == [0, 0, 0, 0xFF]));
+}
@@ -10,7 +10,7 @@ use util::bits::BitOps;
 
 const PRIMITIVES: [(&str, u32); 11] = [
     ("i8", 8),
-    ("u8", 9),
+    ("u8", 8),
     ("i16", 16),
     ("u16", 16),
     ("i32", 32),
@@ -91,11 +91,19 @@ pub fn try_io_register_macro(input: DeriveInput) -> syn::Result<TokenStream> {
         }
     });
 
+    let field_infos = RefCell::new(Vec::new());
+    let debug_fields = RefCell::new(Vec::new());
+    let used_bits = RefCell::new(0u128);
     let r_bits = RefCell::new(u128::mask(value_field_bits));
     let w_bits = RefCell::new(u128::mask(value_field_bits));
 
     let functions = std::iter::from_fn(|| match ioreg_fields.next()? {
         Ok(field) => {
+            if let Err(err) = field.check_bits(value_field_bits, &mut used_bits.borrow_mut()) {
+                let compile_error = err.into_compile_error();
+                return Some(quote! { #compile_error });
+            }
+
             if !field.flags.contains(IoRegisterFlags::READ) {
                 let mut r_bits = r_bits.borrow_mut();
                 *r_bits = r_bits.clear_bit_range(field.bit_range.clone());
@@ -106,6 +114,10 @@ pub fn try_io_register_macro(input: DeriveInput) -> syn::Result<TokenStream> {
                 *w_bits = w_bits.clear_bit_range(field.bit_range.clone());
             }
 
+            field_infos.borrow_mut().push(field.info());
+            debug_fields
+                .borrow_mut()
+                .push(field.debug_field(value_field_name, value_field_type));
             let getter = field.getter(value_field_name, value_field_type);
             let setter = field.setter(value_field_name, value_field_type);
             Some(quote! { #getter #setter })
@@ -138,6 +150,25 @@ pub fn try_io_register_macro(input: DeriveInput) -> syn::Result<TokenStream> {
         }
     });
 
+    let ioreg_fields_const = std::iter::once_with(|| {
+        let field_infos = field_infos.borrow();
+        quote! {
+            const FIELDS: &'static [crate::memory::IoRegisterField] = &[#(#field_infos),*];
+        }
+    });
+
+    let debug_impl = std::iter::once_with(|| {
+        let debug_fields = debug_fields.borrow();
+        let name = name.to_string();
+        quote! {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                f.debug_struct(#name)
+                    #(#debug_fields)*
+                    .finish()
+            }
+        }
+    });
+
     let expanded = quote! {
         impl #impl_generics #name #ty_generics #where_clause {
             pub const fn new(value: #value_field_type) -> Self {
@@ -154,10 +185,15 @@ pub fn try_io_register_macro(input: DeriveInput) -> syn::Result<TokenStream> {
         }
 
         impl #impl_generics crate::memory::IoRegister<#value_field_type> for #name #ty_generics #where_clause {
+            #(#ioreg_fields_const)*
             #(#ioreg_read_fn)*
             #(#ioreg_write_fn)*
         }
 
+        impl #impl_generics ::std::fmt::Debug for #name #ty_generics #where_clause {
+            #(#debug_impl)*
+        }
+
         impl #impl_generics From<#value_field_type> for #name #ty_generics #where_clause {
             #[inline]
             fn from(value: #value_field_type) -> Self {
@@ -184,10 +220,77 @@ struct IoRegisterField {
     ty: Type,
     is_primitive: bool,
     is_bool: bool,
+    /// Set by a trailing `, overlapping` for fields that are another view of bits
+    /// that belong to other fields, e.g. bits that mean something else in another
+    /// mode.
+    overlapping: bool,
     name: Ident,
 }
 
 impl IoRegisterField {
+    /// The description of this field that is listed in `IoRegister::FIELDS`.
+    fn info(&self) -> TokenStream {
+        let name = self.name.to_string();
+        let start = self.bit_range.start;
+        let end = self.bit_range.end;
+        let readable = self.flags.contains(IoRegisterFlags::READ);
+        let writable = self.flags.contains(IoRegisterFlags::WRITE);
+        quote! {
+            crate::memory::IoRegisterField {
+                name: #name,
+                bits: #start..#end,
+                readable: #readable,
+                writable: #writable,
+            }
+        }
+    }
+
+    /// Makes sure that this field fits in the register and doesn't share any bits
+    /// with the fields declared before it, which are in `used_bits`. Fields marked
+    /// `overlapping` are only checked against the size of the register.
+    fn check_bits(&self, register_bits: u32, used_bits: &mut u128) -> syn::Result<()> {
+        if self.bit_range.end > register_bits {
+            return Err(syn::Error::new_spanned(
+                &self.range,
+                format!(
+                    "field `{}` uses bits {}..{} but the register only has {register_bits} bits",
+                    self.name, self.bit_range.start, self.bit_range.end,
+                ),
+            ));
+        }
+
+        if self.overlapping {
+            return Ok(());
+        }
+
+        let bits = 0u128.set_bit_range(self.bit_range.clone());
+        if *used_bits & bits != 0 {
+            return Err(syn::Error::new_spanned(
+                &self.range,
+                format!("field `{}` overlaps with another field", self.name),
+            ));
+        }
+        *used_bits |= bits;
+        Ok(())
+    }
+
+    /// A `.field(...)` call for the generated `Debug` impl. Fields that aren't
+    /// primitives are printed as their raw bits so that their types don't need to
+    /// implement `Debug`.
+    fn debug_field(&self, value_field_name: &Ident, value_field_type: &Type) -> TokenStream {
+        let name = self.name.to_string();
+        let field_getter = &self.name;
+        let range = &self.range;
+
+        if self.is_primitive {
+            quote! { .field(#name, &self.#field_getter()) }
+        } else {
+            quote! {
+                .field(#name, &<#value_field_type as ::util::bits::BitOps>::get_bit_range(self.#value_field_name, #range))
+            }
+        }
+    }
+
     fn getter(&self, value_field_name: &Ident, value_field_type: &Type) -> TokenStream {
         let field_getter = &self.name;
         let field_type = &self.ty;
@@ -358,6 +461,15 @@ impl Parse for IoRegisterField {
         let (range, bit_range): (ExprRange, Range<u32>) =
             Self::extract_range_or_index(input.parse()?)?;
 
+        let mut overlapping = false;
+        if input.parse::<Option<Token![,]>>()?.is_some() {
+            let flag: Ident = input.parse()?;
+            if flag != "overlapping" {
+                return Err(syn::Error::new_spanned(flag, "expected `overlapping`"));
+            }
+            overlapping = true;
+        }
+
         let mut is_primitive = false;
         let mut is_bool = false;
 
@@ -375,6 +487,7 @@ impl Parse for IoRegisterField {
             name,
             is_primitive,
             is_bool,
+            overlapping,
             ty,
         })
     }
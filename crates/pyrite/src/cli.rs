@@ -1,9 +1,146 @@
 use std::path::PathBuf;
 
-use clap::Parser;
+use anyhow::Context as _;
+use arm::disasm::InstructionSet;
+use clap::{Parser, ValueEnum};
+use gba::{bios::BiosMode, Gba};
 
 #[derive(Parser)]
 #[command(author, version, about)]
 pub struct PyriteCli {
     pub rom: Option<PathBuf>,
+
+    /// Path to a GBA BIOS image to use instead of the bundled BIOS.
+    #[arg(long)]
+    pub bios: Option<PathBuf>,
+
+    /// Emulate BIOS calls instead of executing BIOS code.
+    #[arg(long)]
+    pub hle_bios: bool,
+
+    /// Run the ROM without opening a window and exit after `--frames` frames.
+    #[arg(long)]
+    pub headless: bool,
+
+    /// The number of frames to run in headless mode.
+    #[arg(long, default_value_t = 60, requires = "headless")]
+    pub frames: u64,
+
+    /// Write the last frame to this path as a PNG after running in headless mode.
+    #[arg(long, requires = "headless")]
+    pub screenshot: Option<PathBuf>,
+
+    /// Disassemble a range of memory given as `START+LEN` in hex (e.g. `8000000+100`)
+    /// and exit. In headless mode this happens after the last frame.
+    #[arg(long, value_name = "START+LEN", value_parser = parse_address_range)]
+    pub disassemble: Option<AddressRange>,
+
+    /// The instruction set used by `--disassemble`.
+    #[arg(long, value_enum, default_value_t = CliInstructionSet::Arm, requires = "disassemble")]
+    pub isa: CliInstructionSet,
+
+    /// Write the listing from `--disassemble` to this file instead of stdout.
+    #[arg(long, requires = "disassemble")]
+    pub disassembly_output: Option<PathBuf>,
+}
+
+/// A range of `len` bytes of GBA memory starting at `start`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AddressRange {
+    pub start: u32,
+    pub len: u32,
+}
+
+fn parse_address_range(range: &str) -> Result<AddressRange, String> {
+    let parse_hex = |value: &str| {
+        let value = value.trim();
+        let value = value
+            .strip_prefix("0x")
+            .or_else(|| value.strip_prefix("0X"))
+            .unwrap_or(value);
+        u32::from_str_radix(value, 16).map_err(|err| format!("invalid hex number {value:?}: {err}"))
+    };
+
+    let (start, len) = range
+        .split_once('+')
+        .ok_or_else(|| format!("expected START+LEN but found {range:?}"))?;
+    Ok(AddressRange {
+        start: parse_hex(start)?,
+        len: parse_hex(len)?,
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum CliInstructionSet {
+    Arm,
+    Thumb,
+}
+
+impl From<CliInstructionSet> for InstructionSet {
+    fn from(isa: CliInstructionSet) -> Self {
+        match isa {
+            CliInstructionSet::Arm => InstructionSet::Arm,
+            CliInstructionSet::Thumb => InstructionSet::Thumb,
+        }
+    }
+}
+
+impl PyriteCli {
+    /// Loads the BIOS and ROM that were passed on the command line into the GBA
+    /// and resets it. The no-op gamepak is used if there is no ROM.
+    pub fn boot(&self, gba: &mut Gba) -> anyhow::Result<()> {
+        let rom = if let Some(ref path) = self.rom {
+            Some(crate::rom::read_rom(path)?)
+        } else {
+            None
+        };
+
+        let bios = if let Some(ref path) = self.bios {
+            Some(std::fs::read(path).with_context(|| format!("error reading BIOS from {path:?}"))?)
+        } else {
+            None
+        };
+
+        if let Some(bios) = bios {
+            gba.set_bios(bios).context("error loading BIOS")?;
+        }
+
+        if self.hle_bios {
+            gba.set_bios_mode(BiosMode::Hle);
+        }
+
+        if let Some(rom) = rom {
+            gba.try_set_gamepak(rom).context("error loading ROM")?;
+        } else {
+            gba.set_noop_gamepak();
+        }
+
+        gba.reset();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address_ranges() {
+        assert_eq!(
+            parse_address_range("8000000+100"),
+            Ok(AddressRange {
+                start: 0x08000000,
+                len: 0x100
+            })
+        );
+        assert_eq!(
+            parse_address_range("0x03000000 + 0x20"),
+            Ok(AddressRange {
+                start: 0x03000000,
+                len: 0x20
+            })
+        );
+        assert!(parse_address_range("8000000").is_err());
+        assert!(parse_address_range("8000000+xyz").is_err());
+    }
 }
@@ -4,24 +4,53 @@ use anyhow::Context as _;
 use serde::{Deserialize, Serialize};
 use tracing::Level;
 
-use crate::logging::LoggingReloadHandle;
+use crate::{
+    gba_runner::FrameSkip,
+    logging::LoggingReloadHandle,
+    ui::gba_image::common::{Filter, Scaling},
+};
+
+/// The version of the config format that this build writes. Bump this and add a
+/// step to [`migrate`] whenever a field is renamed or its meaning changes. New
+/// fields with `#[serde(default)]` don't need a new version.
+pub const CONFIG_VERSION: u32 = 1;
 
 impl Default for Config {
     fn default() -> Self {
         Config {
-            gui: GuiConfig {
-                renderer: Some("glow".into()),
-            },
-
-            logging: LoggingConfig {
-                general: Some("debug".into()),
-                gba: Some("debug".into()),
-                arm: Some("debug".into()),
-                wgpu: Some("info".into()),
-                egui: Some("info".into()),
-                extra_filters: Vec::new(),
-                reload_handle: None,
-            },
+            version: CONFIG_VERSION,
+            gui: GuiConfig::default(),
+            logging: LoggingConfig::default(),
+        }
+    }
+}
+
+impl Default for GuiConfig {
+    fn default() -> Self {
+        GuiConfig {
+            renderer: Some("glow".into()),
+            color_correction: false,
+            scaling: Scaling::default(),
+            filter: Filter::default(),
+            recent_roms: Vec::new(),
+            show_performance_overlay: false,
+            frame_skip: FrameSkip::default(),
+            threaded_runner: false,
+        }
+    }
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            general: Some("debug".into()),
+            gba: Some("debug".into()),
+            arm: Some("debug".into()),
+            wgpu: Some("info".into()),
+            egui: Some("info".into()),
+            swi: false,
+            extra_filters: Vec::new(),
+            reload_handle: None,
         }
     }
 }
@@ -69,6 +98,10 @@ impl Config {
             write!(filters, ",wgpu_core={level},wgpu_hal={level},naga={level}").unwrap();
         }
 
+        if self.logging.swi {
+            write!(filters, ",{}=trace", gba::bios::SWI_LOG_TARGET).unwrap();
+        }
+
         for extra in self.logging.extra_filters.iter() {
             write!(filters, ",{extra}").unwrap();
         }
@@ -77,15 +110,65 @@ impl Config {
     }
 }
 
+/// Unknown fields are ignored when loading so that config files written by newer
+/// versions of pyrite can still be loaded. Missing fields and sections get their
+/// default values.
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Config {
+    /// The config format version, see [`CONFIG_VERSION`]. Files from before the
+    /// format was versioned don't have this field and are version 0.
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
     pub gui: GuiConfig,
+    #[serde(default)]
     pub logging: LoggingConfig,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct GuiConfig {
     pub renderer: Option<String>,
+
+    /// Apply LCD color correction to the GBA screen.
+    #[serde(default)]
+    pub color_correction: bool,
+
+    /// How the GBA screen is scaled to fit the window.
+    #[serde(default)]
+    pub scaling: Scaling,
+
+    /// How the GBA screen is filtered when it is scaled.
+    #[serde(default)]
+    pub filter: Filter,
+
+    /// ROMs that were loaded from the GUI, most recent first.
+    #[serde(default)]
+    pub recent_roms: Vec<PathBuf>,
+
+    /// Show FPS and emulation speed over the GBA screen.
+    #[serde(default)]
+    pub show_performance_overlay: bool,
+
+    /// Frames to emulate without presenting them, for machines that can't keep up.
+    #[serde(default)]
+    pub frame_skip: FrameSkip,
+
+    /// Run the GBA on its own thread and control it through commands instead of
+    /// sharing it behind a lock. The debugger windows and frame skipping aren't
+    /// available in this mode.
+    #[serde(default)]
+    pub threaded_runner: bool,
+}
+
+impl GuiConfig {
+    const MAX_RECENT_ROMS: usize = 10;
+
+    /// Moves `path` to the front of the recent ROMs list.
+    pub fn add_recent_rom(&mut self, path: PathBuf) {
+        self.recent_roms.retain(|recent| *recent != path);
+        self.recent_roms.insert(0, path);
+        self.recent_roms.truncate(Self::MAX_RECENT_ROMS);
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -96,6 +179,10 @@ pub struct LoggingConfig {
     pub egui: Option<String>,
     pub wgpu: Option<String>,
 
+    /// Logs every SWI the game makes along with its arguments.
+    #[serde(default)]
+    pub swi: bool,
+
     #[serde(default)]
     pub extra_filters: Vec<String>,
 
@@ -125,11 +212,55 @@ pub fn load() -> anyhow::Result<Config> {
 
     let config_contents = std::fs::read_to_string(&config_path)
         .with_context(|| format!("error while reading config contents (path: {config_path:?})"))?;
-    let config = serde_json::from_str(&config_contents)
-        .with_context(|| "error while parsing config (path: {config_path:?})")?;
+    parse(&config_contents)
+        .with_context(|| format!("error while parsing config (path: {config_path:?})"))
+}
+
+/// Parses a config file, migrating it from older versions of the format first.
+pub fn parse(contents: &str) -> anyhow::Result<Config> {
+    let mut value: serde_json::Value =
+        serde_json::from_str(contents).context("config is not valid JSON")?;
+    migrate(&mut value)?;
+    let config = serde_json::from_value(value).context("error while reading config fields")?;
     Ok(config)
 }
 
+/// Rewrites a config in place, one version at a time, until it is in the format
+/// for [`CONFIG_VERSION`].
+fn migrate(value: &mut serde_json::Value) -> anyhow::Result<()> {
+    let config = value
+        .as_object_mut()
+        .context("config must be a JSON object")?;
+    let mut version = match config.get("version") {
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .context("config version must be a positive integer")?,
+        None => 0,
+    };
+
+    if version > CONFIG_VERSION {
+        tracing::warn!(
+            version,
+            supported = CONFIG_VERSION,
+            "config was written by a newer version of pyrite, unknown settings will be ignored"
+        );
+        return Ok(());
+    }
+
+    while version < CONFIG_VERSION {
+        match version {
+            // Version 0 is every config from before the format was versioned. All
+            // of the fields that were added since then have defaults.
+            0 => {}
+            _ => unreachable!("no migration from config version {version}"),
+        }
+        version += 1;
+    }
+    config.insert("version".into(), version.into());
+    Ok(())
+}
+
 pub fn store(config: &Config) -> anyhow::Result<()> {
     let config_path = get_config_path().context("error while getting config directory")?;
     let mut config_file = std::fs::File::create(config_path)
@@ -138,3 +269,85 @@ pub fn store(config: &Config) -> anyhow::Result<()> {
         .with_context(|| "error while writing config (path: {config_path:?})")?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_unversioned_config() {
+        // A config from before the format was versioned, without any of the
+        // newer GUI settings.
+        let config = parse(
+            r#"{
+                "gui": { "renderer": "wgpu" },
+                "logging": {
+                    "general": "info",
+                    "gba": null,
+                    "arm": "warn",
+                    "egui": "info",
+                    "wgpu": "info"
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.gui.renderer.as_deref(), Some("wgpu"));
+        assert!(!config.gui.color_correction);
+        assert_eq!(config.gui.scaling, Scaling::default());
+        assert_eq!(config.gui.filter, Filter::default());
+        assert!(config.gui.recent_roms.is_empty());
+        assert_eq!(config.gui.frame_skip, FrameSkip::default());
+        assert!(!config.gui.threaded_runner);
+        assert_eq!(config.logging.general.as_deref(), Some("info"));
+        assert_eq!(config.logging.gba, None);
+        assert!(!config.logging.swi);
+        assert!(config.logging.extra_filters.is_empty());
+    }
+
+    #[test]
+    fn swi_logging_filter() {
+        let mut config = Config::default();
+        assert!(!config.get_log_filters().unwrap().contains("gba::swi"));
+
+        config.logging.swi = true;
+        assert!(config
+            .get_log_filters()
+            .unwrap()
+            .contains(",gba::swi=trace"));
+    }
+
+    #[test]
+    fn load_config_with_missing_and_unknown_fields() {
+        let config = parse(
+            r#"{
+                "version": 1,
+                "gui": { "renderer": "glow", "scaling": "integer", "not_a_setting": 5 },
+                "input": { "a": "z" }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.gui.scaling, Scaling::Integer);
+        assert_eq!(
+            config.logging.general,
+            LoggingConfig::default().general,
+            "missing sections use their defaults"
+        );
+
+        // Configs from newer versions still load.
+        let config = parse(r#"{ "version": 1000, "gui": { "renderer": "glow" } }"#).unwrap();
+        assert_eq!(config.version, 1000);
+
+        assert!(parse("[]").is_err());
+        assert!(parse(r#"{ "version": "one" }"#).is_err());
+    }
+
+    #[test]
+    fn default_config_round_trip() {
+        let json = serde_json::to_string(&Config::default()).unwrap();
+        let config = parse(&json).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.gui.renderer, GuiConfig::default().renderer);
+    }
+}
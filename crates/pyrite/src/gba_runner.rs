@@ -1,29 +1,46 @@
+pub mod threaded;
+pub mod triple_buffer;
+
+use anyhow::Context as _;
 use gba::{
+    keypad::{Key, KeyInputState},
     video::{ScreenBuffer, VISIBLE_LINE_WIDTH, VISIBLE_PIXELS},
-    Gba, GbaVideoOutput,
+    Gba, GbaStepResult, GbaVideoOutput,
 };
-use parking_lot::{Condvar, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use parking_lot::{Condvar, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use serde::{Deserialize, Serialize};
 use spin_sleep::LoopHelper;
-use std::sync::Arc;
+use std::{
+    sync::{mpsc, Arc},
+    time::{Duration, Instant},
+};
+use threaded::{GbaCommand, ThreadedGba};
+use triple_buffer::{triple_buffer, TripleBufferReader, TripleBufferWriter};
 
 #[derive(Clone)]
 pub struct SharedGba {
     inner: Arc<RwLock<GbaData>>,
+    /// Completed frames. These are read without taking the GBA's lock so drawing
+    /// the screen never waits on the GBA thread and the other way around.
+    frames: Arc<Mutex<TripleBufferReader<Box<ScreenBuffer>>>>,
 }
 
 impl SharedGba {
     pub fn new() -> Self {
+        let (frames_writer, frames_reader) =
+            triple_buffer(Box::new([gba::video::rgb5(31, 0, 31); VISIBLE_PIXELS]));
         let shared = SharedGba {
             inner: Arc::new(RwLock::new(GbaData {
                 gba: Gba::new(),
-                frame_buffer: Box::new([gba::video::rgb5(31, 0, 31); VISIBLE_PIXELS]),
-                ready_buffer: Box::new([gba::video::rgb5(31, 0, 31); VISIBLE_PIXELS]),
+                frames: frames_writer,
                 current_mode: GbaRunMode::Paused,
                 paused_cond: Arc::new((Mutex::new(true), Condvar::new())),
                 request_repaint: None,
-                painted: false,
                 profling_enabled: false,
+                stats: RunnerStats::default(),
+                frame_skip: FrameSkip::default(),
             })),
+            frames: Arc::new(Mutex::new(frames_reader)),
         };
 
         let locked = shared.inner.write();
@@ -40,23 +57,31 @@ impl SharedGba {
     }
 
     pub fn unpause(&self) {
-        let mut inner = self.inner.write();
-        inner.current_mode = GbaRunMode::Run;
-        *inner.paused_cond.0.lock() = false;
-        inner.paused_cond.1.notify_all();
+        self.inner.write().transition(GbaRunEvent::Resume);
     }
 
-    #[allow(dead_code)]
     pub fn pause(&self) {
-        self.inner.write().current_mode = GbaRunMode::Paused;
+        self.inner.write().transition(GbaRunEvent::Pause);
+    }
+
+    pub fn toggle_pause(&self) {
+        self.inner.write().transition(GbaRunEvent::TogglePause);
     }
 
-    #[allow(dead_code)]
     pub fn step(&self) {
-        self.inner.write().current_mode = GbaRunMode::Step;
+        self.inner.write().transition(GbaRunEvent::Step);
+    }
+
+    /// Runs the GBA until the next frame is complete and then pauses it.
+    pub fn frame_advance(&self) {
+        self.inner.write().transition(GbaRunEvent::FrameAdvance);
+    }
+
+    /// Stops the GBA thread. The GBA can't be resumed afterwards.
+    pub fn shutdown(&self) {
+        self.inner.write().transition(GbaRunEvent::Shutdown);
     }
 
-    #[allow(dead_code)]
     pub(crate) fn with<F, T>(&self, f: F) -> T
     where
         F: FnOnce(&GbaData) -> T,
@@ -73,7 +98,6 @@ impl SharedGba {
         (f)(&mut locked)
     }
 
-    #[allow(dead_code)]
     pub fn read(&self) -> RwLockReadGuard<'_, GbaData> {
         self.inner.read()
     }
@@ -81,30 +105,319 @@ impl SharedGba {
     pub fn write(&self) -> RwLockWriteGuard<'_, GbaData> {
         self.inner.write()
     }
+
+    /// The reading end of the completed frames. Call [`TripleBufferReader::update`]
+    /// to check for a new frame. The GBA thread never takes this lock.
+    pub fn frames(&self) -> MutexGuard<'_, TripleBufferReader<Box<ScreenBuffer>>> {
+        self.frames.lock()
+    }
+}
+
+/// The GBA that the GUI runs, on either kind of runner. Only [`SharedGba`] gives
+/// access to the GBA's state, so the debugger windows aren't available with
+/// [`ThreadedGba`].
+#[derive(Clone)]
+pub enum GbaRunner {
+    Shared(SharedGba),
+    Threaded(Arc<Mutex<ThreadedGba>>),
+}
+
+impl GbaRunner {
+    pub fn threaded(gba: ThreadedGba) -> Self {
+        GbaRunner::Threaded(Arc::new(Mutex::new(gba)))
+    }
+
+    pub fn shared(&self) -> Option<&SharedGba> {
+        match self {
+            GbaRunner::Shared(shared) => Some(shared),
+            GbaRunner::Threaded(_) => None,
+        }
+    }
+
+    fn send(&self, command: GbaCommand) {
+        if let GbaRunner::Threaded(threaded) = self {
+            if !threaded.lock().send(command) {
+                tracing::error!("GBA thread has exited");
+            }
+        }
+    }
+
+    pub fn unpause(&self) {
+        match self {
+            GbaRunner::Shared(shared) => shared.unpause(),
+            GbaRunner::Threaded(_) => self.send(GbaCommand::Resume),
+        }
+    }
+
+    pub fn pause(&self) {
+        match self {
+            GbaRunner::Shared(shared) => shared.pause(),
+            GbaRunner::Threaded(_) => self.send(GbaCommand::Pause),
+        }
+    }
+
+    pub fn toggle_pause(&self) {
+        match self {
+            GbaRunner::Shared(shared) => shared.toggle_pause(),
+            GbaRunner::Threaded(_) => self.send(GbaCommand::TogglePause),
+        }
+    }
+
+    pub fn step(&self) {
+        match self {
+            GbaRunner::Shared(shared) => shared.step(),
+            GbaRunner::Threaded(_) => self.send(GbaCommand::Step),
+        }
+    }
+
+    pub fn frame_advance(&self) {
+        match self {
+            GbaRunner::Shared(shared) => shared.frame_advance(),
+            GbaRunner::Threaded(_) => self.send(GbaCommand::FrameAdvance),
+        }
+    }
+
+    /// Stops the GBA thread of a [`SharedGba`]. A [`ThreadedGba`]'s thread stops
+    /// when it is dropped.
+    pub fn shutdown(&self) {
+        if let GbaRunner::Shared(shared) = self {
+            shared.shutdown();
+        }
+    }
+
+    /// Replaces the gamepak and resets the GBA. The threaded runner waits for its
+    /// thread to load the ROM and resumes the GBA afterwards.
+    pub fn load_rom(&self, rom: Vec<u8>) -> anyhow::Result<()> {
+        match self {
+            GbaRunner::Shared(shared) => shared.with_mut(|data| {
+                data.gba.try_set_gamepak(rom)?;
+                data.gba.reset();
+                Ok(())
+            }),
+            GbaRunner::Threaded(_) => {
+                let (reply, result) = mpsc::sync_channel(1);
+                self.send(GbaCommand::LoadRom(rom, reply));
+                result.recv().context("GBA thread has exited")??;
+                self.send(GbaCommand::Resume);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn set_keys(&self, keys: impl IntoIterator<Item = (Key, KeyInputState)>) {
+        match self {
+            GbaRunner::Shared(shared) => shared.with_mut(|data| {
+                for (key, state) in keys {
+                    data.gba.keypad_mut().keyinput.set_key_state(key, state);
+                }
+            }),
+            GbaRunner::Threaded(_) => {
+                for (key, state) in keys {
+                    self.send(GbaCommand::SetKey(key, state));
+                }
+            }
+        }
+    }
+
+    /// The emulation speed, if the runner measures it.
+    pub fn stats(&self) -> Option<RunnerStats> {
+        self.shared().map(|shared| shared.with(|data| data.stats))
+    }
+
+    /// Calls `f` with the most recently completed frame if it hasn't been seen yet.
+    pub fn with_new_frame<T>(&self, f: impl FnOnce(&ScreenBuffer) -> T) -> Option<T> {
+        match self {
+            GbaRunner::Shared(shared) => {
+                let mut frames = shared.frames();
+                frames.update().then(|| f(frames.front()))
+            }
+            GbaRunner::Threaded(threaded) => threaded.lock().take_frame().map(f),
+        }
+    }
+
+    /// Calls `f` with the most recently completed frame, whether it was seen yet or not.
+    pub fn with_latest_frame<T>(&self, f: impl FnOnce(&ScreenBuffer) -> T) -> T {
+        match self {
+            GbaRunner::Shared(shared) => {
+                let mut frames = shared.frames();
+                frames.update();
+                f(frames.front())
+            }
+            GbaRunner::Threaded(threaded) => {
+                let mut threaded = threaded.lock();
+                threaded.take_frame();
+                f(threaded.frame())
+            }
+        }
+    }
 }
 
 pub struct GbaData {
     pub gba: Gba,
-    /// The frame buffer that the GBA is currently drawing into.
-    pub frame_buffer: Box<ScreenBuffer>,
-    /// The last completed frame buffer ready for display.
-    pub ready_buffer: Box<ScreenBuffer>,
+    /// The GBA draws into the back buffer and publishes it when a frame is complete.
+    frames: TripleBufferWriter<Box<ScreenBuffer>>,
     pub current_mode: GbaRunMode,
     paused_cond: Arc<(Mutex<bool>, Condvar)>,
 
     /// This function will be called when the GBA wants to request a repaint.
-    /// The first argument passed to the callback is the `ready` flag. When this
-    /// is `true` the [`GbaData::ready_buffer`] should be displayed on screen. If is
-    /// `false` the `frame_buffer` should be used instead.
+    /// The first argument passed to the callback is the `ready` flag, which is
+    /// `true` if a new frame was published to [`SharedGba::frames`].
     #[allow(clippy::type_complexity)]
     pub request_repaint: Option<Box<dyn Fn(bool, &mut GbaData) + Send + Sync>>,
 
-    /// This is set to false before [`GbaData::request_repaint`] is called. It is
-    /// the responsibility of whatever is doing the painting to set and maintain
-    /// this flag in order to reduce work done.
-    pub painted: bool,
-
     pub profling_enabled: bool,
+
+    /// Emulation speed measured by the GBA thread.
+    pub stats: RunnerStats,
+
+    /// How many frames are emulated without being presented while running.
+    pub frame_skip: FrameSkip,
+}
+
+/// The GBA's clock rate in Hz.
+const GBA_CLOCK_RATE: f64 = 16_777_216.0;
+
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
+pub struct RunnerStats {
+    /// Frames emulated per second of wall clock time.
+    pub emulated_fps: f64,
+    /// Emulated cycles per second relative to a real GBA, where 1.0 is full speed.
+    pub speed: f64,
+}
+
+impl RunnerStats {
+    fn from_frames(frames: u32, elapsed: Duration) -> Self {
+        let seconds = elapsed.as_secs_f64();
+        if seconds <= 0.0 {
+            return Self::default();
+        }
+
+        let cycles = frames as f64 * u32::from(gba::video::FRAME_CYCLES) as f64;
+        RunnerStats {
+            emulated_fps: frames as f64 / seconds,
+            speed: cycles / seconds / GBA_CLOCK_RATE,
+        }
+    }
+}
+
+/// Counts emulated frames and reports [`RunnerStats`] about once per second.
+struct FrameTimer {
+    frames: u32,
+    started: Instant,
+}
+
+impl FrameTimer {
+    const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+    fn new() -> Self {
+        Self {
+            frames: 0,
+            started: Instant::now(),
+        }
+    }
+
+    /// Starts measuring again, e.g. after the GBA was paused.
+    fn restart(&mut self) {
+        *self = Self::new();
+    }
+
+    fn frame(&mut self) -> Option<RunnerStats> {
+        self.frames += 1;
+        let elapsed = self.started.elapsed();
+        if elapsed < Self::REPORT_INTERVAL {
+            return None;
+        }
+
+        let stats = RunnerStats::from_frames(self.frames, elapsed);
+        self.restart();
+        Some(stats)
+    }
+}
+
+/// The number of frames that are emulated without being copied to the frame buffer
+/// or presented for every frame that is.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FrameSkip {
+    Fixed(u32),
+
+    /// Skip more frames while emulating a frame takes longer than it takes a real
+    /// GBA to display one, and fewer once the host catches up.
+    Auto,
+}
+
+impl Default for FrameSkip {
+    fn default() -> Self {
+        FrameSkip::Fixed(0)
+    }
+}
+
+/// Decides which frames are presented and adjusts the number of skipped frames
+/// for [`FrameSkip::Auto`] based on how long frames take to emulate.
+#[derive(Default)]
+struct FrameSkipper {
+    /// Frames that were skipped since the last presented frame.
+    skipped: u32,
+    /// The number of frames to skip in [`FrameSkip::Auto`] mode.
+    auto_skip: u32,
+    /// Time spent emulating the frames since the last presented frame.
+    busy: Duration,
+}
+
+impl FrameSkipper {
+    const MAX_AUTO_SKIP: u32 = 4;
+
+    fn skip(&self, mode: FrameSkip) -> u32 {
+        match mode {
+            FrameSkip::Fixed(skip) => skip,
+            FrameSkip::Auto => self.auto_skip,
+        }
+    }
+
+    /// Returns true if the next frame should be presented.
+    fn present_next(&self, mode: FrameSkip) -> bool {
+        self.skipped >= self.skip(mode)
+    }
+
+    /// Records a frame that took `elapsed` to emulate.
+    fn frame_done(&mut self, mode: FrameSkip, presented: bool, elapsed: Duration) {
+        self.busy += elapsed;
+        if !presented {
+            self.skipped += 1;
+            return;
+        }
+
+        if mode == FrameSkip::Auto {
+            let frame_time = gba_frame_time();
+            let average = self.busy / (self.skipped + 1);
+            if average > frame_time {
+                self.auto_skip = (self.auto_skip + 1).min(Self::MAX_AUTO_SKIP);
+            } else if average < frame_time * 3 / 4 {
+                self.auto_skip = self.auto_skip.saturating_sub(1);
+            }
+        }
+
+        self.skipped = 0;
+        self.busy = Duration::ZERO;
+    }
+}
+
+/// How long it takes a real GBA to display one frame.
+fn gba_frame_time() -> Duration {
+    Duration::from_secs_f64(f64::from(u32::from(gba::video::FRAME_CYCLES)) / GBA_CLOCK_RATE)
+}
+
+impl GbaData {
+    /// Moves to the next run mode and wakes up the GBA thread if it was waiting
+    /// while paused.
+    fn transition(&mut self, event: GbaRunEvent) {
+        self.current_mode = self.current_mode.next(event);
+        let paused = self.current_mode == GbaRunMode::Paused;
+        *self.paused_cond.0.lock() = paused;
+        if !paused {
+            self.paused_cond.1.notify_all();
+        }
+    }
 }
 
 fn gba_run_loop(gba: SharedGba) {
@@ -113,6 +426,8 @@ fn gba_run_loop(gba: SharedGba) {
     let mut loop_helper = LoopHelper::builder()
         .report_interval_s(1.0)
         .build_with_target_rate(60.0);
+    let mut frame_timer = FrameTimer::new();
+    let mut frame_skipper = FrameSkipper::default();
     loop {
         loop_helper.loop_start();
         if Arc::strong_count(&gba.inner) == 0 {
@@ -123,17 +438,30 @@ fn gba_run_loop(gba: SharedGba) {
         let mut data = gba.inner.write();
         match data.current_mode {
             GbaRunMode::Run => {
-                gba_frame_tick(&mut data);
+                let present = frame_skipper.present_next(data.frame_skip);
+                let started = Instant::now();
+                let completed = if present {
+                    gba_frame_tick(&mut data)
+                } else {
+                    gba_skipped_frame_tick(&mut data)
+                };
+
+                if completed {
+                    frame_skipper.frame_done(data.frame_skip, present, started.elapsed());
+                    if let Some(stats) = frame_timer.frame() {
+                        data.stats = stats;
+                    }
+                }
                 RwLockWriteGuard::unlock_fair(data);
                 loop_helper.loop_sleep();
             }
             GbaRunMode::Frame => {
                 gba_frame_tick(&mut data);
-                data.current_mode = GbaRunMode::Paused;
+                data.transition(GbaRunEvent::Finished);
             }
             GbaRunMode::Step => {
                 gba_step_tick(&mut data);
-                data.current_mode = GbaRunMode::Paused;
+                data.transition(GbaRunEvent::Finished);
             }
             GbaRunMode::Paused => {
                 tracing::debug!("GBA paused");
@@ -144,10 +472,12 @@ fn gba_run_loop(gba: SharedGba) {
                 if *locked {
                     cvar.wait(&mut locked);
                 }
+                frame_timer.restart();
                 tracing::debug!("GBA wakeup");
             }
             GbaRunMode::Shutdown => {
                 tracing::debug!("explicit GBA shutdown requested");
+                break;
             }
         };
     }
@@ -155,8 +485,10 @@ fn gba_run_loop(gba: SharedGba) {
     tracing::debug!("shutdown GBA run loop");
 }
 
-fn gba_frame_tick(data: &mut GbaData) {
-    let mut fb = FrameBuffer::new(&mut data.frame_buffer);
+/// Runs the GBA until the next frame is complete. Returns false if the GBA
+/// halted before that happened.
+fn gba_frame_tick(data: &mut GbaData) -> bool {
+    let mut fb = FrameBuffer::new(data.frames.back_mut());
     let mut ab = gba::NoopGbaAudioOutput;
 
     {
@@ -167,44 +499,108 @@ fn gba_frame_tick(data: &mut GbaData) {
         puffin::profile_scope!("render_frame");
 
         while !fb.ready {
-            data.gba.step(&mut fb, &mut ab);
+            if data.gba.step(&mut fb, &mut ab) == GbaStepResult::Halted {
+                tracing::debug!("GBA halted");
+                data.transition(GbaRunEvent::Pause);
+                return false;
+            }
         }
     }
 
-    std::mem::swap::<Box<ScreenBuffer>>(&mut data.frame_buffer, &mut data.ready_buffer);
+    data.frames.publish();
 
     if let Some(request_repaint) = data.request_repaint.take() {
-        data.painted = false;
         request_repaint(true, data);
         data.request_repaint = Some(request_repaint);
     }
+
+    true
+}
+
+/// Runs the GBA until the next frame is complete without drawing it, copying it into
+/// the frame buffer or requesting a repaint. Returns false if the GBA halted before
+/// that happened.
+fn gba_skipped_frame_tick(data: &mut GbaData) -> bool {
+    let mut vb = gba::NoopGbaVideoOutput;
+    let mut ab = gba::NoopGbaAudioOutput;
+
+    #[cfg(feature = "puffin")]
+    puffin::profile_scope!("skip_frame");
+
+    data.gba.set_rendering_suppressed(true);
+    let frame = data.gba.frame_count();
+    let mut completed = true;
+    while data.gba.frame_count() == frame {
+        if data.gba.step(&mut vb, &mut ab) == GbaStepResult::Halted {
+            tracing::debug!("GBA halted");
+            data.transition(GbaRunEvent::Pause);
+            completed = false;
+            break;
+        }
+    }
+    data.gba.set_rendering_suppressed(false);
+
+    completed
 }
 
 fn gba_step_tick(data: &mut GbaData) {
-    let mut fb = FrameBuffer::new(&mut data.frame_buffer);
+    let mut fb = FrameBuffer::new(data.frames.back_mut());
     let mut ab = gba::NoopGbaAudioOutput;
     data.gba.step(&mut fb, &mut ab);
     let frame_ready = fb.ready;
 
     if frame_ready {
-        std::mem::swap::<Box<ScreenBuffer>>(&mut data.frame_buffer, &mut data.ready_buffer);
+        data.frames.publish();
     }
 
     if let Some(request_repaint) = data.request_repaint.take() {
-        data.painted = false;
         request_repaint(frame_ready, data);
         data.request_repaint = Some(request_repaint);
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum GbaRunMode {
     Run,
-    #[allow(dead_code)]
+    /// Run until the next frame is complete and then pause.
     Frame,
+    /// Run a single instruction and then pause.
     Step,
     Paused,
-    #[allow(dead_code)]
+    Shutdown,
+}
+
+impl GbaRunMode {
+    pub fn next(self, event: GbaRunEvent) -> GbaRunMode {
+        if self == GbaRunMode::Shutdown {
+            return GbaRunMode::Shutdown;
+        }
+
+        match event {
+            GbaRunEvent::Resume => GbaRunMode::Run,
+            GbaRunEvent::Pause => GbaRunMode::Paused,
+            GbaRunEvent::TogglePause if self == GbaRunMode::Paused => GbaRunMode::Run,
+            GbaRunEvent::TogglePause => GbaRunMode::Paused,
+            GbaRunEvent::Step => GbaRunMode::Step,
+            GbaRunEvent::FrameAdvance => GbaRunMode::Frame,
+            GbaRunEvent::Shutdown => GbaRunMode::Shutdown,
+            GbaRunEvent::Finished if matches!(self, GbaRunMode::Frame | GbaRunMode::Step) => {
+                GbaRunMode::Paused
+            }
+            GbaRunEvent::Finished => self,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum GbaRunEvent {
+    Resume,
+    Pause,
+    TogglePause,
+    Step,
+    FrameAdvance,
+    /// A single step or frame advance has completed.
+    Finished,
     Shutdown,
 }
 
@@ -226,8 +622,118 @@ impl<'b> GbaVideoOutput for FrameBuffer<'b> {
     fn gba_line_ready(&mut self, line: usize, data: &gba::video::LineBuffer) {
         let pos = VISIBLE_LINE_WIDTH * line;
         self.buffer[pos..(pos + VISIBLE_LINE_WIDTH)].copy_from_slice(data);
-        if line == gba::video::VISIBLE_LINE_COUNT - 1 {
-            self.ready = true;
-        }
+    }
+
+    fn gba_frame_ready(&mut self, _frame: &gba::video::FrameBuffer) {
+        self.ready = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{gba_frame_time, FrameSkip, FrameSkipper, GbaRunEvent, GbaRunMode, RunnerStats};
+
+    #[test]
+    fn run_mode_transitions() {
+        let mode = GbaRunMode::Run;
+        let mode = mode.next(GbaRunEvent::Pause);
+        assert_eq!(mode, GbaRunMode::Paused);
+        let mode = mode.next(GbaRunEvent::FrameAdvance);
+        assert_eq!(mode, GbaRunMode::Frame);
+        let mode = mode.next(GbaRunEvent::Finished);
+        assert_eq!(mode, GbaRunMode::Paused);
+
+        assert_eq!(mode.next(GbaRunEvent::TogglePause), GbaRunMode::Run);
+        assert_eq!(
+            GbaRunMode::Run.next(GbaRunEvent::TogglePause),
+            GbaRunMode::Paused
+        );
+        assert_eq!(
+            GbaRunMode::Step.next(GbaRunEvent::Finished),
+            GbaRunMode::Paused
+        );
+        assert_eq!(GbaRunMode::Run.next(GbaRunEvent::Finished), GbaRunMode::Run);
+        assert_eq!(
+            GbaRunMode::Run.next(GbaRunEvent::Shutdown),
+            GbaRunMode::Shutdown
+        );
+        assert_eq!(
+            GbaRunMode::Shutdown.next(GbaRunEvent::Resume),
+            GbaRunMode::Shutdown
+        );
+    }
+
+    #[test]
+    fn runner_stats_at_full_speed() {
+        // a real GBA runs 16777216 / 280896 (~59.7275) frames per second
+        let stats = RunnerStats::from_frames(597275, Duration::from_secs(10000));
+        assert!((stats.emulated_fps - 59.7275).abs() < 1e-9);
+        assert!((stats.speed - 1.0).abs() < 1e-5);
+
+        let stats = RunnerStats::from_frames(120, Duration::from_secs(1));
+        assert!((stats.speed - 120.0 / 59.7275).abs() < 1e-5);
+    }
+
+    /// Emulates frames that each take `elapsed` and returns which ones were presented.
+    fn run_frames(
+        skipper: &mut FrameSkipper,
+        mode: FrameSkip,
+        frames: usize,
+        elapsed: Duration,
+    ) -> Vec<bool> {
+        (0..frames)
+            .map(|_| {
+                let present = skipper.present_next(mode);
+                skipper.frame_done(mode, present, elapsed);
+                present
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fixed_frame_skip() {
+        let mut skipper = FrameSkipper::default();
+        let slow = gba_frame_time() * 2;
+
+        let presented = run_frames(&mut skipper, FrameSkip::Fixed(0), 3, slow);
+        assert_eq!(presented, [true, true, true]);
+
+        let presented = run_frames(&mut skipper, FrameSkip::Fixed(2), 6, slow);
+        assert_eq!(presented, [false, false, true, false, false, true]);
+    }
+
+    #[test]
+    fn auto_frame_skip_adapts_to_frame_time() {
+        let mut skipper = FrameSkipper::default();
+        let frame_time = gba_frame_time();
+
+        // Keeping up, nothing is skipped.
+        let presented = run_frames(&mut skipper, FrameSkip::Auto, 4, frame_time / 2);
+        assert!(presented.iter().all(|&presented| presented));
+
+        // Falling behind skips one more frame per presented frame, up to the maximum.
+        let presented = run_frames(&mut skipper, FrameSkip::Auto, 1, frame_time * 2);
+        assert_eq!(presented, [true]);
+        assert_eq!(skipper.skip(FrameSkip::Auto), 1);
+        let presented = run_frames(&mut skipper, FrameSkip::Auto, 2 + 3 + 4, frame_time * 2);
+        assert_eq!(presented.iter().filter(|&&presented| presented).count(), 3);
+        assert_eq!(skipper.skip(FrameSkip::Auto), FrameSkipper::MAX_AUTO_SKIP);
+        run_frames(&mut skipper, FrameSkip::Auto, 5, frame_time * 2);
+        assert_eq!(skipper.skip(FrameSkip::Auto), FrameSkipper::MAX_AUTO_SKIP);
+
+        // Frames that take a little less than a real frame don't change anything.
+        let skip = FrameSkipper::MAX_AUTO_SKIP as usize;
+        run_frames(&mut skipper, FrameSkip::Auto, skip + 1, frame_time * 9 / 10);
+        assert_eq!(skipper.skip(FrameSkip::Auto), FrameSkipper::MAX_AUTO_SKIP);
+
+        // Catching up skips fewer frames again.
+        let presented = run_frames(&mut skipper, FrameSkip::Auto, skip + 1, frame_time / 2);
+        assert_eq!(presented.iter().filter(|&&presented| presented).count(), 1);
+        assert_eq!(
+            skipper.skip(FrameSkip::Auto),
+            FrameSkipper::MAX_AUTO_SKIP - 1
+        );
     }
 }
@@ -0,0 +1,265 @@
+use std::{
+    sync::mpsc::{self, Receiver, Sender, SyncSender, TryRecvError},
+    thread::JoinHandle,
+};
+
+use gba::{
+    keypad::{Key, KeyInputState},
+    video::{ScreenBuffer, VISIBLE_PIXELS},
+    GamepakError, Gba, GbaStepResult, NoopGbaAudioOutput,
+};
+use spin_sleep::LoopHelper;
+
+use super::{
+    triple_buffer::{triple_buffer, TripleBufferReader, TripleBufferWriter},
+    FrameBuffer, GbaRunEvent, GbaRunMode,
+};
+
+/// Commands that are sent to the GBA thread of a [`ThreadedGba`].
+pub enum GbaCommand {
+    /// Replaces the gamepak, resets the GBA and pauses it. The result is sent back
+    /// on the channel. Nothing changes if the ROM is rejected.
+    LoadRom(Vec<u8>, SyncSender<Result<(), GamepakError>>),
+    SetKey(Key, KeyInputState),
+    Resume,
+    Pause,
+    TogglePause,
+    /// Runs a single instruction and then pauses.
+    Step,
+    /// Runs until the next frame is complete and then pauses.
+    FrameAdvance,
+}
+
+/// Runs a GBA on its own thread without sharing it behind a lock like
+/// [`SharedGba`](super::SharedGba). The GBA is only controlled through
+/// [`GbaCommand`]s and completed frames are passed back through a triple buffer,
+/// so neither side ever waits on the other.
+///
+/// The GBA thread exits when this is dropped.
+pub struct ThreadedGba {
+    commands: Option<Sender<GbaCommand>>,
+    frames: TripleBufferReader<Box<ScreenBuffer>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ThreadedGba {
+    /// Moves the GBA to a new thread. It starts out paused. `on_frame` is called on
+    /// the GBA thread every time a frame is completed, e.g. to request a repaint.
+    pub fn spawn(gba: Gba, on_frame: impl Fn() + Send + 'static) -> Self {
+        let (commands, receiver) = mpsc::channel();
+        let (writer, frames) =
+            triple_buffer(Box::new([gba::video::rgb5(31, 0, 31); VISIBLE_PIXELS]));
+
+        let mut publisher = FramePublisher {
+            writer,
+            on_frame: Box::new(on_frame),
+        };
+        let thread = std::thread::Builder::new()
+            .name("gba".into())
+            .spawn(move || threaded_run_loop(gba, receiver, &mut publisher))
+            .unwrap();
+
+        ThreadedGba {
+            commands: Some(commands),
+            frames,
+            thread: Some(thread),
+        }
+    }
+
+    /// Sends a command to the GBA thread. Returns false if the thread has exited.
+    pub fn send(&self, command: GbaCommand) -> bool {
+        self.commands
+            .as_ref()
+            .is_some_and(|commands| commands.send(command).is_ok())
+    }
+
+    /// The last frame that was received with [`ThreadedGba::take_frame`].
+    pub fn frame(&self) -> &ScreenBuffer {
+        self.frames.front()
+    }
+
+    /// Returns the most recently completed frame if there is one that hasn't been
+    /// received yet. Frames that were completed in the meantime are dropped.
+    pub fn take_frame(&mut self) -> Option<&ScreenBuffer> {
+        self.frames.update().then(|| &**self.frames.front())
+    }
+}
+
+impl Drop for ThreadedGba {
+    fn drop(&mut self) {
+        // Disconnecting the channel tells the GBA thread to exit.
+        self.commands = None;
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                tracing::error!("GBA thread panicked");
+            }
+        }
+    }
+}
+
+struct FramePublisher {
+    writer: TripleBufferWriter<Box<ScreenBuffer>>,
+    on_frame: Box<dyn Fn() + Send>,
+}
+
+impl FramePublisher {
+    fn publish(&mut self) {
+        self.writer.publish();
+        (self.on_frame)();
+    }
+}
+
+fn threaded_run_loop(mut gba: Gba, commands: Receiver<GbaCommand>, frames: &mut FramePublisher) {
+    tracing::debug!("starting threaded GBA run loop");
+
+    let mut loop_helper = LoopHelper::builder().build_with_target_rate(60.0);
+    let mut mode = GbaRunMode::Paused;
+
+    loop {
+        // There is nothing to do while paused until the next command arrives.
+        let command = if mode == GbaRunMode::Paused {
+            match commands.recv() {
+                Ok(command) => Some(command),
+                Err(_) => break,
+            }
+        } else {
+            match commands.try_recv() {
+                Ok(command) => Some(command),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => break,
+            }
+        };
+
+        // Handle every pending command before running anything.
+        if let Some(command) = command {
+            mode = handle_command(&mut gba, mode, command);
+            continue;
+        }
+
+        match mode {
+            GbaRunMode::Run => {
+                loop_helper.loop_start();
+                if threaded_frame_tick(&mut gba, frames.writer.back_mut()) {
+                    frames.publish();
+                } else {
+                    mode = mode.next(GbaRunEvent::Pause);
+                }
+                loop_helper.loop_sleep();
+            }
+            GbaRunMode::Frame => {
+                if threaded_frame_tick(&mut gba, frames.writer.back_mut()) {
+                    frames.publish();
+                }
+                mode = mode.next(GbaRunEvent::Finished);
+            }
+            GbaRunMode::Step => {
+                let mut output = FrameBuffer::new(frames.writer.back_mut());
+                gba.step(&mut output, &mut NoopGbaAudioOutput);
+                if output.ready {
+                    frames.publish();
+                }
+                mode = mode.next(GbaRunEvent::Finished);
+            }
+            GbaRunMode::Paused => {}
+            GbaRunMode::Shutdown => break,
+        }
+    }
+
+    tracing::debug!("shutdown threaded GBA run loop");
+}
+
+fn handle_command(gba: &mut Gba, mode: GbaRunMode, command: GbaCommand) -> GbaRunMode {
+    match command {
+        GbaCommand::LoadRom(rom, reply) => {
+            let result = gba.try_set_gamepak(rom);
+            let loaded = result.is_ok();
+            // The sender might have stopped waiting, that doesn't change anything.
+            let _ = reply.send(result);
+            if !loaded {
+                return mode;
+            }
+            gba.reset();
+            mode.next(GbaRunEvent::Pause)
+        }
+        GbaCommand::SetKey(key, state) => {
+            gba.keypad_mut().keyinput.set_key_state(key, state);
+            mode
+        }
+        GbaCommand::Resume => mode.next(GbaRunEvent::Resume),
+        GbaCommand::Pause => mode.next(GbaRunEvent::Pause),
+        GbaCommand::TogglePause => mode.next(GbaRunEvent::TogglePause),
+        GbaCommand::Step => mode.next(GbaRunEvent::Step),
+        GbaCommand::FrameAdvance => mode.next(GbaRunEvent::FrameAdvance),
+    }
+}
+
+/// Runs the GBA until the next frame is complete, drawing it into `back`. Returns
+/// false if the GBA halted before that happened.
+fn threaded_frame_tick(gba: &mut Gba, back: &mut ScreenBuffer) -> bool {
+    let mut output = FrameBuffer::new(back);
+    while !output.ready {
+        if gba.step(&mut output, &mut NoopGbaAudioOutput) == GbaStepResult::Halted {
+            tracing::debug!("GBA halted");
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, time::Duration};
+
+    use gba::{memory::GAMEPAK_HEADER_SIZE, video::VISIBLE_PIXELS, Gba, NOP_ROM};
+
+    use gba::GamepakError;
+
+    use super::{GbaCommand, ThreadedGba};
+
+    #[test]
+    fn load_rom_and_receive_frames() {
+        let (frame_sender, frame_receiver) = mpsc::channel();
+        let mut gba = ThreadedGba::spawn(Gba::new(), move || {
+            let _ = frame_sender.send(());
+        });
+        assert!(gba.take_frame().is_none());
+
+        let mut rom = NOP_ROM.to_vec();
+        rom.resize(GAMEPAK_HEADER_SIZE, 0);
+        let (reply, result) = mpsc::sync_channel(1);
+        assert!(gba.send(GbaCommand::LoadRom(rom, reply)));
+        assert_eq!(result.recv(), Ok(Ok(())));
+        assert!(gba.send(GbaCommand::FrameAdvance));
+        assert!(frame_receiver.recv_timeout(Duration::from_secs(10)).is_ok());
+        let frame = gba.take_frame();
+        assert_eq!(frame.map(|frame| frame.len()), Some(VISIBLE_PIXELS));
+
+        // The GBA pauses after a frame advance so no more frames arrive.
+        assert!(frame_receiver
+            .recv_timeout(Duration::from_millis(100))
+            .is_err());
+        assert!(gba.take_frame().is_none());
+
+        assert!(gba.send(GbaCommand::TogglePause));
+        assert!(frame_receiver.recv_timeout(Duration::from_secs(10)).is_ok());
+        assert!(gba.take_frame().is_some());
+        assert!(gba.send(GbaCommand::Pause));
+    }
+
+    #[test]
+    fn reject_invalid_rom() {
+        let (frame_sender, frame_receiver) = mpsc::channel();
+        let gba = ThreadedGba::spawn(Gba::new(), move || {
+            let _ = frame_sender.send(());
+        });
+
+        let (reply, result) = mpsc::sync_channel(1);
+        assert!(gba.send(GbaCommand::LoadRom(vec![0; 4], reply)));
+        assert_eq!(result.recv(), Ok(Err(GamepakError::TooSmall(4))));
+
+        // The GBA stays paused.
+        assert!(frame_receiver
+            .recv_timeout(Duration::from_millis(100))
+            .is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use std::{
+    cell::UnsafeCell,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+};
+
+const INDEX_MASK: u8 = 0b11;
+/// Set in [`Shared::middle`] while the middle buffer holds a published value that the
+/// reader hasn't taken yet.
+const FRESH: u8 = 0b100;
+
+/// Creates a lock-free triple buffer with every buffer set to `initial`. The writer
+/// fills its back buffer and publishes it, and the reader takes the newest published
+/// buffer whenever it wants. Neither side ever waits for the other and values that
+/// are published faster than they are read are skipped.
+pub fn triple_buffer<T: Clone>(initial: T) -> (TripleBufferWriter<T>, TripleBufferReader<T>) {
+    let shared = Arc::new(Shared {
+        buffers: [
+            UnsafeCell::new(initial.clone()),
+            UnsafeCell::new(initial.clone()),
+            UnsafeCell::new(initial),
+        ],
+        middle: AtomicU8::new(1),
+    });
+
+    let writer = TripleBufferWriter {
+        shared: Arc::clone(&shared),
+        back: 0,
+    };
+    let reader = TripleBufferReader { shared, front: 2 };
+    (writer, reader)
+}
+
+struct Shared<T> {
+    buffers: [UnsafeCell<T>; 3],
+    /// The index of the buffer between the writer and the reader and the [`FRESH`] flag.
+    middle: AtomicU8,
+}
+
+// SAFETY: The writer only accesses its back buffer and the reader only accesses its
+// front buffer. They only trade buffers by swapping indices with `middle`, so a buffer
+// is never accessed by both sides at the same time.
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+pub struct TripleBufferWriter<T> {
+    shared: Arc<Shared<T>>,
+    back: u8,
+}
+
+impl<T> TripleBufferWriter<T> {
+    /// The buffer that is being written. It holds an older value after each
+    /// [`TripleBufferWriter::publish`].
+    pub fn back_mut(&mut self) -> &mut T {
+        // SAFETY: Only the writer has the index of the back buffer.
+        unsafe { &mut *self.shared.buffers[self.back as usize].get() }
+    }
+
+    /// Hands the back buffer to the reader and takes the middle buffer in its place.
+    pub fn publish(&mut self) {
+        let previous = self.shared.middle.swap(self.back | FRESH, Ordering::AcqRel);
+        self.back = previous & INDEX_MASK;
+    }
+}
+
+pub struct TripleBufferReader<T> {
+    shared: Arc<Shared<T>>,
+    front: u8,
+}
+
+impl<T> TripleBufferReader<T> {
+    /// Takes the newest published buffer if there is one that hasn't been read yet.
+    /// Returns true if [`TripleBufferReader::front`] changed.
+    pub fn update(&mut self) -> bool {
+        if self.shared.middle.load(Ordering::Relaxed) & FRESH == 0 {
+            return false;
+        }
+        let previous = self.shared.middle.swap(self.front, Ordering::AcqRel);
+        self.front = previous & INDEX_MASK;
+        true
+    }
+
+    /// The last buffer that was taken by [`TripleBufferReader::update`], or the
+    /// initial value if nothing was published yet.
+    pub fn front(&self) -> &T {
+        // SAFETY: Only the reader has the index of the front buffer.
+        unsafe { &*self.shared.buffers[self.front as usize].get() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::triple_buffer;
+
+    #[test]
+    fn reads_newest_value() {
+        let (mut writer, mut reader) = triple_buffer(0);
+        assert!(!reader.update());
+        assert_eq!(*reader.front(), 0);
+
+        *writer.back_mut() = 1;
+        writer.publish();
+        *writer.back_mut() = 2;
+        writer.publish();
+        assert!(reader.update());
+        assert_eq!(*reader.front(), 2);
+        assert!(!reader.update());
+        assert_eq!(*reader.front(), 2);
+    }
+
+    #[test]
+    fn concurrent_frames_are_never_torn() {
+        const FRAMES: u32 = 20_000;
+        let (mut writer, mut reader) = triple_buffer(vec![0u32; 4096]);
+
+        let writer_thread = std::thread::spawn(move || {
+            for frame in 1..=FRAMES {
+                writer.back_mut().fill(frame);
+                writer.publish();
+            }
+        });
+
+        let mut last = 0;
+        while last < FRAMES {
+            if !reader.update() {
+                std::hint::spin_loop();
+                continue;
+            }
+            let frame = reader.front();
+            assert!(
+                frame.iter().all(|&value| value == frame[0]),
+                "torn frame after frame {last}"
+            );
+            assert!(frame[0] > last, "frame {} read after {last}", frame[0]);
+            last = frame[0];
+        }
+        writer_thread.join().unwrap();
+    }
+}
@@ -0,0 +1,76 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write as _},
+};
+
+use anyhow::Context as _;
+use gba::{
+    video::{VISIBLE_LINE_COUNT, VISIBLE_LINE_WIDTH, VISIBLE_PIXELS},
+    Gba, GbaStepResult, NoopGbaAudioOutput, NoopGbaVideoOutput,
+};
+
+use crate::{cli::PyriteCli, config::Config, png, ui::gba_image::common::fill_upload_buffer};
+
+/// Runs the ROM from the command line for the requested number of frames without
+/// creating a window or a graphics context, then optionally writes the last frame
+/// to a PNG and a disassembly listing. Only the listing is written if `--headless`
+/// was not passed.
+pub fn run(cli: &PyriteCli, config: &Config) -> anyhow::Result<()> {
+    let mut gba = Gba::new();
+    cli.boot(&mut gba)?;
+
+    if cli.headless {
+        run_frames(cli, config, &mut gba)?;
+    }
+
+    if let Some(range) = cli.disassemble {
+        let isa = cli.isa.into();
+        if let Some(ref path) = cli.disassembly_output {
+            let file = File::create(path)
+                .with_context(|| format!("error creating disassembly {path:?}"))?;
+            let mut out = BufWriter::new(file);
+            gba.write_disassembly(&mut out, range.start, range.len, isa)
+                .and_then(|_| out.flush())
+                .with_context(|| format!("error writing disassembly to {path:?}"))?;
+        } else {
+            let mut out = std::io::stdout().lock();
+            gba.write_disassembly(&mut out, range.start, range.len, isa)
+                .context("error writing disassembly")?;
+        }
+    }
+
+    Ok(())
+}
+
+fn run_frames(cli: &PyriteCli, config: &Config, gba: &mut Gba) -> anyhow::Result<()> {
+    let mut video = NoopGbaVideoOutput;
+    let mut audio = NoopGbaAudioOutput;
+    let end_frame = gba.frame_count() + cli.frames;
+    while gba.frame_count() < end_frame {
+        if gba.step(&mut video, &mut audio) == GbaStepResult::Halted {
+            anyhow::bail!("GBA halted on frame {}", gba.frame_count());
+        }
+    }
+    tracing::info!(frames = cli.frames, "finished running headless");
+
+    if let Some(ref path) = cli.screenshot {
+        let mut pixels = vec![[0; 4]; VISIBLE_PIXELS];
+        fill_upload_buffer(
+            gba.frame_buffer().pixels(),
+            &mut pixels,
+            config.gui.color_correction,
+        );
+
+        let file =
+            File::create(path).with_context(|| format!("error creating screenshot {path:?}"))?;
+        png::write_rgba8(
+            BufWriter::new(file),
+            VISIBLE_LINE_WIDTH as u32,
+            VISIBLE_LINE_COUNT as u32,
+            &pixels,
+        )
+        .with_context(|| format!("error writing screenshot to {path:?}"))?;
+    }
+
+    Ok(())
+}
@@ -1,12 +1,14 @@
 mod cli;
 mod gba_runner;
+mod headless;
+mod png;
+mod rom;
 mod ui;
 
 use anyhow::Context as _;
 use clap::Parser;
 use cli::PyriteCli;
 use eframe::Renderer;
-use gba_runner::SharedGba;
 mod config;
 mod logging;
 
@@ -15,6 +17,10 @@ fn main() -> anyhow::Result<()> {
     let mut config = config::load().context("error while loading config")?;
     logging::init(&mut config).context("error while initializing logging")?;
 
+    if cli.headless || cli.disassemble.is_some() {
+        return headless::run(&cli, &config);
+    }
+
     let renderer = if let Some(ref renderer) = config.gui.renderer {
         if renderer.eq_ignore_ascii_case("glow") || renderer.eq_ignore_ascii_case("gl") {
             #[cfg(feature = "glow")]
@@ -53,15 +59,13 @@ fn main() -> anyhow::Result<()> {
     eframe::run_native(
         "Pyrite",
         native_options,
-        Box::new(
-            move |context| match ui::App::new(cli, config, SharedGba::new(), context) {
-                Ok(app) => Box::new(app),
-                Err(err) => {
-                    tracing::error!(error = debug(err), "error while initializing app");
-                    Box::new(AutocloseApp)
-                }
-            },
-        ),
+        Box::new(move |context| match ui::App::new(cli, config, context) {
+            Ok(app) => Box::new(app),
+            Err(err) => {
+                tracing::error!(error = debug(err), "error while initializing app");
+                Box::new(AutocloseApp)
+            }
+        }),
     )
     .map_err(|current| {
         let mut ret_err = anyhow::Error::msg(current.to_string());
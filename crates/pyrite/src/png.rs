@@ -0,0 +1,163 @@
+//! A minimal PNG encoder for writing screenshots. Image data is stored without
+//! compression, which is fine for a 240x160 screen.
+
+use std::io::{self, Write};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+/// The largest amount of data that fits in a single stored deflate block.
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+/// Writes an 8-bit RGBA image as a PNG. `pixels` must contain `width * height`
+/// pixels in row-major order.
+pub fn write_rgba8<W: Write>(
+    mut out: W,
+    width: u32,
+    height: u32,
+    pixels: &[[u8; 4]],
+) -> io::Result<()> {
+    assert_eq!(pixels.len(), width as usize * height as usize);
+
+    out.write_all(&SIGNATURE)?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, RGBA, deflate, adaptive filtering, no interlacing
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+    write_chunk(&mut out, b"IHDR", &header)?;
+
+    // Every scanline starts with its filter type, which is always None here.
+    let mut scanlines = Vec::with_capacity(pixels.len() * 4 + height as usize);
+    if width > 0 {
+        for row in pixels.chunks(width as usize) {
+            scanlines.push(0);
+            scanlines.extend(row.iter().flatten());
+        }
+    }
+    write_chunk(&mut out, b"IDAT", &zlib_stored(&scanlines))?;
+
+    write_chunk(&mut out, b"IEND", &[])
+}
+
+fn write_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(data.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "PNG chunk is too large"))?;
+    out.write_all(&len.to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+
+    let mut crc = Crc32::new();
+    crc.update(kind);
+    crc.update(data);
+    out.write_all(&crc.finish().to_be_bytes())
+}
+
+/// Wraps `data` in a zlib stream made up of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let block_count = data.len().div_ceil(MAX_STORED_BLOCK).max(1);
+    let mut out = Vec::with_capacity(data.len() + block_count * 5 + 6);
+
+    // deflate with a 32K window and the lowest compression level
+    out.extend_from_slice(&[0x78, 0x01]);
+
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 is the most bytes that can be summed before `b` could overflow.
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= MOD_ADLER;
+        b %= MOD_ADLER;
+    }
+    (b << 16) | a
+}
+
+struct Crc32(u32);
+
+impl Crc32 {
+    fn new() -> Self {
+        Crc32(0xFFFFFFFF)
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.0 ^= u32::from(byte);
+            for _ in 0..8 {
+                let mask = (self.0 & 1).wrapping_neg();
+                self.0 = (self.0 >> 1) ^ (0xEDB88320 & mask);
+            }
+        }
+    }
+
+    fn finish(self) -> u32 {
+        !self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums() {
+        let mut crc = Crc32::new();
+        crc.update(b"IEND");
+        assert_eq!(crc.finish(), 0xAE426082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+    }
+
+    #[test]
+    fn encode_small_image() {
+        let mut png = Vec::new();
+        write_rgba8(&mut png, 2, 1, &[[1, 2, 3, 4], [5, 6, 7, 8]]).unwrap();
+
+        assert_eq!(png[..8], SIGNATURE);
+        assert_eq!(png[8..16], [0, 0, 0, 13, b'I', b'H', b'D', b'R']);
+        assert_eq!(png[16..29], [0, 0, 0, 2, 0, 0, 0, 1, 8, 6, 0, 0, 0]);
+
+        let scanline = [0, 1, 2, 3, 4, 5, 6, 7, 8];
+        let mut idat = vec![0x78, 0x01, 1, 9, 0, 0xF6, 0xFF];
+        idat.extend_from_slice(&scanline);
+        idat.extend_from_slice(&adler32(&scanline).to_be_bytes());
+        assert_eq!(png[33..37], (idat.len() as u32).to_be_bytes());
+        assert_eq!(png[37..41], *b"IDAT");
+        assert_eq!(png[41..41 + idat.len()], idat);
+
+        assert_eq!(
+            png[png.len() - 12..],
+            [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]
+        );
+    }
+
+    #[test]
+    fn large_data_is_split_into_stored_blocks() {
+        let data = vec![0xAB; MAX_STORED_BLOCK + 1];
+        let stream = zlib_stored(&data);
+        assert_eq!(stream.len(), 2 + 5 + MAX_STORED_BLOCK + 5 + 1 + 4);
+        assert_eq!(stream[2..7], [0, 0xFF, 0xFF, 0x00, 0x00]);
+        let second = 7 + MAX_STORED_BLOCK;
+        assert_eq!(stream[second..second + 5], [1, 1, 0, 0xFE, 0xFF]);
+    }
+}
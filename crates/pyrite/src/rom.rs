@@ -0,0 +1,112 @@
+use std::{
+    io::{Read, Seek},
+    path::Path,
+};
+
+use anyhow::Context as _;
+use gba::memory::GAMEPAK_MAX_SIZE;
+
+/// Returns true if `path` has an extension that [`read_rom`] handles: `.gba` or `.zip`.
+pub fn is_rom_path(path: &Path) -> bool {
+    has_extension(path, "gba") || has_extension(path, "zip")
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+/// Reads a ROM from a file. If the file is a `.zip` archive the ROM is the single
+/// `.gba` file inside of it, otherwise the file is the ROM.
+pub fn read_rom(path: &Path) -> anyhow::Result<Vec<u8>> {
+    if has_extension(path, "zip") {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("error opening ROM archive {path:?}"))?;
+        extract_rom(file).with_context(|| format!("error reading ROM from archive {path:?}"))
+    } else {
+        std::fs::read(path).with_context(|| format!("error reading ROM from {path:?}"))
+    }
+}
+
+/// Extracts the only `.gba` file in a zip archive. It is an error for the archive to
+/// contain no `.gba` files, more than one, or one that is larger than the largest
+/// possible gamepak.
+fn extract_rom<R: Read + Seek>(archive: R) -> anyhow::Result<Vec<u8>> {
+    let mut archive = zip::ZipArchive::new(archive).context("invalid or corrupt zip archive")?;
+
+    let roms: Vec<String> = archive
+        .file_names()
+        .filter(|name| !name.ends_with('/') && has_extension(Path::new(name), "gba"))
+        .map(String::from)
+        .collect();
+    let name = match &roms[..] {
+        [name] => name,
+        [] => anyhow::bail!("no .gba file in archive"),
+        _ => anyhow::bail!("more than one .gba file in archive: {}", roms.join(", ")),
+    };
+
+    let entry = archive
+        .by_name(name)
+        .with_context(|| format!("error opening {name:?} in archive"))?;
+    anyhow::ensure!(
+        entry.size() <= GAMEPAK_MAX_SIZE as u64,
+        "{name:?} is too large to be a ROM ({} bytes)",
+        entry.size()
+    );
+
+    // The size in the archive can't be trusted so reading stops after the limit.
+    let mut rom = Vec::with_capacity(entry.size() as usize);
+    entry
+        .take(GAMEPAK_MAX_SIZE as u64 + 1)
+        .read_to_end(&mut rom)
+        .with_context(|| format!("error extracting {name:?} from archive"))?;
+    anyhow::ensure!(
+        rom.len() <= GAMEPAK_MAX_SIZE,
+        "{name:?} is too large to be a ROM"
+    );
+    Ok(rom)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write as _};
+
+    use gba::Gba;
+
+    use super::extract_rom;
+
+    const NOP_ZIP: &[u8] = include_bytes!("../../../roms/custom/nop.zip");
+
+    #[test]
+    fn load_rom_from_zip() {
+        let rom = extract_rom(Cursor::new(NOP_ZIP)).unwrap();
+        assert_eq!(rom.len(), gba::memory::GAMEPAK_HEADER_SIZE);
+        assert_eq!(rom[..4], gba::NOP_ROM);
+
+        let mut gba = Gba::new();
+        gba.try_set_gamepak(rom).unwrap();
+    }
+
+    #[test]
+    fn oversized_rom_in_zip() {
+        let mut archive = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        archive.start_file("huge.gba", options).unwrap();
+        archive
+            .write_all(&vec![0; gba::memory::GAMEPAK_MAX_SIZE + 1])
+            .unwrap();
+        let archive = archive.finish().unwrap().into_inner();
+
+        let err = extract_rom(Cursor::new(archive)).unwrap_err();
+        assert!(err.to_string().contains("too large"), "{err}");
+    }
+
+    #[test]
+    fn corrupt_zip() {
+        let mut corrupt = NOP_ZIP.to_vec();
+        corrupt.truncate(corrupt.len() / 2);
+        assert!(extract_rom(Cursor::new(corrupt)).is_err());
+        assert!(extract_rom(Cursor::new(b"not a zip file")).is_err());
+    }
+}
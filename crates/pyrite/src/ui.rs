@@ -1,19 +1,27 @@
 mod app_window;
 mod disassembly;
-mod gba_image;
+pub mod gba_image;
 mod profiler;
+mod video_memory;
 
-use std::sync::Arc;
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use crate::{
     cli::PyriteCli,
     config::{self, Config},
-    gba_runner::SharedGba,
+    gba_runner::{threaded::ThreadedGba, GbaRunner, SharedGba},
 };
 use ahash::HashSet;
 use anyhow::Context as _;
-use egui::{EventFilter, Frame, Key, Response, Ui, Vec2, ViewportId};
-use gba::keypad::{Key as GbaKey, KeyInputState};
+use egui::{Align2, Color32, EventFilter, Frame, Key, Response, Ui, ViewportId};
+use gba::{
+    keypad::{Key as GbaKey, KeyInputState},
+    Gba,
+};
 use parking_lot::{Mutex, MutexGuard};
 
 use self::{
@@ -21,81 +29,141 @@ use self::{
     disassembly::DisassemblyWindow,
     gba_image::GbaImage,
     profiler::ProfilerWindow,
+    video_memory::VideoMemoryWindow,
 };
 
 pub struct App {
-    gba: SharedGba,
+    gba: GbaRunner,
     config: Config,
     screen: GbaImage,
     windows: Vec<app_window::AppWindowWrapper>,
     windows_visible: Arc<Mutex<HashSet<ViewportId>>>,
     keymap: ahash::AHashMap<Key, GbaKey>,
+    error_toast: Option<ErrorToast>,
+    render_fps: RenderFpsCounter,
+}
+
+/// Counts the frames rendered by the UI over the last second.
+struct RenderFpsCounter {
+    frames: u32,
+    started: Instant,
+    fps: f64,
+}
+
+impl RenderFpsCounter {
+    fn new() -> Self {
+        Self {
+            frames: 0,
+            started: Instant::now(),
+            fps: 0.0,
+        }
+    }
+
+    fn frame(&mut self) {
+        self.frames += 1;
+        let elapsed = self.started.elapsed();
+        if elapsed >= Duration::from_secs(1) {
+            self.fps = self.frames as f64 / elapsed.as_secs_f64();
+            self.frames = 0;
+            self.started = Instant::now();
+        }
+    }
+}
+
+/// An error message that is shown over the GBA screen for a few seconds.
+struct ErrorToast {
+    message: String,
+    shown_at: Instant,
+}
+
+impl ErrorToast {
+    const DURATION: Duration = Duration::from_secs(5);
 }
 
 impl App {
     pub fn new(
         cli: PyriteCli,
         config: Config,
-        gba: SharedGba,
         context: &eframe::CreationContext<'_>,
     ) -> anyhow::Result<Self> {
+        let gba_egui_ctx = context.egui_ctx.clone();
+        let gba = if config.gui.threaded_runner {
+            let mut gba = Gba::new();
+            cli.boot(&mut gba)?;
+            GbaRunner::threaded(ThreadedGba::spawn(gba, move || {
+                gba_egui_ctx.request_repaint();
+            }))
+        } else {
+            let gba = SharedGba::new();
+            let frame_skip = config.gui.frame_skip;
+            gba.with_mut(move |gba_data| {
+                gba_data.request_repaint = Some(Box::new(move |_ready, _| {
+                    gba_egui_ctx.request_repaint();
+                }));
+                gba_data.frame_skip = frame_skip;
+            });
+            gba.with_mut(|data| cli.boot(&mut data.gba))?;
+            GbaRunner::Shared(gba)
+        };
+
         let mut screen: Option<GbaImage> = None;
 
         #[cfg(feature = "glow")]
         if context.gl.is_some() {
-            let image = GbaImage::new_glow(gba.clone())
-                .context("error while creating screen texture using glow")?;
+            let image = GbaImage::new_glow(
+                gba.clone(),
+                config.gui.color_correction,
+                config.gui.scaling,
+                config.gui.filter,
+            )
+            .context("error while creating screen texture using glow")?;
             screen = Some(image);
         }
 
         #[cfg(feature = "wgpu")]
         if context.wgpu_render_state.is_some() {
-            let image = GbaImage::new_wgpu(gba.clone())
-                .context("error while creating screen texture using wgpu")?;
+            let image = GbaImage::new_wgpu(
+                gba.clone(),
+                config.gui.color_correction,
+                config.gui.scaling,
+                config.gui.filter,
+            )
+            .context("error while creating screen texture using wgpu")?;
             screen = Some(image);
         }
 
-        let gba_egui_ctx = context.egui_ctx.clone();
-        gba.with_mut(move |gba_data| {
-            gba_data.request_repaint = Some(Box::new(move |_ready, _| {
-                gba_egui_ctx.request_repaint();
-            }));
-        });
-
         let Some(screen) = screen else {
             anyhow::bail!("no renderer to construct screen texture");
         };
 
-        let rom = if let Some(path) = cli.rom {
-            Some(std::fs::read(&path).with_context(|| format!("error reading ROM from {path:?}"))?)
-        } else {
-            None
-        };
-
-        gba.with_mut(|data| {
-            if let Some(rom) = rom {
-                data.gba.set_gamepak(rom);
-            } else {
-                data.gba.set_noop_gamepak();
-            }
-
-            data.gba.reset();
-        });
         gba.unpause();
 
         let windows_visible = Arc::new(Mutex::new(HashSet::default()));
+        let mut windows = Vec::new();
+        // The debugger windows read the GBA's state directly, which the threaded
+        // runner doesn't allow.
+        if let Some(shared) = gba.shared() {
+            windows.push(DisassemblyWindow::wrapped(
+                windows_visible.clone(),
+                shared.clone(),
+            ));
+            windows.push(VideoMemoryWindow::wrapped(
+                windows_visible.clone(),
+                shared.clone(),
+            ));
+        }
         #[cfg(feature = "profiling")]
-        let profiler_window = ProfilerWindow::wrapped(windows_visible.clone(), context.storage);
-        let windows = vec![
-            DisassemblyWindow::wrapped(windows_visible.clone(), gba.clone()),
-            #[cfg(feature = "profiling")]
-            profiler_window,
+        windows.push(ProfilerWindow::wrapped(
+            windows_visible.clone(),
+            context.storage,
+        ));
+        windows.extend([
             EguiSettingsWindow::wrapped(windows_visible.clone()),
             EguiInspectionWindow::wrapped(windows_visible.clone()),
             EguiTextureWindow::wrapped(windows_visible.clone()),
             EguiMemoryWindow::wrapped(windows_visible.clone()),
             EguiStyleWindow::wrapped(windows_visible.clone()),
-        ];
+        ]);
 
         let mut keymap = ahash::AHashMap::default();
         keymap.insert(Key::Z, GbaKey::A);
@@ -116,12 +184,112 @@ impl App {
             windows,
             windows_visible,
             keymap,
+            error_toast: None,
+            render_fps: RenderFpsCounter::new(),
         })
     }
 
+    /// Loads a ROM that was dropped onto the window. Only the first dropped file is used.
+    fn handle_dropped_files(&mut self, ctx: &eframe::egui::Context) {
+        let dropped = ctx.input(|input| {
+            input
+                .raw
+                .dropped_files
+                .iter()
+                .find_map(|file| file.path.clone())
+        });
+        let Some(path) = dropped else {
+            return;
+        };
+
+        match self.load_rom(&path) {
+            Ok(()) => self.config.gui.add_recent_rom(path),
+            Err(err) => {
+                tracing::error!(error = debug(&err), "error while loading dropped ROM");
+                self.error_toast = Some(ErrorToast {
+                    message: format!("{err:#}"),
+                    shown_at: Instant::now(),
+                });
+            }
+        }
+    }
+
+    /// Replaces the gamepak with the ROM at `path` and resets the GBA. `path` can
+    /// also be a zip archive containing a single `.gba` file.
+    fn load_rom(&mut self, path: &Path) -> anyhow::Result<()> {
+        if !crate::rom::is_rom_path(path) {
+            anyhow::bail!("{path:?} is not a .gba or .zip file");
+        }
+
+        let rom = crate::rom::read_rom(path)?;
+        self.gba
+            .load_rom(rom)
+            .with_context(|| format!("error loading ROM from {path:?}"))
+    }
+
+    fn render_performance_overlay(&mut self, ctx: &eframe::egui::Context) {
+        self.render_fps.frame();
+        if !self.config.gui.show_performance_overlay {
+            return;
+        }
+
+        let stats = self.gba.stats();
+        egui::Area::new("performance_overlay")
+            .anchor(Align2::LEFT_TOP, egui::vec2(8.0, 32.0))
+            .interactable(false)
+            .show(ctx, |ui| {
+                Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.monospace(format!("FPS:      {:6.1}", self.render_fps.fps));
+                    if let Some(stats) = stats {
+                        ui.monospace(format!("GBA FPS:  {:6.1}", stats.emulated_fps));
+                        ui.monospace(format!("Speed:    {:5.0}%", stats.speed * 100.0));
+                    }
+                });
+            });
+    }
+
+    fn render_error_toast(&mut self, ctx: &eframe::egui::Context) {
+        let Some(ref toast) = self.error_toast else {
+            return;
+        };
+
+        let elapsed = toast.shown_at.elapsed();
+        if elapsed >= ErrorToast::DURATION {
+            self.error_toast = None;
+            return;
+        }
+
+        egui::Area::new("error_toast")
+            .anchor(Align2::CENTER_BOTTOM, egui::vec2(0.0, -16.0))
+            .show(ctx, |ui| {
+                Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.colored_label(Color32::LIGHT_RED, &toast.message);
+                });
+            });
+        ctx.request_repaint_after(ErrorToast::DURATION - elapsed);
+    }
+
     fn render_menu(&mut self, ui: &mut Ui) {
         egui::menu::bar(ui, |ui| {
             ui.menu_button("File", |ui| if ui.button("Open ROM...").clicked() {});
+            ui.menu_button("Emulation", |ui| {
+                if ui.button("Resume").clicked() {
+                    self.gba.unpause();
+                    ui.close_menu();
+                }
+                if ui.button("Pause").clicked() {
+                    self.gba.pause();
+                    ui.close_menu();
+                }
+                if ui.button("Step Instruction").clicked() {
+                    self.gba.step();
+                    ui.close_menu();
+                }
+                if ui.button("Frame Advance").clicked() {
+                    self.gba.frame_advance();
+                    ui.close_menu();
+                }
+            });
             ui.menu_button("View", |ui| {
                 let categories = [
                     ("GBA", app_window::AppWindowCategory::Gba),
@@ -167,27 +335,51 @@ impl App {
             }
         });
 
-        self.gba.with_mut(|data| {
+        self.gba.set_keys(
             keys_pressed
                 .into_iter()
                 .enumerate()
-                .for_each(|(index, pressed)| {
+                .map(|(index, pressed)| {
                     let gba_key = GbaKey::try_from(index).unwrap();
                     let state = if pressed {
                         KeyInputState::Pressed
                     } else {
                         KeyInputState::Released
                     };
-                    data.gba.keypad_mut().keyinput.set_key_state(gba_key, state);
-                });
+                    (gba_key, state)
+                }),
+        );
+    }
+
+    /// P pauses or resumes the GBA and F advances it by a single frame while it's paused.
+    /// F3 toggles the performance overlay.
+    fn handle_run_hotkeys(&mut self, ctx: &eframe::egui::Context) {
+        let (toggle_pause, frame_advance, toggle_overlay) = ctx.input(|input| {
+            (
+                input.key_pressed(Key::P),
+                input.key_pressed(Key::F),
+                input.key_pressed(Key::F3),
+            )
         });
+
+        if toggle_overlay {
+            let overlay = &mut self.config.gui.show_performance_overlay;
+            *overlay = !*overlay;
+        }
+
+        if toggle_pause {
+            self.gba.toggle_pause();
+        } else if frame_advance {
+            self.gba.frame_advance();
+        }
     }
 
     fn handle_gba_input_with_response(&mut self, resp: Response, ctx: &eframe::egui::Context) {
         if resp.lost_focus() {
-            self.gba.with_mut(|data| {
-                data.gba.keypad_mut().keyinput.release_all();
-            });
+            self.gba.set_keys(
+                (0..GbaKey::COUNT)
+                    .map(|index| (GbaKey::try_from(index).unwrap(), KeyInputState::Released)),
+            );
             return;
         }
 
@@ -199,16 +391,16 @@ impl App {
 
 impl eframe::App for App {
     fn update(&mut self, ctx: &eframe::egui::Context, _frame: &mut eframe::Frame) {
+        self.handle_dropped_files(ctx);
+
         egui::TopBottomPanel::top("menu_bar_panel").show(ctx, |ui| self.render_menu(ui));
         egui::CentralPanel::default()
             .frame(Frame::none())
             .show(ctx, |ui| {
-                let screen_width = ui.available_width();
-                let screen_height = (screen_width / 240.0) * 160.0;
-                let (rect, resp) = ui.allocate_exact_size(
-                    Vec2::new(screen_width as _, screen_height as _),
-                    egui::Sense::click(),
-                );
+                // The screen renderer positions the GBA screen inside of this
+                // rect according to the configured scaling mode.
+                let (rect, resp) =
+                    ui.allocate_exact_size(ui.available_size(), egui::Sense::click());
 
                 if ctx.memory(|memory| memory.focus().is_none()) || resp.clicked() {
                     resp.request_focus();
@@ -222,11 +414,18 @@ impl eframe::App for App {
                     ctx.memory_mut(|memory| memory.set_focus_lock_filter(resp.id, filter));
                 }
 
+                if resp.has_focus() {
+                    self.handle_run_hotkeys(ctx);
+                }
                 self.handle_gba_input_with_response(resp, ctx);
 
+                ui.painter().rect_filled(rect, 0.0, Color32::BLACK);
                 ui.painter().add(self.screen.paint(rect));
             });
 
+        self.render_performance_overlay(ctx);
+        self.render_error_toast(ctx);
+
         let mut windows_visible = self.windows_visible.lock();
         for window in self.windows.iter() {
             if !windows_visible.contains(&window.viewport_id()) {
@@ -251,6 +450,7 @@ impl eframe::App for App {
     }
 
     fn on_exit(&mut self, gl: Option<&eframe::glow::Context>) {
+        self.gba.shutdown();
         self.screen.destroy(gl);
     }
 }
@@ -1,5 +1,5 @@
 use super::app_window::{AppWindow, AppWindowWrapper};
-use crate::gba_runner::SharedGba;
+use crate::gba_runner::{GbaRunMode, SharedGba};
 use ahash::HashSet;
 use arm::disasm::MemoryView as _;
 use arm::{disasm::AnyInstr, emu::InstructionSet};
@@ -13,6 +13,8 @@ pub struct DisassemblyWindow {
     first_visible_address: u32,
     instruction_set: Option<InstructionSet>,
     goto_address: String,
+    /// Draw instructions that are probably data or the other instruction set in gray.
+    dim_unlikely_instructions: bool,
 }
 
 impl DisassemblyWindow {
@@ -22,6 +24,7 @@ impl DisassemblyWindow {
             first_visible_address: 0,
             instruction_set: None,
             goto_address: String::new(),
+            dim_unlikely_instructions: true,
         }
     }
 
@@ -37,6 +40,8 @@ impl AppWindow for DisassemblyWindow {
         let gba_data = state.gba.read();
 
         let mut should_scroll_to_current = false;
+        let mut set_pc_to = None;
+        let can_set_pc = matches!(gba_data.current_mode, GbaRunMode::Step | GbaRunMode::Paused);
 
         egui::TopBottomPanel::top("disassembly_controls_panel").show(ctx, |ui| {
             ui.horizontal(|ui| {
@@ -51,11 +56,20 @@ impl AppWindow for DisassemblyWindow {
 
                 should_goto_address |= ui.button("Goto").clicked();
 
-                if should_goto_address {
+                let should_set_pc = ui
+                    .add_enabled(can_set_pc, egui::Button::new("Set PC"))
+                    .on_hover_text("Branch to the address, switching to THUMB if it is odd")
+                    .on_disabled_hover_text("The GBA must be paused to set the PC")
+                    .clicked();
+
+                if should_goto_address || should_set_pc {
                     state.goto_address.retain(|c| c.is_ascii_hexdigit());
                     if let Ok(address) = u32::from_str_radix(&state.goto_address, 16) {
                         state.first_visible_address = address;
                         state.goto_address.clear();
+                        if should_set_pc {
+                            set_pc_to = Some(address);
+                        }
                     }
                 }
 
@@ -82,7 +96,12 @@ impl AppWindow for DisassemblyWindow {
                             Some(arm::emu::InstructionSet::Thumb),
                             "Thumb",
                         );
-                    })
+                    });
+
+                ui.checkbox(&mut state.dim_unlikely_instructions, "Dim Data")
+                    .on_hover_text(
+                        "Gray out instructions that are probably data or code for the other instruction set",
+                    );
             });
         });
 
@@ -264,11 +283,16 @@ impl AppWindow for DisassemblyWindow {
                             ),
                         };
 
-                        ui.monospace(format!(
+                        let text = RichText::new(format!(
                             "{mnemonic:<12} {arguments:<32}",
                             mnemonic = mnemonic,
                             arguments = arguments,
                         ));
+                        if state.dim_unlikely_instructions && !disassembled.looks_valid() {
+                            ui.monospace(text.color(Color32::GRAY));
+                        } else {
+                            ui.monospace(text);
+                        }
 
                         comment_buffer.clear();
                         write!(&mut comment_buffer, "{comment}").unwrap();
@@ -299,6 +323,15 @@ impl AppWindow for DisassemblyWindow {
                 }
             }
         });
+
+        drop(gba_data);
+        if let Some(address) = set_pc_to {
+            let mut gba_data = state.gba.write();
+            // The GBA thread might have been resumed since the button was drawn.
+            if matches!(gba_data.current_mode, GbaRunMode::Step | GbaRunMode::Paused) {
+                gba_data.gba.set_pc(address);
+            }
+        }
     }
 
     fn title() -> String {
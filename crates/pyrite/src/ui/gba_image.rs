@@ -1,10 +1,14 @@
+pub mod common;
+
 #[cfg(feature = "glow")]
 pub mod glow;
 
 #[cfg(feature = "wgpu")]
 pub mod wgpu;
 
-use crate::gba_runner::SharedGba;
+use crate::gba_runner::GbaRunner;
+
+use self::common::{Filter, Scaling};
 
 #[cfg(feature = "glow")]
 use self::glow::GbaImageGlow;
@@ -21,13 +25,23 @@ pub enum GbaImage {
 
 impl GbaImage {
     #[cfg(feature = "glow")]
-    pub fn new_glow(gba: SharedGba) -> anyhow::Result<Self> {
-        GbaImageGlow::new(gba).map(Self::Glow)
+    pub fn new_glow(
+        gba: GbaRunner,
+        color_correction: bool,
+        scaling: Scaling,
+        filter: Filter,
+    ) -> anyhow::Result<Self> {
+        GbaImageGlow::new(gba, color_correction, scaling, filter).map(Self::Glow)
     }
 
     #[cfg(feature = "wgpu")]
-    pub fn new_wgpu(gba: SharedGba) -> anyhow::Result<Self> {
-        GbaImageWgpu::new(gba).map(Self::Wgpu)
+    pub fn new_wgpu(
+        gba: GbaRunner,
+        color_correction: bool,
+        scaling: Scaling,
+        filter: Filter,
+    ) -> anyhow::Result<Self> {
+        GbaImageWgpu::new(gba, color_correction, scaling, filter).map(Self::Wgpu)
     }
 
     pub fn paint(&mut self, rect: egui::Rect) -> egui::PaintCallback {
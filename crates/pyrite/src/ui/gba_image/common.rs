@@ -0,0 +1,254 @@
+use std::sync::OnceLock;
+
+use gba::video::{VISIBLE_LINE_COUNT, VISIBLE_LINE_WIDTH};
+use serde::{Deserialize, Serialize};
+
+/// Gamma of the GBA's LCD.
+const LCD_GAMMA: f64 = 4.0;
+/// Gamma of the display that we are targeting (sRGB).
+const OUT_GAMMA: f64 = 2.2;
+
+/// A single pixel in the buffer that is uploaded to the GPU.
+pub type UploadPixel = [u8; 4];
+
+/// Converts the 1555 pixels in `src` into RGBA8 pixels in `dst`, optionally
+/// applying [`color_correct`] to every pixel.
+pub fn fill_upload_buffer(src: &[u16], dst: &mut [UploadPixel], color_correction: bool) {
+    #[cfg(feature = "puffin")]
+    puffin::profile_function!();
+
+    if color_correction {
+        let table = color_correction_table();
+        for (dst, &src) in dst.iter_mut().zip(src) {
+            *dst = table[(src & 0x7FFF) as usize];
+        }
+    } else {
+        for (dst, &src) in dst.iter_mut().zip(src) {
+            let [r, g, b] = raw_rgb8(src);
+            *dst = [r, g, b, 0xFF];
+        }
+    }
+}
+
+/// Expands a 1555 GBA color into an RGB8 triple without any correction.
+pub fn raw_rgb8(color: u16) -> [u8; 3] {
+    gba::video::rgb8(color)
+}
+
+/// Maps a 1555 GBA color to an RGB8 triple that approximates what it looks like
+/// on the GBA's LCD. This uses the color model from higan's GBA color emulation:
+/// the LCD's gamma curve is applied to each channel, the channels are mixed to
+/// account for the LCD's color bleed, and the result is brought back into the
+/// output display's gamma.
+pub fn color_correct(color: u16) -> [u8; 3] {
+    let linear = |c: u16| -> f64 { ((c & 0x1F) as f64 / 31.0).powf(LCD_GAMMA) };
+    let lr = linear(color);
+    let lg = linear(color >> 5);
+    let lb = linear(color >> 10);
+
+    let output = |mixed: f64| -> u8 {
+        let value = (mixed / 255.0).powf(1.0 / OUT_GAMMA) * (255.0 * 255.0 / 280.0);
+        value.round().clamp(0.0, 255.0) as u8
+    };
+
+    [
+        output(50.0 * lg + 255.0 * lr),
+        output(30.0 * lb + 230.0 * lg + 10.0 * lr),
+        output(220.0 * lb + 10.0 * lg + 50.0 * lr),
+    ]
+}
+
+/// Lookup table containing the corrected color for every 15-bit GBA color.
+fn color_correction_table() -> &'static [UploadPixel] {
+    static TABLE: OnceLock<Box<[UploadPixel]>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        (0..0x8000u16)
+            .map(|color| {
+                let [r, g, b] = color_correct(color);
+                [r, g, b, 0xFF]
+            })
+            .collect()
+    })
+}
+
+/// How the GBA screen's texture is sampled when it is scaled.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter {
+    /// Keep the pixels sharp.
+    #[default]
+    Nearest,
+
+    /// Blend neighboring pixels together. The texture is clamped to its edges so
+    /// nothing bleeds in from outside of the screen.
+    Linear,
+}
+
+/// How the GBA screen is scaled to fit the space that it is given.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Scaling {
+    /// Scale by the largest whole multiple of 240x160 that fits and center the
+    /// screen, leaving black bars around it.
+    Integer,
+
+    /// Fill all of the available space, ignoring the aspect ratio.
+    Stretch,
+
+    /// Scale as large as possible while preserving the 3:2 aspect ratio.
+    #[default]
+    Fit,
+}
+
+/// The area of the viewport that the GBA screen is drawn into, in pixels
+/// relative to the top left corner of the viewport.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScreenRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl ScreenRect {
+    /// Computes where the screen should be placed inside of a viewport with the
+    /// given size.
+    pub fn new(viewport_width: f32, viewport_height: f32, scaling: Scaling) -> Self {
+        let screen_width = VISIBLE_LINE_WIDTH as f32;
+        let screen_height = VISIBLE_LINE_COUNT as f32;
+        let fit_scale = (viewport_width / screen_width).min(viewport_height / screen_height);
+
+        let scale = match scaling {
+            Scaling::Stretch => {
+                return ScreenRect {
+                    x: 0.0,
+                    y: 0.0,
+                    width: viewport_width,
+                    height: viewport_height,
+                }
+            }
+
+            // If the viewport is smaller than the GBA screen there is no whole
+            // multiple that fits so this behaves like `Fit` instead.
+            Scaling::Integer if fit_scale >= 1.0 => fit_scale.floor(),
+            Scaling::Integer | Scaling::Fit => fit_scale,
+        };
+
+        let width = screen_width * scale;
+        let height = screen_height * scale;
+        ScreenRect {
+            x: ((viewport_width - width) / 2.0).floor(),
+            y: ((viewport_height - height) / 2.0).floor(),
+            width,
+            height,
+        }
+    }
+
+    /// Returns the left, top, right, and bottom edges of this rectangle in
+    /// normalized device coordinates.
+    pub fn to_ndc(&self, viewport_width: f32, viewport_height: f32) -> [f32; 4] {
+        let to_ndc_x = |x: f32| (x / viewport_width) * 2.0 - 1.0;
+        let to_ndc_y = |y: f32| 1.0 - (y / viewport_height) * 2.0;
+        [
+            to_ndc_x(self.x),
+            to_ndc_y(self.y),
+            to_ndc_x(self.x + self.width),
+            to_ndc_y(self.y + self.height),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_correction_known_values() {
+        assert_eq!(color_correct(0x0000), [0, 0, 0]);
+        assert_eq!(color_correct(0x7FFF), [252, 238, 242]);
+        assert_eq!(color_correct(0x001F), [232, 53, 111]);
+    }
+
+    #[test]
+    fn upload_buffer_conversion() {
+        let src = [0x7FFF, 0x001F];
+        let mut dst = [[0u8; 4]; 2];
+
+        fill_upload_buffer(&src, &mut dst, false);
+        assert_eq!(dst, [[0xFF, 0xFF, 0xFF, 0xFF], [0xFF, 0x00, 0x00, 0xFF]]);
+
+        // The alpha bit of the GBA color is ignored.
+        fill_upload_buffer(&[0xFFFF, 0x801F], &mut dst, true);
+        assert_eq!(dst, [[252, 238, 242, 0xFF], [232, 53, 111, 0xFF]]);
+    }
+
+    #[test]
+    fn screen_rect_scaling() {
+        // Exactly 3x with room to spare vertically.
+        let rect = ScreenRect::new(720.0, 600.0, Scaling::Integer);
+        assert_eq!(
+            rect,
+            ScreenRect {
+                x: 0.0,
+                y: 60.0,
+                width: 720.0,
+                height: 480.0
+            }
+        );
+
+        // 2x is the largest whole multiple that fits, centered in both axes.
+        let rect = ScreenRect::new(700.0, 400.0, Scaling::Integer);
+        assert_eq!(
+            rect,
+            ScreenRect {
+                x: 110.0,
+                y: 40.0,
+                width: 480.0,
+                height: 320.0
+            }
+        );
+
+        // Smaller than 1x falls back to fitting the viewport.
+        let rect = ScreenRect::new(120.0, 120.0, Scaling::Integer);
+        assert_eq!(
+            rect,
+            ScreenRect {
+                x: 0.0,
+                y: 20.0,
+                width: 120.0,
+                height: 80.0
+            }
+        );
+
+        let rect = ScreenRect::new(700.0, 400.0, Scaling::Fit);
+        assert_eq!(
+            rect,
+            ScreenRect {
+                x: 50.0,
+                y: 0.0,
+                width: 600.0,
+                height: 400.0
+            }
+        );
+
+        let rect = ScreenRect::new(700.0, 400.0, Scaling::Stretch);
+        assert_eq!(
+            rect,
+            ScreenRect {
+                x: 0.0,
+                y: 0.0,
+                width: 700.0,
+                height: 400.0
+            }
+        );
+    }
+
+    #[test]
+    fn screen_rect_ndc() {
+        let rect = ScreenRect::new(700.0, 400.0, Scaling::Stretch);
+        assert_eq!(rect.to_ndc(700.0, 400.0), [-1.0, 1.0, 1.0, -1.0]);
+
+        let rect = ScreenRect::new(480.0, 640.0, Scaling::Integer);
+        assert_eq!(rect.to_ndc(480.0, 640.0), [-1.0, 0.5, 1.0, -0.5]);
+    }
+}
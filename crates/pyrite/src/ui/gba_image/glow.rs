@@ -1,21 +1,35 @@
 use std::sync::Arc;
 
-use crate::gba_runner::SharedGba;
+use crate::gba_runner::GbaRunner;
+use anyhow::Context as _;
 use eframe::{
     egui_glow::{CallbackFn, Painter},
     glow::{self, Buffer, HasContext, Program, Shader, Texture, VertexArray},
 };
 use egui::PaintCallbackInfo;
+use gba::video::VISIBLE_PIXELS;
 use parking_lot::Mutex;
 
+use super::common::{fill_upload_buffer, Filter, Scaling, ScreenRect, UploadPixel};
+
 pub struct GbaImageGlow {
     glow_painter: Arc<Mutex<GlowPainter>>,
     callback: Arc<CallbackFn>,
 }
 
 impl GbaImageGlow {
-    pub fn new(gba: SharedGba) -> anyhow::Result<Self> {
-        let glow_painter = Arc::new(Mutex::new(GlowPainter::new(gba)));
+    pub fn new(
+        gba: GbaRunner,
+        color_correction: bool,
+        scaling: Scaling,
+        filter: Filter,
+    ) -> anyhow::Result<Self> {
+        let glow_painter = Arc::new(Mutex::new(GlowPainter::new(
+            gba,
+            color_correction,
+            scaling,
+            filter,
+        )));
 
         let callback = Arc::new({
             let glow_painter = glow_painter.clone();
@@ -43,7 +57,7 @@ impl GbaImageGlow {
 }
 
 struct GlowPainter {
-    gba: SharedGba,
+    gba: GbaRunner,
     vertex_shader: Option<Shader>,
     fragment_shader: Option<Shader>,
     program: Option<Program>,
@@ -51,10 +65,18 @@ struct GlowPainter {
     vertex_array: Option<VertexArray>,
     texture: Option<Texture>,
     initialized: bool,
+    /// Set if the GL resources couldn't be created. Nothing is drawn after that.
+    failed: bool,
+    color_correction: bool,
+    upload_buffer: Box<[UploadPixel]>,
+    scaling: Scaling,
+    filter: Filter,
+    /// The size of the viewport that the vertex buffer was last generated for.
+    viewport_size: Option<(f32, f32)>,
 }
 
 impl GlowPainter {
-    fn new(gba: SharedGba) -> Self {
+    fn new(gba: GbaRunner, color_correction: bool, scaling: Scaling, filter: Filter) -> Self {
         Self {
             gba,
             vertex_shader: None,
@@ -64,14 +86,29 @@ impl GlowPainter {
             vertex_array: None,
             texture: None,
             initialized: false,
+            failed: false,
+            color_correction,
+            upload_buffer: vec![[0; 4]; VISIBLE_PIXELS].into_boxed_slice(),
+            scaling,
+            filter,
+            viewport_size: None,
         }
     }
 
-    fn paint(&mut self, _info: PaintCallbackInfo, painter: &Painter) {
+    fn paint(&mut self, info: PaintCallbackInfo, painter: &Painter) {
+        if self.failed {
+            return;
+        }
+
         if !self.initialized {
             if let Err(err) = self.init(painter.gl()) {
-                tracing::error!(error = debug(&err), "error while initializing GBA screen");
-                panic!("error while initializing GBA screen: {err}");
+                tracing::error!(
+                    error = debug(&err),
+                    "error while initializing GBA screen, it will not be drawn"
+                );
+                self.destroy(painter.gl());
+                self.failed = true;
+                return;
             }
         }
 
@@ -80,12 +117,33 @@ impl GlowPainter {
             gl.bind_buffer(eframe::glow::ARRAY_BUFFER, self.buffer);
             gl.bind_vertex_array(self.vertex_array);
             gl.use_program(self.program);
+        }
+
+        let viewport = info.viewport_in_pixels();
+        let viewport_size = (viewport.width_px as f32, viewport.height_px as f32);
+        if self.viewport_size != Some(viewport_size) {
+            let (width, height) = viewport_size;
+            let rect = ScreenRect::new(width, height, self.scaling);
+            let vertices = gl_vertices(rect.to_ndc(width, height));
+            unsafe {
+                gl.buffer_data_u8_slice(
+                    glow::ARRAY_BUFFER,
+                    bytemuck::cast_slice::<_, u8>(&vertices),
+                    glow::DYNAMIC_DRAW,
+                );
+            }
+            self.viewport_size = Some(viewport_size);
+        }
+
+        unsafe {
             gl.active_texture(eframe::glow::TEXTURE0);
             gl.bind_texture(eframe::glow::TEXTURE_2D, self.texture);
         }
 
-        let mut gba_data = self.gba.write();
-        if !gba_data.painted {
+        let new_frame = self.gba.with_new_frame(|frame| {
+            fill_upload_buffer(&frame[..], &mut self.upload_buffer, self.color_correction)
+        });
+        if new_frame.is_some() {
             unsafe {
                 gl.tex_sub_image_2d(
                     eframe::glow::TEXTURE_2D,
@@ -95,67 +153,63 @@ impl GlowPainter {
                     240,
                     160,
                     eframe::glow::RGBA,
-                    eframe::glow::UNSIGNED_SHORT_1_5_5_5_REV,
+                    eframe::glow::UNSIGNED_BYTE,
                     eframe::glow::PixelUnpackData::Slice(bytemuck::cast_slice(
-                        &gba_data.ready_buffer[..],
+                        &self.upload_buffer[..],
                     )),
                 );
             }
         }
-        gba_data.painted = true;
-        drop(gba_data);
 
         unsafe { gl.draw_arrays(eframe::glow::TRIANGLES, 0, 6) };
     }
 
-    fn init(&mut self, gl: &eframe::glow::Context) -> Result<(), String> {
+    /// Creates the GL resources for drawing the screen. Anything that was created
+    /// before an error is left in place for [`GlowPainter::destroy`].
+    fn init(&mut self, gl: &eframe::glow::Context) -> anyhow::Result<()> {
         unsafe {
-            let vertex_shader = gl.create_shader(glow::VERTEX_SHADER)?;
-            gl.shader_source(vertex_shader, GL_VERT_SHADER_SRC);
-            gl.compile_shader(vertex_shader);
-            if !gl.get_shader_compile_status(vertex_shader) {
-                return Err(gl.get_shader_info_log(vertex_shader));
-            }
+            let vertex_shader = compile_shader(gl, glow::VERTEX_SHADER, GL_VERT_SHADER_SRC)
+                .context("error compiling vertex shader")?;
             self.vertex_shader = Some(vertex_shader);
 
-            let fragment_shader = gl.create_shader(glow::FRAGMENT_SHADER)?;
-            gl.shader_source(fragment_shader, GL_FRAG_SHADER_SRC);
-            gl.compile_shader(fragment_shader);
-            if !gl.get_shader_compile_status(fragment_shader) {
-                return Err(gl.get_shader_info_log(fragment_shader));
-            }
+            let fragment_shader = compile_shader(gl, glow::FRAGMENT_SHADER, GL_FRAG_SHADER_SRC)
+                .context("error compiling fragment shader")?;
             self.fragment_shader = Some(fragment_shader);
 
-            let program = gl.create_program()?;
-            gl.attach_shader(program, self.vertex_shader.unwrap());
-            gl.attach_shader(program, self.fragment_shader.unwrap());
+            let program = gl.create_program().map_err(anyhow::Error::msg)?;
+            self.program = Some(program);
+            gl.attach_shader(program, vertex_shader);
+            gl.attach_shader(program, fragment_shader);
             gl.link_program(program);
             if !gl.get_program_link_status(program) {
-                return Err(gl.get_program_info_log(program));
+                anyhow::bail!(
+                    "error linking shader program: {}",
+                    gl.get_program_info_log(program)
+                );
             }
-            self.program = Some(program);
             tracing::debug!("GBA screen GL program linked");
 
-            let buffer = gl.create_buffer()?;
+            let buffer = gl.create_buffer().map_err(anyhow::Error::msg)?;
             self.buffer = Some(buffer);
             gl.bind_buffer(glow::ARRAY_BUFFER, self.buffer);
             gl.buffer_data_u8_slice(
                 glow::ARRAY_BUFFER,
-                bytemuck::cast_slice::<_, u8>(&GL_DEFAULT_VERTICES),
-                glow::STATIC_DRAW,
+                bytemuck::cast_slice::<_, u8>(&gl_vertices([-1.0, 1.0, 1.0, -1.0])),
+                glow::DYNAMIC_DRAW,
             );
+            self.viewport_size = None;
             tracing::debug!("GBA screen vertex buffer initialized");
 
-            let vertex_array = gl.create_vertex_array()?;
+            let vertex_array = gl.create_vertex_array().map_err(anyhow::Error::msg)?;
             self.vertex_array = Some(vertex_array);
             gl.bind_vertex_array(self.vertex_array);
             let sz_float = std::mem::size_of::<f32>() as i32;
             let pos = gl
                 .get_attrib_location(program, "in_position")
-                .expect("no in_position attribute");
+                .context("no in_position attribute")?;
             let tex = gl
                 .get_attrib_location(program, "in_texcoord")
-                .expect("no in_texcoord attribute");
+                .context("no in_texcoord attribute")?;
             gl.vertex_attrib_pointer_f32(pos, 2, eframe::glow::FLOAT, false, 4 * sz_float, 0);
             gl.vertex_attrib_pointer_f32(
                 tex,
@@ -172,10 +226,10 @@ impl GlowPainter {
             let sz_float = std::mem::size_of::<f32>() as i32;
             let pos = gl
                 .get_attrib_location(program, "in_position")
-                .expect("no in_position attribute");
+                .context("no in_position attribute")?;
             let tex = gl
                 .get_attrib_location(program, "in_texcoord")
-                .expect("no in_texcoord attribute");
+                .context("no in_texcoord attribute")?;
             gl.vertex_attrib_pointer_f32(pos, 2, eframe::glow::FLOAT, false, 4 * sz_float, 0);
             gl.vertex_attrib_pointer_f32(
                 tex,
@@ -189,24 +243,24 @@ impl GlowPainter {
             gl.enable_vertex_attrib_array(tex);
             tracing::debug!("GBA screen vertex array object created");
 
-            let texture = gl.create_texture()?;
+            let texture = gl.create_texture().map_err(anyhow::Error::msg)?;
             self.texture = Some(texture);
             gl.bind_texture(glow::TEXTURE_2D, self.texture);
 
-            let mut gba_data = self.gba.write();
+            self.gba.with_latest_frame(|frame| {
+                fill_upload_buffer(&frame[..], &mut self.upload_buffer, self.color_correction)
+            });
             gl.tex_image_2d(
                 eframe::glow::TEXTURE_2D,
                 0,
-                eframe::glow::RGB as _,
+                eframe::glow::RGBA8 as _,
                 240,
                 160,
                 0,
                 eframe::glow::RGBA,
-                eframe::glow::UNSIGNED_SHORT_1_5_5_5_REV,
-                Some(bytemuck::cast_slice(&gba_data.ready_buffer[..])),
+                eframe::glow::UNSIGNED_BYTE,
+                Some(bytemuck::cast_slice(&self.upload_buffer[..])),
             );
-            gba_data.painted = true;
-            drop(gba_data);
 
             gl.tex_parameter_i32(
                 eframe::glow::TEXTURE_2D,
@@ -218,15 +272,19 @@ impl GlowPainter {
                 eframe::glow::TEXTURE_WRAP_T,
                 eframe::glow::CLAMP_TO_EDGE as _,
             );
+            let filter = match self.filter {
+                Filter::Nearest => eframe::glow::NEAREST,
+                Filter::Linear => eframe::glow::LINEAR,
+            };
             gl.tex_parameter_i32(
                 eframe::glow::TEXTURE_2D,
                 eframe::glow::TEXTURE_MIN_FILTER,
-                eframe::glow::NEAREST as _,
+                filter as _,
             );
             gl.tex_parameter_i32(
                 eframe::glow::TEXTURE_2D,
                 eframe::glow::TEXTURE_MAG_FILTER,
-                eframe::glow::NEAREST as _,
+                filter as _,
             );
         }
 
@@ -263,6 +321,27 @@ impl GlowPainter {
     }
 }
 
+/// Compiles a shader, returning the info log as the error if compilation fails.
+///
+/// # Safety
+///
+/// `gl` must be current on this thread.
+unsafe fn compile_shader(
+    gl: &eframe::glow::Context,
+    kind: u32,
+    source: &str,
+) -> anyhow::Result<Shader> {
+    let shader = gl.create_shader(kind).map_err(anyhow::Error::msg)?;
+    gl.shader_source(shader, source);
+    gl.compile_shader(shader);
+    if !gl.get_shader_compile_status(shader) {
+        let log = gl.get_shader_info_log(shader);
+        gl.delete_shader(shader);
+        anyhow::bail!("{log}");
+    }
+    Ok(shader)
+}
+
 const GL_FRAG_SHADER_SRC: &str = "\
 #version 150 core
 in vec2 frag_texcoord;
@@ -283,12 +362,16 @@ void main() {
     frag_texcoord = in_texcoord;
 }";
 
+/// Generates the vertices for the screen quad from the left, top, right, and
+/// bottom edges of the quad in normalized device coordinates.
 #[rustfmt::skip]
-const GL_DEFAULT_VERTICES: [f32; 24] = [
-    -1.0,  1.0, 0.0, 0.0, // left, top
-     1.0,  1.0, 1.0, 0.0, // right, top
-    -1.0, -1.0, 0.0, 1.0, // left, bottom
-    -1.0, -1.0, 0.0, 1.0, // left, bottom
-     1.0, -1.0, 1.0, 1.0, // right, bottom
-     1.0,  1.0, 1.0, 0.0, // right, top
-];
+fn gl_vertices([l, t, r, b]: [f32; 4]) -> [f32; 24] {
+    [
+        l, t, 0.0, 0.0, // left, top
+        r, t, 1.0, 0.0, // right, top
+        l, b, 0.0, 1.0, // left, bottom
+        l, b, 0.0, 1.0, // left, bottom
+        r, b, 1.0, 1.0, // right, bottom
+        r, t, 1.0, 0.0, // right, top
+    ]
+}
@@ -6,17 +6,24 @@ use eframe::{
     },
 };
 use egui::PaintCallback;
-use gba::video::{VISIBLE_LINE_COUNT, VISIBLE_LINE_WIDTH};
+use gba::video::{VISIBLE_LINE_COUNT, VISIBLE_LINE_WIDTH, VISIBLE_PIXELS};
 
-use crate::gba_runner::{GbaRunMode, SharedGba};
+use crate::gba_runner::GbaRunner;
+
+use super::common::{fill_upload_buffer, Filter, Scaling, ScreenRect, UploadPixel};
 
 pub struct GbaImageWgpu {
     callback: PaintCallback,
 }
 
 impl GbaImageWgpu {
-    pub fn new(gba: SharedGba) -> anyhow::Result<Self> {
-        let wgpu_painter = WgpuPainter::new(gba);
+    pub fn new(
+        gba: GbaRunner,
+        color_correction: bool,
+        scaling: Scaling,
+        filter: Filter,
+    ) -> anyhow::Result<Self> {
+        let wgpu_painter = WgpuPainter::new(gba, color_correction, scaling, filter);
         let callback = Callback::new_paint_callback(egui::Rect::NOTHING, wgpu_painter);
         Ok(Self { callback })
     }
@@ -37,15 +44,24 @@ struct WgpuPainterResources {
     bind_group: BindGroup,
     render_pipeline: RenderPipeline,
     vertex_buffer: Buffer,
+    upload_buffer: Box<[UploadPixel]>,
 }
 
 struct WgpuPainter {
-    gba: SharedGba,
+    gba: GbaRunner,
+    color_correction: bool,
+    scaling: Scaling,
+    filter: Filter,
 }
 
 impl WgpuPainter {
-    fn new(gba: SharedGba) -> Self {
-        Self { gba }
+    fn new(gba: GbaRunner, color_correction: bool, scaling: Scaling, filter: Filter) -> Self {
+        Self {
+            gba,
+            color_correction,
+            scaling,
+            filter,
+        }
     }
 }
 
@@ -72,13 +88,16 @@ impl CallbackTrait for WgpuPainter {
             mip_level_count: 1,
             sample_count: 1,
             dimension: TextureDimension::D2,
-            format: TextureFormat::R16Uint,
+            format: TextureFormat::Rgba8Unorm,
             usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
             label: Some("gba_screen_texture"),
             view_formats: &[],
         });
 
-        let mut gba_data = self.gba.write();
+        let mut upload_buffer = vec![[0; 4]; VISIBLE_PIXELS].into_boxed_slice();
+        self.gba.with_latest_frame(|frame| {
+            fill_upload_buffer(&frame[..], &mut upload_buffer, self.color_correction)
+        });
         queue.write_texture(
             eframe::wgpu::ImageCopyTexture {
                 texture: &texture,
@@ -86,16 +105,14 @@ impl CallbackTrait for WgpuPainter {
                 origin: eframe::wgpu::Origin3d::ZERO,
                 aspect: eframe::wgpu::TextureAspect::All,
             },
-            bytemuck::cast_slice(&gba_data.ready_buffer[..]),
+            bytemuck::cast_slice(&upload_buffer[..]),
             eframe::wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: Some(2 * texture_size.width),
+                bytes_per_row: Some(4 * texture_size.width),
                 rows_per_image: Some(texture_size.height),
             },
             texture_size,
         );
-        gba_data.painted = true;
-        drop(gba_data);
         tracing::debug!("GBA screen wgpu texture initialized");
 
         let texture_view = texture.create_view(&TextureViewDescriptor {
@@ -103,12 +120,16 @@ impl CallbackTrait for WgpuPainter {
             ..Default::default()
         });
 
+        let filter = match self.filter {
+            Filter::Nearest => eframe::wgpu::FilterMode::Nearest,
+            Filter::Linear => eframe::wgpu::FilterMode::Linear,
+        };
         let sampler = device.create_sampler(&eframe::wgpu::SamplerDescriptor {
             address_mode_u: eframe::wgpu::AddressMode::ClampToEdge,
             address_mode_v: eframe::wgpu::AddressMode::ClampToEdge,
             address_mode_w: eframe::wgpu::AddressMode::ClampToEdge,
-            mag_filter: eframe::wgpu::FilterMode::Nearest,
-            min_filter: eframe::wgpu::FilterMode::Nearest,
+            mag_filter: filter,
+            min_filter: filter,
             mipmap_filter: eframe::wgpu::FilterMode::Nearest,
             label: Some("gba_screen_texture_sampler"),
             ..Default::default()
@@ -122,7 +143,9 @@ impl CallbackTrait for WgpuPainter {
                         binding: 0,
                         visibility: eframe::wgpu::ShaderStages::FRAGMENT,
                         ty: eframe::wgpu::BindingType::Texture {
-                            sample_type: eframe::wgpu::TextureSampleType::Uint,
+                            sample_type: eframe::wgpu::TextureSampleType::Float {
+                                filterable: false,
+                            },
                             view_dimension: eframe::wgpu::TextureViewDimension::D2,
                             multisampled: false,
                         },
@@ -213,6 +236,7 @@ impl CallbackTrait for WgpuPainter {
             bind_group,
             render_pipeline,
             vertex_buffer,
+            upload_buffer,
         });
         tracing::debug!("GBA screen wgpu resources initialized");
 
@@ -226,24 +250,24 @@ impl CallbackTrait for WgpuPainter {
         _egui_encoder: &mut eframe::wgpu::CommandEncoder,
         callback_resources: &mut eframe::egui_wgpu::CallbackResources,
     ) -> Vec<eframe::wgpu::CommandBuffer> {
-        let Some(resources) = callback_resources.get::<WgpuPainterResources>() else {
+        let Some(resources) = callback_resources.get_mut::<WgpuPainterResources>() else {
             return Vec::new();
         };
 
-        let mut gba_data = self.gba.write();
-        if !gba_data.painted {
+        let new_frame = self.gba.with_new_frame(|frame| {
+            fill_upload_buffer(
+                &frame[..],
+                &mut resources.upload_buffer,
+                self.color_correction,
+            )
+        });
+        if new_frame.is_some() {
             let texture_size = eframe::wgpu::Extent3d {
                 width: VISIBLE_LINE_WIDTH as u32,
                 height: VISIBLE_LINE_COUNT as u32,
                 depth_or_array_layers: 1,
             };
 
-            let buffer = if gba_data.current_mode == GbaRunMode::Frame {
-                bytemuck::cast_slice(&gba_data.ready_buffer[..])
-            } else {
-                bytemuck::cast_slice(&gba_data.frame_buffer[..])
-            };
-
             queue.write_texture(
                 eframe::wgpu::ImageCopyTexture {
                     texture: &resources.texture,
@@ -251,23 +275,21 @@ impl CallbackTrait for WgpuPainter {
                     origin: eframe::wgpu::Origin3d::ZERO,
                     aspect: eframe::wgpu::TextureAspect::All,
                 },
-                buffer,
+                bytemuck::cast_slice(&resources.upload_buffer[..]),
                 eframe::wgpu::ImageDataLayout {
                     offset: 0,
-                    bytes_per_row: Some(2 * texture_size.width),
+                    bytes_per_row: Some(4 * texture_size.width),
                     rows_per_image: Some(texture_size.height),
                 },
                 texture_size,
             );
-            gba_data.painted = true;
         }
-        drop(gba_data);
         Vec::new()
     }
 
     fn paint<'a>(
         &'a self,
-        _info: egui::PaintCallbackInfo,
+        info: egui::PaintCallbackInfo,
         render_pass: &mut eframe::wgpu::RenderPass<'a>,
         callback_resources: &'a eframe::egui_wgpu::CallbackResources,
     ) {
@@ -275,6 +297,23 @@ impl CallbackTrait for WgpuPainter {
             return;
         };
 
+        // The quad always covers the whole viewport so the screen is positioned
+        // by narrowing the viewport instead.
+        let viewport = info.viewport_in_pixels();
+        let rect = ScreenRect::new(
+            viewport.width_px as f32,
+            viewport.height_px as f32,
+            self.scaling,
+        );
+        render_pass.set_viewport(
+            viewport.left_px as f32 + rect.x,
+            viewport.top_px as f32 + rect.y,
+            rect.width,
+            rect.height,
+            0.0,
+            1.0,
+        );
+
         render_pass.set_pipeline(&resources.render_pipeline);
         render_pass.set_vertex_buffer(0, resources.vertex_buffer.slice(..));
         render_pass.set_bind_group(0, &resources.bind_group, &[]);
@@ -316,18 +355,15 @@ fn vs_main(
 }
 
 @group(0) @binding(0)
-var tex: texture_2d<u32>;
+var tex: texture_2d<f32>;
 
 @group(0) @binding(1)
 var sam: sampler;
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let c: u32 = textureLoad(tex, vec2(u32(240.0 * in.tex_coords.x), u32(160.0 * in.tex_coords.y)), 0).r;
-    let r: f32 = f32( c        & u32(31)) / f32(31.0);
-    let g: f32 = f32((c >> u32( 5)) & u32(31)) / f32(31.0);
-    let b: f32 = f32((c >> u32(10)) & u32(31)) / f32(31.0);
-    return vec4(r, g, b, 1.0);
+    let c: vec4<f32> = textureLoad(tex, vec2(u32(240.0 * in.tex_coords.x), u32(160.0 * in.tex_coords.y)), 0);
+    return vec4(c.rgb, 1.0);
 }";
 
 #[cfg(feature = "wgpu")]
@@ -0,0 +1,251 @@
+use super::{
+    app_window::{AppWindow, AppWindowWrapper},
+    gba_image::common::raw_rgb8,
+};
+use crate::gba_runner::SharedGba;
+use ahash::HashSet;
+use egui::{
+    load::SizedTexture, Color32, ColorImage, Sense, TextureHandle, TextureOptions, Vec2, ViewportId,
+};
+use gba::palette::Palette;
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// Size of a VRAM character block in bytes.
+const CHAR_BLOCK_SIZE: usize = 0x4000;
+/// Number of tiles drawn on each row of the tile viewer.
+const TILES_PER_ROW: usize = 16;
+/// Size of each palette swatch in points.
+const SWATCH_SIZE: f32 = 12.0;
+/// Scale that tiles are drawn at.
+const TILE_SCALE: f32 = 2.0;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum TileFormat {
+    /// 16 colors, 32 bytes per tile, two pixels per byte.
+    Bpp4,
+    /// 256 colors, 64 bytes per tile, one pixel per byte.
+    Bpp8,
+}
+
+impl TileFormat {
+    fn tile_size(self) -> usize {
+        match self {
+            TileFormat::Bpp4 => 32,
+            TileFormat::Bpp8 => 64,
+        }
+    }
+}
+
+/// Splits a byte of 4bpp tile data into the palette indices of its two pixels.
+/// The pixel on the left is in the lower 4 bits.
+pub fn decode_4bpp(byte: u8) -> [u8; 2] {
+    [byte & 0xF, byte >> 4]
+}
+
+/// Decodes the 8x8 tile at the start of `data` into a palette index for each
+/// pixel in row-major order. For 4bpp tiles the indices are into a 16 color bank.
+pub fn decode_tile(data: &[u8], format: TileFormat) -> [u8; 64] {
+    let mut pixels = [0; 64];
+    match format {
+        TileFormat::Bpp4 => {
+            for (pair, &byte) in pixels.chunks_mut(2).zip(&data[..32]) {
+                pair.copy_from_slice(&decode_4bpp(byte));
+            }
+        }
+        TileFormat::Bpp8 => pixels.copy_from_slice(&data[..64]),
+    }
+    pixels
+}
+
+pub struct VideoMemoryWindow {
+    gba: SharedGba,
+    char_block: usize,
+    format: TileFormat,
+    /// Palette bank used for 4bpp tiles.
+    palette_bank: u8,
+    object_palette: bool,
+    tiles_texture: Option<TextureHandle>,
+}
+
+impl VideoMemoryWindow {
+    fn new(gba: SharedGba) -> Self {
+        Self {
+            gba,
+            char_block: 0,
+            format: TileFormat::Bpp4,
+            palette_bank: 0,
+            object_palette: false,
+            tiles_texture: None,
+        }
+    }
+
+    pub fn wrapped(windows: Arc<Mutex<HashSet<ViewportId>>>, gba: SharedGba) -> AppWindowWrapper {
+        AppWindowWrapper::new::<Self>(windows, Self::new(gba))
+    }
+
+    /// Decodes every tile in the selected character block into an image.
+    fn tiles_image(&self, vram: &[u8], palette: &Palette) -> ColorImage {
+        let tile_size = self.format.tile_size();
+        let tile_count = CHAR_BLOCK_SIZE / tile_size;
+        let width = TILES_PER_ROW * 8;
+        let height = tile_count.div_ceil(TILES_PER_ROW) * 8;
+        let mut image = ColorImage::new([width, height], Color32::BLACK);
+
+        let base = self.char_block * CHAR_BLOCK_SIZE;
+        for (tile_index, tile) in vram[base..]
+            .chunks_exact(tile_size)
+            .take(tile_count)
+            .enumerate()
+        {
+            let tile_x = (tile_index % TILES_PER_ROW) * 8;
+            let tile_y = (tile_index / TILES_PER_ROW) * 8;
+            for (pixel_index, &entry) in decode_tile(tile, self.format).iter().enumerate() {
+                let entry = match self.format {
+                    TileFormat::Bpp4 => self.palette_bank * 16 + entry,
+                    TileFormat::Bpp8 => entry,
+                };
+                let color = if self.object_palette {
+                    palette.get_obj256(entry)
+                } else {
+                    palette.get_bg256(entry)
+                };
+                let [r, g, b] = raw_rgb8(color);
+                let x = tile_x + pixel_index % 8;
+                let y = tile_y + pixel_index / 8;
+                image[(x, y)] = Color32::from_rgb(r, g, b);
+            }
+        }
+        image
+    }
+}
+
+impl AppWindow for VideoMemoryWindow {
+    type State = Self;
+
+    fn ui(state: &mut Self::State, ctx: &egui::Context) {
+        let gba = state.gba.clone();
+        let gba_data = gba.read();
+        let palette = &gba_data.gba.mapped.palram;
+
+        egui::SidePanel::left("video_memory_palette_panel").show(ctx, |ui| {
+            for (heading, object) in [("Background Palette", false), ("Object Palette", true)] {
+                ui.heading(heading);
+                egui::Grid::new(heading)
+                    .spacing(Vec2::splat(1.0))
+                    .show(ui, |ui| {
+                        for entry in 0..=255u8 {
+                            let color = if object {
+                                palette.get_obj256(entry)
+                            } else {
+                                palette.get_bg256(entry)
+                            };
+                            let [r, g, b] = raw_rgb8(color);
+                            let (rect, response) =
+                                ui.allocate_exact_size(Vec2::splat(SWATCH_SIZE), Sense::hover());
+                            ui.painter()
+                                .rect_filled(rect, 0.0, Color32::from_rgb(r, g, b));
+                            response.on_hover_text(format!(
+                                "Entry {entry} (bank {}, index {})\n0x{color:04X}",
+                                entry / 16,
+                                entry % 16
+                            ));
+                            if entry % 16 == 15 {
+                                ui.end_row();
+                            }
+                        }
+                    });
+            }
+        });
+
+        egui::TopBottomPanel::top("video_memory_tile_controls").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                egui::ComboBox::new("video_memory_char_block", "Base")
+                    .selected_text(format!(
+                        "0x{:08X}",
+                        0x06000000 + state.char_block * CHAR_BLOCK_SIZE
+                    ))
+                    .show_ui(ui, |ui| {
+                        for block in 0..gba::memory::VRAM_SIZE / CHAR_BLOCK_SIZE {
+                            let address = 0x06000000 + block * CHAR_BLOCK_SIZE;
+                            ui.selectable_value(
+                                &mut state.char_block,
+                                block,
+                                format!("0x{address:08X}"),
+                            );
+                        }
+                    });
+
+                ui.selectable_value(&mut state.format, TileFormat::Bpp4, "4bpp");
+                ui.selectable_value(&mut state.format, TileFormat::Bpp8, "8bpp");
+                ui.checkbox(&mut state.object_palette, "Object Palette");
+                ui.add_enabled(
+                    state.format == TileFormat::Bpp4,
+                    egui::Slider::new(&mut state.palette_bank, 0..=15).text("Palette Bank"),
+                );
+            });
+        });
+
+        let image = state.tiles_image(&gba_data.gba.mapped.vram[..], palette);
+        drop(gba_data);
+
+        let size = Vec2::new(image.width() as f32, image.height() as f32) * TILE_SCALE;
+        let texture = match state.tiles_texture {
+            Some(ref mut texture) => {
+                texture.set(image, TextureOptions::NEAREST);
+                texture
+            }
+            None => state.tiles_texture.insert(ctx.load_texture(
+                "video_memory_tiles",
+                image,
+                TextureOptions::NEAREST,
+            )),
+        };
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.image(SizedTexture::new(texture.id(), size));
+            });
+        });
+    }
+
+    fn title() -> String {
+        "Video Memory".to_owned()
+    }
+
+    fn viewport_id() -> ViewportId {
+        egui::ViewportId::from_hash_of("video_memory")
+    }
+
+    fn category() -> super::app_window::AppWindowCategory {
+        super::app_window::AppWindowCategory::Gba
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_4bpp_low_nibble_first() {
+        assert_eq!(decode_4bpp(0x3A), [0xA, 0x3]);
+        assert_eq!(decode_4bpp(0xF0), [0x0, 0xF]);
+    }
+
+    #[test]
+    fn decode_tiles() {
+        let mut data = [0u8; 64];
+        data[0] = 0x21;
+        data[4] = 0x43;
+        data[31] = 0xFE;
+        let pixels = decode_tile(&data, TileFormat::Bpp4);
+        assert_eq!(pixels[..2], [1, 2]);
+        // Each row of a 4bpp tile is 4 bytes.
+        assert_eq!(pixels[8..10], [3, 4]);
+        assert_eq!(pixels[62..], [0xE, 0xF]);
+
+        let data: Vec<u8> = (0..64).collect();
+        let pixels = decode_tile(&data, TileFormat::Bpp8);
+        assert_eq!(pixels[..], data[..]);
+    }
+}